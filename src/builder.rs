@@ -1,18 +1,49 @@
 use anyhow::Result;
+use chrono::{DateTime, Utc};
 use log::{debug, info};
 use rayon::prelude::*;
-use std::collections::{HashMap, HashSet};
+use serde::Serialize;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::io::{self, Write};
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, LazyLock, Mutex};
 use std::time::{Duration, Instant};
+use tokio::sync::watch;
 
 use crate::cache::BuildCache;
 use crate::config::BuildConfig;
-use crate::document::Document;
-use crate::error::{BuildErrorReport, BuildWarning};
-use crate::extensions::{ExtensionLoader, SphinxApp};
+use crate::directives::{parse_toctree_entries, ToctreeEntry};
+use crate::document::{Document, DocumentContent, RstNode};
+use crate::environment::BuildEnvironment;
+use crate::error::{BuildError, BuildErrorReport, BuildWarning, ErrorType, WarningType};
+use crate::extensions::{write_githubpages_files, ExtensionLoader, SphinxApp};
+use crate::intersphinx;
+use crate::inventory::Inventory;
+use crate::lint;
 use crate::parser::Parser;
+use crate::query::DocumentQuery;
+use crate::renderer::HtmlRenderer;
+use crate::search::SearchIndexBuilder;
+use crate::tags::Tags;
+use crate::template::TemplateEngine;
 use crate::utils;
+use crate::writer::DocumentWriter;
+
+/// Name of the build cache directory inside the output directory
+const CACHE_DIR_NAME: &str = ".sphinx-ultra-cache";
+
+/// Search index state kept in the cache directory for incremental updates
+/// (in a subdirectory, away from the cached documents); renamed whenever the
+/// way terms are derived changes, so a stale index is rebuilt from scratch
+const SEARCH_INDEX_STATE_NAME: &str = "search/index-v2.json";
+
+/// Build manifest with a content-hash ETag per generated page
+pub const BUILD_MANIFEST_NAME: &str = ".manifest.json";
+
+/// `src` of an `<img>` tag in rendered HTML
+static IMG_SRC_REGEX: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r#"<img\b[^>]*?\bsrc\s*=\s*["']([^"']+)["']"#).unwrap());
 
 #[derive(Debug, Clone)]
 pub struct BuildStats {
@@ -27,17 +58,167 @@ pub struct BuildStats {
     pub error_details: Vec<BuildErrorReport>,
 }
 
+/// Build health in a form ready to serve as JSON, e.g. from a status endpoint
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BuildStatus {
+    pub files_processed: usize,
+    pub warnings: usize,
+    pub errors: usize,
+    pub build_time_ms: u128,
+    /// Located error messages, as `file:line: message`
+    pub error_messages: Vec<String>,
+}
+
+impl BuildStats {
+    /// Summary of this build for editors and dashboards
+    pub fn status(&self) -> BuildStatus {
+        BuildStatus {
+            files_processed: self.files_processed,
+            warnings: self.warnings,
+            errors: self.errors,
+            build_time_ms: self.build_time.as_millis(),
+            error_messages: self
+                .error_details
+                .iter()
+                .map(|error| match error.line {
+                    Some(line) => {
+                        format!("{}:{}: {}", error.file.display(), line, error.message)
+                    }
+                    None => format!("{}: {}", error.file.display(), error.message),
+                })
+                .collect(),
+        }
+    }
+
+    /// Whether any cross-reference failed to resolve under `--strict-refs`
+    pub fn has_broken_references(&self) -> bool {
+        self.error_details
+            .iter()
+            .any(|error| matches!(error.error_type, ErrorType::BrokenCrossReference))
+    }
+
+    /// Write all warnings, then all errors, one per line in Sphinx's
+    /// `file:line: WARNING: message` format; `color` highlights the severity
+    pub fn write_diagnostics(&self, out: &mut impl Write, color: bool) -> io::Result<()> {
+        let warnings = self
+            .warning_details
+            .iter()
+            .map(|warning| (&warning.file, warning.line, &warning.message, "WARNING"));
+        let errors = self
+            .error_details
+            .iter()
+            .map(|error| (&error.file, error.line, &error.message, "ERROR"));
+
+        for (file, line, message, severity) in warnings.chain(errors) {
+            let location = match line {
+                Some(line) => format!("{}:{}", file.display(), line),
+                None => file.display().to_string(),
+            };
+            let severity = match (color, severity) {
+                (false, _) => severity.to_string(),
+                (true, "WARNING") => format!("{}{}{}", ANSI_YELLOW, severity, ANSI_RESET),
+                (true, _) => format!("{}{}{}", ANSI_RED, severity, ANSI_RESET),
+            };
+            writeln!(out, "{}: {}: {}", location, severity, message)?;
+        }
+
+        Ok(())
+    }
+
+    /// The closing line of a build, e.g. `build succeeded, 2 warnings.`
+    pub fn summary(&self) -> String {
+        let status = if self.errors > 0 {
+            "build succeeded with problems"
+        } else {
+            "build succeeded"
+        };
+        match (self.warnings, self.errors) {
+            (0, 0) => format!("{}.", status),
+            (warnings, 0) => format!("{}, {} warnings.", status, warnings),
+            (0, errors) => format!("{}, {} errors.", status, errors),
+            (warnings, errors) => {
+                format!("{}, {} warnings, {} errors.", status, warnings, errors)
+            }
+        }
+    }
+
+    /// Write the diagnostics followed by the summary line
+    pub fn write_report(&self, out: &mut impl Write, color: bool) -> io::Result<()> {
+        self.write_diagnostics(out, color)?;
+
+        let summary = self.summary();
+        if !color {
+            writeln!(out, "{}", summary)
+        } else if self.errors > 0 {
+            writeln!(out, "{}{}{}", ANSI_RED, summary, ANSI_RESET)
+        } else if self.warnings > 0 {
+            writeln!(out, "{}{}{}", ANSI_YELLOW, summary, ANSI_RESET)
+        } else {
+            writeln!(out, "{}{}{}", ANSI_GREEN, summary, ANSI_RESET)
+        }
+    }
+}
+
+const ANSI_RED: &str = "\x1b[31m";
+const ANSI_YELLOW: &str = "\x1b[33m";
+const ANSI_GREEN: &str = "\x1b[32m";
+const ANSI_RESET: &str = "\x1b[0m";
+
+/// A parsed document and whether it came from the cache
+type LoadedDocument = (Document, bool);
+
+/// Warnings and errors recorded by one task of a parallel phase; merged into
+/// the builder's collections once the phase is done, so workers never share a lock
+#[derive(Debug, Default)]
+struct Diagnostics {
+    warnings: Vec<BuildWarning>,
+    errors: Vec<BuildErrorReport>,
+}
+
+/// Rewrites a generated page before it is written; gets the page's output path
+pub type OutputTransform = Box<dyn Fn(&Path, &mut String) + Send + Sync>;
+
+/// How documents map to HTML pages, as Sphinx's `html`, `dirhtml` and
+/// `singlehtml` builders
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PageLayout {
+    /// A page per document at `docname.html`
+    #[default]
+    Flat,
+    /// A page per document at `docname/index.html`, linked as `docname/`
+    Directory,
+    /// All documents on the root document's page, in toctree order
+    Single,
+}
+
 pub struct SphinxBuilder {
     config: BuildConfig,
     source_dir: PathBuf,
     output_dir: PathBuf,
+    extra_output_dirs: Vec<PathBuf>,
     cache: BuildCache,
     parser: Parser,
+    renderer: HtmlRenderer,
     parallel_jobs: usize,
     incremental: bool,
+    lint: bool,
+    strict_refs: bool,
+    layout: PageLayout,
+    /// Docname globs limiting which documents are rendered; empty renders all
+    only: Vec<glob::Pattern>,
+    /// Compiled `exclude_patterns`, matched against source-relative paths
+    exclude: Vec<glob::Pattern>,
+    tags: Tags,
+    writers: tokio::sync::Mutex<Vec<Box<dyn DocumentWriter>>>,
+    /// Named output transforms, applied in registration order
+    output_transforms: Vec<(String, OutputTransform)>,
+    documents: Mutex<Arc<DocumentQuery>>,
+    /// Inventories of `intersphinx_mapping`'s projects, loaded by the last build
+    inventories: Mutex<Arc<HashMap<String, Inventory>>>,
+    /// Stats of the latest finished build, for embedders that rebuild repeatedly
+    last_stats: watch::Sender<Option<Arc<BuildStats>>>,
     warnings: Arc<Mutex<Vec<BuildWarning>>>,
     errors: Arc<Mutex<Vec<BuildErrorReport>>>,
-    #[allow(dead_code)]
     sphinx_app: Option<SphinxApp>,
     #[allow(dead_code)]
     extension_loader: ExtensionLoader,
@@ -45,10 +226,46 @@ pub struct SphinxBuilder {
 
 impl SphinxBuilder {
     pub fn new(config: BuildConfig, source_dir: PathBuf, output_dir: PathBuf) -> Result<Self> {
-        let cache_dir = output_dir.join(".sphinx-ultra-cache");
+        let cache_dir = output_dir.join(CACHE_DIR_NAME);
         let cache = BuildCache::new(cache_dir)?;
 
-        let parser = Parser::new(&config)?;
+        let mut parser = Parser::new(&config)?;
+        parser.set_source_root(source_dir.clone());
+        let mut renderer = HtmlRenderer::new(&config);
+        renderer.set_source_root(source_dir.clone());
+        let tags = renderer.tags().clone();
+
+        let mut output_transforms: Vec<(String, OutputTransform)> = Vec::new();
+        for replacement in &config.output_replacements {
+            let regex = regex::Regex::new(&replacement.pattern).map_err(|e| {
+                BuildError::Config(format!(
+                    "Invalid output_replacements pattern '{}': {}",
+                    replacement.pattern, e
+                ))
+            })?;
+            let with = replacement.replacement.clone();
+            output_transforms.push((
+                format!("replace '{}'", replacement.pattern),
+                Box::new(move |_, html| {
+                    if let Cow::Owned(replaced) = regex.replace_all(html, with.as_str()) {
+                        *html = replaced;
+                    }
+                }),
+            ));
+        }
+
+        let exclude = config
+            .exclude_patterns
+            .iter()
+            .map(|pattern| {
+                glob::Pattern::new(pattern).map_err(|e| {
+                    BuildError::Config(format!(
+                        "Invalid exclude_patterns entry '{}': {}",
+                        pattern, e
+                    ))
+                })
+            })
+            .collect::<std::result::Result<_, _>>()?;
 
         let parallel_jobs = config.parallel_jobs.unwrap_or_else(|| {
             std::thread::available_parallelism()
@@ -78,10 +295,23 @@ impl SphinxBuilder {
             config,
             source_dir,
             output_dir,
+            extra_output_dirs: Vec::new(),
             cache,
             parser,
+            renderer,
             parallel_jobs,
             incremental: false,
+            lint: false,
+            strict_refs: false,
+            layout: PageLayout::Flat,
+            only: Vec::new(),
+            exclude,
+            tags,
+            writers: tokio::sync::Mutex::new(Vec::new()),
+            output_transforms,
+            documents: Mutex::new(Arc::new(DocumentQuery::default())),
+            inventories: Mutex::new(Arc::new(HashMap::new())),
+            last_stats: watch::channel(None).0,
             warnings: Arc::new(Mutex::new(Vec::new())),
             errors: Arc::new(Mutex::new(Vec::new())),
             sphinx_app: Some(sphinx_app),
@@ -97,18 +327,119 @@ impl SphinxBuilder {
         self.incremental = true;
     }
 
+    /// Copy the finished site to an additional directory after each build
+    pub fn add_output_dir(&mut self, dir: PathBuf) {
+        self.extra_output_dirs.push(dir);
+    }
+
+    /// Rewrite every generated page before it is written, after any earlier
+    /// transforms and the configured `output_replacements`
+    pub fn add_output_transform<F>(&mut self, name: &str, transform: F)
+    where
+        F: Fn(&Path, &mut String) + Send + Sync + 'static,
+    {
+        self.output_transforms
+            .push((name.to_string(), Box::new(transform)));
+    }
+
+    /// Report source lint warnings (tabs, short underlines, long lines, ...)
+    pub fn enable_lint(&mut self) {
+        self.lint = true;
+    }
+
+    /// Lay pages out as `layout`; pages are rendered with `.html` links, which
+    /// the directory and single-page layouts rewrite
+    pub fn set_page_layout(&mut self, layout: PageLayout) {
+        if layout != PageLayout::Flat {
+            self.renderer.set_link_suffix(".html");
+        }
+        self.layout = layout;
+    }
+
+    /// Set the tags of the active builder, as Sphinx does: its name and format,
+    /// and `builder_<name>` / `format_<format>`, on top of the configured tags
+    pub fn set_builder_tags(&mut self, name: &str, format: &str) {
+        self.tags = Tags::for_builder(&self.config.tags, name, format);
+        self.renderer.set_tags(self.tags.clone());
+    }
+
+    /// Report unresolved `:doc:` and `:ref:` targets as errors instead of warnings
+    pub fn enable_strict_refs(&mut self) {
+        self.strict_refs = true;
+    }
+
+    /// Render only the documents whose docname or source path matches one of
+    /// `patterns`, plus the toctree ancestors linking to them; other pages keep
+    /// their existing output
+    pub fn set_only(&mut self, patterns: &[String]) -> Result<()> {
+        self.only = patterns
+            .iter()
+            .map(|pattern| {
+                glob::Pattern::new(pattern).map_err(|e| {
+                    BuildError::Config(format!("Invalid --only pattern '{}': {}", pattern, e))
+                })
+            })
+            .collect::<std::result::Result<_, _>>()?;
+        Ok(())
+    }
+
+    /// Hand every built document to an additional output format
+    pub fn add_writer(&mut self, writer: Box<dyn DocumentWriter>) {
+        self.writers.get_mut().push(writer);
+    }
+
+    /// Follow the stats of each finished build; the receiver holds the latest
+    /// (`None` before the first build completes)
+    pub fn subscribe_stats(&self) -> watch::Receiver<Option<Arc<BuildStats>>> {
+        self.last_stats.subscribe()
+    }
+
+    /// Stats of the latest finished build
+    pub fn last_stats(&self) -> Option<Arc<BuildStats>> {
+        self.last_stats.borrow().clone()
+    }
+
+    /// Query the documents parsed by the last build
+    pub fn documents(&self) -> Arc<DocumentQuery> {
+        self.documents.lock().unwrap().clone()
+    }
+
+    /// Inventories of other projects, by their `intersphinx_mapping` name,
+    /// as loaded by the last build
+    pub fn inventories(&self) -> Arc<HashMap<String, Inventory>> {
+        self.inventories.lock().unwrap().clone()
+    }
+
     /// Add a warning to the collection
-    #[allow(dead_code)]
     pub fn add_warning(&self, warning: BuildWarning) {
         self.warnings.lock().unwrap().push(warning);
     }
 
     /// Add an error to the collection
-    #[allow(dead_code)]
     pub fn add_error(&self, error: BuildErrorReport) {
         self.errors.lock().unwrap().push(error);
     }
 
+    /// Load the inventories of `intersphinx_mapping`, warning about those
+    /// that can't be fetched
+    async fn load_inventories(&self) {
+        let (inventories, failures) = intersphinx::load_inventories(
+            &self.config,
+            self.source_dir.clone(),
+            &self.output_dir.join(CACHE_DIR_NAME),
+        )
+        .await;
+        for failure in failures {
+            self.add_warning(BuildWarning::new(
+                self.source_dir.clone(),
+                None,
+                failure,
+                WarningType::Other,
+            ));
+        }
+        *self.inventories.lock().unwrap() = Arc::new(inventories);
+    }
+
     /// Check if warnings should be treated as errors
     #[allow(dead_code)]
     pub fn should_fail_on_warning(&self) -> bool {
@@ -125,13 +456,29 @@ impl SphinxBuilder {
     pub async fn build(&self) -> Result<BuildStats> {
         let start_time = Instant::now();
         info!("Starting build process...");
+        // Stats report this build only, also when the builder is reused
+        self.warnings.lock().unwrap().clear();
+        self.errors.lock().unwrap().clear();
 
         // Ensure output directory exists
         tokio::fs::create_dir_all(&self.output_dir).await?;
 
+        if !self.config.intersphinx_mapping.is_empty() {
+            self.load_inventories().await;
+        }
+
         // Discover all source files
         let source_files = self.discover_source_files().await?;
         info!("Discovered {} source files", source_files.len());
+        let source_files = self.drop_output_collisions(source_files)?;
+        if !self.output_transforms.is_empty() {
+            let names: Vec<&str> = self
+                .output_transforms
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .collect();
+            info!("Output transforms, in order: {}", names.join(", "));
+        }
 
         // Build dependency graph
         let dependency_graph = self.build_dependency_graph(&source_files).await?;
@@ -144,22 +491,49 @@ impl SphinxBuilder {
         let processed_docs = self
             .process_files_parallel(&source_files, &dependency_graph)
             .await?;
+        *self.documents.lock().unwrap() = Arc::new(DocumentQuery::new(
+            processed_docs
+                .iter()
+                .map(|doc| (self.docname(doc), doc.clone())),
+        ));
 
         // Validate documents and collect warnings/errors
         self.validate_documents(&processed_docs, &source_files)
             .await?;
+        self.validate_cross_references(&processed_docs);
+
+        if self.layout == PageLayout::Single {
+            self.write_single_page(&processed_docs)?;
+        }
 
         // Generate cross-references and indices
         self.generate_indices(&processed_docs).await?;
 
-        // Copy static assets
+        // Copy static assets and the images documents show
         self.copy_static_assets().await?;
+        self.copy_images(&processed_docs).await?;
+
+        // Check that every referenced image made it into the output
+        self.validate_images(&processed_docs).await?;
 
         // Generate sitemap and search index
         self.generate_search_index(&processed_docs).await?;
 
+        // Write redirect pages for moved documents
+        self.write_redirects(&processed_docs).await?;
+
+        // Record page hashes for HTTP caching
+        self.write_build_manifest(&processed_docs).await?;
+
+        // Drive any registered output formats
+        self.run_writers(&processed_docs).await?;
+
+        // Sync the finished site to any additional output directories
+        self.sync_output_dirs().await?;
+
         let build_time = start_time.elapsed();
         let output_size = utils::calculate_directory_size(&self.output_dir).await?;
+        self.check_size_budgets(&processed_docs, output_size);
 
         let warnings = self.warnings.lock().unwrap();
         let errors = self.errors.lock().unwrap();
@@ -177,9 +551,267 @@ impl SphinxBuilder {
         };
 
         info!("Build completed in {:?}", build_time);
+        self.last_stats.send_replace(Some(Arc::new(stats.clone())));
         Ok(stats)
     }
 
+    /// Parse every source file into the cache without rendering anything, so
+    /// that parse problems surface early and a following incremental build
+    /// only has to render
+    ///
+    /// Documents already cached and unchanged are not parsed again.
+    pub async fn prebuild(&self) -> Result<BuildStats> {
+        let start_time = Instant::now();
+        info!("Parsing documents into the cache...");
+        self.warnings.lock().unwrap().clear();
+        self.errors.lock().unwrap().clear();
+
+        let source_files = self.discover_source_files().await?;
+        let source_files = self.drop_output_collisions(source_files)?;
+
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(self.parallel_jobs)
+            .build()?;
+        let parsed: Vec<(bool, Diagnostics)> = pool.install(|| {
+            source_files
+                .par_iter()
+                .map(|file_path| {
+                    if let Ok(cached_doc) = self.cache.get_document(file_path) {
+                        if cached_doc.source_mtime >= utils::get_file_mtime(file_path)? {
+                            return Ok((false, Diagnostics::default()));
+                        }
+                    }
+                    let (document, diagnostics) = self.parse_single_file(file_path)?;
+                    self.cache.store_parsed(file_path, &document)?;
+                    Ok((true, diagnostics))
+                })
+                .collect::<Result<_>>()
+        })?;
+        let (parsed, diagnostics): (Vec<_>, Vec<_>) = parsed.into_iter().unzip();
+        self.merge_diagnostics(diagnostics);
+
+        let build_time = start_time.elapsed();
+        let warnings = self.warnings.lock().unwrap();
+        let errors = self.errors.lock().unwrap();
+        let files_processed = parsed.iter().filter(|&&parsed| parsed).count();
+        debug!(
+            "Parsed {} documents ({} already cached) in {:?}",
+            files_processed,
+            source_files.len() - files_processed,
+            build_time
+        );
+
+        Ok(BuildStats {
+            files_processed,
+            files_skipped: source_files.len() - files_processed,
+            build_time,
+            output_size_mb: 0.0,
+            cache_hits: self.cache.hit_count(),
+            errors: errors.len(),
+            warnings: warnings.len(),
+            warning_details: warnings.clone(),
+            error_details: errors.clone(),
+        })
+    }
+
+    /// Run each registered writer through init, one write_doc per document, and finish
+    async fn run_writers(&self, documents: &[Document]) -> Result<()> {
+        let mut writers = self.writers.lock().await;
+        for writer in writers.iter_mut() {
+            info!("Writing {} output", writer.name());
+            writer.init().await?;
+            for doc in documents {
+                writer.write_doc(&self.docname(doc), doc).await?;
+            }
+            writer.finish().await?;
+        }
+        Ok(())
+    }
+
+    /// Write a meta-refresh page at the old path of every configured redirect
+    /// whose target is a document of this build
+    async fn write_redirects(&self, documents: &[Document]) -> Result<()> {
+        let docnames: HashSet<String> = documents.iter().map(|doc| self.docname(doc)).collect();
+        let mut redirects: Vec<(&String, &String)> = self.config.redirects.iter().collect();
+        redirects.sort();
+
+        for (from, to) in redirects {
+            // The old name becomes a path below the output directory
+            let inside_output = !from.is_empty()
+                && !from.contains('\\')
+                && Path::new(from)
+                    .components()
+                    .all(|component| matches!(component, std::path::Component::Normal(_)));
+            if !inside_output {
+                self.add_warning(BuildWarning::new(
+                    self.source_dir.clone(),
+                    None,
+                    format!(
+                        "redirect from '{}' not written: it must be a relative docname without '..'",
+                        from
+                    ),
+                    WarningType::Other,
+                ));
+                continue;
+            }
+            let target = to.split('#').next().unwrap_or(to);
+            if !docnames.contains(target) {
+                self.add_warning(BuildWarning::broken_redirect(
+                    self.source_dir.clone(),
+                    from,
+                    to,
+                ));
+                continue;
+            }
+            if docnames.contains(from) {
+                self.add_warning(BuildWarning::new(
+                    self.source_dir.clone(),
+                    None,
+                    format!(
+                        "redirect from '{}' not written: '{}' is still a document",
+                        from, from
+                    ),
+                    WarningType::Other,
+                ));
+                continue;
+            }
+
+            // A single-page build still needs one redirect file per old name
+            let layout = match self.layout {
+                PageLayout::Single => PageLayout::Flat,
+                layout => layout,
+            };
+            let redirect_uri = self.page_uri(from, layout);
+            let target = self.page_link(to, self.layout);
+            let href = utils::relative_uri(&redirect_uri, &target, "");
+            let canonical = match &self.config.html_baseurl {
+                Some(baseurl) => format!("{}/{}", baseurl.trim_end_matches('/'), target),
+                None => href.clone(),
+            };
+            let page = format!(
+                "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Redirecting&hellip;</title>\n<link rel=\"canonical\" href=\"{canonical}\">\n<meta http-equiv=\"refresh\" content=\"0; url={href}\">\n</head>\n<body>\n<p>This page has moved to <a href=\"{href}\">{href}</a>.</p>\n</body>\n</html>\n",
+                canonical = html_escape::encode_double_quoted_attribute(&canonical),
+                href = html_escape::encode_double_quoted_attribute(&href),
+            );
+
+            let output_path = self.output_dir.join(redirect_uri);
+            if let Some(parent) = output_path.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            tokio::fs::write(&output_path, page).await?;
+            debug!("Redirect {} -> {}", from, to);
+        }
+        Ok(())
+    }
+
+    /// Warn about pages over `page_size_budget_kb` and output over `output_size_budget_mb`
+    fn check_size_budgets(&self, documents: &[Document], output_size: u64) {
+        if let Some(budget_kb) = self.config.page_size_budget_kb {
+            for doc in documents {
+                let Ok(output_path) = self.get_output_path(&doc.source_path) else {
+                    continue;
+                };
+                let Ok(metadata) = std::fs::metadata(&output_path) else {
+                    continue;
+                };
+                if metadata.len() > budget_kb * 1024 {
+                    let page = output_path
+                        .strip_prefix(&self.output_dir)
+                        .unwrap_or(&output_path)
+                        .to_string_lossy()
+                        .replace('\\', "/");
+                    self.add_warning(BuildWarning::page_size_budget(
+                        doc.source_path.clone(),
+                        &page,
+                        metadata.len().div_ceil(1024),
+                        budget_kb,
+                    ));
+                }
+            }
+        }
+
+        if let Some(budget_mb) = self.config.output_size_budget_mb {
+            let size_mb = output_size as f64 / 1024.0 / 1024.0;
+            if size_mb > budget_mb {
+                self.add_warning(BuildWarning::new(
+                    self.output_dir.clone(),
+                    None,
+                    format!(
+                        "output is {:.2} MB, over the output size budget of {} MB",
+                        size_mb, budget_mb
+                    ),
+                    WarningType::SizeBudget,
+                ));
+            }
+        }
+    }
+
+    /// Write `.manifest.json` mapping each page (relative to the output directory)
+    /// to an ETag of its content, for servers answering conditional requests
+    async fn write_build_manifest(&self, documents: &[Document]) -> Result<()> {
+        let mut pages = BTreeMap::new();
+        for doc in documents {
+            let output_path = self.get_output_path(&doc.source_path)?;
+            let content = match tokio::fs::read(&output_path).await {
+                Ok(content) => content,
+                // Left out of an `--only` build and never built before
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound && !self.only.is_empty() => {
+                    continue
+                }
+                Err(e) => return Err(e.into()),
+            };
+            let page = output_path
+                .strip_prefix(&self.output_dir)?
+                .to_string_lossy()
+                .replace('\\', "/");
+            pages.insert(
+                page,
+                serde_json::json!({ "etag": utils::content_etag(&content) }),
+            );
+        }
+
+        let manifest = serde_json::json!({ "pages": pages });
+        tokio::fs::write(
+            self.output_dir.join(BUILD_MANIFEST_NAME),
+            serde_json::to_string_pretty(&manifest)?,
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn sync_output_dirs(&self) -> Result<()> {
+        // Check every target before touching any of them. Paths are resolved,
+        // so `..` and symlinks can't hide nesting
+        let output_dir = utils::resolve_path(&self.output_dir)?;
+        let source_dir = utils::resolve_path(&self.source_dir)?;
+        for dir in &self.extra_output_dirs {
+            let target = utils::resolve_path(dir)?;
+            let problem = if target.starts_with(&output_dir) {
+                Some(("is inside the output directory", &self.output_dir))
+            } else if output_dir.starts_with(&target) {
+                Some(("contains the output directory", &self.output_dir))
+            } else if source_dir.starts_with(&target) {
+                Some(("contains the source directory", &self.source_dir))
+            } else {
+                None
+            };
+            if let Some((problem, other)) = problem {
+                return Err(anyhow::anyhow!(
+                    "Additional output directory {} {} {}",
+                    dir.display(),
+                    problem,
+                    other.display()
+                ));
+            }
+        }
+
+        for dir in &self.extra_output_dirs {
+            let copied = utils::sync_dir(&self.output_dir, dir, &[CACHE_DIR_NAME]).await?;
+            info!("Synced {} changed files to {}", copied, dir.display());
+        }
+        Ok(())
+    }
+
     async fn discover_source_files(&self) -> Result<Vec<PathBuf>> {
         // For now, use a simple synchronous approach to avoid async recursion issues
         let mut files = Vec::new();
@@ -187,11 +819,60 @@ impl SphinxBuilder {
         Ok(files)
     }
 
+    /// Keep only the first source (in path order) of each output path, reporting
+    /// an error for every other source that would overwrite it
+    fn drop_output_collisions(&self, mut files: Vec<PathBuf>) -> Result<Vec<PathBuf>> {
+        files.sort();
+        let mut outputs: HashMap<PathBuf, PathBuf> = HashMap::new();
+        let mut kept = Vec::with_capacity(files.len());
+
+        // Every document lands on the single page; only flat names can clash
+        let layout = match self.layout {
+            PageLayout::Single => PageLayout::Flat,
+            layout => layout,
+        };
+        for file in files {
+            let output_path = self.page_path(&file, layout)?;
+            match outputs.get(&output_path) {
+                Some(first) => {
+                    let output = output_path
+                        .strip_prefix(&self.output_dir)
+                        .unwrap_or(&output_path);
+                    self.add_error(BuildErrorReport::output_collision(
+                        file.clone(),
+                        first,
+                        output,
+                    ));
+                }
+                None => {
+                    outputs.insert(output_path, file.clone());
+                    kept.push(file);
+                }
+            }
+        }
+
+        Ok(kept)
+    }
+
     fn discover_files_sync(&self, dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
         for entry in std::fs::read_dir(dir)? {
             let entry = entry?;
             let path = entry.path();
 
+            // Extra files, e.g. robots.txt, are copied, not built
+            if self
+                .config
+                .html_extra_path
+                .iter()
+                .any(|extra| path == self.source_dir.join(extra))
+            {
+                continue;
+            }
+            if self.is_excluded(&path) {
+                debug!("Excluded by exclude_patterns: {}", path.display());
+                continue;
+            }
+
             if path.is_dir() {
                 // Skip hidden directories and build artifacts
                 if let Some(name) = path.file_name() {
@@ -211,33 +892,139 @@ impl SphinxBuilder {
         Ok(())
     }
 
+    /// Whether `path` matches one of the `exclude_patterns`; `*` does not
+    /// cross directories, `**` does
+    fn is_excluded(&self, path: &Path) -> bool {
+        let Ok(relative) = path.strip_prefix(&self.source_dir) else {
+            return false;
+        };
+        let relative = relative.to_string_lossy().replace('\\', "/");
+        let options = glob::MatchOptions {
+            require_literal_separator: true,
+            ..glob::MatchOptions::new()
+        };
+        self.exclude
+            .iter()
+            .any(|pattern| pattern.matches_with(&relative, options))
+    }
+
     fn is_source_file(&self, path: &Path) -> bool {
-        if let Some(ext) = path.extension() {
-            matches!(ext.to_string_lossy().as_ref(), "rst" | "md" | "txt")
-        } else {
-            false
+        self.config.source_suffix_of(path).is_some()
+    }
+
+    /// `path` with its configured source suffix removed
+    fn strip_source_suffix(&self, path: &Path) -> PathBuf {
+        match self.config.source_suffix_of(path) {
+            Some(suffix) => {
+                let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+                path.with_file_name(&file_name[..file_name.len() - suffix.len()])
+            }
+            None => path.with_extension(""),
         }
     }
 
+    /// Map each source file to the files it depends on: those its `include`
+    /// and `literalinclude` directives read, directly or through other
+    /// included files, and the documents its toctrees list
     async fn build_dependency_graph(
         &self,
         files: &[PathBuf],
     ) -> Result<HashMap<PathBuf, Vec<PathBuf>>> {
-        let mut graph = HashMap::new();
+        let by_docname: HashMap<String, &PathBuf> = files
+            .iter()
+            .map(|file| (self.docname_of(file), file))
+            .collect();
 
-        // For now, simple implementation - process files in alphabetical order
-        // TODO: Parse files to find actual dependencies (includes, references, etc.)
+        let documents: HashSet<&PathBuf> = files.iter().collect();
+        let mut graph = HashMap::new();
         for file in files {
-            graph.insert(file.clone(), Vec::new());
+            let content = self.parser.read_source(file)?;
+            let mut dependencies = self.source_dependencies(file, &content, &by_docname);
+
+            // Included files are parsed into the document, so whatever they
+            // include is a dependency too
+            let mut i = 0;
+            while i < dependencies.len() {
+                let dependency = dependencies[i].clone();
+                i += 1;
+                if documents.contains(&dependency) {
+                    continue;
+                }
+                let Ok(included) = self.parser.read_source(&dependency) else {
+                    continue;
+                };
+                for nested in self.source_dependencies(&dependency, &included, &HashMap::new()) {
+                    if !dependencies.contains(&nested) && nested != *file {
+                        dependencies.push(nested);
+                    }
+                }
+            }
+            graph.insert(file.clone(), dependencies);
         }
 
         Ok(graph)
     }
 
+    /// Scan a source for the files it includes and the documents its toctrees
+    /// list, without parsing it
+    fn source_dependencies(
+        &self,
+        file: &Path,
+        content: &str,
+        by_docname: &HashMap<String, &PathBuf>,
+    ) -> Vec<PathBuf> {
+        let docname = self.docname_of(file);
+        let indentation = |line: &str| line.len() - line.trim_start().len();
+        let lines: Vec<&str> = content.lines().collect();
+
+        let mut dependencies = Vec::new();
+        for (i, line) in lines.iter().enumerate() {
+            let Some((name, argument)) = line
+                .trim_start()
+                .strip_prefix("..")
+                .and_then(|rest| rest.split_once("::"))
+            else {
+                continue;
+            };
+            match name.trim() {
+                "include" | "literalinclude" => {
+                    // `<name>` includes come with docutils, not the project
+                    let argument = argument.trim();
+                    if argument.is_empty() || argument.starts_with('<') {
+                        continue;
+                    }
+                    dependencies.push(match argument.strip_prefix('/') {
+                        Some(rooted) => self.source_dir.join(rooted),
+                        None => file.parent().unwrap_or(&self.source_dir).join(argument),
+                    });
+                }
+                "toctree" => {
+                    let body = lines[i + 1..].iter().take_while(|body_line| {
+                        body_line.trim().is_empty() || indentation(body_line) > indentation(line)
+                    });
+                    for entry in parse_toctree_entries(body.copied()) {
+                        if entry.external {
+                            continue;
+                        }
+                        let child = utils::docname_join(&docname, &entry.target);
+                        if let Some(source) = by_docname
+                            .get(&child)
+                            .or_else(|| by_docname.get(&format!("{}/index", child)))
+                        {
+                            dependencies.push((*source).clone());
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        dependencies
+    }
+
     async fn process_files_parallel(
         &self,
         files: &[PathBuf],
-        _dependency_graph: &HashMap<PathBuf, Vec<PathBuf>>,
+        dependency_graph: &HashMap<PathBuf, Vec<PathBuf>>,
     ) -> Result<Vec<Document>> {
         info!(
             "Processing {} files with {} parallel jobs",
@@ -250,17 +1037,147 @@ impl SphinxBuilder {
             .num_threads(self.parallel_jobs)
             .build()?;
 
-        let documents: Result<Vec<_>, _> = pool.install(|| {
+        let templates_mtime = self.newest_template_mtime();
+        let files = dependency_order(files, dependency_graph);
+        let loaded: Vec<(LoadedDocument, Diagnostics)> = pool.install(|| {
             files
                 .par_iter()
-                .map(|file_path| self.process_single_file(file_path))
-                .collect()
-        });
+                .map(|file_path| {
+                    let dependencies = dependency_graph
+                        .get(file_path)
+                        .map(Vec::as_slice)
+                        .unwrap_or_default();
+                    self.load_single_file(file_path, dependencies, templates_mtime)
+                })
+                .collect::<Result<_>>()
+        })?;
+        let (loaded, diagnostics): (Vec<_>, Vec<_>) = loaded.into_iter().unzip();
+        self.merge_diagnostics(diagnostics);
+
+        let (loaded, excluded) = self.exclude_tagged_documents(loaded)?;
+        let selected = self.select_documents(&loaded);
+        let env = self.toctree_environment(&loaded);
+
+        let rendered: Vec<(Document, Diagnostics)> = pool.install(|| {
+            loaded
+                .into_par_iter()
+                .map(|(document, cached)| {
+                    let skipped = selected
+                        .as_ref()
+                        .is_some_and(|selected| !selected.contains(&self.docname(&document)));
+                    if cached || skipped {
+                        Ok((document, Diagnostics::default()))
+                    } else {
+                        self.render_single_file(document, &excluded, &env)
+                    }
+                })
+                .collect::<Result<_>>()
+        })?;
+        let (documents, diagnostics): (Vec<_>, Vec<_>) = rendered.into_iter().unzip();
+        self.merge_diagnostics(diagnostics);
+
+        Ok(documents)
+    }
 
-        documents
+    /// An environment holding the title and toctrees of every document, for
+    /// resolving toctrees while rendering
+    fn toctree_environment(&self, documents: &[LoadedDocument]) -> BuildEnvironment {
+        let mut env = BuildEnvironment::new(self.config.clone());
+        env.note_documents(
+            documents
+                .iter()
+                .map(|(document, _)| (self.docname(document), document)),
+        );
+        env
+    }
+
+    /// Docnames to render under `--only`: the matching documents and every
+    /// document whose toctree leads to one of them; `None` renders everything
+    fn select_documents(&self, documents: &[LoadedDocument]) -> Option<HashSet<String>> {
+        if self.only.is_empty() {
+            return None;
+        }
+
+        let mut parents: HashMap<String, Vec<String>> = HashMap::new();
+        let mut selected = Vec::new();
+        for (document, _) in documents {
+            let docname = self.docname(document);
+            let relative = document
+                .source_path
+                .strip_prefix(&self.source_dir)
+                .unwrap_or(&document.source_path)
+                .to_string_lossy()
+                .replace('\\', "/");
+            if self
+                .only
+                .iter()
+                .any(|pattern| pattern.matches(&docname) || pattern.matches(&relative))
+            {
+                selected.push(docname.clone());
+            }
+
+            for entry in self
+                .extract_toctree_references(document)
+                .unwrap_or_default()
+            {
+                let child = utils::docname_join(&docname, &entry);
+                for child in [format!("{}/index", child), child] {
+                    parents.entry(child).or_default().push(docname.clone());
+                }
+            }
+        }
+
+        let matched = selected.len();
+        let mut keep: HashSet<String> = HashSet::new();
+        while let Some(docname) = selected.pop() {
+            if keep.insert(docname.clone()) {
+                selected.extend(parents.get(&docname).into_iter().flatten().cloned());
+            }
+        }
+        info!(
+            "Rendering {} of {} documents ({} matching --only, plus their toctree parents)",
+            keep.len(),
+            documents.len(),
+            matched
+        );
+        Some(keep)
+    }
+
+    /// Fold the per-task diagnostics of a parallel phase into the build's
+    /// collections, in source order
+    fn merge_diagnostics(&self, diagnostics: Vec<Diagnostics>) {
+        let mut warnings = self.warnings.lock().unwrap();
+        let mut errors = self.errors.lock().unwrap();
+        for diagnostics in diagnostics {
+            warnings.extend(diagnostics.warnings);
+            errors.extend(diagnostics.errors);
+        }
     }
 
-    fn process_single_file(&self, file_path: &Path) -> Result<Document> {
+    /// Newest modification time of the project's templates, which every page
+    /// depends on
+    fn newest_template_mtime(&self) -> DateTime<Utc> {
+        let dirs: Vec<PathBuf> = self
+            .config
+            .templates_path
+            .iter()
+            .chain(&self.config.template_dirs)
+            .map(|dir| self.source_dir.join(dir))
+            .collect();
+        TemplateEngine::newest_mtime(&dirs).into()
+    }
+
+    /// Read and parse a source file, or take it from the cache
+    ///
+    /// A cached document not rendered since `templates_mtime`, or never
+    /// rendered, is returned for rendering again. One with a dependency
+    /// modified since it was rendered is parsed again.
+    fn load_single_file(
+        &self,
+        file_path: &Path,
+        dependencies: &[PathBuf],
+        templates_mtime: DateTime<Utc>,
+    ) -> Result<(LoadedDocument, Diagnostics)> {
         let relative_path = file_path.strip_prefix(&self.source_dir)?;
         debug!("Processing file: {}", relative_path.display());
 
@@ -268,52 +1185,336 @@ impl SphinxBuilder {
         if self.incremental {
             if let Ok(cached_doc) = self.cache.get_document(file_path) {
                 let file_mtime = utils::get_file_mtime(file_path)?;
-                if cached_doc.source_mtime >= file_mtime {
-                    debug!("Using cached version of {}", relative_path.display());
-                    return Ok(cached_doc);
+                let rendered_at = self.cache.rendered_at(file_path);
+                let changed_dependency = dependencies.iter().find(|dependency| {
+                    rendered_at.is_some_and(|rendered_at| {
+                        utils::get_file_mtime(dependency).is_ok_and(|mtime| mtime > rendered_at)
+                    })
+                });
+                if let Some(dependency) = changed_dependency {
+                    debug!(
+                        "Rebuilding {}: {} changed",
+                        relative_path.display(),
+                        dependency.display()
+                    );
+                } else if cached_doc.source_mtime >= file_mtime {
+                    let rendered =
+                        rendered_at.is_some_and(|rendered_at| rendered_at >= templates_mtime);
+                    if rendered {
+                        debug!("Using cached version of {}", relative_path.display());
+                    } else {
+                        debug!(
+                            "Rendering cached {}: not rendered since the templates changed",
+                            relative_path.display()
+                        );
+                    }
+                    return Ok(((cached_doc, rendered), Diagnostics::default()));
                 }
             }
         }
 
-        // Read and parse the file
-        let content = std::fs::read_to_string(file_path)?;
-        let document = self.parser.parse(file_path, &content)?;
+        let (document, diagnostics) = self.parse_single_file(file_path)?;
+        Ok(((document, false), diagnostics))
+    }
 
-        // Simple document rendering (placeholder)
-        let rendered_html = format!(
-            "<html><body>{}</body></html>",
-            html_escape::encode_text(&document.content.to_string())
-        );
+    /// Read, lint and parse a source file
+    fn parse_single_file(&self, file_path: &Path) -> Result<(Document, Diagnostics)> {
+        let content = self.parser.read_source(file_path)?;
+        let mut diagnostics = Diagnostics::default();
+        if self.lint {
+            let max_line_length = self
+                .config
+                .lint_max_line_length
+                .unwrap_or(lint::DEFAULT_MAX_LINE_LENGTH);
+            diagnostics.warnings = lint::lint_source(file_path, &content, max_line_length);
+        }
+        let (document, warnings, errors) =
+            self.parser.parse_with_diagnostics(file_path, &content)?;
+        diagnostics.warnings.extend(warnings);
+        diagnostics.errors.extend(errors);
 
-        // Write output file
-        let output_path = self.get_output_path(file_path)?;
-        if let Some(parent) = output_path.parent() {
-            std::fs::create_dir_all(parent)?;
+        Ok((document, diagnostics))
+    }
+
+    /// Drop documents whose `:only:` expression doesn't match the active tags,
+    /// returning the kept documents and the excluded docnames
+    fn exclude_tagged_documents(
+        &self,
+        documents: Vec<LoadedDocument>,
+    ) -> Result<(Vec<LoadedDocument>, HashSet<String>)> {
+        let mut kept = Vec::new();
+        let mut excluded = HashSet::new();
+
+        for (document, cached) in documents {
+            let included = self.tags.includes(&document.metadata).unwrap_or_else(|e| {
+                self.warnings.lock().unwrap().push(BuildWarning::new(
+                    document.source_path.clone(),
+                    None,
+                    e.to_string(),
+                    WarningType::Other,
+                ));
+                true
+            });
+            if included {
+                kept.push((document, cached));
+                continue;
+            }
+
+            info!(
+                "Excluding {}: its only expression doesn't match the active tags",
+                document.source_path.display()
+            );
+            // Don't leave a page from an earlier build with other tags behind
+            let output_path = self.get_output_path(&document.source_path)?;
+            if output_path.exists() {
+                std::fs::remove_file(&output_path)?;
+            }
+            excluded.insert(self.docname(&document));
+        }
+
+        Ok((kept, excluded))
+    }
+
+    fn render_single_file(
+        &self,
+        mut document: Document,
+        excluded: &HashSet<String>,
+        env: &BuildEnvironment,
+    ) -> Result<(Document, Diagnostics)> {
+        if !excluded.is_empty() {
+            self.prune_toctrees(&mut document, excluded);
+        }
+
+        let rendered = self
+            .renderer
+            .render_in_env(&document, &self.docname(&document), env);
+        let diagnostics = Diagnostics {
+            warnings: rendered.warnings,
+            errors: rendered.errors,
+        };
+        document.html = rendered.html;
+
+        // The single page is written once every document is rendered
+        if self.layout != PageLayout::Single {
+            let body = self.relocate_links(&self.docname(&document), &document.html);
+            self.write_page(&self.get_output_path(&document.source_path)?, &body)?;
         }
-        std::fs::write(&output_path, &rendered_html)?;
 
         // Cache the document
         if self.incremental {
-            self.cache.store_document(file_path, &document)?;
+            self.cache
+                .store_document(&document.source_path, &document)?;
         }
 
-        Ok(document)
+        Ok((document, diagnostics))
     }
 
-    fn get_output_path(&self, source_path: &Path) -> Result<PathBuf> {
-        let relative_path = source_path.strip_prefix(&self.source_dir)?;
-        let mut output_path = self.output_dir.join(relative_path);
+    /// Wrap a page body with the configured extras, apply the output
+    /// transforms and write it to `output_path`
+    fn write_page(&self, output_path: &Path, body: &str) -> Result<()> {
+        let head = self
+            .config
+            .html_head_extra
+            .as_ref()
+            .map(|extra| format!("<head>{}</head>", extra))
+            .unwrap_or_default();
+        let mut body_end = self.config.html_body_end_extra.clone().unwrap_or_default();
+        if self.config.html_copy_code_button {
+            let page = output_path
+                .strip_prefix(&self.output_dir)?
+                .to_string_lossy()
+                .replace('\\', "/");
+            body_end.push_str(&format!(
+                "<script src=\"{}\"></script>",
+                utils::relative_uri(&page, "_static/copybutton.js", "")
+            ));
+        }
+        let mut rendered_html = format!("<html>{}<body>{}{}</body></html>", head, body, body_end);
+
+        for (name, transform) in &self.output_transforms {
+            debug!(
+                "Applying output transform {} to {}",
+                name,
+                output_path.display()
+            );
+            transform(output_path, &mut rendered_html);
+        }
+        if let Some(parent) = output_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(output_path, &rendered_html)?;
+        Ok(())
+    }
 
-        // Change extension to .html
-        output_path.set_extension("html");
+    /// Rewrite the links of `docname`'s rendered HTML, which point at
+    /// `.html` pages relative to the document, for the page layout
+    fn relocate_links(&self, docname: &str, html: &str) -> String {
+        // Target page (if any), path relative to the output root and fragment
+        let resolve = |url: &str| {
+            let (path, fragment) = match url.split_once('#') {
+                Some((path, fragment)) => (path, Some(fragment)),
+                None => (url, None),
+            };
+            let path = utils::docname_join(docname, path);
+            let page = path.strip_suffix(".html").map(str::to_string);
+            (page, path, fragment.map(str::to_string))
+        };
 
-        Ok(output_path)
+        match self.layout {
+            PageLayout::Flat => html.to_string(),
+            PageLayout::Directory => utils::rewrite_relative_urls(html, |url| {
+                let (page, path, fragment) = resolve(url);
+                let mut target = match page {
+                    Some(page) => utils::target_uri(&page, "/"),
+                    None => path,
+                };
+                if let Some(fragment) = fragment {
+                    target = format!("{}#{}", target, fragment);
+                }
+                match utils::relative_uri(&utils::target_uri(docname, "/"), &target, "") {
+                    uri if uri.is_empty() => "./".to_string(),
+                    uri => uri,
+                }
+            }),
+            PageLayout::Single => utils::rewrite_relative_urls(html, |url| match resolve(url) {
+                (Some(_), _, Some(fragment)) => format!("#{}", fragment),
+                (Some(page), _, None) => format!("#document-{}", page),
+                (None, path, fragment) => match fragment {
+                    Some(fragment) => format!("{}#{}", path, fragment),
+                    None => path,
+                },
+            }),
+        }
     }
 
-    async fn generate_indices(&self, _documents: &[Document]) -> Result<()> {
-        info!("Generating indices and cross-references");
-        // TODO: Implement index generation
-        Ok(())
+    /// Write every document onto the root document's page, each in a
+    /// `document-<docname>` section, in toctree order; documents outside the
+    /// toctree follow by docname
+    fn write_single_page(&self, documents: &[Document]) -> Result<()> {
+        let mut env = BuildEnvironment::new(self.config.clone());
+        env.note_documents(documents.iter().map(|doc| (self.docname(doc), doc)));
+        let order = env.toctree_order();
+
+        let mut documents: Vec<(String, &Document)> = documents
+            .iter()
+            .map(|doc| (self.docname(doc), doc))
+            .collect();
+        documents.sort_by_key(|(docname, _)| {
+            (
+                order
+                    .iter()
+                    .position(|ordered| ordered == docname)
+                    .unwrap_or(usize::MAX),
+                docname.clone(),
+            )
+        });
+
+        let body: Vec<String> = documents
+            .iter()
+            .map(|(docname, doc)| {
+                format!(
+                    "<section id=\"document-{}\">\n{}\n</section>",
+                    html_escape::encode_double_quoted_attribute(docname),
+                    self.relocate_links(docname, &doc.html)
+                )
+            })
+            .collect();
+        let root_doc = self.config.root_doc.as_deref().unwrap_or("index");
+        self.write_page(
+            &self.output_dir.join(format!("{}.html", root_doc)),
+            &body.join("\n"),
+        )
+    }
+
+    /// Remove toctree entries that point at excluded documents
+    fn prune_toctrees(&self, document: &mut Document, excluded: &HashSet<String>) {
+        let docname = self.docname(document);
+        if let DocumentContent::RestructuredText(rst) = &mut document.content {
+            for node in &mut rst.ast {
+                if let RstNode::Directive { name, content, .. } = node {
+                    if name != "toctree" {
+                        continue;
+                    }
+                    *content = content
+                        .lines()
+                        .filter(|line| {
+                            ToctreeEntry::parse(line).is_none_or(|entry| {
+                                entry.external
+                                    || !excluded
+                                        .contains(&utils::docname_join(&docname, &entry.target))
+                            })
+                        })
+                        .collect::<Vec<_>>()
+                        .join("\n");
+                }
+            }
+        }
+    }
+
+    fn get_output_path(&self, source_path: &Path) -> Result<PathBuf> {
+        self.page_path(source_path, self.layout)
+    }
+
+    /// Output path of a source's page under `layout`
+    fn page_path(&self, source_path: &Path, layout: PageLayout) -> Result<PathBuf> {
+        source_path.strip_prefix(&self.source_dir)?;
+        let docname = self.docname_of(source_path);
+        Ok(self.output_dir.join(self.page_uri(&docname, layout)))
+    }
+
+    /// Path of `docname`'s page relative to the output root under `layout`
+    fn page_uri(&self, docname: &str, layout: PageLayout) -> String {
+        match layout {
+            PageLayout::Flat => format!("{}.html", docname),
+            // `guide/index` stays put, `guide/intro` moves to `guide/intro/`
+            PageLayout::Directory => format!("{}index.html", utils::target_uri(docname, "/")),
+            PageLayout::Single => format!(
+                "{}.html",
+                self.config.root_doc.as_deref().unwrap_or("index")
+            ),
+        }
+    }
+
+    /// Link to `target` (a docname with an optional `#fragment`) relative to
+    /// the output root under `layout`
+    fn page_link(&self, target: &str, layout: PageLayout) -> String {
+        match layout {
+            PageLayout::Flat => utils::target_uri(target, ".html"),
+            PageLayout::Directory => utils::target_uri(target, "/"),
+            PageLayout::Single => {
+                let (docname, fragment) = match target.split_once('#') {
+                    Some((docname, fragment)) => (docname, fragment.to_string()),
+                    None => (target, format!("document-{}", target)),
+                };
+                format!("{}#{}", self.page_uri(docname, layout), fragment)
+            }
+        }
+    }
+
+    /// Copy the images of `image` and `figure` directives to `_images/`
+    async fn copy_images(&self, documents: &[Document]) -> Result<()> {
+        let mut env = BuildEnvironment::new(self.config.clone());
+        for document in documents {
+            env.note_images(&self.docname(document), document);
+        }
+
+        let images_dir = self.output_dir.join("_images");
+        for (source, dest) in &env.images {
+            let source_path = self.source_dir.join(source);
+            // Missing images are reported by validate_images
+            if !source_path.is_file() {
+                continue;
+            }
+            tokio::fs::create_dir_all(&images_dir).await?;
+            tokio::fs::copy(&source_path, images_dir.join(dest)).await?;
+        }
+        Ok(())
+    }
+
+    async fn generate_indices(&self, _documents: &[Document]) -> Result<()> {
+        info!("Generating indices and cross-references");
+        // TODO: Implement index generation
+        Ok(())
     }
 
     async fn copy_static_assets(&self) -> Result<()> {
@@ -377,12 +1578,36 @@ impl SphinxBuilder {
             }
         }
 
+        // Copy html_extra_path entries verbatim into the output root
+        for extra_path in &self.config.html_extra_path {
+            let source = self.source_dir.join(extra_path);
+            if source.exists() {
+                utils::copy_extra_path(&source, &self.output_dir).await?;
+                debug!("Copied extra path: {:?}", source);
+            } else {
+                self.add_warning(BuildWarning::new(
+                    source.clone(),
+                    None,
+                    "html_extra_path entry not found".to_string(),
+                    WarningType::Other,
+                ));
+            }
+        }
+
+        if self
+            .sphinx_app
+            .as_ref()
+            .is_some_and(|app| app.has_extension("sphinx.ext.githubpages"))
+        {
+            write_githubpages_files(&self.output_dir, &self.config)?;
+        }
+
         Ok(())
     }
 
     async fn create_default_static_assets(&self, static_dir: &Path) -> Result<()> {
-        // Create basic pygments.css
-        let pygments_css = include_str!("../static/pygments.css");
+        // Style highlighted code with the configured theme
+        let pygments_css = crate::highlight::theme_css(&self.config.output.highlight_theme)?;
         tokio::fs::write(static_dir.join("pygments.css"), pygments_css).await?;
 
         // Create basic theme.css
@@ -399,6 +1624,9 @@ impl SphinxBuilder {
         let sphinx_highlight_js = include_str!("../static/sphinx_highlight.js");
         tokio::fs::write(static_dir.join("sphinx_highlight.js"), sphinx_highlight_js).await?;
 
+        let copybutton_js = include_str!("../static/copybutton.js");
+        tokio::fs::write(static_dir.join("copybutton.js"), copybutton_js).await?;
+
         debug!("Created default static assets");
         Ok(())
     }
@@ -416,13 +1644,7 @@ impl SphinxBuilder {
 
         // Collect all documents and their toctree references
         for doc in processed_docs {
-            // Get relative path for comparison
-            let doc_path_relative = doc
-                .source_path
-                .strip_prefix(&self.source_dir)
-                .unwrap_or(&doc.source_path);
-            let doc_path_no_ext = doc_path_relative.with_extension("");
-            all_documents.insert(doc_path_no_ext.to_string_lossy().to_string());
+            all_documents.insert(self.docname(doc));
 
             // Check for toctree directives and collect their references
             if let Some(toctree_refs) = self.extract_toctree_references(doc) {
@@ -450,12 +1672,7 @@ impl SphinxBuilder {
 
         // Check for orphaned documents
         for doc in processed_docs {
-            let doc_path_relative = doc
-                .source_path
-                .strip_prefix(&self.source_dir)
-                .unwrap_or(&doc.source_path);
-            let doc_path_no_ext = doc_path_relative.with_extension("");
-            let doc_path_str = doc_path_no_ext.to_string_lossy().to_string();
+            let doc_path_str = self.docname(doc);
 
             // Skip the main index file
             if doc_path_str == "index" {
@@ -481,25 +1698,177 @@ impl SphinxBuilder {
         Ok(())
     }
 
-    fn extract_toctree_references(&self, doc: &Document) -> Option<Vec<String>> {
-        use crate::document::DocumentContent;
+    /// Check that every `:doc:` and `:ref:` target exists in the project
+    fn validate_cross_references(&self, processed_docs: &[Document]) {
+        let docnames: HashSet<String> =
+            processed_docs.iter().map(|doc| self.docname(doc)).collect();
+        // The labels `:ref:` resolves against while rendering
+        let mut labels = BuildEnvironment::new(self.config.clone());
+        for doc in processed_docs {
+            labels.note_labels(&self.docname(doc), doc);
+            labels.note_python_objects(&self.docname(doc), doc);
+        }
+
+        for doc in processed_docs {
+            let docname = self.docname(doc);
+            for cross_ref in &doc.cross_refs {
+                let target = explicit_target(&cross_ref.target);
+                let resolved = match cross_ref.ref_type.as_str() {
+                    "doc" => docnames.contains(&utils::docname_join(&docname, target)),
+                    "ref" => labels.resolve_label(target).is_some(),
+                    _ => continue,
+                };
+                if resolved {
+                    continue;
+                }
+
+                let file = doc.source_path.clone();
+                let line = Some(cross_ref.line_number);
+                if self.strict_refs {
+                    self.errors
+                        .lock()
+                        .unwrap()
+                        .push(BuildErrorReport::broken_cross_reference(file, line, target));
+                } else {
+                    self.warnings
+                        .lock()
+                        .unwrap()
+                        .push(BuildWarning::broken_cross_reference(file, line, target));
+                }
+            }
+        }
+    }
 
+    /// Source path relative to the source directory, without extension
+    fn docname(&self, doc: &Document) -> String {
+        self.docname_of(&doc.source_path)
+    }
+
+    /// The docname of the source file at `source_path`
+    fn docname_of(&self, source_path: &Path) -> String {
+        self.strip_source_suffix(
+            source_path
+                .strip_prefix(&self.source_dir)
+                .unwrap_or(source_path),
+        )
+        .to_string_lossy()
+        .replace('\\', "/")
+    }
+
+    async fn validate_images(&self, documents: &[Document]) -> Result<()> {
+        // Name the copies as copy_images does
+        let mut env = BuildEnvironment::new(self.config.clone());
+        for document in documents {
+            env.note_images(&self.docname(document), document);
+        }
+
+        for doc in documents {
+            let output_path = self.get_output_path(&doc.source_path)?;
+            let html = match tokio::fs::read_to_string(&output_path).await {
+                Ok(html) => html,
+                Err(_) => continue,
+            };
+
+            let warnings = self.check_image_references(doc, &output_path, &html, &env);
+            self.warnings.lock().unwrap().extend(warnings);
+        }
+
+        Ok(())
+    }
+
+    /// Find `_images/` references in rendered HTML that have no copied file
+    ///
+    /// Images of `image` and `figure` directives are reported with the path
+    /// the author wrote and the directive's line; other `<img>` tags (raw
+    /// HTML) with the line of the matching mention of the file in the source.
+    fn check_image_references(
+        &self,
+        doc: &Document,
+        output_path: &Path,
+        html: &str,
+        env: &BuildEnvironment,
+    ) -> Vec<BuildWarning> {
+        let output_dir = output_path.parent().unwrap_or(&self.output_dir);
+        let docname = self.docname(doc);
+
+        // Image nodes in document order, by the name of their copy
+        let mut nodes: HashMap<&str, VecDeque<(&str, usize)>> = HashMap::new();
+        if let DocumentContent::RestructuredText(rst) = &doc.content {
+            for node in &rst.ast {
+                let RstNode::Directive {
+                    name, args, line, ..
+                } = node
+                else {
+                    continue;
+                };
+                let Some(uri) = args.first().map(|uri| uri.trim()) else {
+                    continue;
+                };
+                if !matches!(name.as_str(), "image" | "figure") {
+                    continue;
+                }
+                if let Some(dest) = env.images.get(&utils::docname_join(&docname, uri)) {
+                    nodes
+                        .entry(dest.as_str())
+                        .or_default()
+                        .push_back((uri, *line));
+                }
+            }
+        }
+
+        let source = doc.content.to_string();
+        let mut mentions: HashMap<&str, usize> = HashMap::new();
+        let mut warnings = Vec::new();
+        for captures in IMG_SRC_REGEX.captures_iter(html) {
+            let src = captures.get(1).unwrap().as_str();
+            if !src.contains("_images/") {
+                continue;
+            }
+
+            let path = src.split(['?', '#']).next().unwrap_or(src);
+            let file_name = path.rsplit('/').next().unwrap_or(path);
+            let (image, line) = match nodes.get_mut(file_name).and_then(VecDeque::pop_front) {
+                Some((uri, line)) => (uri, Some(line)),
+                None => {
+                    let seen = mentions.entry(file_name).or_default();
+                    let line = source
+                        .lines()
+                        .enumerate()
+                        .filter(|(_, line)| line.contains(file_name))
+                        .nth(*seen)
+                        .map(|(index, _)| index + 1);
+                    *seen += 1;
+                    (path, line)
+                }
+            };
+            if output_dir.join(path).exists() {
+                continue;
+            }
+
+            warnings.push(BuildWarning::missing_image(
+                doc.source_path.clone(),
+                line,
+                image,
+            ));
+        }
+
+        warnings
+    }
+
+    fn extract_toctree_references(&self, doc: &Document) -> Option<Vec<String>> {
         let mut references = Vec::new();
 
         if let DocumentContent::RestructuredText(rst_content) = &doc.content {
             for node in &rst_content.ast {
-                if let crate::document::RstNode::Directive { name, content, .. } = node {
+                if let RstNode::Directive { name, content, .. } = node {
                     if name == "toctree" {
-                        // Extract references from toctree content
-                        for line in content.lines() {
-                            let trimmed = line.trim();
-                            if !trimmed.is_empty()
-                                && !trimmed.starts_with(':')
-                                && !trimmed.starts_with("..")
-                            {
-                                references.push(trimmed.to_string());
-                            }
-                        }
+                        // External entries are plain links, not documents
+                        references.extend(
+                            parse_toctree_entries(content.lines())
+                                .into_iter()
+                                .filter(|entry| !entry.external)
+                                .map(|entry| entry.target),
+                        );
                     }
                 }
             }
@@ -512,9 +1881,1937 @@ impl SphinxBuilder {
         }
     }
 
-    async fn generate_search_index(&self, _documents: &[Document]) -> Result<()> {
+    async fn generate_search_index(&self, documents: &[Document]) -> Result<()> {
+        if !self.config.output.search_index {
+            return Ok(());
+        }
         info!("Generating search index");
-        // TODO: Implement search index generation
+
+        // Incremental builds start from the index persisted by the previous build
+        let state_path = self
+            .output_dir
+            .join(CACHE_DIR_NAME)
+            .join(SEARCH_INDEX_STATE_NAME);
+        let language = self
+            .config
+            .language
+            .clone()
+            .unwrap_or_else(|| "en".to_string());
+        let mut search_index = match tokio::fs::read(&state_path).await {
+            Ok(state) if self.incremental => {
+                serde_json::from_slice(&state).unwrap_or_else(|_| SearchIndexBuilder::new(language))
+            }
+            _ => SearchIndexBuilder::new(language),
+        };
+
+        let docnames: HashSet<String> = documents.iter().map(|doc| self.docname(doc)).collect();
+        search_index.retain_documents(&docnames);
+
+        let mut reindexed = 0;
+        for doc in documents {
+            let docname = self.docname(doc);
+            let filename = match self.layout {
+                PageLayout::Single => format!(
+                    "{}#document-{}",
+                    self.page_uri(&docname, self.layout),
+                    docname
+                ),
+                layout => self.page_uri(&docname, layout),
+            };
+            // Index the rendered text, so markup doesn't turn up as search terms
+            let text = if doc.html.is_empty() {
+                doc.content.to_string()
+            } else {
+                utils::html_to_text(&doc.html)
+            };
+            let changed = search_index.update_document_if_changed(
+                docname.clone(),
+                filename,
+                doc.title.clone(),
+                &text,
+            )?;
+            if changed {
+                // The objects the document describes are searchable by name
+                let mut env = BuildEnvironment::new(self.config.clone());
+                env.note_python_objects(&docname, doc);
+                search_index.add_document_objects(&docname, &env)?;
+                reindexed += 1;
+            }
+        }
+        debug!(
+            "Re-indexed {} of {} documents for search",
+            reindexed,
+            documents.len()
+        );
+
+        crate::html_builder::write_search_index(
+            &self.output_dir,
+            "searchindex.js",
+            search_index.index(),
+            self.config.html_search_shards,
+        )
+        .await?;
+        if let Some(parent) = state_path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::write(&state_path, serde_json::to_vec(&search_index)?).await?;
+
         Ok(())
     }
 }
+
+/// Target of a role body, dropping an explicit title (`Title <target>`)
+fn explicit_target(body: &str) -> &str {
+    match body
+        .trim()
+        .strip_suffix('>')
+        .and_then(|rest| rest.rsplit_once('<'))
+    {
+        Some((_, target)) => target.trim(),
+        None => body.trim(),
+    }
+}
+
+/// `files` ordered so that each comes after the sources it depends on; a
+/// cycle is broken at the file first reached
+fn dependency_order(files: &[PathBuf], graph: &HashMap<PathBuf, Vec<PathBuf>>) -> Vec<PathBuf> {
+    fn visit<'a>(
+        file: &'a PathBuf,
+        graph: &'a HashMap<PathBuf, Vec<PathBuf>>,
+        visited: &mut HashSet<&'a PathBuf>,
+        order: &mut Vec<PathBuf>,
+    ) {
+        if !visited.insert(file) {
+            return;
+        }
+        for dependency in graph.get(file).into_iter().flatten() {
+            if graph.contains_key(dependency) {
+                visit(dependency, graph, visited, order);
+            }
+        }
+        order.push(file.clone());
+    }
+
+    let mut visited = HashSet::new();
+    let mut order = Vec::with_capacity(files.len());
+    for file in files {
+        visit(file, graph, &mut visited, &mut order);
+    }
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list_files(root: &Path) -> Vec<(PathBuf, Vec<u8>)> {
+        let mut files = Vec::new();
+        let mut dirs = vec![root.to_path_buf()];
+        while let Some(dir) = dirs.pop() {
+            for entry in std::fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.ends_with(CACHE_DIR_NAME) || path.ends_with(utils::SYNC_MANIFEST_NAME) {
+                    continue;
+                }
+                if path.is_dir() {
+                    dirs.push(path);
+                } else {
+                    let contents = std::fs::read(&path).unwrap();
+                    files.push((path.strip_prefix(root).unwrap().to_path_buf(), contents));
+                }
+            }
+        }
+        files.sort();
+        files
+    }
+
+    #[tokio::test]
+    async fn test_site_is_synced_to_additional_output_dirs() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        let output_dir = temp_dir.path().join("build");
+        let mirrors = [
+            temp_dir.path().join("mirror-a"),
+            temp_dir.path().join("mirror-b"),
+        ];
+        std::fs::create_dir_all(source_dir.join("guide")).unwrap();
+        std::fs::write(source_dir.join("index.rst"), "Index\n#####\n").unwrap();
+        std::fs::write(source_dir.join("guide/intro.rst"), "Intro\n#####\n").unwrap();
+
+        let mut builder = SphinxBuilder::new(
+            BuildConfig::default(),
+            source_dir.clone(),
+            output_dir.clone(),
+        )
+        .unwrap();
+        for mirror in &mirrors {
+            builder.add_output_dir(mirror.clone());
+        }
+        builder.build().await.unwrap();
+
+        let expected = list_files(&output_dir);
+        assert!(expected
+            .iter()
+            .any(|(path, _)| path == Path::new("guide/intro.html")));
+        for mirror in &mirrors {
+            assert_eq!(list_files(mirror), expected);
+            assert!(!mirror.join(CACHE_DIR_NAME).exists());
+        }
+
+        // A second sync has nothing left to copy
+        assert_eq!(
+            utils::sync_dir(&output_dir, &mirrors[0], &[CACHE_DIR_NAME])
+                .await
+                .unwrap(),
+            0
+        );
+
+        // Pages gone from the output are removed from the mirrors
+        std::fs::remove_file(source_dir.join("guide/intro.rst")).unwrap();
+        std::fs::remove_dir_all(&output_dir).unwrap();
+        builder.build().await.unwrap();
+        let expected = list_files(&output_dir);
+        assert!(!expected.iter().any(|(path, _)| path.starts_with("guide")));
+        for mirror in &mirrors {
+            assert_eq!(list_files(mirror), expected);
+            assert!(!mirror.join("guide").exists());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_output_dir_nested_through_dot_dot_is_rejected() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        let output_dir = temp_dir.path().join("build");
+        std::fs::create_dir_all(&source_dir).unwrap();
+        std::fs::write(source_dir.join("index.rst"), "Index\n#####\n").unwrap();
+
+        let mut builder =
+            SphinxBuilder::new(BuildConfig::default(), source_dir, output_dir.clone()).unwrap();
+        builder.add_output_dir(temp_dir.path().join("mirror/../build/mirror"));
+        let error = builder.build().await.unwrap_err().to_string();
+
+        assert!(
+            error.contains("is inside the output directory"),
+            "{}",
+            error
+        );
+    }
+
+    #[tokio::test]
+    async fn test_output_dir_containing_the_project_is_rejected() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let source_dir = temp_dir.path().join("docs");
+        let output_dir = source_dir.join("_build");
+        std::fs::create_dir_all(&source_dir).unwrap();
+        std::fs::write(source_dir.join("index.rst"), "Index\n#####\n").unwrap();
+
+        for (mirror, problem) in [
+            (source_dir.clone(), "contains the output directory"),
+            (
+                temp_dir.path().to_path_buf(),
+                "contains the output directory",
+            ),
+            (output_dir.clone(), "is inside the output directory"),
+        ] {
+            let mut builder = SphinxBuilder::new(
+                BuildConfig::default(),
+                source_dir.clone(),
+                output_dir.clone(),
+            )
+            .unwrap();
+            builder.add_output_dir(mirror);
+            let error = builder.build().await.unwrap_err().to_string();
+            assert!(error.contains(problem), "{}", error);
+        }
+
+        // Mirroring over a parent of the sources is rejected even when the
+        // output directory lives elsewhere
+        let mut builder = SphinxBuilder::new(
+            BuildConfig::default(),
+            source_dir.clone(),
+            temp_dir.path().join("site"),
+        )
+        .unwrap();
+        builder.add_output_dir(source_dir.clone());
+        let error = builder.build().await.unwrap_err().to_string();
+        assert!(error.contains("contains the source directory"), "{}", error);
+        assert!(source_dir.join("index.rst").exists());
+    }
+
+    #[tokio::test]
+    async fn test_sync_only_removes_files_it_wrote() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let site = temp_dir.path().join("site");
+        let mirror = temp_dir.path().join("mirror");
+        std::fs::create_dir_all(site.join("guide")).unwrap();
+        std::fs::create_dir_all(mirror.join("guide")).unwrap();
+        std::fs::write(site.join("index.html"), "index").unwrap();
+        std::fs::write(site.join("guide/intro.html"), "intro").unwrap();
+        std::fs::write(mirror.join("README.md"), "not ours").unwrap();
+        std::fs::write(mirror.join("guide/notes.txt"), "not ours").unwrap();
+
+        assert_eq!(utils::sync_dir(&site, &mirror, &[]).await.unwrap(), 2);
+        std::fs::remove_file(site.join("guide/intro.html")).unwrap();
+        utils::sync_dir(&site, &mirror, &[]).await.unwrap();
+
+        assert!(!mirror.join("guide/intro.html").exists());
+        assert!(mirror.join("guide/notes.txt").exists());
+        assert!(mirror.join("README.md").exists());
+        assert!(mirror.join("index.html").exists());
+    }
+
+    #[tokio::test]
+    async fn test_external_toctree_entry_is_not_a_missing_doc() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        std::fs::create_dir_all(&source_dir).unwrap();
+
+        let index_path = source_dir.join("index.rst");
+        let index =
+            "Index\n#####\n\n.. toctree::\n\n   Intro <intro>\n   Example <https://example.com>\n";
+        std::fs::write(&index_path, index).unwrap();
+        let intro_path = source_dir.join("intro.rst");
+        std::fs::write(&intro_path, "Intro\n#####\n").unwrap();
+
+        let builder = SphinxBuilder::new(
+            BuildConfig::default(),
+            source_dir.clone(),
+            temp_dir.path().join("build"),
+        )
+        .unwrap();
+        let documents = vec![
+            builder.parser.parse(&index_path, index).unwrap(),
+            builder.parser.parse(&intro_path, "Intro\n#####\n").unwrap(),
+        ];
+
+        builder.validate_documents(&documents, &[]).await.unwrap();
+
+        assert!(builder.warnings.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_missing_image_produces_located_warning() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        let output_dir = temp_dir.path().join("build");
+        std::fs::create_dir_all(source_dir.join("_images")).unwrap();
+
+        let source_path = source_dir.join("index.rst");
+        let content = "Index\n#####\n\n.. raw:: html\n\n   <img src=\"_images/missing.png\">\n   <img src=\"_images/present.png\">\n";
+        std::fs::write(&source_path, content).unwrap();
+
+        let builder = SphinxBuilder::new(
+            BuildConfig::default(),
+            source_dir.clone(),
+            output_dir.clone(),
+        )
+        .unwrap();
+        let document = builder.parser.parse(&source_path, content).unwrap();
+
+        std::fs::create_dir_all(output_dir.join("_images")).unwrap();
+        std::fs::write(output_dir.join("_images/present.png"), b"").unwrap();
+        std::fs::write(
+            output_dir.join("index.html"),
+            r#"<p><img src="_images/missing.png"><img alt="" src="_images/present.png"></p>"#,
+        )
+        .unwrap();
+
+        builder.validate_images(&[document]).await.unwrap();
+
+        let warnings = builder.warnings.lock().unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].file, source_path);
+        assert_eq!(warnings[0].line, Some(6));
+        assert!(warnings[0].message.contains("_images/missing.png"));
+    }
+
+    #[tokio::test]
+    async fn test_missing_image_warnings_name_the_source_path_and_its_line() {
+        let (_temp_dir, _, stats) = build_project(
+            &[(
+                "guide/index.rst",
+                "Guide\n#####\n\n.. image:: pics/missing.png\n\nText.\n\n\
+                 .. figure:: pics/missing.png\n\n   Again.\n",
+            )],
+            BuildConfig::default(),
+        )
+        .await;
+
+        let missing: Vec<(Option<usize>, &str)> = stats
+            .warning_details
+            .iter()
+            .filter(|warning| warning.message.starts_with("image file not found"))
+            .map(|warning| (warning.line, warning.message.as_str()))
+            .collect();
+        assert_eq!(
+            missing,
+            [
+                (Some(4), "image file not found: 'pics/missing.png'"),
+                (Some(8), "image file not found: 'pics/missing.png'"),
+            ]
+        );
+    }
+
+    fn write_project(source_dir: &Path, files: &[(&str, &str)]) {
+        for (name, content) in files {
+            let path = source_dir.join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_strict_refs_turns_broken_doc_link_into_error() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        write_project(
+            &source_dir,
+            &[
+                (
+                    "index.rst",
+                    "Index\n#####\n\nSee :doc:`guide/intro` and :doc:`Missing <guide/missing>`.\n",
+                ),
+                (
+                    "guide/intro.rst",
+                    ".. _intro-label:\n\nIntro\n#####\n\nBack to :doc:`../index`, :ref:`intro-label`.\n\n\
+                     .. note::\n   :name: intro-note\n\n   See :ref:`intro-note`.\n",
+                ),
+            ],
+        );
+
+        let mut builder = SphinxBuilder::new(
+            BuildConfig::default(),
+            source_dir.clone(),
+            temp_dir.path().join("build"),
+        )
+        .unwrap();
+        builder.enable_strict_refs();
+        let stats = builder.build().await.unwrap();
+
+        assert!(stats.has_broken_references());
+        assert_eq!(stats.errors, 1);
+        assert_eq!(
+            stats.error_details[0].message,
+            "cross-reference target not found: 'guide/missing'"
+        );
+        assert!(!stats
+            .warning_details
+            .iter()
+            .any(|warning| warning.message.contains("cross-reference")));
+
+        // Without strict mode the same reference is only a warning
+        let builder = SphinxBuilder::new(
+            BuildConfig::default(),
+            source_dir,
+            temp_dir.path().join("build-lenient"),
+        )
+        .unwrap();
+        let stats = builder.build().await.unwrap();
+        assert!(!stats.has_broken_references());
+        assert_eq!(
+            stats
+                .warning_details
+                .iter()
+                .filter(|warning| warning.message.contains("guide/missing"))
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_explicit_target() {
+        assert_eq!(explicit_target("guide/intro"), "guide/intro");
+        assert_eq!(explicit_target("The guide <guide/intro>"), "guide/intro");
+    }
+
+    #[tokio::test]
+    async fn test_internal_tagged_page_excluded_without_tag() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        write_project(
+            &source_dir,
+            &[
+                (
+                    "index.rst",
+                    "Index\n#####\n\n.. toctree::\n\n   public\n   secret\n",
+                ),
+                (
+                    "public.rst",
+                    "Public\n######\n\n:tags: python, tutorial\n\nFor everyone.\n",
+                ),
+                (
+                    "secret.rst",
+                    "Secret\n######\n\n:only: internal\n\nInternal only.\n",
+                ),
+            ],
+        );
+
+        let output_dir = temp_dir.path().join("build");
+        let builder = SphinxBuilder::new(
+            BuildConfig::default(),
+            source_dir.clone(),
+            output_dir.clone(),
+        )
+        .unwrap();
+        let stats = builder.build().await.unwrap();
+
+        assert_eq!(stats.files_processed, 2);
+        assert!(output_dir.join("public.html").exists());
+        assert!(!output_dir.join("secret.html").exists());
+        let index = std::fs::read_to_string(output_dir.join("index.html")).unwrap();
+        assert!(index.contains("public.html"));
+        assert!(!index.contains("secret.html"));
+        assert!(!stats
+            .warning_details
+            .iter()
+            .any(|warning| warning.message.contains("secret")));
+
+        // The internal build keeps the page
+        let config = BuildConfig {
+            tags: vec!["internal".to_string()],
+            ..BuildConfig::default()
+        };
+        let builder = SphinxBuilder::new(config, source_dir, output_dir.clone()).unwrap();
+        let stats = builder.build().await.unwrap();
+        assert_eq!(stats.files_processed, 3);
+        assert!(output_dir.join("secret.html").exists());
+    }
+
+    #[tokio::test]
+    async fn test_markdown_front_matter_only_filters_but_tags_do_not() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        write_project(
+            &source_dir,
+            &[
+                ("index.rst", "Index\n#####\n"),
+                (
+                    "setup.md",
+                    "---\ntags: [intro, setup]\n---\n# Setup\n\nFor everyone.\n",
+                ),
+                (
+                    "draft.md",
+                    "---\nonly: internal\n---\n# Draft\n\nNot yet.\n",
+                ),
+            ],
+        );
+        let output_dir = temp_dir.path().join("build");
+        std::fs::create_dir_all(&output_dir).unwrap();
+        std::fs::write(output_dir.join("draft.html"), "stale").unwrap();
+
+        let builder = SphinxBuilder::new(
+            BuildConfig::default(),
+            source_dir.clone(),
+            output_dir.clone(),
+        )
+        .unwrap();
+        let stats = builder.build().await.unwrap();
+
+        assert_eq!(stats.files_processed, 2);
+        assert!(output_dir.join("setup.html").exists());
+        assert!(!output_dir.join("draft.html").exists());
+    }
+
+    /// Write `files` into a fresh project and build it with `config`, returning
+    /// the temporary root (the project is deleted when it drops), the output
+    /// directory and the build's stats
+    async fn build_project(
+        files: &[(&str, &str)],
+        config: BuildConfig,
+    ) -> (tempfile::TempDir, PathBuf, BuildStats) {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        let output_dir = temp_dir.path().join("build");
+        write_project(&source_dir, files);
+        let stats = SphinxBuilder::new(config, source_dir, output_dir.clone())
+            .unwrap()
+            .build()
+            .await
+            .unwrap();
+        (temp_dir, output_dir, stats)
+    }
+
+    /// Build an index page with `only` blocks under the named builder and
+    /// return the written page
+    async fn build_with_builder(name: &str, layout: PageLayout, page: &str) -> String {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        let output_dir = temp_dir.path().join("build");
+        let content = format!(
+            "Index\n#####\n\n.. only:: {name} and builder_{name} and format_html\n\n   \
+             Builder text.\n\n.. only:: latex\n\n   Print text.\n"
+        );
+        write_project(&source_dir, &[("index.rst", content.as_str())]);
+
+        let config = BuildConfig::default();
+        let mut builder =
+            SphinxBuilder::new(config.clone(), source_dir.clone(), output_dir.clone()).unwrap();
+        if name == "json" {
+            builder.add_writer(Box::new(
+                crate::JSONBuilder::new(config, source_dir, output_dir.clone()).unwrap(),
+            ));
+        }
+        builder.set_page_layout(layout);
+        builder.set_builder_tags(name, "html");
+        builder.build().await.unwrap();
+        std::fs::read_to_string(output_dir.join(page)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_html_builder_tags() {
+        let page = build_with_builder("html", PageLayout::Flat, "index.html").await;
+        assert!(page.contains("Builder text."));
+        assert!(!page.contains("Print text."));
+    }
+
+    #[tokio::test]
+    async fn test_dirhtml_builder_tags() {
+        let page = build_with_builder("dirhtml", PageLayout::Directory, "index.html").await;
+        assert!(page.contains("Builder text."));
+        assert!(!page.contains("Print text."));
+    }
+
+    #[tokio::test]
+    async fn test_singlehtml_builder_tags() {
+        let page = build_with_builder("singlehtml", PageLayout::Single, "index.html").await;
+        assert!(page.contains("Builder text."));
+        assert!(!page.contains("Print text."));
+    }
+
+    #[tokio::test]
+    async fn test_json_builder_tags() {
+        let page = build_with_builder("json", PageLayout::Flat, "index.fjson").await;
+        assert!(page.contains("Builder text."));
+        assert!(!page.contains("Print text."));
+    }
+
+    #[tokio::test]
+    async fn test_build_manifest_etags_are_stable() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        write_project(
+            &source_dir,
+            &[
+                ("index.rst", "Index\n#####\n\nHello.\n"),
+                ("guide/intro.rst", "Intro\n#####\n"),
+            ],
+        );
+
+        let read_manifest = |output_dir: &Path| -> serde_json::Value {
+            let manifest = std::fs::read_to_string(output_dir.join(BUILD_MANIFEST_NAME)).unwrap();
+            serde_json::from_str(&manifest).unwrap()
+        };
+
+        let mut manifests = Vec::new();
+        for name in ["build-a", "build-b"] {
+            let output_dir = temp_dir.path().join(name);
+            let builder = SphinxBuilder::new(
+                BuildConfig::default(),
+                source_dir.clone(),
+                output_dir.clone(),
+            )
+            .unwrap();
+            builder.build().await.unwrap();
+            manifests.push(read_manifest(&output_dir));
+        }
+
+        assert_eq!(manifests[0], manifests[1]);
+        let etag = manifests[0]["pages"]["guide/intro.html"]["etag"]
+            .as_str()
+            .unwrap();
+        let page = std::fs::read(temp_dir.path().join("build-a/guide/intro.html")).unwrap();
+        assert_eq!(etag, utils::content_etag(&page));
+        assert_ne!(etag, manifests[0]["pages"]["index.html"]["etag"]);
+    }
+
+    #[tokio::test]
+    async fn test_incremental_build_updates_only_changed_search_terms() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        let output_dir = temp_dir.path().join("build");
+        write_project(
+            &source_dir,
+            &[
+                ("index.rst", "Index\n#####\n\nAlpha zebra.\n"),
+                ("other.rst", "Other\n#####\n\nBeta giraffe.\n"),
+            ],
+        );
+
+        let build = || async {
+            let mut builder = SphinxBuilder::new(
+                BuildConfig::default(),
+                source_dir.clone(),
+                output_dir.clone(),
+            )
+            .unwrap();
+            builder.enable_incremental();
+            builder.build().await.unwrap();
+
+            let state = std::fs::read(
+                output_dir
+                    .join(CACHE_DIR_NAME)
+                    .join(SEARCH_INDEX_STATE_NAME),
+            )
+            .unwrap();
+            let search_index: SearchIndexBuilder = serde_json::from_slice(&state).unwrap();
+            search_index.build()
+        };
+        let docs_for = |index: &crate::search::SearchIndex, term: &str| -> Vec<String> {
+            index
+                .search(term)
+                .into_iter()
+                .map(|result| result.docname)
+                .collect()
+        };
+
+        let first = build().await;
+        assert_eq!(docs_for(&first, "zebra"), vec!["index"]);
+        assert_eq!(docs_for(&first, "giraffe"), vec!["other"]);
+
+        let other = source_dir.join("other.rst");
+        std::fs::write(&other, "Other\n#####\n\nBeta okapi.\n").unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&other)
+            .unwrap()
+            .set_modified(std::time::SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+
+        let second = build().await;
+        assert_eq!(docs_for(&second, "zebra"), vec!["index"]);
+        assert_eq!(docs_for(&second, "okapi"), vec!["other"]);
+        assert!(docs_for(&second, "giraffe").is_empty());
+        // The unchanged document keeps its original term entries
+        assert_eq!(
+            first.terms["zebra"][0].positions,
+            second.terms["zebra"][0].positions
+        );
+
+        let searchindex = std::fs::read_to_string(output_dir.join("searchindex.js")).unwrap();
+        assert!(searchindex.starts_with("Search.setIndex("));
+        assert!(searchindex.contains("okapi"));
+    }
+
+    /// Records the lifecycle calls it receives
+    struct RecordingWriter {
+        calls: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl DocumentWriter for RecordingWriter {
+        fn name(&self) -> &str {
+            "recording"
+        }
+
+        async fn init(&mut self) -> Result<()> {
+            self.calls.lock().unwrap().push("init".to_string());
+            Ok(())
+        }
+
+        async fn write_doc(&mut self, docname: &str, doctree: &Document) -> Result<()> {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("{}: {}", docname, doctree.title));
+            Ok(())
+        }
+
+        async fn finish(&mut self) -> Result<()> {
+            self.calls.lock().unwrap().push("finish".to_string());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_custom_writer_receives_every_document() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        write_project(
+            &source_dir,
+            &[
+                ("index.rst", "Index\n#####\n\nHello.\n"),
+                ("guide/intro.rst", "Intro\n#####\n"),
+            ],
+        );
+
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let mut builder = SphinxBuilder::new(
+            BuildConfig::default(),
+            source_dir,
+            temp_dir.path().join("build"),
+        )
+        .unwrap();
+        builder.add_writer(Box::new(RecordingWriter {
+            calls: calls.clone(),
+        }));
+        builder.build().await.unwrap();
+
+        let calls = calls.lock().unwrap();
+        assert_eq!(calls.first().map(String::as_str), Some("init"));
+        assert_eq!(calls.last().map(String::as_str), Some("finish"));
+        let mut docs = calls[1..calls.len() - 1].to_vec();
+        docs.sort();
+        assert_eq!(docs, ["guide/intro: Intro", "index: Index"]);
+    }
+
+    #[tokio::test]
+    async fn test_document_query_finds_code_blocks() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        write_project(
+            &source_dir,
+            &[
+                (
+                    "index.rst",
+                    "Index\n#####\n\n.. code-block:: python\n\n   print(1)\n\n.. note::\n\n   Hi.\n",
+                ),
+                (
+                    "guide/intro.rst",
+                    "Intro\n#####\n\nSee :doc:`/index`.\n\n.. code-block:: rust\n\n   fn main() {}\n\n.. code-block::\n\n   plain\n",
+                ),
+                ("about.rst", "About\n#####\n"),
+            ],
+        );
+
+        let builder = SphinxBuilder::new(
+            BuildConfig::default(),
+            source_dir,
+            temp_dir.path().join("build"),
+        )
+        .unwrap();
+        assert!(builder.documents().is_empty());
+        builder.build().await.unwrap();
+
+        let query = builder.documents();
+        let code_blocks: Vec<(&str, usize, Option<&str>)> = query
+            .directives("code-block")
+            .into_iter()
+            .map(|(docname, directive)| {
+                (
+                    docname,
+                    directive.line,
+                    directive.args.first().map(String::as_str),
+                )
+            })
+            .collect();
+        assert_eq!(
+            code_blocks,
+            [
+                ("guide/intro", 6, Some("rust")),
+                ("guide/intro", 10, None),
+                ("index", 4, Some("python")),
+            ]
+        );
+
+        assert_eq!(query.get("guide/intro").unwrap().title, "Intro");
+        assert_eq!(
+            query.titles(),
+            [
+                ("about", "About"),
+                ("guide/intro", "Intro"),
+                ("index", "Index")
+            ]
+        );
+        let refs: Vec<(&str, &str)> = query
+            .cross_references()
+            .into_iter()
+            .map(|(docname, cross_ref)| (docname, cross_ref.target.as_str()))
+            .collect();
+        assert_eq!(refs, [("guide/intro", "/index")]);
+    }
+
+    #[tokio::test]
+    async fn test_page_size_budget_warns_about_oversized_page() {
+        let huge = format!("Huge\n####\n\n{}\n", "All work and no play. ".repeat(200));
+        let config = BuildConfig {
+            page_size_budget_kb: Some(2),
+            output_size_budget_mb: Some(0.001),
+            ..BuildConfig::default()
+        };
+        let (temp_dir, _, stats) = build_project(
+            &[
+                ("index.rst", "Index\n#####\n\nSmall.\n"),
+                ("guide/huge.rst", &huge),
+            ],
+            config,
+        )
+        .await;
+        let source_dir = temp_dir.path().join("source");
+
+        let budget_warnings: Vec<&BuildWarning> = stats
+            .warning_details
+            .iter()
+            .filter(|warning| matches!(warning.warning_type, WarningType::SizeBudget))
+            .collect();
+        assert_eq!(budget_warnings.len(), 2);
+        assert_eq!(budget_warnings[0].file, source_dir.join("guide/huge.rst"));
+        assert_eq!(
+            budget_warnings[0].message,
+            "rendered page 'guide/huge.html' is 5 KB, over the page size budget of 2 KB"
+        );
+        assert!(budget_warnings[1]
+            .message
+            .ends_with("over the output size budget of 0.001 MB"));
+    }
+
+    #[tokio::test]
+    async fn test_output_transforms_apply_to_every_page_in_order() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        let output_dir = temp_dir.path().join("build");
+        write_project(
+            &source_dir,
+            &[
+                (
+                    "index.rst",
+                    "Index\n#####\n\nSee https://example.org/a.png.\n",
+                ),
+                ("guide/intro.rst", "Intro\n#####\n"),
+            ],
+        );
+
+        let config = BuildConfig {
+            output_replacements: vec![crate::config::OutputReplacement {
+                pattern: r"https://example\.org/(\w+\.png)".to_string(),
+                replacement: "https://cdn.example.org/$1".to_string(),
+            }],
+            ..BuildConfig::default()
+        };
+        let mut builder = SphinxBuilder::new(config, source_dir, output_dir.clone()).unwrap();
+        builder.add_output_transform("analytics", |path, html| {
+            assert_eq!(path.extension().unwrap(), "html");
+            // Runs after the configured replacements
+            assert!(!html.contains("https://example.org/"));
+            *html = html.replace("</body>", "<script src=\"stats.js\"></script></body>");
+        });
+        builder.build().await.unwrap();
+
+        for page in ["index.html", "guide/intro.html"] {
+            let html = std::fs::read_to_string(output_dir.join(page)).unwrap();
+            assert!(html.ends_with("<script src=\"stats.js\"></script></body></html>"));
+        }
+        let index = std::fs::read_to_string(output_dir.join("index.html")).unwrap();
+        assert!(index.contains("https://cdn.example.org/a.png"));
+    }
+
+    #[tokio::test]
+    async fn test_colliding_output_paths_are_reported() {
+        let (temp_dir, output_dir, stats) = build_project(
+            &[
+                ("index.rst", "Index\n#####\n"),
+                ("guide/setup.rst", "Setup RST\n#########\n"),
+                ("guide/setup.md", "# Setup Markdown\n"),
+            ],
+            BuildConfig::default(),
+        )
+        .await;
+        let source_dir = temp_dir.path().join("source");
+
+        assert_eq!(stats.files_processed, 2);
+        assert_eq!(stats.error_details.len(), 1);
+        let error = &stats.error_details[0];
+        assert!(matches!(error.error_type, ErrorType::OutputCollision));
+        assert_eq!(error.file, source_dir.join("guide/setup.rst"));
+        assert_eq!(
+            error.message,
+            format!(
+                "output path 'guide/setup.html' is also the output of '{}'; this document is not written",
+                source_dir.join("guide/setup.md").display()
+            )
+        );
+        let html = std::fs::read_to_string(output_dir.join("guide/setup.html")).unwrap();
+        assert!(html.contains("Setup Markdown"));
+    }
+
+    #[tokio::test]
+    async fn test_redirect_page_points_to_moved_document() {
+        let config = BuildConfig {
+            redirects: HashMap::from([
+                (
+                    "setup/install".to_string(),
+                    "guide/install#linux".to_string(),
+                ),
+                ("old/gone".to_string(), "nowhere".to_string()),
+                ("../escaped".to_string(), "index".to_string()),
+                ("/tmp/absolute".to_string(), "index".to_string()),
+            ]),
+            ..BuildConfig::default()
+        };
+        let (temp_dir, output_dir, stats) = build_project(
+            &[
+                ("index.rst", "Index\n#####\n"),
+                ("guide/install.rst", "Install\n#######\n"),
+            ],
+            config,
+        )
+        .await;
+
+        let page = std::fs::read_to_string(output_dir.join("setup/install.html")).unwrap();
+        assert!(page.contains(
+            "<meta http-equiv=\"refresh\" content=\"0; url=../guide/install.html#linux\">"
+        ));
+        assert!(page.contains("<link rel=\"canonical\" href=\"../guide/install.html#linux\">"));
+
+        assert!(!output_dir.join("old/gone.html").exists());
+        let messages: Vec<&str> = stats
+            .warning_details
+            .iter()
+            .map(|warning| warning.message.as_str())
+            .filter(|message| message.starts_with("redirect"))
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "redirect from '../escaped' not written: it must be a relative docname without '..'",
+                "redirect from '/tmp/absolute' not written: it must be a relative docname without '..'",
+                "redirect from 'old/gone' points to 'nowhere', which is not a document",
+            ]
+        );
+        assert!(!temp_dir.path().join("escaped.html").exists());
+    }
+
+    #[tokio::test]
+    async fn test_redirect_pages_follow_the_page_layout() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        write_project(
+            &source_dir,
+            &[
+                ("index.rst", "Index\n#####\n"),
+                ("guide/install.rst", "Install\n#######\n"),
+            ],
+        );
+        let config = BuildConfig {
+            redirects: HashMap::from([("setup/install".to_string(), "guide/install".to_string())]),
+            ..BuildConfig::default()
+        };
+
+        for (layout, page, href) in [
+            (
+                PageLayout::Directory,
+                "setup/install/index.html",
+                "../../guide/install/",
+            ),
+            (
+                PageLayout::Single,
+                "setup/install.html",
+                "../index.html#document-guide/install",
+            ),
+        ] {
+            let output_dir = temp_dir.path().join(format!("build-{:?}", layout));
+            let mut builder =
+                SphinxBuilder::new(config.clone(), source_dir.clone(), output_dir.clone()).unwrap();
+            builder.set_page_layout(layout);
+            builder.build().await.unwrap();
+
+            let redirect = std::fs::read_to_string(output_dir.join(page)).unwrap();
+            assert!(
+                redirect.contains(&format!("content=\"0; url={}\"", href)),
+                "{}",
+                redirect
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_parallel_build_collects_warnings_from_every_page() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        let pages: Vec<(String, String)> = (0..32)
+            .map(|i| {
+                (
+                    format!("page{:02}.rst", i),
+                    format!("Page {:02}\n#######\n\nSee [missing]_. \n", i),
+                )
+            })
+            .collect();
+        let files: Vec<(&str, &str)> = pages
+            .iter()
+            .map(|(name, content)| (name.as_str(), content.as_str()))
+            .collect();
+        write_project(&source_dir, &files);
+
+        let mut builder = SphinxBuilder::new(
+            BuildConfig::default(),
+            source_dir.clone(),
+            temp_dir.path().join("build"),
+        )
+        .unwrap();
+        builder.set_parallel_jobs(4);
+        builder.enable_lint();
+        let stats = builder.build().await.unwrap();
+
+        let files_with = |warning_type: fn(&WarningType) -> bool| -> Vec<PathBuf> {
+            stats
+                .warning_details
+                .iter()
+                .filter(|warning| warning_type(&warning.warning_type))
+                .map(|warning| warning.file.clone())
+                .collect()
+        };
+        let expected: Vec<PathBuf> = pages
+            .iter()
+            .map(|(name, _)| source_dir.join(name))
+            .collect();
+
+        // Merged in source order, one footnote warning per page
+        let footnotes = files_with(|t| matches!(t, WarningType::BrokenCrossReference));
+        assert_eq!(footnotes, expected);
+        let mut linted = files_with(|t| matches!(t, WarningType::Lint));
+        linted.dedup();
+        assert_eq!(linted, expected);
+    }
+
+    #[tokio::test]
+    async fn test_only_renders_matching_documents_and_their_parents() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        let output_dir = temp_dir.path().join("build");
+        let project = |version: &str| {
+            write_project(
+                &source_dir,
+                &[
+                    (
+                        "index.rst",
+                        &format!(
+                            "Index\n#####\n\n{}\n\n.. toctree::\n\n   api/index\n   guide\n",
+                            version
+                        ),
+                    ),
+                    (
+                        "api/index.rst",
+                        &format!("API\n###\n\n{}\n\n.. toctree::\n\n   funcs\n", version),
+                    ),
+                    ("api/funcs.rst", &format!("Funcs\n#####\n\n{}\n", version)),
+                    ("guide.rst", &format!("Guide\n#####\n\n{}\n", version)),
+                ],
+            )
+        };
+
+        project("First.");
+        SphinxBuilder::new(
+            BuildConfig::default(),
+            source_dir.clone(),
+            output_dir.clone(),
+        )
+        .unwrap()
+        .build()
+        .await
+        .unwrap();
+
+        project("Second.");
+        write_project(&source_dir, &[("draft.rst", "Draft\n#####\n\nNew.\n")]);
+        let mut builder = SphinxBuilder::new(
+            BuildConfig::default(),
+            source_dir.clone(),
+            output_dir.clone(),
+        )
+        .unwrap();
+        builder.set_only(&["api/*".to_string()]).unwrap();
+        builder.build().await.unwrap();
+
+        let page = |name: &str| std::fs::read_to_string(output_dir.join(name)).unwrap();
+        assert!(page("api/funcs.html").contains("Second."));
+        assert!(page("api/index.html").contains("Second."));
+        // The root toctree leads to the API pages, so navigation is refreshed too
+        assert!(page("index.html").contains("Second."));
+        assert!(page("guide.html").contains("First."));
+        assert!(!output_dir.join("draft.html").exists());
+    }
+
+    #[tokio::test]
+    async fn test_latest_build_status_follows_rebuilds() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        write_project(&source_dir, &[("index.rst", "Index\n#####\n\nFine.\n")]);
+        let builder = SphinxBuilder::new(
+            BuildConfig::default(),
+            source_dir.clone(),
+            temp_dir.path().join("build"),
+        )
+        .unwrap();
+
+        let mut stats = builder.subscribe_stats();
+        assert!(stats.borrow().is_none());
+
+        builder.build().await.unwrap();
+        assert!(stats.has_changed().unwrap());
+        let status = stats.borrow_and_update().as_ref().unwrap().status();
+        assert_eq!((status.files_processed, status.warnings), (1, 0));
+
+        write_project(
+            &source_dir,
+            &[("index.rst", "Index\n#####\n\nSee [missing]_.\n")],
+        );
+        builder.build().await.unwrap();
+        let status = builder.last_stats().unwrap().status();
+        assert_eq!(status.warnings, 1);
+        let json = serde_json::to_value(&status).unwrap();
+        assert_eq!(json["warnings"], 1);
+        assert_eq!(json["errors"], 0);
+
+        // Counts are per build, not accumulated
+        write_project(&source_dir, &[("index.rst", "Index\n#####\n\nFixed.\n")]);
+        builder.build().await.unwrap();
+        assert_eq!(builder.last_stats().unwrap().status().warnings, 0);
+    }
+
+    #[tokio::test]
+    async fn test_head_and_body_end_extra_land_in_every_page() {
+        let head = r#"<meta name="google-site-verification" content="abc" />"#;
+        let body_end = r#"<script src="https://stats.example.com/a.js"></script>"#;
+        let config = BuildConfig {
+            html_head_extra: Some(head.to_string()),
+            html_body_end_extra: Some(body_end.to_string()),
+            ..BuildConfig::default()
+        };
+        let (_temp_dir, output_dir, _) = build_project(
+            &[
+                (
+                    "index.rst",
+                    "Index\n#####\n\n.. toctree::\n\n   guide/intro\n",
+                ),
+                ("guide/intro.rst", "Intro\n#####\n\nHello.\n"),
+            ],
+            config,
+        )
+        .await;
+
+        for page in ["index.html", "guide/intro.html"] {
+            let html = std::fs::read_to_string(output_dir.join(page)).unwrap();
+            assert!(html.starts_with(&format!("<html><head>{}</head><body>", head)));
+            assert!(html.ends_with(&format!("{}</body></html>", body_end)));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_extra_path_is_copied_to_output_root() {
+        let config = BuildConfig {
+            html_extra_path: vec![PathBuf::from("_extra")],
+            ..BuildConfig::default()
+        };
+        let (_temp_dir, output_dir, stats) = build_project(
+            &[
+                ("index.rst", "Index\n#####\n\nHello.\n"),
+                ("_extra/.nojekyll", ""),
+                ("_extra/robots.txt", "User-agent: *\n"),
+                ("_extra/well-known/security.txt", "Contact: a@example.com\n"),
+            ],
+            config,
+        )
+        .await;
+
+        assert!(output_dir.join(".nojekyll").exists());
+        assert_eq!(
+            std::fs::read_to_string(output_dir.join("robots.txt")).unwrap(),
+            "User-agent: *\n"
+        );
+        assert!(output_dir.join("well-known/security.txt").exists());
+        // The .txt files are copied, not built as documents
+        assert_eq!(stats.files_processed, 1);
+        assert!(!output_dir.join("_extra").exists());
+    }
+
+    #[tokio::test]
+    async fn test_githubpages_writes_nojekyll_and_cname() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        write_project(&source_dir, &[("index.rst", "Index\n#####\n\nHello.\n")]);
+
+        let build = |name: &str, baseurl: &str| {
+            let output_dir = temp_dir.path().join(name);
+            let config = BuildConfig {
+                extensions: vec!["sphinx.ext.githubpages".to_string()],
+                html_baseurl: Some(baseurl.to_string()),
+                ..BuildConfig::default()
+            };
+            let builder =
+                SphinxBuilder::new(config, source_dir.clone(), output_dir.clone()).unwrap();
+            (builder, output_dir)
+        };
+
+        let (builder, output_dir) = build("custom", "https://docs.example.com:443/en/");
+        builder.build().await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(output_dir.join(".nojekyll")).unwrap(),
+            ""
+        );
+        assert_eq!(
+            std::fs::read_to_string(output_dir.join("CNAME")).unwrap(),
+            "docs.example.com"
+        );
+
+        // GitHub's own domain needs no CNAME
+        let (builder, output_dir) = build("github", "https://user.github.io/project/");
+        builder.build().await.unwrap();
+        assert!(output_dir.join(".nojekyll").exists());
+        assert!(!output_dir.join("CNAME").exists());
+    }
+
+    #[tokio::test]
+    async fn test_template_change_rerenders_cached_pages() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        let output_dir = temp_dir.path().join("build");
+        write_project(
+            &source_dir,
+            &[
+                ("index.rst", "Index\n#####\n\n.. toctree::\n\n   other\n"),
+                ("other.rst", "Other\n#####\n\nText.\n"),
+                ("_templates/page.html", "{{ body }}\n"),
+            ],
+        );
+
+        let build = || async {
+            let rendered = Arc::new(Mutex::new(Vec::new()));
+            let mut builder = SphinxBuilder::new(
+                BuildConfig::default(),
+                source_dir.clone(),
+                output_dir.clone(),
+            )
+            .unwrap();
+            builder.enable_incremental();
+            let seen = rendered.clone();
+            builder.add_output_transform("record", move |path, _| {
+                seen.lock()
+                    .unwrap()
+                    .push(path.file_name().unwrap().to_owned());
+            });
+            builder.build().await.unwrap();
+            let mut rendered = rendered.lock().unwrap().clone();
+            rendered.sort();
+            rendered
+        };
+
+        assert_eq!(build().await, ["index.html", "other.html"]);
+        assert!(build().await.is_empty());
+
+        let template = source_dir.join("_templates/page.html");
+        std::fs::write(&template, "<main>{{ body }}</main>\n").unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&template)
+            .unwrap()
+            .set_modified(std::time::SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+
+        assert_eq!(build().await, ["index.html", "other.html"]);
+    }
+
+    #[test]
+    fn test_report_lists_warnings_then_errors_then_summary() {
+        let stats = BuildStats {
+            files_processed: 2,
+            files_skipped: 0,
+            build_time: Duration::from_millis(5),
+            output_size_mb: 0.0,
+            cache_hits: 0,
+            errors: 1,
+            warnings: 2,
+            warning_details: vec![
+                BuildWarning::unknown_role(PathBuf::from("b.rst"), Some(3), "foo"),
+                BuildWarning::orphaned_document(PathBuf::from("a.rst")),
+            ],
+            error_details: vec![BuildErrorReport::broken_cross_reference(
+                PathBuf::from("a.rst"),
+                Some(7),
+                "missing",
+            )],
+        };
+
+        let mut plain = Vec::new();
+        stats.write_report(&mut plain, false).unwrap();
+        let lines: Vec<String> = String::from_utf8(plain)
+            .unwrap()
+            .lines()
+            .map(|line| line.split(": ").take(2).collect::<Vec<_>>().join(": "))
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                "b.rst:3: WARNING",
+                "a.rst: WARNING",
+                "a.rst:7: ERROR",
+                "build succeeded with problems, 2 warnings, 1 errors.",
+            ]
+        );
+
+        let mut colored = Vec::new();
+        stats.write_report(&mut colored, true).unwrap();
+        let colored = String::from_utf8(colored).unwrap();
+        assert!(colored.starts_with("b.rst:3: \x1b[33mWARNING\x1b[0m: "));
+        assert!(colored
+            .ends_with("\x1b[31mbuild succeeded with problems, 2 warnings, 1 errors.\x1b[0m\n"));
+    }
+
+    #[tokio::test]
+    async fn test_prebuild_fills_cache_for_incremental_build() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        let output_dir = temp_dir.path().join("build");
+        write_project(
+            &source_dir,
+            &[
+                ("index.rst", "Index\n#####\n\n.. toctree::\n\n   a\n   b\n"),
+                ("a.rst", "A\n#\n\nText.\n"),
+                ("b.rst", "B\n#\n\nText.\n"),
+            ],
+        );
+        let builder = || {
+            let mut builder = SphinxBuilder::new(
+                BuildConfig::default(),
+                source_dir.clone(),
+                output_dir.clone(),
+            )
+            .unwrap();
+            builder.enable_incremental();
+            builder
+        };
+
+        let stats = builder().prebuild().await.unwrap();
+        assert_eq!(stats.files_processed, 3);
+        assert!(!output_dir.join("index.html").exists());
+        // Nothing left to parse on a second run
+        assert_eq!(builder().prebuild().await.unwrap().files_processed, 0);
+
+        let stats = builder().build().await.unwrap();
+        assert_eq!(stats.cache_hits, 3);
+        // Prebuilt documents are still rendered
+        for page in ["index.html", "a.html", "b.html"] {
+            assert!(output_dir.join(page).exists(), "missing {}", page);
+        }
+        assert!(std::fs::read_to_string(output_dir.join("a.html"))
+            .unwrap()
+            .contains("Text."));
+    }
+
+    #[tokio::test]
+    async fn test_search_index_holds_rendered_text_and_ranks_titles_first() {
+        let (_temp_dir, output_dir, _) = build_project(
+            &[
+                (
+                    "index.rst",
+                    "Index\n#####\n\nA zebra, a zebra and **another zebra**.\n\n\
+                     .. code-block:: python\n\n   print(42)\n",
+                ),
+                ("zebra.rst", "Zebra\n#####\n\nStripes.\n"),
+            ],
+            BuildConfig::default(),
+        )
+        .await;
+
+        let js = std::fs::read_to_string(output_dir.join("searchindex.js")).unwrap();
+        let json = js
+            .strip_prefix("Search.setIndex(")
+            .and_then(|rest| rest.strip_suffix(')'))
+            .unwrap();
+        let data: crate::search::SphinxSearchData = serde_json::from_str(json).unwrap();
+
+        let mut docnames = data.docnames.clone();
+        docnames.sort();
+        assert_eq!(docnames, vec!["index", "zebra"]);
+        let index = data.docnames.iter().position(|d| d == "index").unwrap();
+        let zebra = data.docnames.iter().position(|d| d == "zebra").unwrap();
+        assert_eq!(data.terms["stripe"], vec![zebra]);
+        assert!(data.terms["anoth"].contains(&index));
+        assert!(data.terms.contains_key("print"));
+        assert!(!data.terms.contains_key("code-block"));
+        assert_eq!(data.titleterms["zebra"], vec![zebra]);
+
+        let state = std::fs::read(
+            output_dir
+                .join(CACHE_DIR_NAME)
+                .join(SEARCH_INDEX_STATE_NAME),
+        )
+        .unwrap();
+        let search_index: SearchIndexBuilder = serde_json::from_slice(&state).unwrap();
+        let ranked: Vec<String> = search_index
+            .build()
+            .search("zebra")
+            .into_iter()
+            .map(|result| result.docname)
+            .collect();
+        assert_eq!(ranked, vec!["zebra", "index"]);
+    }
+
+    #[tokio::test]
+    async fn test_search_index_is_sharded_when_configured() {
+        let config = BuildConfig {
+            html_search_shards: Some(4),
+            ..BuildConfig::default()
+        };
+        let (_temp_dir, output_dir, _) = build_project(
+            &[
+                ("index.rst", "Index\n#####\n\nSome zebras.\n"),
+                ("zebra.rst", "Zebra\n#####\n\nStripes.\n"),
+            ],
+            config,
+        )
+        .await;
+
+        assert!(!output_dir.join("searchindex.js").exists());
+        let index_dir = output_dir.join(crate::html_builder::SHARDED_SEARCHINDEX_DIR);
+        let manifest: crate::search::SearchIndexManifest = serde_json::from_str(
+            &std::fs::read_to_string(index_dir.join("manifest.json")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(manifest.shard_count, 4);
+        let mut docnames = manifest.docnames.clone();
+        docnames.sort();
+        assert_eq!(docnames, vec!["index", "zebra"]);
+
+        let zebra = manifest.docnames.iter().position(|d| d == "zebra").unwrap();
+        let shards: Vec<crate::search::SearchIndexShard> = (0..4)
+            .map(|i| {
+                let shard = index_dir.join(format!("shard-{}.json", i));
+                serde_json::from_str(&std::fs::read_to_string(shard).unwrap()).unwrap()
+            })
+            .collect();
+        let stripe = shards
+            .iter()
+            .find_map(|shard| shard.terms.get("stripe"))
+            .unwrap();
+        assert!(stripe.iter().any(|m| m.docname_idx == zebra));
+    }
+
+    #[tokio::test]
+    async fn test_configured_source_suffixes_select_and_name_documents() {
+        let config = BuildConfig {
+            source_suffix: HashMap::from([(
+                ".rst.txt".to_string(),
+                "restructuredtext".to_string(),
+            )]),
+            ..BuildConfig::default()
+        };
+        let (_temp_dir, output_dir, _) = build_project(
+            &[
+                (
+                    "index.rst.txt",
+                    "Index\n#####\n\n.. toctree::\n\n   guide\n",
+                ),
+                ("guide.rst.txt", "Guide\n#####\n\n**Bold** text.\n"),
+                ("notes.md", "# Notes\n"),
+            ],
+            config,
+        )
+        .await;
+
+        assert!(output_dir.join("index.html").exists());
+        assert!(!output_dir.join("notes.html").exists());
+        let guide = std::fs::read_to_string(output_dir.join("guide.html")).unwrap();
+        assert!(guide.contains("<strong>Bold</strong>"));
+    }
+
+    #[tokio::test]
+    async fn test_exclude_patterns_skip_matching_sources() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        let output_dir = temp_dir.path().join("build");
+        write_project(
+            &source_dir,
+            &[
+                ("index.rst", "Index\n#####\n"),
+                ("drafts/wip.rst", "WIP\n###\n"),
+                ("guide/drafts/old.rst", "Old\n###\n"),
+                ("guide/intro.rst", "Intro\n#####\n"),
+                ("api/module.rst", "Module\n######\n"),
+                ("api/nested/deep.rst", "Deep\n####\n"),
+            ],
+        );
+
+        let config = BuildConfig {
+            exclude_patterns: vec!["**/drafts/**".to_string(), "api/*.rst".to_string()],
+            ..BuildConfig::default()
+        };
+        let builder = SphinxBuilder::new(config, source_dir, output_dir.clone()).unwrap();
+        builder.build().await.unwrap();
+
+        let docnames: Vec<String> = builder.documents().docnames().map(String::from).collect();
+        assert_eq!(docnames, ["api/nested/deep", "guide/intro", "index"]);
+        assert!(!output_dir.join("drafts/wip.html").exists());
+        assert!(!output_dir.join("api/module.html").exists());
+
+        let config = BuildConfig {
+            exclude_patterns: vec!["[".to_string()],
+            ..BuildConfig::default()
+        };
+        assert!(SphinxBuilder::new(config, temp_dir.path().into(), output_dir).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_changed_include_rebuilds_the_including_document() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        let output_dir = temp_dir.path().join("build");
+        write_project(
+            &source_dir,
+            &[
+                (
+                    "index.rst",
+                    "Index\n#####\n\n.. include:: fragment.inc\n   :literal:\n\n.. toctree::\n   :maxdepth: 1\n\n   guide/index\n",
+                ),
+                (
+                    "guide/index.rst",
+                    "Guide\n#####\n\n.. literalinclude:: /example.py\n",
+                ),
+                ("other.rst", "Other\n#####\n"),
+                ("fragment.inc", "Original fragment.\n"),
+                ("example.py", "print(1)\n"),
+            ],
+        );
+
+        let builder = SphinxBuilder::new(
+            BuildConfig::default(),
+            source_dir.clone(),
+            output_dir.clone(),
+        )
+        .unwrap();
+        let files = builder.discover_source_files().await.unwrap();
+        let graph = builder.build_dependency_graph(&files).await.unwrap();
+        let index = source_dir.join("index.rst");
+        let guide = source_dir.join("guide/index.rst");
+        assert_eq!(
+            graph[&index],
+            [source_dir.join("fragment.inc"), guide.clone()]
+        );
+        assert_eq!(graph[&guide], [source_dir.join("example.py")]);
+        assert!(graph[&source_dir.join("other.rst")].is_empty());
+        let order = dependency_order(&files, &graph);
+        assert!(order.iter().position(|f| *f == guide) < order.iter().position(|f| *f == index));
+
+        let build = || async {
+            let rendered = Arc::new(Mutex::new(Vec::new()));
+            let mut builder = SphinxBuilder::new(
+                BuildConfig::default(),
+                source_dir.clone(),
+                output_dir.clone(),
+            )
+            .unwrap();
+            builder.enable_incremental();
+            let seen = rendered.clone();
+            let root = output_dir.clone();
+            builder.add_output_transform("record", move |path, _| {
+                seen.lock()
+                    .unwrap()
+                    .push(path.strip_prefix(&root).unwrap().to_owned());
+            });
+            builder.build().await.unwrap();
+            let mut rendered = rendered.lock().unwrap().clone();
+            rendered.sort();
+            rendered
+        };
+
+        assert_eq!(build().await.len(), 3);
+        assert!(build().await.is_empty());
+
+        let fragment = source_dir.join("fragment.inc");
+        std::fs::write(&fragment, "Edited fragment.\n").unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&fragment)
+            .unwrap()
+            .set_modified(std::time::SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+
+        assert_eq!(build().await, [PathBuf::from("index.html")]);
+        let html = std::fs::read_to_string(output_dir.join("index.html")).unwrap();
+        assert!(html.contains("Edited fragment."));
+    }
+
+    #[tokio::test]
+    async fn test_toctree_nests_listed_documents_to_maxdepth() {
+        let (_temp_dir, output_dir, _) = build_project(&[
+                (
+                    "index.rst",
+                    "Index\n#####\n\n.. toctree::\n   :maxdepth: 2\n   :caption: Contents\n\n   guide/index\n   missing\n\n.. toctree::\n   :hidden:\n\n   about\n",
+                ),
+                (
+                    "guide/index.rst",
+                    "User Guide\n##########\n\n.. toctree::\n   :glob:\n\n   *\n",
+                ),
+                (
+                    "guide/install.rst",
+                    "Installing\n##########\n\n.. toctree::\n\n   /about\n",
+                ),
+                ("guide/usage.rst", "Usage\n#####\n"),
+                ("about.rst", "About\n#####\n"),
+            ], BuildConfig::default()).await;
+
+        let index = std::fs::read_to_string(output_dir.join("index.html")).unwrap();
+        assert!(index.contains("<span class=\"caption-text\">Contents</span>"));
+        assert!(index.contains(
+            "<li class=\"toctree-l1\"><a class=\"reference internal\" href=\"guide/index.html\">User Guide</a>\n<ul>\n\
+             <li class=\"toctree-l2\"><a class=\"reference internal\" href=\"guide/install.html\">Installing</a></li>\n\
+             <li class=\"toctree-l2\"><a class=\"reference internal\" href=\"guide/usage.html\">Usage</a></li>\n\
+             </ul>\n</li>"
+        ));
+        // Beyond maxdepth, and the hidden toctree, list nothing
+        assert!(!index.contains("toctree-l3"));
+        assert!(!index.contains("about.html"));
+        assert!(!index.contains("missing"));
+
+        let guide = std::fs::read_to_string(output_dir.join("guide/index.html")).unwrap();
+        assert!(guide.contains(
+            "<li class=\"toctree-l2\"><a class=\"reference internal\" href=\"../about.html\">About</a></li>"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_dirhtml_layout_writes_directory_pages() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        let output_dir = temp_dir.path().join("build");
+        write_project(
+            &source_dir,
+            &[
+                (
+                    "index.rst",
+                    "Index\n#####\n\n.. toctree::\n\n   guide/index\n   about\n",
+                ),
+                (
+                    "guide/index.rst",
+                    "User Guide\n##########\n\n.. toctree::\n\n   intro\n",
+                ),
+                (
+                    "guide/intro.rst",
+                    "Introduction\n############\n\nSee :doc:`../about`.\n",
+                ),
+                ("about.rst", "About\n#####\n"),
+            ],
+        );
+
+        let mut builder =
+            SphinxBuilder::new(BuildConfig::default(), source_dir, output_dir.clone()).unwrap();
+        builder.set_page_layout(PageLayout::Directory);
+        builder.build().await.unwrap();
+
+        for page in [
+            "index.html",
+            "guide/index.html",
+            "guide/intro/index.html",
+            "about/index.html",
+        ] {
+            assert!(output_dir.join(page).exists(), "{} missing", page);
+        }
+        assert!(!output_dir.join("about.html").exists());
+
+        let index = std::fs::read_to_string(output_dir.join("index.html")).unwrap();
+        assert!(index.contains("href=\"guide/\""));
+        assert!(index.contains("href=\"about/\""));
+        let guide = std::fs::read_to_string(output_dir.join("guide/index.html")).unwrap();
+        assert!(guide.contains("href=\"intro/\""));
+        let intro = std::fs::read_to_string(output_dir.join("guide/intro/index.html")).unwrap();
+        assert!(intro.contains("href=\"../../about/\""));
+    }
+
+    #[tokio::test]
+    async fn test_singlehtml_layout_writes_one_page_in_toctree_order() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        let output_dir = temp_dir.path().join("build");
+        write_project(
+            &source_dir,
+            &[
+                (
+                    "index.rst",
+                    "Index\n#####\n\n.. toctree::\n\n   usage\n   intro\n",
+                ),
+                (
+                    "intro.rst",
+                    "Introduction\n############\n\nSee :doc:`usage`.\n",
+                ),
+                ("usage.rst", "Usage\n#####\n\nRun it.\n"),
+            ],
+        );
+
+        let mut builder =
+            SphinxBuilder::new(BuildConfig::default(), source_dir, output_dir.clone()).unwrap();
+        builder.set_page_layout(PageLayout::Single);
+        builder.build().await.unwrap();
+
+        assert!(!output_dir.join("intro.html").exists());
+        assert!(!output_dir.join("usage.html").exists());
+        let page = std::fs::read_to_string(output_dir.join("index.html")).unwrap();
+        let position = |section: &str| page.find(section).unwrap();
+        assert!(
+            position("<section id=\"document-index\">")
+                < position("<section id=\"document-usage\">")
+        );
+        assert!(
+            position("<section id=\"document-usage\">")
+                < position("<section id=\"document-intro\">")
+        );
+        assert!(page.contains("href=\"#document-usage\""));
+        assert!(!page.contains("usage.html"));
+    }
+
+    #[tokio::test]
+    async fn test_nested_include_is_rendered_and_tracked() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        let output_dir = temp_dir.path().join("build");
+        write_project(
+            &source_dir,
+            &[
+                (
+                    "index.rst",
+                    "Index\n#####\n\n.. include:: shared/outer.inc\n",
+                ),
+                (
+                    "shared/outer.inc",
+                    "Outer text with **markup**.\n\n.. include:: /deep.inc\n",
+                ),
+                ("deep.inc", "Original deep text.\n"),
+            ],
+        );
+
+        let build = || async {
+            let mut builder = SphinxBuilder::new(
+                BuildConfig::default(),
+                source_dir.clone(),
+                output_dir.clone(),
+            )
+            .unwrap();
+            builder.enable_incremental();
+            let stats = builder.build().await.unwrap();
+            (
+                stats.errors,
+                std::fs::read_to_string(output_dir.join("index.html")).unwrap(),
+            )
+        };
+
+        let (errors, html) = build().await;
+        assert_eq!(errors, 0);
+        assert!(html.contains("<strong>markup</strong>"));
+        assert!(html.contains("Original deep text."));
+
+        let deep = source_dir.join("deep.inc");
+        std::fs::write(&deep, "Edited deep text.\n").unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&deep)
+            .unwrap()
+            .set_modified(std::time::SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+
+        let (_, html) = build().await;
+        assert!(html.contains("Edited deep text."));
+    }
+
+    #[tokio::test]
+    async fn test_images_are_rendered_and_copied() {
+        let (_temp_dir, output_dir, stats) = build_project(&[
+                ("index.rst", "Index\n#####\n\n.. toctree::\n\n   guide/intro\n"),
+                (
+                    "guide/intro.rst",
+                    "Intro\n#####\n\n.. image:: ../img/logo.png\n   :alt: Logo\n   :width: 200\n   :scale: 50%\n   :align: center\n\n\
+                     .. figure:: /img/logo.png\n   :name: logo-figure\n\n   The *logo*.\n\n   Drawn by hand.\n\n\
+                     .. figure:: shots/logo.png\n\n.. image:: https://example.com/badge.svg\n",
+                ),
+                ("img/logo.png", "first"),
+                ("guide/shots/logo.png", "second"),
+            ], BuildConfig::default()).await;
+        assert_eq!(stats.warnings, 0, "{:?}", stats.warning_details);
+
+        let html = std::fs::read_to_string(output_dir.join("guide/intro.html")).unwrap();
+        assert!(html.contains(
+            "<img alt=\"Logo\" class=\"align-center\" src=\"../_images/logo.png\" style=\"width: 100px;\" />"
+        ));
+        assert!(html.contains(
+            "<figure class=\"align-default\" id=\"logo-figure\">\n\
+             <img alt=\"../_images/logo.png\" src=\"../_images/logo.png\" />\n\
+             <figcaption>\n<p><span class=\"caption-text\">The <em>logo</em>.</span></p>\n\
+             <div class=\"legend\">\n<p>Drawn by hand.</p>\n</div>\n</figcaption>\n</figure>"
+        ));
+        // A different file of the same name gets a name of its own
+        assert!(html.contains("src=\"../_images/logo1.png\""));
+        assert!(html.contains("src=\"https://example.com/badge.svg\""));
+
+        assert_eq!(
+            std::fs::read_to_string(output_dir.join("_images/logo.png")).unwrap(),
+            "first"
+        );
+        assert_eq!(
+            std::fs::read_to_string(output_dir.join("_images/logo1.png")).unwrap(),
+            "second"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ref_resolves_labels_within_and_across_documents() {
+        let (_temp_dir, output_dir, stats) = build_project(&[
+                (
+                    "index.rst",
+                    ".. _top:\n\nIndex\n#####\n\nSee :ref:`install`, :ref:`the command <Setup-Cmd>` \
+                     and :ref:`top`.\n\nNot :ref:`nowhere`.\n\n.. toctree::\n\n   guide/install\n",
+                ),
+                (
+                    "guide/install.rst",
+                    ".. _install:\n\nInstalling\n##########\n\nBack to :ref:`top`.\n\n\
+                     .. code-block:: sh\n   :caption: Setup command\n   :name: setup-cmd\n\n   make\n",
+                ),
+            ], BuildConfig::default()).await;
+
+        let index = std::fs::read_to_string(output_dir.join("index.html")).unwrap();
+        assert!(index.contains("<span id=\"top\"></span>"));
+        assert!(index.contains(
+            "See <a class=\"reference internal\" href=\"guide/install.html#install\">Installing</a>, \
+             <a class=\"reference internal\" href=\"guide/install.html#setup-cmd\">the command</a> \
+             and <a class=\"reference internal\" href=\"#top\">Index</a>."
+        ));
+        assert!(index.contains("Not <span class=\"xref std std-ref\">nowhere</span>."));
+
+        let install = std::fs::read_to_string(output_dir.join("guide/install.html")).unwrap();
+        assert!(install.contains(
+            "Back to <a class=\"reference internal\" href=\"../index.html#top\">Index</a>."
+        ));
+        assert!(install.contains("<div class=\"code-block-caption\" id=\"setup-cmd\">"));
+
+        let broken: Vec<_> = stats
+            .warning_details
+            .iter()
+            .filter(|warning| matches!(warning.warning_type, WarningType::BrokenCrossReference))
+            .collect();
+        assert_eq!(broken.len(), 1, "{:?}", stats.warning_details);
+        assert_eq!(
+            broken[0].message,
+            "cross-reference target not found: 'nowhere'"
+        );
+        assert_eq!(broken[0].line, Some(8));
+    }
+
+    #[tokio::test]
+    async fn test_doc_role_links_relative_to_the_referencing_document() {
+        let (_temp_dir, output_dir, stats) = build_project(
+            &[
+                (
+                    "index.rst",
+                    "Index\n#####\n\n.. toctree::\n\n   guide/intro\n   api/reference\n",
+                ),
+                (
+                    "guide/intro.rst",
+                    "Intro\n#####\n\nSee :doc:`../api/reference`, :doc:`/api/reference`, \
+                     :doc:`the API <../api/reference>` and :doc:`setup`.\n",
+                ),
+                ("api/reference.rst", "API Reference\n#############\n"),
+            ],
+            BuildConfig::default(),
+        )
+        .await;
+
+        let html = std::fs::read_to_string(output_dir.join("guide/intro.html")).unwrap();
+        let link = "<a class=\"reference internal\" href=\"../api/reference.html\">";
+        assert!(html.contains(&format!(
+            "See {link}API Reference</a>, {link}API Reference</a>, {link}the API</a> \
+             and <span class=\"xref std std-doc\">setup</span>."
+        )));
+
+        assert_eq!(stats.warnings, 1, "{:?}", stats.warning_details);
+        assert_eq!(
+            stats.warning_details[0].message,
+            "cross-reference target not found: 'setup'"
+        );
+        assert_eq!(stats.warning_details[0].line, Some(4));
+    }
+}
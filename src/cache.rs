@@ -28,6 +28,10 @@ struct CachedDocument {
     document: Document,
     hash: String,
     cached_at: DateTime<Utc>,
+    /// When the document's output was last written; `None` for a document
+    /// that was only parsed, e.g. by `prebuild`
+    #[serde(default)]
+    rendered_at: Option<DateTime<Utc>>,
     access_count: usize,
     size_bytes: usize,
 }
@@ -55,20 +59,29 @@ impl BuildCache {
     pub fn get_document(&self, file_path: &Path) -> Result<Document> {
         let hash = self.calculate_file_hash(file_path)?;
 
-        if let Some(cached) = self.documents.get(file_path) {
-            if cached.hash == hash && !self.is_expired(&cached.cached_at) {
+        // Release the map guard before updating or removing the entry, as
+        // DashMap deadlocks on a write to a shard the same thread is reading
+        let cached = self.documents.get(file_path).map(|cached| {
+            (cached.hash == hash && !self.is_expired(&cached.cached_at))
+                .then(|| cached.document.clone())
+        });
+
+        match cached {
+            Some(Some(document)) => {
                 // Update access count
-                self.documents.alter(file_path, |_, mut cached| {
+                if let Some(mut cached) = self.documents.get_mut(file_path) {
                     cached.access_count += 1;
-                    cached
-                });
+                }
 
                 *self.hit_count.write() += 1;
                 debug!("Cache hit for {}", file_path.display());
-                return Ok(cached.document.clone());
+                return Ok(document);
+            }
+            Some(None) => {
+                // Remove expired or outdated entry
+                self.documents.remove(file_path);
             }
-            // Remove expired or outdated entry
-            self.documents.remove(file_path);
+            None => {}
         }
 
         *self.miss_count.write() += 1;
@@ -76,7 +89,22 @@ impl BuildCache {
         Err(BuildError::Cache("Document not found in cache".to_string()).into())
     }
 
+    /// Cache a document whose output was just written
     pub fn store_document(&self, file_path: &Path, document: &Document) -> Result<()> {
+        self.store(file_path, document, Some(Utc::now()))
+    }
+
+    /// Cache a parsed document that still needs rendering
+    pub fn store_parsed(&self, file_path: &Path, document: &Document) -> Result<()> {
+        self.store(file_path, document, None)
+    }
+
+    fn store(
+        &self,
+        file_path: &Path,
+        document: &Document,
+        rendered_at: Option<DateTime<Utc>>,
+    ) -> Result<()> {
         let hash = self.calculate_file_hash(file_path)?;
         let size_bytes = self.estimate_document_size(document);
 
@@ -84,6 +112,7 @@ impl BuildCache {
             document: document.clone(),
             hash: hash.clone(),
             cached_at: Utc::now(),
+            rendered_at,
             access_count: 1,
             size_bytes,
         };
@@ -108,6 +137,13 @@ impl BuildCache {
         Ok(())
     }
 
+    /// When the cached document was last rendered; `None` if it never was
+    pub fn rendered_at(&self, file_path: &Path) -> Option<DateTime<Utc>> {
+        self.documents
+            .get(file_path)
+            .and_then(|cached| cached.rendered_at)
+    }
+
     #[allow(dead_code)]
     pub fn invalidate(&self, file_path: &Path) {
         self.documents.remove(file_path);
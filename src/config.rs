@@ -1,5 +1,6 @@
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::PathBuf;
 
 use crate::python_config::PythonConfigParser;
@@ -52,6 +53,9 @@ pub struct BuildConfig {
     /// Root document
     pub root_doc: Option<String>,
 
+    /// Encoding of source files (defaults to utf-8)
+    pub source_encoding: Option<String>,
+
     /// HTML theme style files
     pub html_style: Vec<String>,
 
@@ -91,20 +95,270 @@ pub struct BuildConfig {
     /// Source link suffix
     pub html_sourcelink_suffix: Option<String>,
 
+    /// Suffix of links between pages; empty or `/` for directory-style links
+    pub html_link_suffix: Option<String>,
+
     /// Use index
     pub html_use_index: Option<bool>,
 
+    /// Domain indices to generate
+    pub html_domain_indices: Option<DomainIndices>,
+
     /// Use OpenSearch
     pub html_use_opensearch: Option<bool>,
 
     /// Last updated format
     pub html_last_updated_fmt: Option<String>,
 
+    /// Base URL the documentation is served from
+    pub html_baseurl: Option<String>,
+
+    /// Split the search index into this many shards loaded on demand (monolithic if unset)
+    pub html_search_shards: Option<usize>,
+
+    /// Version segment of `html_baseurl` (e.g. "v1.2") left out of canonical URLs
+    pub html_version_path: Option<String>,
+
+    /// Emit OpenGraph and Twitter card meta tags
+    pub html_opengraph: Option<bool>,
+
     /// Templates path
     pub templates_path: Vec<PathBuf>,
 
     /// Turn warnings into errors
     pub fail_on_warning: bool,
+
+    /// Warning types to suppress (e.g. "directive.unknown", or "directive" for all subtypes)
+    pub suppress_warnings: Option<Vec<String>>,
+
+    /// Maximum source line length reported by the linter
+    pub lint_max_line_length: Option<usize>,
+
+    /// Active build tags (`-t`), checked against `only` conditions
+    #[serde(default)]
+    pub tags: Vec<String>,
+
+    /// Project-specific roles defined without Rust code, keyed by role name
+    #[serde(default)]
+    pub custom_roles: HashMap<String, CustomRoleConfig>,
+
+    /// Warn about rendered pages larger than this many kilobytes
+    #[serde(default)]
+    pub page_size_budget_kb: Option<u64>,
+
+    /// Warn when the output directory grows beyond this many megabytes
+    #[serde(default)]
+    pub output_size_budget_mb: Option<f64>,
+
+    /// Regex replacements applied, in order, to every generated page
+    #[serde(default)]
+    pub output_replacements: Vec<OutputReplacement>,
+
+    /// Old docnames mapped to the docname (optionally with `#anchor`) they moved to;
+    /// each gets a redirect page at its old path
+    #[serde(default)]
+    pub redirects: HashMap<String, String>,
+
+    /// Opening and closing delimiters recognized as inline math in text, e.g.
+    /// `[["$", "$"], ["\\(", "\\)"]]`; empty by default, so dollar signs stay literal
+    #[serde(default)]
+    pub math_inline_delimiters: Vec<(String, String)>,
+
+    /// Heading levels below the page title shown in the page's local table of
+    /// contents; all of them when unset
+    #[serde(default)]
+    pub localtoc_depth: Option<usize>,
+
+    /// List domain objects (functions, classes, ...) in the local table of
+    /// contents under their section, as Sphinx's `toc_object_entries`
+    #[serde(default)]
+    pub toc_object_entries: Option<bool>,
+
+    /// Fail page rendering when a template uses an undefined variable, instead of
+    /// rendering it empty; for developing themes
+    #[serde(default)]
+    pub strict_templates: bool,
+
+    /// Show `sectionauthor`/`moduleauthor`/`codeauthor` bylines, as Sphinx's
+    /// `show_authors`; authors are recorded in the metadata either way
+    #[serde(default)]
+    pub show_authors: bool,
+
+    /// Raw HTML added to the end of every page's `<head>`, e.g. analytics or
+    /// site verification tags
+    #[serde(default)]
+    pub html_head_extra: Option<String>,
+
+    /// Raw HTML added to every page just before `</body>`
+    #[serde(default)]
+    pub html_body_end_extra: Option<String>,
+
+    /// Files and directories copied verbatim into the output root, such as
+    /// `CNAME`, `.nojekyll` or `robots.txt`; relative to the source directory
+    #[serde(default)]
+    pub html_extra_path: Vec<PathBuf>,
+
+    /// Give code blocks a copy-to-clipboard button and a language label
+    #[serde(default)]
+    pub html_copy_code_button: bool,
+
+    /// conf.py was read by pattern matching its assignments rather than by
+    /// executing it with the embedded Python interpreter
+    #[serde(default)]
+    pub conf_py_statically: bool,
+
+    /// Source file suffixes mapped to the file type they are parsed as, as
+    /// Sphinx's `source_suffix`
+    #[serde(default = "default_source_suffix")]
+    pub source_suffix: HashMap<String, String>,
+
+    /// Globs, relative to the source directory, of files and directories not
+    /// read as sources, as Sphinx's `exclude_patterns`
+    #[serde(default)]
+    pub exclude_patterns: Vec<String>,
+
+    /// Number every displayed equation, not only labeled ones, as Sphinx's
+    /// `math_number_all`
+    #[serde(default)]
+    pub math_number_all: bool,
+
+    /// Layout of numbered code lines: `table` (default) or `inline`
+    #[serde(default)]
+    pub html_codeblock_linenos_style: Option<String>,
+
+    /// Other projects' inventories, by the name cross-references use for
+    /// them, as `sphinx.ext.intersphinx`'s `intersphinx_mapping`
+    #[serde(default)]
+    pub intersphinx_mapping: HashMap<String, IntersphinxTarget>,
+
+    /// Days a fetched inventory is reused before it is fetched again; a
+    /// negative value keeps it forever
+    #[serde(default = "default_intersphinx_cache_limit")]
+    pub intersphinx_cache_limit: i64,
+
+    /// Seconds before fetching an inventory gives up
+    #[serde(default)]
+    pub intersphinx_timeout: Option<f64>,
+
+    /// Check the certificates of HTTPS servers
+    #[serde(default = "default_tls_verify")]
+    pub tls_verify: bool,
+
+    /// CA certificate bundle HTTPS servers are checked against, instead of
+    /// the system's: one for all hosts, or one per host
+    #[serde(default)]
+    pub tls_cacerts: Option<TlsCacerts>,
+
+    /// `User-Agent` sent with HTTP requests
+    #[serde(default)]
+    pub user_agent: Option<String>,
+}
+
+/// `tls_cacerts`: a CA bundle for every host, or bundles by host name (`host`
+/// or `host:port`, as Sphinx accepts)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum TlsCacerts {
+    Bundle(String),
+    PerHost(HashMap<String, String>),
+}
+
+impl TlsCacerts {
+    /// The bundle to check the server of `url` against, if any
+    pub fn for_url(&self, url: &str) -> Option<&str> {
+        match self {
+            TlsCacerts::Bundle(path) => Some(path),
+            TlsCacerts::PerHost(paths) => {
+                let netloc = url.split_once("://")?.1.split(['/', '?', '#']).next()?;
+                let netloc = netloc.rsplit_once('@').map_or(netloc, |(_, host)| host);
+                let host = netloc.split(':').next().unwrap_or(netloc);
+                paths
+                    .get(netloc)
+                    .or_else(|| paths.get(host))
+                    .map(String::as_str)
+            }
+        }
+    }
+}
+
+/// An `intersphinx_mapping` entry
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IntersphinxTarget {
+    /// Base URI of the project's documentation, which inventory entries are
+    /// relative to
+    pub uri: String,
+
+    /// Where the inventory is, if not at `<uri>/objects.inv`: a URL or a
+    /// path relative to the source directory
+    #[serde(default)]
+    pub inventory: Option<String>,
+}
+
+fn default_intersphinx_cache_limit() -> i64 {
+    5
+}
+
+fn default_tls_verify() -> bool {
+    true
+}
+
+/// The entry of a `source_suffix` map that `path` ends with, preferring the
+/// longest suffix so that `.rst.txt` wins over `.txt`
+pub fn match_source_suffix<'a>(
+    source_suffix: &'a HashMap<String, String>,
+    path: &std::path::Path,
+) -> Option<(&'a String, &'a String)> {
+    let file_name = path.file_name()?.to_str()?;
+    source_suffix
+        .iter()
+        .filter(|(suffix, _)| {
+            file_name.len() > suffix.len() && file_name.ends_with(suffix.as_str())
+        })
+        .max_by_key(|(suffix, _)| suffix.len())
+}
+
+/// The suffixes read as sources when none are configured
+fn default_source_suffix() -> HashMap<String, String> {
+    [
+        (".rst", "restructuredtext"),
+        (".md", "markdown"),
+        (".txt", "text"),
+    ]
+    .into_iter()
+    .map(|(suffix, file_type)| (suffix.to_string(), file_type.to_string()))
+    .collect()
+}
+
+/// Which domain indices to generate, as Sphinx's `html_domain_indices`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum DomainIndices {
+    /// All (`true`) or none (`false`)
+    All(bool),
+    /// Only the named indices, e.g. `["py-modindex"]`
+    Only(Vec<String>),
+}
+
+/// A role defined in configuration (`custom_roles`)
+///
+/// `{target}` and `{text}` in the template are replaced by the role's target and
+/// display text. A template containing `://` renders a link to the formatted URL;
+/// any other template renders a span with the formatted text.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CustomRoleConfig {
+    pub template: String,
+
+    /// CSS class of the generated element (defaults to the role name)
+    #[serde(default)]
+    pub class: Option<String>,
+}
+
+/// A regex replacement applied to generated pages (`output_replacements`);
+/// `$1`/`${name}` in the replacement refer to capture groups
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OutputReplacement {
+    pub pattern: String,
+    pub replacement: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -185,6 +439,7 @@ impl Default for BuildConfig {
             copyright: Some("2024, Sphinx Ultra".to_string()),
             language: Some("en".to_string()),
             root_doc: Some("index".to_string()),
+            source_encoding: None,
             html_style: vec!["sphinx_rtd_theme.css".to_string()],
             html_css_files: vec![],
             html_js_files: vec![],
@@ -198,13 +453,47 @@ impl Default for BuildConfig {
             html_copy_source: Some(true),
             html_show_sourcelink: Some(true),
             html_sourcelink_suffix: Some(".txt".to_string()),
+            html_link_suffix: None,
             html_use_index: Some(true),
+            html_domain_indices: Some(DomainIndices::All(true)),
             html_use_opensearch: Some(false),
             html_last_updated_fmt: Some("%b %d, %Y".to_string()),
+            html_baseurl: None,
+            html_search_shards: None,
+            html_version_path: None,
+            html_opengraph: Some(false),
             templates_path: vec![PathBuf::from("_templates")],
 
             // Warning handling
             fail_on_warning: false,
+            suppress_warnings: None,
+            lint_max_line_length: None,
+            tags: Vec::new(),
+            custom_roles: HashMap::new(),
+            page_size_budget_kb: None,
+            output_size_budget_mb: None,
+            output_replacements: Vec::new(),
+            redirects: HashMap::new(),
+            math_inline_delimiters: Vec::new(),
+            localtoc_depth: None,
+            toc_object_entries: Some(true),
+            strict_templates: false,
+            show_authors: false,
+            html_head_extra: None,
+            html_body_end_extra: None,
+            html_extra_path: Vec::new(),
+            html_copy_code_button: false,
+            conf_py_statically: false,
+            source_suffix: default_source_suffix(),
+            exclude_patterns: Vec::new(),
+            math_number_all: false,
+            html_codeblock_linenos_style: None,
+            intersphinx_mapping: HashMap::new(),
+            intersphinx_cache_limit: default_intersphinx_cache_limit(),
+            intersphinx_timeout: None,
+            tls_verify: true,
+            tls_cacerts: None,
+            user_agent: None,
         }
     }
 }
@@ -259,21 +548,42 @@ impl BuildConfig {
         Ok(config)
     }
 
-    /// Load configuration from a Sphinx conf.py file
+    /// Load configuration from a Sphinx conf.py file, executing it with Python
     pub fn from_conf_py<P: AsRef<std::path::Path>>(conf_py_path: P) -> Result<Self> {
+        Self::from_conf_py_with(conf_py_path, false)
+    }
+
+    /// Load configuration from a Sphinx conf.py file; `statically` reads its
+    /// plain assignments by pattern matching instead of executing it
+    pub fn from_conf_py_with<P: AsRef<std::path::Path>>(
+        conf_py_path: P,
+        statically: bool,
+    ) -> Result<Self> {
         let mut parser = PythonConfigParser::new()?;
+        parser.set_execute(!statically);
         let conf_py_config = parser.parse_conf_py(conf_py_path)?;
-        Ok(conf_py_config.to_build_config())
+        let mut config = conf_py_config.to_build_config();
+        config.conf_py_statically = statically;
+        Ok(config)
     }
 
     /// Try to auto-detect and load configuration from various sources
     pub fn auto_detect<P: AsRef<std::path::Path>>(source_dir: P) -> Result<Self> {
+        Self::auto_detect_with(source_dir, false)
+    }
+
+    /// [`BuildConfig::auto_detect`], reading a conf.py statically if asked to
+    /// (see [`BuildConfig::from_conf_py_with`])
+    pub fn auto_detect_with<P: AsRef<std::path::Path>>(
+        source_dir: P,
+        conf_py_statically: bool,
+    ) -> Result<Self> {
         let source_dir = source_dir.as_ref();
 
         // Try conf.py first (Sphinx standard)
         let conf_py_path = source_dir.join("conf.py");
         if conf_py_path.exists() {
-            return Self::from_conf_py(conf_py_path);
+            return Self::from_conf_py_with(conf_py_path, conf_py_statically);
         }
 
         // Try sphinx-ultra.yaml
@@ -298,6 +608,30 @@ impl BuildConfig {
         Ok(Self::default())
     }
 
+    /// Whether warnings of `warning_type` (`type.subtype`) are suppressed,
+    /// matching Sphinx's `suppress_warnings` semantics
+    pub fn is_warning_suppressed(&self, warning_type: &str) -> bool {
+        let main_type = warning_type.split('.').next().unwrap_or(warning_type);
+        self.suppress_warnings
+            .iter()
+            .flatten()
+            .any(|suppressed| suppressed == warning_type || suppressed == main_type)
+    }
+
+    /// The configured source suffix `path` ends with
+    pub fn source_suffix_of(&self, path: &std::path::Path) -> Option<&str> {
+        match_source_suffix(&self.source_suffix, path).map(|(suffix, _)| suffix.as_str())
+    }
+
+    /// Whether the domain index `name` (e.g. "py-modindex") should be generated
+    pub fn is_domain_index_enabled(&self, name: &str) -> bool {
+        match &self.html_domain_indices {
+            None | Some(DomainIndices::All(true)) => true,
+            Some(DomainIndices::All(false)) => false,
+            Some(DomainIndices::Only(names)) => names.iter().any(|n| n == name),
+        }
+    }
+
     #[allow(dead_code)]
     pub fn save_to_file<P: AsRef<std::path::Path>>(&self, path: P) -> Result<()> {
         let content = if path.as_ref().extension().and_then(|s| s.to_str()) == Some("yaml")
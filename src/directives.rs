@@ -2,6 +2,9 @@ use anyhow::{anyhow, Result};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::highlight::highlight_code;
 
 /// Represents a parsed Sphinx directive
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -12,6 +15,10 @@ pub struct Directive {
     pub content: Vec<String>,
     pub line_number: usize,
     pub source_file: String,
+    /// Root of the documentation source, against which `/`-prefixed file
+    /// arguments resolve
+    #[serde(default)]
+    pub source_root: Option<PathBuf>,
 }
 
 /// Directive processor trait
@@ -19,6 +26,11 @@ pub trait DirectiveProcessor {
     fn process(&self, directive: &Directive) -> Result<String>;
     fn get_name(&self) -> &str;
     fn get_option_spec(&self) -> HashMap<String, DirectiveOptionType>;
+
+    /// Whether this directive is a placeholder that doesn't render real output yet
+    fn is_stub(&self) -> bool {
+        false
+    }
 }
 
 /// Directive option types
@@ -27,6 +39,8 @@ pub enum DirectiveOptionType {
     Flag,
     String,
     Integer,
+    /// An integer, or no value at all, as for `dedent`
+    OptionalInteger,
     Float,
     Choice(Vec<String>),
     Unchanged,
@@ -37,6 +51,82 @@ pub enum DirectiveOptionType {
     Class,
     ClassOption,
     Encoding,
+    /// Comma-separated line numbers and ranges, e.g. `1,3-5,8-`
+    LineRanges,
+}
+
+impl DirectiveOptionType {
+    /// Check an option value against this type, with a docutils-style message
+    /// when it doesn't fit
+    pub fn check(&self, value: &str) -> std::result::Result<(), String> {
+        let value = value.trim();
+        let is_number = |number: &str| number.parse::<f64>().is_ok_and(|n| n >= 0.0);
+        match self {
+            DirectiveOptionType::Flag if !value.is_empty() => {
+                Err(format!("no argument is permitted; \"{}\" supplied", value))
+            }
+            DirectiveOptionType::Integer if value.parse::<i64>().is_err() => {
+                Err(format!("\"{}\" is not an integer", value))
+            }
+            DirectiveOptionType::OptionalInteger
+                if !value.is_empty() && value.parse::<i64>().is_err() =>
+            {
+                Err(format!("\"{}\" is not an integer", value))
+            }
+            DirectiveOptionType::Float if value.parse::<f64>().is_err() => {
+                Err(format!("\"{}\" is not a number", value))
+            }
+            DirectiveOptionType::Choice(choices) if !choices.iter().any(|c| c == value) => {
+                Err(format!(
+                    "\"{}\" unknown; choose from {}",
+                    value,
+                    choices
+                        .iter()
+                        .map(|choice| format!("\"{}\"", choice))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ))
+            }
+            DirectiveOptionType::UnchangedRequired
+            | DirectiveOptionType::Path
+            | DirectiveOptionType::Class
+            | DirectiveOptionType::Encoding
+                if value.is_empty() =>
+            {
+                Err("argument required but none supplied".to_string())
+            }
+            DirectiveOptionType::Encoding if crate::parser::encoding_for_label(value).is_none() => {
+                Err(format!("unknown encoding: \"{}\"", value))
+            }
+            DirectiveOptionType::Percentage
+                if value
+                    .strip_suffix('%')
+                    .unwrap_or(value)
+                    .trim()
+                    .parse::<f64>()
+                    .is_err() =>
+            {
+                Err(format!("\"{}\" is not a percentage", value))
+            }
+            DirectiveOptionType::LineRanges if !is_line_ranges(value) => Err(format!(
+                "\"{}\" is not a list of line numbers and ranges such as \"1,3-5\"",
+                value
+            )),
+            DirectiveOptionType::LengthOrPercentage => {
+                let number = ["em", "ex", "px", "in", "cm", "mm", "pt", "pc", "%"]
+                    .iter()
+                    .find_map(|unit| value.strip_suffix(unit))
+                    .unwrap_or(value)
+                    .trim();
+                if is_number(number) {
+                    Ok(())
+                } else {
+                    Err(format!("\"{}\" is not a length or percentage", value))
+                }
+            }
+            _ => Ok(()),
+        }
+    }
 }
 
 /// Built-in directive processors
@@ -70,6 +160,49 @@ impl DirectiveRegistry {
         self.processors.get(name).map(|boxed| boxed.as_ref())
     }
 
+    /// Names of all registered directives, sorted
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.processors.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Option spec of a registered directive
+    pub fn option_spec(&self, name: &str) -> Option<HashMap<String, DirectiveOptionType>> {
+        self.get(name).map(|processor| processor.get_option_spec())
+    }
+
+    /// Problems with a directive's options, in option name order: options its
+    /// spec doesn't know and values of the wrong type
+    ///
+    /// Directives without a registered option spec aren't checked.
+    pub fn validate_options(&self, directive: &Directive) -> Vec<String> {
+        let spec = match self.option_spec(&directive.name) {
+            Some(spec) if !spec.is_empty() => spec,
+            _ => return Vec::new(),
+        };
+
+        let mut names: Vec<&String> = directive.options.keys().collect();
+        names.sort();
+        names
+            .into_iter()
+            .filter_map(|name| match spec.get(name) {
+                None => Some(format!("unknown option: \"{}\"", name)),
+                Some(option_type) => {
+                    option_type
+                        .check(&directive.options[name])
+                        .err()
+                        .map(|problem| {
+                            format!(
+                                "invalid option value: (option: \"{}\"; value: \"{}\") {}",
+                                name, directive.options[name], problem
+                            )
+                        })
+                }
+            })
+            .collect()
+    }
+
     pub fn process_directive(&self, directive: &Directive) -> Result<String> {
         if let Some(processor) = self.get(&directive.name) {
             processor.process(directive)
@@ -79,24 +212,47 @@ impl DirectiveRegistry {
         }
     }
 
+    /// Re-register the code directives to number lines in `style`
+    pub fn set_linenos_style(&mut self, style: LinenosStyle) {
+        for name in ["code-block", "code"] {
+            self.register(Box::new(CodeBlockDirective {
+                name: name.to_string(),
+                linenos_style: style,
+            }));
+        }
+        self.register(Box::new(LiteralIncludeDirective {
+            linenos_style: style,
+        }));
+    }
+
     fn register_builtin_directives(&mut self) {
         // Admonition directives
-        self.register(Box::new(AdmonitionDirective::new("note")));
-        self.register(Box::new(AdmonitionDirective::new("warning")));
-        self.register(Box::new(AdmonitionDirective::new("important")));
-        self.register(Box::new(AdmonitionDirective::new("tip")));
-        self.register(Box::new(AdmonitionDirective::new("caution")));
-        self.register(Box::new(AdmonitionDirective::new("danger")));
-        self.register(Box::new(AdmonitionDirective::new("error")));
-        self.register(Box::new(AdmonitionDirective::new("hint")));
-        self.register(Box::new(AdmonitionDirective::new("attention")));
-        self.register(Box::new(AdmonitionDirective::new("seealso")));
+        for (name, _) in ADMONITION_DIRECTIVES {
+            self.register(Box::new(AdmonitionDirective::new(name)));
+        }
         self.register(Box::new(GenericAdmonitionDirective));
+        self.register(Box::new(AcksDirective));
 
         // Code directives
-        self.register(Box::new(CodeBlockDirective));
-        self.register(Box::new(LiteralIncludeDirective));
+        self.register(Box::new(CodeBlockDirective::new("code-block")));
+        // docutils' own spelling, common in RST written outside Sphinx
+        self.register(Box::new(CodeBlockDirective::new("code")));
+        self.register(Box::new(LiteralIncludeDirective {
+            linenos_style: LinenosStyle::default(),
+        }));
         self.register(Box::new(HighlightDirective));
+        self.register(Box::new(ParsedLiteralDirective));
+
+        // Authorship directives
+        self.register(Box::new(AuthorDirective::new(
+            "sectionauthor",
+            "Section author",
+        )));
+        self.register(Box::new(AuthorDirective::new(
+            "moduleauthor",
+            "Module author",
+        )));
+        self.register(Box::new(AuthorDirective::new("codeauthor", "Code author")));
 
         // Structure directives
         self.register(Box::new(ToctreeDirective));
@@ -121,6 +277,13 @@ impl DirectiveRegistry {
         self.register(Box::new(MathDirective));
 
         // Domain-specific directives
+        self.register(Box::new(PyModuleDirective::new("py:module")));
+        self.register(Box::new(PyModuleDirective::new("module")));
+        self.register(Box::new(PyModuleDirective::new("py:currentmodule")));
+        self.register(Box::new(PyModuleDirective::new("currentmodule")));
+        for name in PY_OBJECT_DIRECTIVES {
+            self.register(Box::new(PyObjectDirective::new(name)));
+        }
         self.register(Box::new(AutoDocDirective));
         self.register(Box::new(AutoModuleDirective));
         self.register(Box::new(AutoClassDirective));
@@ -136,11 +299,13 @@ impl DirectiveRegistry {
         self.register(Box::new(PullQuoteDirective));
         self.register(Box::new(CompoundDirective));
         self.register(Box::new(ContainerDirective));
+        self.register(Box::new(ClassDirective::new("rst-class")));
+        self.register(Box::new(ClassDirective::new("class")));
 
         // Version directives
-        self.register(Box::new(VersionAddedDirective));
-        self.register(Box::new(VersionChangedDirective));
-        self.register(Box::new(DeprecatedDirective));
+        for name in VERSION_CHANGE_DIRECTIVES {
+            self.register(Box::new(VersionChangeDirective::new(name)));
+        }
     }
 }
 
@@ -170,12 +335,43 @@ pub fn parse_directive(
             content: Vec::new(),
             line_number,
             source_file: source_file.to_string(),
+            source_root: None,
         }))
     } else {
         Ok(None)
     }
 }
 
+/// HTML id for a `:name:` or `:ref:` label: lowercased, with runs of other
+/// characters collapsed to `-` (as docutils' `make_id`)
+pub fn name_to_id(name: &str) -> String {
+    name.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Id of the target an index entry links to: `index-<line>` for an
+/// `.. index::` directive, with the role's target appended for `:index:`
+pub fn index_anchor(line: usize, role_target: Option<&str>) -> String {
+    match role_target {
+        Some(target) => format!("index-{}-{}", line, name_to_id(target)),
+        None => format!("index-{}", line),
+    }
+}
+
+/// ` id="..."` for a directive with a `:name:` option, so it can be linked to
+fn id_attribute(directive: &Directive) -> String {
+    directive
+        .options
+        .get("name")
+        .map(|name| name_to_id(name))
+        .filter(|id| !id.is_empty())
+        .map(|id| format!(" id=\"{}\"", id))
+        .unwrap_or_default()
+}
+
 // Admonition Directive
 struct AdmonitionDirective {
     name: String,
@@ -191,35 +387,8 @@ impl AdmonitionDirective {
 
 impl DirectiveProcessor for AdmonitionDirective {
     fn process(&self, directive: &Directive) -> Result<String> {
-        let class = if self.name == "seealso" {
-            "seealso"
-        } else {
-            &self.name
-        };
-        let title = if directive.arguments.is_empty() {
-            match self.name.as_str() {
-                "note" => "Note",
-                "warning" => "Warning",
-                "important" => "Important",
-                "tip" => "Tip",
-                "caution" => "Caution",
-                "danger" => "Danger",
-                "error" => "Error",
-                "hint" => "Hint",
-                "attention" => "Attention",
-                "seealso" => "See also",
-                _ => &self.name,
-            }
-        } else {
-            &directive.arguments[0]
-        };
-
-        let content = directive.content.join("\n");
-
-        Ok(format!(
-            "<div class=\"admonition {}\"><p class=\"admonition-title\">{}</p>{}</div>",
-            class, title, content
-        ))
+        let body = escaped_paragraphs(&admonition_content(directive));
+        Ok(admonition_html(directive, "", &body))
     }
 
     fn get_name(&self) -> &str {
@@ -234,19 +403,76 @@ impl DirectiveProcessor for AdmonitionDirective {
     }
 }
 
+// Acknowledgements Directive
+struct AcksDirective;
+
+impl DirectiveProcessor for AcksDirective {
+    fn process(&self, directive: &Directive) -> Result<String> {
+        let items: Vec<String> = directive
+            .content
+            .iter()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty())
+            .map(|line| {
+                let item = line
+                    .strip_prefix("* ")
+                    .or_else(|| line.strip_prefix("- "))
+                    .or_else(|| line.strip_prefix("+ "))
+                    .unwrap_or(line);
+                format!("<li>{}</li>", html_escape::encode_text(item.trim()))
+            })
+            .collect();
+
+        let body = format!("<ul class=\"simple\">{}</ul>", items.join(""));
+        Ok(admonition_html(directive, "", &body))
+    }
+
+    fn get_name(&self) -> &str {
+        "acks"
+    }
+
+    fn get_option_spec(&self) -> HashMap<String, DirectiveOptionType> {
+        HashMap::new()
+    }
+}
+
+// Class Directive (rst-class / class)
+//
+// Renders nothing itself; the renderer attaches the classes to the next element.
+struct ClassDirective {
+    name: String,
+}
+
+impl ClassDirective {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+        }
+    }
+}
+
+impl DirectiveProcessor for ClassDirective {
+    fn process(&self, _directive: &Directive) -> Result<String> {
+        Ok(String::new())
+    }
+
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    fn get_option_spec(&self) -> HashMap<String, DirectiveOptionType> {
+        HashMap::new()
+    }
+}
+
 // Generic Admonition Directive
 struct GenericAdmonitionDirective;
 
 impl DirectiveProcessor for GenericAdmonitionDirective {
     fn process(&self, directive: &Directive) -> Result<String> {
-        let default_title = "Admonition".to_string();
-        let title = directive.arguments.first().unwrap_or(&default_title);
-        let content = directive.content.join("\n");
-
-        Ok(format!(
-            "<div class=\"admonition admonition-generic\"><p class=\"admonition-title\">{}</p>{}</div>",
-            title, content
-        ))
+        let title = html_escape::encode_text(directive.arguments.join(" ").trim()).into_owned();
+        let body = escaped_paragraphs(&admonition_content(directive));
+        Ok(admonition_html(directive, &title, &body))
     }
 
     fn get_name(&self) -> &str {
@@ -262,57 +488,390 @@ impl DirectiveProcessor for GenericAdmonitionDirective {
 }
 
 // Code Block Directive
-struct CodeBlockDirective;
+struct CodeBlockDirective {
+    name: String,
+    linenos_style: LinenosStyle,
+}
+
+impl CodeBlockDirective {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            linenos_style: LinenosStyle::default(),
+        }
+    }
+}
 
 impl DirectiveProcessor for CodeBlockDirective {
     fn process(&self, directive: &Directive) -> Result<String> {
         let default_language = "text".to_string();
         let language = directive.arguments.first().unwrap_or(&default_language);
-        let _linenos = directive.options.contains_key("linenos");
-        let _emphasize_lines = directive.options.get("emphasize-lines");
+        // `:lineno-start:` implies `:linenos:`, as in Sphinx; docutils spells
+        // both as `:number-lines: [start]`
+        let lineno_start = directive
+            .options
+            .get("lineno-start")
+            .or_else(|| directive.options.get("number-lines"))
+            .and_then(|start| start.trim().parse().ok());
+        let linenos = directive.options.contains_key("linenos")
+            || directive.options.contains_key("number-lines")
+            || lineno_start.is_some();
+        let emphasize = directive.options.get("emphasize-lines");
         let caption = directive.options.get("caption");
-        let _name = directive.options.get("name");
 
-        let content = directive.content.join("\n");
+        let mut lines = directive.content.clone();
+        if let Some(width) = directive.options.get("dedent").map(|width| width.trim()) {
+            let width = match width {
+                "" => None,
+                width => Some(
+                    width
+                        .parse()
+                        .map_err(|_| anyhow!("invalid dedent value: {}", width))?,
+                ),
+            };
+            dedent_lines(lines.iter_mut().collect(), width);
+        }
+        let mut code = highlight_code(language, &lines.join("\n"));
+        if let Some(spec) = emphasize {
+            code = emphasize_lines(&code, spec);
+        }
 
+        // A `:name:` anchors the caption, or the block itself
         let mut html = String::new();
-
-        if let Some(caption_text) = caption {
-            html.push_str(&format!(
-                "<div class=\"code-block-caption\">{}</div>",
+        match caption {
+            Some(caption_text) => html.push_str(&format!(
+                "<div class=\"code-block-caption\"{}>{}</div>",
+                id_attribute(directive),
                 caption_text
-            ));
+            )),
+            None if directive.options.contains_key("name") => {
+                html.push_str(&format!("<span{}></span>", id_attribute(directive)))
+            }
+            None => {}
         }
 
-        html.push_str(&format!(
-            "<div class=\"highlight-{}\"><pre><code class=\"language-{}\">{}</code></pre></div>",
-            language,
+        html.push_str(&highlight_block(
             language,
-            html_escape::encode_text(&content)
+            &code,
+            linenos.then(|| lineno_start.unwrap_or(1)),
+            self.linenos_style,
         ));
 
         Ok(html)
     }
 
     fn get_name(&self) -> &str {
-        "code-block"
+        &self.name
     }
 
     fn get_option_spec(&self) -> HashMap<String, DirectiveOptionType> {
         let mut options = HashMap::new();
         options.insert("linenos".to_string(), DirectiveOptionType::Flag);
         options.insert("lineno-start".to_string(), DirectiveOptionType::Integer);
-        options.insert("emphasize-lines".to_string(), DirectiveOptionType::String);
+        options.insert("number-lines".to_string(), DirectiveOptionType::String);
+        options.insert(
+            "emphasize-lines".to_string(),
+            DirectiveOptionType::LineRanges,
+        );
         options.insert("caption".to_string(), DirectiveOptionType::String);
         options.insert("name".to_string(), DirectiveOptionType::String);
-        options.insert("dedent".to_string(), DirectiveOptionType::Integer);
+        options.insert("dedent".to_string(), DirectiveOptionType::OptionalInteger);
         options.insert("force".to_string(), DirectiveOptionType::Flag);
+        options.insert("class".to_string(), DirectiveOptionType::ClassOption);
         options
     }
 }
 
+/// Whether a value is a comma-separated list of line numbers and ranges, as
+/// Sphinx's `parselinenos` accepts: `3`, `3-5`, `3-` or `-5`
+fn is_line_ranges(value: &str) -> bool {
+    let is_number = |part: &str| part.trim().parse::<usize>().is_ok();
+    !value.is_empty()
+        && value.split(',').all(|part| match part.split_once('-') {
+            Some((start, end)) => {
+                (is_number(start) || start.trim().is_empty())
+                    && (is_number(end) || end.trim().is_empty())
+                    && !(start.trim().is_empty() && end.trim().is_empty())
+            }
+            None => is_number(part),
+        })
+}
+
+/// The 1-based line numbers a spec such as `1,3-5,8-` selects out of `total`
+/// lines; numbers past the end are kept, for the caller to drop
+fn parse_line_ranges(spec: &str, total: usize) -> Result<Vec<usize>> {
+    let number = |part: &str| {
+        part.trim()
+            .parse::<usize>()
+            .ok()
+            .filter(|&n| n > 0)
+            .ok_or_else(|| anyhow!("invalid line number spec: {:?}", spec))
+    };
+    let mut numbers = Vec::new();
+    for part in spec.split(',') {
+        let (start, end) = match part.split_once('-') {
+            Some((start, end)) if start.trim().is_empty() => (1, number(end)?),
+            Some((start, end)) if end.trim().is_empty() => (number(start)?, total),
+            Some((start, end)) => match (number(start)?, number(end)?) {
+                (start, end) if start > end => {
+                    return Err(anyhow!("invalid line number spec: {:?}", spec))
+                }
+                range => range,
+            },
+            None => (number(part)?, number(part)?),
+        };
+        numbers.extend(start..=end);
+    }
+    Ok(numbers)
+}
+
+/// Mark the lines of escaped code an `emphasize-lines` spec selects; an invalid
+/// spec, already reported by option validation, marks nothing
+fn emphasize_lines(code: &str, spec: &str) -> String {
+    let total = code.lines().count();
+    let marked = parse_line_ranges(spec, total).unwrap_or_default();
+    code.lines()
+        .enumerate()
+        .map(|(i, line)| {
+            if marked.contains(&(i + 1)) {
+                format!("<span class=\"hll\">{}</span>", line)
+            } else {
+                line.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Resolve a file argument of an include-like directive: relative to the
+/// including document, or to the source root when it starts with `/`
+pub fn resolve_include_path(directive: &Directive, name: &str) -> PathBuf {
+    let name = name.trim();
+    match (name.strip_prefix('/'), &directive.source_root) {
+        (Some(rooted), Some(root)) => root.join(rooted),
+        _ => Path::new(&directive.source_file)
+            .parent()
+            .unwrap_or(Path::new(""))
+            .join(name),
+    }
+}
+
+/// The lines of an included file that `:start-line:` and `:end-line:` select,
+/// counted from zero with negative values counting from the end (as in
+/// Python slices)
+pub fn select_include_lines(text: &str, options: &HashMap<String, String>) -> String {
+    let lines: Vec<&str> = text.lines().collect();
+    let bound = |option: &str, default: usize| match options
+        .get(option)
+        .and_then(|value| value.trim().parse::<isize>().ok())
+    {
+        Some(line) if line < 0 => lines.len().saturating_sub(line.unsigned_abs()),
+        Some(line) => (line as usize).min(lines.len()),
+        None => default,
+    };
+    let (start, end) = (bound("start-line", 0), bound("end-line", lines.len()));
+    if start >= end {
+        return String::new();
+    }
+    lines[start..end].join("\n") + "\n"
+}
+
+/// Read the file an include-like directive names, decoded per its `:encoding:`
+pub fn read_include_file(directive: &Directive, name: &str) -> Result<String> {
+    let path = resolve_include_path(directive, name);
+    let bytes =
+        std::fs::read(&path).map_err(|e| anyhow!("cannot read {}: {}", path.display(), e))?;
+
+    let encoding = match directive.options.get("encoding") {
+        Some(label) => crate::parser::encoding_for_label(label.trim())
+            .ok_or_else(|| anyhow!("unknown encoding '{}'", label.trim()))?,
+        None => encoding_rs::UTF_8,
+    };
+    encoding
+        .decode_without_bom_handling_and_without_replacement(&bytes)
+        .map(|text| text.into_owned())
+        .ok_or_else(|| {
+            anyhow!(
+                "invalid {} byte sequence in {}",
+                encoding.name(),
+                path.display()
+            )
+        })
+}
+
+/// Replace tabs with spaces up to the next multiple of `width` columns
+fn expand_tabs(line: &str, width: usize) -> String {
+    let width = width.max(1);
+    let mut expanded = String::with_capacity(line.len());
+    let mut column = 0;
+    for c in line.chars() {
+        if c == '\t' {
+            let spaces = width - column % width;
+            expanded.extend(std::iter::repeat_n(' ', spaces));
+            column += spaces;
+        } else {
+            expanded.push(c);
+            column += 1;
+        }
+    }
+    expanded
+}
+
+fn indentation(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+/// Strip the indentation `:dedent:` asks for: up to `width` columns, or the
+/// lines' common indentation when no width is given
+fn dedent_lines(mut lines: Vec<&mut String>, width: Option<usize>) {
+    let width = width.unwrap_or_else(|| {
+        lines
+            .iter()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| indentation(line))
+            .min()
+            .unwrap_or(0)
+    });
+    for line in &mut lines {
+        let strip = indentation(line).min(width);
+        line.drain(..strip);
+    }
+}
+
+/// The lines of a Python class or function, named like `Class.method`,
+/// together with its decorators
+fn python_object(lines: &[(usize, String)], name: &str) -> Option<Vec<(usize, String)>> {
+    let mut range = 0..lines.len();
+    let mut parent_indent = None;
+    for part in name.split('.') {
+        let definition = Regex::new(&format!(
+            r"^(\s*)(?:async\s+)?(?:def|class)\s+{}\b",
+            regex::escape(part)
+        ))
+        .ok()?;
+        let (start, indent) = (range.start..range.end).find_map(|i| {
+            let indent = definition.captures(&lines[i].1)?.get(1)?.as_str().len();
+            match parent_indent {
+                None if indent == 0 => Some((i, indent)),
+                Some(parent) if indent > parent => Some((i, indent)),
+                _ => None,
+            }
+        })?;
+
+        // The body starts once the brackets of the signature are closed
+        let mut depth = 0i32;
+        let mut body = start;
+        for (i, (_, line)) in lines.iter().enumerate().skip(start) {
+            for c in line.chars() {
+                match c {
+                    '(' | '[' | '{' => depth += 1,
+                    ')' | ']' | '}' => depth -= 1,
+                    _ => {}
+                }
+            }
+            body = i + 1;
+            if depth <= 0 {
+                break;
+            }
+        }
+        let mut end = (body..range.end)
+            .find(|&i| !lines[i].1.trim().is_empty() && indentation(&lines[i].1) <= indent)
+            .unwrap_or(range.end);
+        while end > body && lines[end - 1].1.trim().is_empty() {
+            end -= 1;
+        }
+
+        let mut first = start;
+        while first > range.start
+            && lines[first - 1].1.trim_start().starts_with('@')
+            && indentation(&lines[first - 1].1) == indent
+        {
+            first -= 1;
+        }
+        range = first..end;
+        parent_indent = Some(indent);
+    }
+    Some(lines[range].to_vec())
+}
+
+/// Prefix each line of escaped code with its line number, counting from `start`
+pub fn number_lines(code: &str, start: usize) -> String {
+    let width = (start + code.lines().count().saturating_sub(1))
+        .to_string()
+        .len();
+    code.lines()
+        .enumerate()
+        .map(|(i, line)| {
+            format!(
+                "<span class=\"linenos\">{:>width$}</span>{}",
+                start + i,
+                line,
+                width = width
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Layout of numbered code lines, as Sphinx's `html_codeblock_linenos_style`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LinenosStyle {
+    /// Numbers in a column of their own, beside the code
+    #[default]
+    Table,
+    /// Numbers at the start of each line
+    Inline,
+}
+
+impl LinenosStyle {
+    /// The configured style; anything but `inline` lays out a table
+    pub fn from_config(style: Option<&str>) -> Self {
+        match style.map(str::trim) {
+            Some("inline") => Self::Inline,
+            _ => Self::Table,
+        }
+    }
+}
+
+/// A highlighted block of escaped code, its lines numbered from
+/// `lineno_start` when given
+pub fn highlight_block(
+    language: &str,
+    code: &str,
+    lineno_start: Option<usize>,
+    style: LinenosStyle,
+) -> String {
+    match (lineno_start, style) {
+        (Some(start), LinenosStyle::Table) => {
+            let numbers = (start..start + code.lines().count().max(1))
+                .map(|number| format!("<span class=\"normal\">{}</span>", number))
+                .collect::<Vec<_>>()
+                .join("\n");
+            format!(
+                "<div class=\"highlight-{}\"><table class=\"highlighttable\"><tr>\
+                 <td class=\"linenos\"><div class=\"linenodiv\"><pre>{}</pre></div></td>\
+                 <td class=\"code\"><pre><code class=\"language-{}\">{}</code></pre></td>\
+                 </tr></table></div>",
+                language, numbers, language, code
+            )
+        }
+        (Some(start), LinenosStyle::Inline) => format!(
+            "<div class=\"highlight-{}\"><pre><code class=\"language-{}\">{}</code></pre></div>",
+            language,
+            language,
+            number_lines(code, start)
+        ),
+        (None, _) => format!(
+            "<div class=\"highlight-{}\"><pre><code class=\"language-{}\">{}</code></pre></div>",
+            language, language, code
+        ),
+    }
+}
+
 // Literal Include Directive
-struct LiteralIncludeDirective;
+struct LiteralIncludeDirective {
+    linenos_style: LinenosStyle,
+}
 
 impl DirectiveProcessor for LiteralIncludeDirective {
     fn process(&self, directive: &Directive) -> Result<String> {
@@ -362,12 +921,112 @@ impl DirectiveProcessor for LiteralIncludeDirective {
             })
             .unwrap_or_else(|| "text".to_string());
 
-        // For now, return a placeholder. In a full implementation,
-        // you would read the file and include its contents
-        Ok(format!(
-            "<div class=\"literal-include\"><div class=\"highlight-{}\"><pre><code class=\"language-{}\"><!-- Content of {} would be included here --></code></pre></div></div>",
-            language, language, filename
-        ))
+        let option = |name: &str| directive.options.get(name).map(|value| value.trim());
+        let number_option = |name: &str| -> Result<Option<usize>> {
+            option(name)
+                .map(|value| {
+                    value
+                        .parse()
+                        .map_err(|_| anyhow!("invalid {} value: {:?}", name, value))
+                })
+                .transpose()
+        };
+
+        let text = read_include_file(directive, filename)?;
+        let tab_width = number_option("tab-width")?;
+        // Each line keeps its number in the file, for `:lineno-match:`
+        let mut lines: Vec<(usize, String)> = text
+            .lines()
+            .enumerate()
+            .map(|(i, line)| match tab_width {
+                Some(width) => (i + 1, expand_tabs(line, width)),
+                None => (i + 1, line.to_string()),
+            })
+            .collect();
+
+        // `start-line` and `end-line` slice the file as docutils' include does
+        if let Some(end) = number_option("end-line")? {
+            lines.truncate(end);
+        }
+        if let Some(start) = number_option("start-line")? {
+            lines.drain(..start.min(lines.len()));
+        }
+        if let Some(object) = option("pyobject") {
+            lines = python_object(&lines, object)
+                .ok_or_else(|| anyhow!("object named {:?} not found in {}", object, filename))?;
+        }
+        if let Some(marker) = option("start-after") {
+            let position = lines
+                .iter()
+                .position(|(_, line)| line.contains(marker))
+                .ok_or_else(|| anyhow!("start-after pattern not found: {}", marker))?;
+            lines.drain(..=position);
+        }
+        if let Some(marker) = option("end-before") {
+            let position = lines
+                .iter()
+                .position(|(_, line)| line.contains(marker))
+                .ok_or_else(|| anyhow!("end-before pattern not found: {}", marker))?;
+            lines.truncate(position);
+        }
+        if let Some(spec) = option("lines") {
+            let selected: Vec<_> = parse_line_ranges(spec, lines.len())?
+                .into_iter()
+                .filter_map(|number| lines.get(number - 1).cloned())
+                .collect();
+            if selected.is_empty() {
+                return Err(anyhow!(
+                    "line number spec is out of range (1-{}): {:?}",
+                    lines.len(),
+                    spec
+                ));
+            }
+            lines = selected;
+        }
+        if let Some(width) = option("dedent") {
+            let width = match width {
+                "" => None,
+                _ => number_option("dedent")?,
+            };
+            dedent_lines(lines.iter_mut().map(|(_, line)| line).collect(), width);
+        }
+
+        let first_line = lines.first().map_or(1, |(number, _)| *number);
+        let mut source: Vec<String> = lines.into_iter().map(|(_, line)| line).collect();
+        if let Some(prepend) = directive.options.get("prepend") {
+            source.insert(0, prepend.clone());
+        }
+        if let Some(append) = directive.options.get("append") {
+            source.push(append.clone());
+        }
+
+        let mut code = highlight_code(&language, &source.join("\n"));
+        if let Some(spec) = option("emphasize-lines") {
+            code = emphasize_lines(&code, spec);
+        }
+        let lineno_start = if directive.options.contains_key("lineno-match") {
+            Some(first_line)
+        } else {
+            number_option("lineno-start")?
+        };
+        let lineno_start = (directive.options.contains_key("linenos") || lineno_start.is_some())
+            .then(|| lineno_start.unwrap_or(1));
+
+        let mut html = String::from("<div class=\"literal-include\">");
+        if let Some(caption) = directive.options.get("caption") {
+            html.push_str(&format!(
+                "<div class=\"code-block-caption\">{}</div>",
+                html_escape::encode_text(caption)
+            ));
+        }
+        html.push_str(&highlight_block(
+            &language,
+            &code,
+            lineno_start,
+            self.linenos_style,
+        ));
+        html.push_str("</div>");
+        Ok(html)
     }
 
     fn get_name(&self) -> &str {
@@ -379,15 +1038,18 @@ impl DirectiveProcessor for LiteralIncludeDirective {
         options.insert("language".to_string(), DirectiveOptionType::String);
         options.insert("linenos".to_string(), DirectiveOptionType::Flag);
         options.insert("lineno-start".to_string(), DirectiveOptionType::Integer);
-        options.insert("emphasize-lines".to_string(), DirectiveOptionType::String);
-        options.insert("lines".to_string(), DirectiveOptionType::String);
+        options.insert(
+            "emphasize-lines".to_string(),
+            DirectiveOptionType::LineRanges,
+        );
+        options.insert("lines".to_string(), DirectiveOptionType::LineRanges);
         options.insert("start-line".to_string(), DirectiveOptionType::Integer);
         options.insert("end-line".to_string(), DirectiveOptionType::Integer);
         options.insert("start-after".to_string(), DirectiveOptionType::String);
         options.insert("end-before".to_string(), DirectiveOptionType::String);
         options.insert("prepend".to_string(), DirectiveOptionType::String);
         options.insert("append".to_string(), DirectiveOptionType::String);
-        options.insert("dedent".to_string(), DirectiveOptionType::Integer);
+        options.insert("dedent".to_string(), DirectiveOptionType::OptionalInteger);
         options.insert("tab-width".to_string(), DirectiveOptionType::Integer);
         options.insert("encoding".to_string(), DirectiveOptionType::Encoding);
         options.insert("pyobject".to_string(), DirectiveOptionType::String);
@@ -395,6 +1057,110 @@ impl DirectiveProcessor for LiteralIncludeDirective {
         options.insert("name".to_string(), DirectiveOptionType::String);
         options.insert("class".to_string(), DirectiveOptionType::ClassOption);
         options.insert("diff".to_string(), DirectiveOptionType::String);
+        options.insert("lineno-match".to_string(), DirectiveOptionType::Flag);
+        options.insert("force".to_string(), DirectiveOptionType::Flag);
+        options
+    }
+}
+
+/// A single entry of a toctree directive
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToctreeEntry {
+    pub title: Option<String>,
+    pub target: String,
+    pub external: bool,
+}
+
+/// A toctree entry with an explicit title, `Title <target>`
+static TOCTREE_EXPLICIT_TITLE_REGEX: std::sync::LazyLock<Regex> =
+    std::sync::LazyLock::new(|| Regex::new(r"^(.+?)\s*<([^<>]+)>$").unwrap());
+
+impl ToctreeEntry {
+    /// Parse an entry such as `intro`, `Intro <intro>` or `Site <https://example.com>`
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(':') || line.starts_with("..") {
+            return None;
+        }
+
+        let (title, target) = match TOCTREE_EXPLICIT_TITLE_REGEX.captures(line) {
+            Some(captures) => (
+                Some(captures[1].to_string()),
+                captures[2].trim().to_string(),
+            ),
+            None => (None, line.to_string()),
+        };
+
+        let external = target.contains("://") || target.starts_with("mailto:");
+        Some(Self {
+            title,
+            target,
+            external,
+        })
+    }
+}
+
+/// Parse the entries of a toctree body, skipping options and comments
+pub fn parse_toctree_entries<'a>(lines: impl IntoIterator<Item = &'a str>) -> Vec<ToctreeEntry> {
+    lines.into_iter().filter_map(ToctreeEntry::parse).collect()
+}
+
+// Toctree Directive
+struct ToctreeDirective;
+
+impl DirectiveProcessor for ToctreeDirective {
+    fn process(&self, directive: &Directive) -> Result<String> {
+        // Hidden toctrees only feed navigation
+        if directive.options.contains_key("hidden") {
+            return Ok(String::new());
+        }
+
+        let entries = parse_toctree_entries(directive.content.iter().map(|line| line.as_str()));
+
+        let mut html = String::from("<div class=\"toctree-wrapper compound\">\n");
+        if let Some(caption) = directive.options.get("caption") {
+            html.push_str(&format!(
+                "<p class=\"caption\"><span class=\"caption-text\">{}</span></p>\n",
+                html_escape::encode_text(caption)
+            ));
+        }
+
+        html.push_str("<ul>\n");
+        for entry in &entries {
+            let title = entry.title.as_deref().unwrap_or(&entry.target);
+            let (class, href) = if entry.external {
+                ("reference external", entry.target.clone())
+            } else {
+                ("reference internal", format!("{}.html", entry.target))
+            };
+            html.push_str(&format!(
+                "<li class=\"toctree-l1\"><a class=\"{}\" href=\"{}\">{}</a></li>\n",
+                class,
+                html_escape::encode_double_quoted_attribute(&href),
+                html_escape::encode_text(title)
+            ));
+        }
+        html.push_str("</ul>\n</div>");
+
+        Ok(html)
+    }
+
+    fn get_name(&self) -> &str {
+        "toctree"
+    }
+
+    fn get_option_spec(&self) -> HashMap<String, DirectiveOptionType> {
+        let mut options = HashMap::new();
+        options.insert("maxdepth".to_string(), DirectiveOptionType::Integer);
+        options.insert("caption".to_string(), DirectiveOptionType::String);
+        options.insert("name".to_string(), DirectiveOptionType::String);
+        options.insert("class".to_string(), DirectiveOptionType::ClassOption);
+        options.insert("glob".to_string(), DirectiveOptionType::Flag);
+        options.insert("hidden".to_string(), DirectiveOptionType::Flag);
+        options.insert("includehidden".to_string(), DirectiveOptionType::Flag);
+        options.insert("numbered".to_string(), DirectiveOptionType::Unchanged);
+        options.insert("titlesonly".to_string(), DirectiveOptionType::Flag);
+        options.insert("reversed".to_string(), DirectiveOptionType::Flag);
         options
     }
 }
@@ -422,20 +1188,673 @@ impl DirectiveProcessor for HighlightDirective {
     }
 }
 
-// Additional directive implementations would go here...
-// For brevity, I'll provide stub implementations for the remaining directives
+struct ParsedLiteralDirective;
 
-macro_rules! stub_directive {
-    ($name:ident, $directive_name:expr) => {
-        struct $name;
+impl DirectiveProcessor for ParsedLiteralDirective {
+    fn process(&self, directive: &Directive) -> Result<String> {
+        // The renderer interprets inline markup; here the text stays literal
+        let content = directive.content.join("\n");
+        Ok(parsed_literal(
+            directive,
+            &html_escape::encode_text(&content),
+        ))
+    }
 
-        impl DirectiveProcessor for $name {
-            fn process(&self, directive: &Directive) -> Result<String> {
-                Ok(format!(
-                    "<!-- {} directive: {} -->",
-                    $directive_name,
-                    directive.arguments.join(" ")
-                ))
+    fn get_name(&self) -> &str {
+        "parsed-literal"
+    }
+
+    fn get_option_spec(&self) -> HashMap<String, DirectiveOptionType> {
+        let mut options = HashMap::new();
+        options.insert("class".to_string(), DirectiveOptionType::String);
+        options.insert("name".to_string(), DirectiveOptionType::String);
+        options
+    }
+}
+
+/// Wrap the rendered body of a `parsed-literal` in its preformatted block
+pub fn parsed_literal(directive: &Directive, body: &str) -> String {
+    let mut classes = String::from("literal-block");
+    if let Some(class) = directive.options.get("class") {
+        classes.push(' ');
+        classes.push_str(&html_escape::encode_double_quoted_attribute(class.trim()));
+    }
+    format!(
+        "<pre class=\"{}\"{}>{}</pre>",
+        classes,
+        id_attribute(directive),
+        body
+    )
+}
+
+/// `sectionauthor` and friends: a byline, which the renderer only keeps when
+/// `show_authors` is on
+struct AuthorDirective {
+    name: String,
+    label: String,
+}
+
+impl AuthorDirective {
+    fn new(name: &str, label: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            label: label.to_string(),
+        }
+    }
+}
+
+impl DirectiveProcessor for AuthorDirective {
+    fn process(&self, directive: &Directive) -> Result<String> {
+        Ok(format!(
+            "<p><em>{}: {}</em></p>",
+            self.label,
+            html_escape::encode_text(directive.arguments.join(" ").trim())
+        ))
+    }
+
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    fn get_option_spec(&self) -> HashMap<String, DirectiveOptionType> {
+        HashMap::new()
+    }
+}
+
+/// `py:module` (and `module`, the default domain's): the target the module
+/// index links to, unless `:noindex:`; `py:currentmodule` renders nothing
+struct PyModuleDirective {
+    name: String,
+}
+
+impl PyModuleDirective {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+        }
+    }
+}
+
+impl DirectiveProcessor for PyModuleDirective {
+    fn process(&self, directive: &Directive) -> Result<String> {
+        let module = directive.arguments.join(" ");
+        if self.name.ends_with("currentmodule")
+            || directive.options.contains_key("noindex")
+            || module.trim().is_empty()
+        {
+            return Ok(String::new());
+        }
+        Ok(format!(
+            "<span id=\"{}\"></span>",
+            html_escape::encode_double_quoted_attribute(&module_anchor(module.trim()))
+        ))
+    }
+
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    fn get_option_spec(&self) -> HashMap<String, DirectiveOptionType> {
+        let mut options = HashMap::new();
+        options.insert("platform".to_string(), DirectiveOptionType::String);
+        options.insert("synopsis".to_string(), DirectiveOptionType::String);
+        options.insert("noindex".to_string(), DirectiveOptionType::Flag);
+        options.insert("deprecated".to_string(), DirectiveOptionType::Flag);
+        options
+    }
+}
+
+/// HTML id of a Python module's target (`module-<name>`, as Sphinx)
+pub fn module_anchor(module: &str) -> String {
+    format!("module-{}", module)
+}
+
+/// Directives describing a Python object
+pub const PY_OBJECT_DIRECTIVES: [&str; 4] =
+    ["py:function", "py:class", "py:method", "py:attribute"];
+
+/// Python signatures: `prefix.name(arglist) -> annotation`
+static PY_SIGNATURE_REGEX: std::sync::LazyLock<Regex> = std::sync::LazyLock::new(|| {
+    Regex::new(r"^([\w.]*\.)?(\w+)\s*(?:\((.*)\)(?:\s*->\s*(.*))?)?$").unwrap()
+});
+
+/// A nested object directive or version change in a Python object's content
+static PY_NESTED_DIRECTIVE_REGEX: std::sync::LazyLock<Regex> = std::sync::LazyLock::new(|| {
+    Regex::new(r"^\.\.\s+(py:\w+|versionadded|versionchanged|deprecated)::\s*(.*)$").unwrap()
+});
+
+/// `py:function` and friends: a signature block describing the object
+struct PyObjectDirective {
+    name: String,
+}
+
+impl PyObjectDirective {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+        }
+    }
+}
+
+impl DirectiveProcessor for PyObjectDirective {
+    fn process(&self, directive: &Directive) -> Result<String> {
+        let module = directive.options.get("module").map(String::as_str);
+        Ok(py_object_html(
+            directive,
+            module,
+            None,
+            &mut PyObject::fullname,
+            &mut |text| html_escape::encode_text(text).into_owned(),
+        ))
+    }
+
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    fn get_option_spec(&self) -> HashMap<String, DirectiveOptionType> {
+        let mut options = HashMap::new();
+        for flag in [
+            "noindex",
+            "noindexentry",
+            "async",
+            "classmethod",
+            "staticmethod",
+            "abstractmethod",
+            "final",
+        ] {
+            options.insert(flag.to_string(), DirectiveOptionType::Flag);
+        }
+        for option in ["module", "type", "value", "canonical", "annotation"] {
+            options.insert(option.to_string(), DirectiveOptionType::String);
+        }
+        options
+    }
+}
+
+/// A Python object signature, split as Sphinx's `py_sig_re`
+#[derive(Debug, Clone, PartialEq)]
+pub struct PySignature {
+    /// Dotted prefix written before the name, with its trailing `.`
+    pub prefix: String,
+    pub name: String,
+    /// The parameters between the parentheses, if there are any
+    pub arglist: Option<String>,
+    pub retann: Option<String>,
+}
+
+impl PySignature {
+    pub fn parse(signature: &str) -> Option<Self> {
+        let captures = PY_SIGNATURE_REGEX.captures(signature.trim())?;
+        let group = |i: usize| captures.get(i).map(|m| m.as_str().trim().to_string());
+        Some(Self {
+            prefix: group(1).unwrap_or_default(),
+            name: group(2)?,
+            arglist: group(3),
+            retann: group(4).filter(|retann| !retann.is_empty()),
+        })
+    }
+}
+
+/// A Python object described by a directive, as it is registered and linked
+#[derive(Debug, Clone, PartialEq)]
+pub struct PyObject {
+    /// `function`, `class`, `method` or `attribute`
+    pub objtype: String,
+    pub signature: PySignature,
+    pub module: Option<String>,
+    /// Name within the module: enclosing class, prefix and name
+    pub qualname: String,
+}
+
+impl PyObject {
+    /// The object a `py:*` directive describes, within `module` and, when
+    /// nested in a class's content, the class `class` (module-relative)
+    pub fn new(directive: &Directive, module: Option<&str>, class: Option<&str>) -> Option<Self> {
+        let objtype = directive.name.strip_prefix("py:")?.to_string();
+        let signature = PySignature::parse(&directive.arguments.join(" "))?;
+        let module = directive
+            .options
+            .get("module")
+            .map(String::as_str)
+            .or(module)
+            .map(str::trim)
+            .filter(|module| !module.is_empty() && *module != "None")
+            .map(str::to_string);
+        let qualname = match class {
+            Some(class) => format!("{}.{}{}", class, signature.prefix, signature.name),
+            None => format!("{}{}", signature.prefix, signature.name),
+        };
+        Some(Self {
+            objtype,
+            signature,
+            module,
+            qualname,
+        })
+    }
+
+    /// The object's full dotted name, which is also its HTML id
+    pub fn fullname(&self) -> String {
+        match &self.module {
+            Some(module) => format!("{}.{}", module, self.qualname),
+            None => self.qualname.clone(),
+        }
+    }
+
+    /// The object's general index entry text (as Sphinx's `get_index_text`)
+    pub fn index_text(&self) -> String {
+        let (owner, name) = match self.qualname.rsplit_once('.') {
+            Some((owner, name)) => (Some(owner), name),
+            None => (None, self.qualname.as_str()),
+        };
+        let owner = owner.map(|owner| match &self.module {
+            Some(module) => format!("{}.{}", module, owner),
+            None => owner.to_string(),
+        });
+        match (self.objtype.as_str(), &owner, &self.module) {
+            ("method", Some(owner), _) => format!("{}() ({} method)", name, owner),
+            ("attribute", Some(owner), _) => format!("{} ({} attribute)", name, owner),
+            ("class", _, Some(module)) => format!("{} (class in {})", self.qualname, module),
+            ("class", _, None) => format!("{} (built-in class)", self.qualname),
+            (_, _, Some(module)) if self.signature.arglist.is_some() => {
+                format!("{}() (in module {})", self.qualname, module)
+            }
+            (_, _, Some(module)) => format!("{} (in module {})", self.qualname, module),
+            _ => format!("{}() (built-in function)", self.qualname),
+        }
+    }
+}
+
+/// A block of a Python object's content
+#[derive(Debug, Clone)]
+pub enum PyContent {
+    /// A paragraph of text
+    Text(String),
+    /// A nested object directive, like a class's `py:method`
+    Object(Directive),
+    /// A `versionadded`, `versionchanged` or `deprecated` note
+    VersionChange(Directive),
+}
+
+/// Split a Python object's content into paragraphs and the object
+/// directives and version changes nested in it, with their options and
+/// indented content
+pub fn py_object_content(directive: &Directive) -> Vec<PyContent> {
+    let mut blocks = Vec::new();
+    let mut paragraph: Vec<&str> = Vec::new();
+    let mut lines = directive.content.iter().enumerate().peekable();
+
+    let flush = |paragraph: &mut Vec<&str>, blocks: &mut Vec<PyContent>| {
+        if !paragraph.is_empty() {
+            blocks.push(PyContent::Text(paragraph.join(" ")));
+            paragraph.clear();
+        }
+    };
+
+    while let Some((i, line)) = lines.next() {
+        let Some(captures) = PY_NESTED_DIRECTIVE_REGEX.captures(line) else {
+            if line.trim().is_empty() {
+                flush(&mut paragraph, &mut blocks);
+            } else {
+                paragraph.push(line.trim());
+            }
+            continue;
+        };
+        flush(&mut paragraph, &mut blocks);
+
+        let mut options = HashMap::new();
+        while let Some((_, option)) = lines.peek() {
+            let Some((name, value)) = option
+                .strip_prefix("   ")
+                .and_then(|option| option.trim().strip_prefix(':'))
+                .and_then(|option| option.split_once(':'))
+            else {
+                break;
+            };
+            options.insert(name.to_string(), value.trim().to_string());
+            lines.next();
+        }
+        let mut content = Vec::new();
+        while let Some((_, body)) = lines.peek() {
+            if body.trim().is_empty() {
+                content.push(String::new());
+            } else if let Some(body) = body.strip_prefix("   ") {
+                content.push(body.to_string());
+            } else {
+                break;
+            }
+            lines.next();
+        }
+        while content.last().is_some_and(String::is_empty) {
+            content.pop();
+        }
+
+        let nested = Directive {
+            name: captures[1].to_string(),
+            arguments: vec![captures[2].trim().to_string()],
+            options,
+            content,
+            line_number: directive.line_number + i + 1,
+            source_file: directive.source_file.clone(),
+            source_root: directive.source_root.clone(),
+        };
+        blocks.push(
+            match VERSION_CHANGE_DIRECTIVES.contains(&nested.name.as_str()) {
+                true => PyContent::VersionChange(nested),
+                false => PyContent::Object(nested),
+            },
+        );
+    }
+    flush(&mut paragraph, &mut blocks);
+    blocks
+}
+
+/// Split a parameter list at its top-level commas
+fn split_parameters(arglist: &str) -> Vec<&str> {
+    let mut parameters = Vec::new();
+    let (mut depth, mut start) = (0i32, 0);
+    for (i, c) in arglist.char_indices() {
+        match c {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth -= 1,
+            ',' if depth == 0 => {
+                parameters.push(arglist[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parameters.push(arglist[start..].trim());
+    parameters.retain(|parameter| !parameter.is_empty());
+    parameters
+}
+
+/// Render a Python object directive as Sphinx's `<dl class="py ...">`
+/// signature block; `anchor` gives the id an object renders under and
+/// `render_text` renders the paragraphs of its content
+///
+/// Objects nested in a class's content are qualified by the class. A
+/// signature that doesn't parse is shown as written, without an id.
+pub fn py_object_html(
+    directive: &Directive,
+    module: Option<&str>,
+    class: Option<&str>,
+    anchor: &mut dyn FnMut(&PyObject) -> String,
+    render_text: &mut dyn FnMut(&str) -> String,
+) -> String {
+    let pre = |text: &str| {
+        format!(
+            "<span class=\"pre\">{}</span>",
+            html_escape::encode_text(text)
+        )
+    };
+    let objtype = directive.name.trim_start_matches("py:");
+    let object = PyObject::new(directive, module, class);
+
+    let mut signature = String::new();
+    let mut id = String::new();
+    match &object {
+        Some(object) => {
+            let sig = &object.signature;
+            for (flag, keyword) in [
+                ("final", "final"),
+                ("abstractmethod", "abstract"),
+                ("async", "async"),
+                ("classmethod", "classmethod"),
+                ("staticmethod", "static"),
+            ] {
+                if directive.options.contains_key(flag) {
+                    signature.push_str(&format!(
+                        "<em class=\"property\">{}<span class=\"w\"> </span></em>",
+                        pre(keyword)
+                    ));
+                }
+            }
+            if objtype == "class" {
+                signature.push_str(&format!(
+                    "<em class=\"property\">{}<span class=\"w\"> </span></em>",
+                    pre("class")
+                ));
+            }
+            // Nested objects are shown by their own name, others qualified
+            let prename = match (&object.module, class) {
+                (Some(module), None) => format!("{}.{}", module, sig.prefix),
+                _ => sig.prefix.clone(),
+            };
+            if !prename.is_empty() {
+                signature.push_str(&format!(
+                    "<span class=\"sig-prename descclassname\">{}</span>",
+                    pre(&prename)
+                ));
+            }
+            signature.push_str(&format!(
+                "<span class=\"sig-name descname\">{}</span>",
+                pre(&sig.name)
+            ));
+            if let Some(arglist) = &sig.arglist {
+                let parameters: Vec<String> = split_parameters(arglist)
+                    .into_iter()
+                    .map(|parameter| format!("<em class=\"sig-param\">{}</em>", pre(parameter)))
+                    .collect();
+                signature.push_str(&format!(
+                    "<span class=\"sig-paren\">(</span>{}<span class=\"sig-paren\">)</span>",
+                    parameters.join(", ")
+                ));
+            }
+            if let Some(retann) = &sig.retann {
+                signature.push_str(&format!(
+                    "<span class=\"sig-return\"><span class=\"sig-return-icon\">&#x2192;</span> \
+                     <span class=\"sig-return-typehint\">{}</span></span>",
+                    pre(retann)
+                ));
+            }
+            if let Some(annotation) = directive.options.get("type") {
+                signature.push_str(&format!(
+                    "<em class=\"property\"><span class=\"p\">:</span> {}</em>",
+                    pre(annotation)
+                ));
+            }
+            if let Some(value) = directive.options.get("value") {
+                signature.push_str(&format!(
+                    "<em class=\"property\"> <span class=\"p\">=</span> {}</em>",
+                    pre(value)
+                ));
+            }
+            if !directive.options.contains_key("noindex") {
+                id = anchor(object);
+            }
+        }
+        None => signature.push_str(&format!(
+            "<span class=\"sig-name descname\">{}</span>",
+            pre(directive.arguments.join(" ").trim())
+        )),
+    }
+
+    // Objects nested in a class belong to it
+    let class = match &object {
+        Some(object) if objtype == "class" => Some(object.qualname.clone()),
+        _ => class.map(str::to_string),
+    };
+    let module = object
+        .as_ref()
+        .and_then(|object| object.module.clone())
+        .or(module.map(str::to_string));
+    let body: Vec<String> = py_object_content(directive)
+        .into_iter()
+        .map(|block| match block {
+            PyContent::Text(text) => format!("<p>{}</p>", render_text(&text)),
+            PyContent::Object(nested) => py_object_html(
+                &nested,
+                module.as_deref(),
+                class.as_deref(),
+                &mut *anchor,
+                render_text,
+            ),
+            PyContent::VersionChange(change) => version_change_html(&change, &mut *render_text),
+        })
+        .collect();
+
+    let (id_attribute, headerlink) = if id.is_empty() {
+        (String::new(), String::new())
+    } else {
+        let id = html_escape::encode_double_quoted_attribute(&id).into_owned();
+        (
+            format!(" id=\"{}\"", id),
+            format!(
+                "<a class=\"headerlink\" href=\"#{}\" title=\"Link to this definition\">¶</a>",
+                id
+            ),
+        )
+    };
+    format!(
+        "<dl class=\"py {}\">\n<dt class=\"sig sig-object py\"{}>\n{}{}</dt>\n<dd>{}</dd>\n</dl>",
+        objtype,
+        id_attribute,
+        signature,
+        headerlink,
+        body.join("\n")
+    )
+}
+
+struct MathDirective;
+
+impl DirectiveProcessor for MathDirective {
+    fn process(&self, directive: &Directive) -> Result<String> {
+        // The renderer numbers labeled equations within their document
+        Ok(math_block(directive, None))
+    }
+
+    fn get_name(&self) -> &str {
+        "math"
+    }
+
+    fn get_option_spec(&self) -> HashMap<String, DirectiveOptionType> {
+        let mut options = HashMap::new();
+        options.insert("label".to_string(), DirectiveOptionType::String);
+        options.insert("name".to_string(), DirectiveOptionType::String);
+        options.insert("class".to_string(), DirectiveOptionType::String);
+        options.insert("nowrap".to_string(), DirectiveOptionType::Flag);
+        options
+    }
+}
+
+/// Anchor of a labeled equation, as `:eq:` links to it
+pub fn equation_id(label: &str) -> String {
+    format!("equation-{}", name_to_id(label))
+}
+
+/// Display math of a `math` directive, with its equation number when numbered
+pub fn math_block(directive: &Directive, number: Option<usize>) -> String {
+    let mut tex = directive.arguments.join(" ");
+    if !directive.content.is_empty() {
+        if !tex.is_empty() {
+            tex.push('\n');
+        }
+        tex.push_str(directive.content.join("\n").trim_end());
+    }
+    let tex = if directive.options.contains_key("nowrap") {
+        tex
+    } else {
+        wrap_displaymath(&tex)
+    };
+    let tex = html_escape::encode_text(&tex);
+
+    match directive.options.get("label").map(|label| label.trim()) {
+        Some(label) if !label.is_empty() => {
+            let id = equation_id(label);
+            let eqno = number
+                .map(|number| {
+                    format!(
+                        "<span class=\"eqno\">({})<a class=\"headerlink\" href=\"#{}\" title=\"Link to this equation\">\u{b6}</a></span>",
+                        number, id
+                    )
+                })
+                .unwrap_or_default();
+            format!(
+                "<div class=\"math notranslate nohighlight\" id=\"{}\">{}{}</div>",
+                id, eqno, tex
+            )
+        }
+        _ => format!(
+            "<div class=\"math notranslate nohighlight\"{}>{}{}</div>",
+            id_attribute(directive),
+            number
+                .map(|number| format!("<span class=\"eqno\">({})</span>", number))
+                .unwrap_or_default(),
+            tex
+        ),
+    }
+}
+
+/// Wrap displayed math in MathJax's `\[...\]` delimiters, as Sphinx's
+/// mathjax writer does
+///
+/// Blank-line separated equations are aligned together, and an equation
+/// spanning several `\\` lines is split.
+fn wrap_displaymath(tex: &str) -> String {
+    let parts: Vec<&str> = tex
+        .split("\n\n")
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .collect();
+    let wrap_part = |part: &str| {
+        if part.contains("\\\\") {
+            format!("\\begin{{split}}{}\\end{{split}}", part)
+        } else {
+            part.to_string()
+        }
+    };
+    match parts.as_slice() {
+        [] => "\\[\\]".to_string(),
+        [part] => format!("\\[{}\\]", wrap_part(part)),
+        parts => format!(
+            "\\[\\begin{{align}}\\begin{{aligned}}{}\\end{{aligned}}\\end{{align}}\\]",
+            parts
+                .iter()
+                .map(|part| wrap_part(part))
+                .collect::<Vec<_>>()
+                .join("\\\\")
+        ),
+    }
+}
+
+struct OnlyDirective;
+
+impl DirectiveProcessor for OnlyDirective {
+    fn process(&self, directive: &Directive) -> Result<String> {
+        // The renderer evaluates the tag expression against the build's tags
+        Ok(format!(
+            "<!-- only: {} -->",
+            html_escape::encode_text(&directive.arguments.join(" "))
+        ))
+    }
+
+    fn get_name(&self) -> &str {
+        "only"
+    }
+
+    fn get_option_spec(&self) -> HashMap<String, DirectiveOptionType> {
+        HashMap::new()
+    }
+}
+
+// Additional directive implementations would go here...
+// For brevity, I'll provide stub implementations for the remaining directives
+
+macro_rules! stub_directive {
+    ($name:ident, $directive_name:expr) => {
+        stub_directive!($name, $directive_name, HashMap::new);
+    };
+    ($name:ident, $directive_name:expr, $option_spec:expr) => {
+        struct $name;
+
+        impl DirectiveProcessor for $name {
+            fn process(&self, directive: &Directive) -> Result<String> {
+                Ok(format!(
+                    "<!-- {} directive: {} -->",
+                    $directive_name,
+                    directive.arguments.join(" ")
+                ))
             }
 
             fn get_name(&self) -> &str {
@@ -443,24 +1862,673 @@ macro_rules! stub_directive {
             }
 
             fn get_option_spec(&self) -> HashMap<String, DirectiveOptionType> {
-                HashMap::new()
+                $option_spec()
+            }
+
+            fn is_stub(&self) -> bool {
+                true
             }
         }
     };
 }
 
-stub_directive!(ToctreeDirective, "toctree");
-stub_directive!(IndexDirective, "index");
-stub_directive!(OnlyDirective, "only");
+/// Options of docutils' `image` directive
+fn image_option_spec() -> HashMap<String, DirectiveOptionType> {
+    let mut options = HashMap::new();
+    options.insert("alt".to_string(), DirectiveOptionType::Unchanged);
+    options.insert(
+        "height".to_string(),
+        DirectiveOptionType::LengthOrPercentage,
+    );
+    options.insert("width".to_string(), DirectiveOptionType::LengthOrPercentage);
+    options.insert("scale".to_string(), DirectiveOptionType::Percentage);
+    options.insert(
+        "align".to_string(),
+        DirectiveOptionType::Choice(
+            ["top", "middle", "bottom", "left", "center", "right"]
+                .map(String::from)
+                .to_vec(),
+        ),
+    );
+    options.insert("target".to_string(), DirectiveOptionType::UnchangedRequired);
+    options.insert(
+        "loading".to_string(),
+        DirectiveOptionType::Choice(["embed", "link", "lazy"].map(String::from).to_vec()),
+    );
+    options.insert("class".to_string(), DirectiveOptionType::ClassOption);
+    options.insert("name".to_string(), DirectiveOptionType::String);
+    options
+}
+
+/// Options of docutils' `figure` directive: the image's, with the figure
+/// aligned only horizontally
+fn figure_option_spec() -> HashMap<String, DirectiveOptionType> {
+    let mut options = image_option_spec();
+    options.insert(
+        "align".to_string(),
+        DirectiveOptionType::Choice(["left", "center", "right"].map(String::from).to_vec()),
+    );
+    options.insert(
+        "figwidth".to_string(),
+        DirectiveOptionType::LengthOrPercentage,
+    );
+    options.insert("figclass".to_string(), DirectiveOptionType::ClassOption);
+    options
+}
+
+/// `.. index::` renders the target its entries link to; the entries are
+/// collected into the general index by the build environment
+struct IndexDirective;
+
+impl DirectiveProcessor for IndexDirective {
+    fn process(&self, directive: &Directive) -> Result<String> {
+        Ok(format!(
+            "<span id=\"{}\"></span>",
+            index_anchor(directive.line_number, None)
+        ))
+    }
+
+    fn get_name(&self) -> &str {
+        "index"
+    }
+
+    fn get_option_spec(&self) -> HashMap<String, DirectiveOptionType> {
+        HashMap::from([("name".to_string(), DirectiveOptionType::String)])
+    }
+}
+
 stub_directive!(IfConfigDirective, "ifconfig");
-stub_directive!(ImageDirective, "image");
-stub_directive!(FigureDirective, "figure");
+/// Whether an image URI points outside the project (`https://...`, `data:...`)
+pub fn is_remote_image(uri: &str) -> bool {
+    uri.contains("://") || uri.starts_with("data:")
+}
+
+/// A length option with `:scale:` applied; unitless lengths are pixels
+fn scaled_length(length: &str, scale: Option<f64>) -> String {
+    let length = length.trim();
+    let number_end = length
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(length.len());
+    let (number, unit) = length.split_at(number_end);
+    let unit = if unit.is_empty() { "px" } else { unit.trim() };
+    match (number.parse::<f64>(), scale) {
+        (Ok(number), Some(scale)) => {
+            format!("{}{}", (number * scale * 100.0).round() / 100.0, unit)
+        }
+        _ => format!("{}{}", number, unit),
+    }
+}
+
+/// The `<img>` of an `image` or `figure` directive, linked to its `:target:`
+///
+/// The `:align:` and `:name:` of a figure go on the figure, not its image.
+pub fn image_html(directive: &Directive) -> String {
+    let uri = directive
+        .arguments
+        .first()
+        .map(|uri| uri.trim())
+        .unwrap_or_default();
+    let scale = directive
+        .options
+        .get("scale")
+        .and_then(|scale| {
+            scale
+                .trim()
+                .trim_end_matches('%')
+                .trim()
+                .parse::<f64>()
+                .ok()
+        })
+        .map(|scale| scale / 100.0);
+
+    let mut attributes = format!(
+        " alt=\"{}\"",
+        html_escape::encode_double_quoted_attribute(
+            directive.options.get("alt").map_or(uri, |alt| alt.trim())
+        )
+    );
+    let mut classes: Vec<String> = Vec::new();
+    if let (Some(align), "image") = (directive.options.get("align"), directive.name.as_str()) {
+        classes.push(format!("align-{}", align.trim()));
+    }
+    if let Some(class) = directive.options.get("class") {
+        classes.extend(class.split_whitespace().map(str::to_string));
+    }
+    if !classes.is_empty() {
+        attributes.push_str(&format!(
+            " class=\"{}\"",
+            html_escape::encode_double_quoted_attribute(&classes.join(" "))
+        ));
+    }
+    if directive.name == "image" {
+        attributes.push_str(&id_attribute(directive));
+    }
+    if directive
+        .options
+        .get("loading")
+        .map(|loading| loading.trim())
+        == Some("lazy")
+    {
+        attributes.push_str(" loading=\"lazy\"");
+    }
+    attributes.push_str(&format!(
+        " src=\"{}\"",
+        html_escape::encode_double_quoted_attribute(uri)
+    ));
+    let style: Vec<String> = ["width", "height"]
+        .into_iter()
+        .filter_map(|option| {
+            let length = directive.options.get(option)?;
+            Some(format!("{}: {};", option, scaled_length(length, scale)))
+        })
+        .collect();
+    if !style.is_empty() {
+        attributes.push_str(&format!(" style=\"{}\"", style.join(" ")));
+    }
+
+    let img = format!("<img{} />", attributes);
+    match directive.options.get("target") {
+        Some(target) => format!(
+            "<a class=\"reference external image-reference\" href=\"{}\">{}</a>",
+            html_escape::encode_double_quoted_attribute(target.trim()),
+            img
+        ),
+        None => img,
+    }
+}
+
+/// A `figure`: its image with the first paragraph of the content as caption
+/// and the rest as legend; `render_inline` renders each paragraph
+pub fn figure_html(directive: &Directive, mut render_inline: impl FnMut(&str) -> String) -> String {
+    let mut classes = vec![format!(
+        "align-{}",
+        directive
+            .options
+            .get("align")
+            .map_or("default", |align| align.trim())
+    )];
+    if let Some(class) = directive.options.get("figclass") {
+        classes.extend(class.split_whitespace().map(str::to_string));
+    }
+    let style = directive
+        .options
+        .get("figwidth")
+        .map(|width| width.trim())
+        .filter(|width| *width != "image")
+        .map(|width| format!(" style=\"width: {}\"", scaled_length(width, None)))
+        .unwrap_or_default();
+
+    let mut html = format!(
+        "<figure class=\"{}\"{}{}>\n{}\n",
+        html_escape::encode_double_quoted_attribute(&classes.join(" ")),
+        id_attribute(directive),
+        style,
+        image_html(directive)
+    );
+
+    let content = directive.content.join("\n");
+    let mut paragraphs = content
+        .split("\n\n")
+        .map(str::trim)
+        .filter(|paragraph| !paragraph.is_empty());
+    if let Some(caption) = paragraphs.next() {
+        html.push_str(&format!(
+            "<figcaption>\n<p><span class=\"caption-text\">{}</span></p>\n",
+            render_inline(caption)
+        ));
+        let legend: String = paragraphs
+            .map(|paragraph| format!("<p>{}</p>\n", render_inline(paragraph)))
+            .collect();
+        if !legend.is_empty() {
+            html.push_str(&format!("<div class=\"legend\">\n{}</div>\n", legend));
+        }
+        html.push_str("</figcaption>\n");
+    }
+    html.push_str("</figure>");
+    html
+}
+
+/// Images; the renderer points their URI at the copy in `_images/`
+struct ImageDirective;
+
+impl DirectiveProcessor for ImageDirective {
+    fn process(&self, directive: &Directive) -> Result<String> {
+        if directive.arguments.is_empty() {
+            return Err(anyhow!("missing image URI"));
+        }
+        Ok(image_html(directive))
+    }
+
+    fn get_name(&self) -> &str {
+        "image"
+    }
+
+    fn get_option_spec(&self) -> HashMap<String, DirectiveOptionType> {
+        image_option_spec()
+    }
+}
+
+/// Figures; the renderer points their URI at the copy in `_images/` and
+/// interprets the caption's inline markup
+struct FigureDirective;
+
+impl DirectiveProcessor for FigureDirective {
+    fn process(&self, directive: &Directive) -> Result<String> {
+        if directive.arguments.is_empty() {
+            return Err(anyhow!("missing image URI"));
+        }
+        Ok(figure_html(directive, |text| {
+            html_escape::encode_text(text).into_owned()
+        }))
+    }
+
+    fn get_name(&self) -> &str {
+        "figure"
+    }
+
+    fn get_option_spec(&self) -> HashMap<String, DirectiveOptionType> {
+        figure_option_spec()
+    }
+}
+
+/// Cell text of a `csv-table` or `list-table`, split into header and body
+/// rows; cells keep their line breaks, blank lines separating paragraphs
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TableData {
+    pub header: Vec<Vec<String>>,
+    pub body: Vec<Vec<String>>,
+}
+
+/// Options shared by docutils' `csv-table` and `list-table` directives
+fn table_option_spec() -> HashMap<String, DirectiveOptionType> {
+    let mut options = HashMap::new();
+    options.insert("header-rows".to_string(), DirectiveOptionType::Integer);
+    options.insert("stub-columns".to_string(), DirectiveOptionType::Integer);
+    options.insert("widths".to_string(), DirectiveOptionType::Unchanged);
+    options.insert("width".to_string(), DirectiveOptionType::LengthOrPercentage);
+    options.insert(
+        "align".to_string(),
+        DirectiveOptionType::Choice(["left", "center", "right"].map(String::from).to_vec()),
+    );
+    options.insert("class".to_string(), DirectiveOptionType::ClassOption);
+    options.insert("name".to_string(), DirectiveOptionType::String);
+    options
+}
+
+/// Options of docutils' `csv-table` directive
+fn csv_table_option_spec() -> HashMap<String, DirectiveOptionType> {
+    let mut options = table_option_spec();
+    options.insert("header".to_string(), DirectiveOptionType::Unchanged);
+    options.insert("file".to_string(), DirectiveOptionType::Path);
+    options.insert("encoding".to_string(), DirectiveOptionType::Encoding);
+    options.insert("delim".to_string(), DirectiveOptionType::UnchangedRequired);
+    options.insert("quote".to_string(), DirectiveOptionType::UnchangedRequired);
+    options.insert("escape".to_string(), DirectiveOptionType::UnchangedRequired);
+    options.insert("keepspace".to_string(), DirectiveOptionType::Flag);
+    options
+}
+
+/// Read the cells of a `csv-table` or `list-table` directive
+pub fn read_table(directive: &Directive) -> Result<TableData> {
+    let TableData {
+        mut header,
+        mut body,
+    } = match directive.name.as_str() {
+        "csv-table" => csv_table_rows(directive)?,
+        "list-table" => TableData {
+            header: Vec::new(),
+            body: list_table_rows(directive)?,
+        },
+        name => return Err(anyhow!("\"{}\" is not a table directive", name)),
+    };
+
+    let header_rows = match directive.options.get("header-rows") {
+        Some(rows) => rows.trim().parse::<usize>()?,
+        None => 0,
+    };
+    if header_rows > body.len() {
+        return Err(anyhow!(
+            "{} header row(s) specified but only {} row(s) of data supplied",
+            header_rows,
+            body.len()
+        ));
+    }
+    header.extend(body.drain(..header_rows));
+
+    // Short rows are padded, as docutils does
+    let columns = header.iter().chain(&body).map(Vec::len).max().unwrap_or(0);
+    if columns == 0 {
+        return Err(anyhow!("no table data supplied"));
+    }
+    for row in header.iter_mut().chain(body.iter_mut()) {
+        row.resize(columns, String::new());
+    }
+
+    Ok(TableData { header, body })
+}
+
+/// Header and body rows of a `csv-table`, read from its content or `:file:`
+fn csv_table_rows(directive: &Directive) -> Result<TableData> {
+    let single_char = |option: &str| -> Result<Option<char>> {
+        let Some(value) = directive.options.get(option) else {
+            return Ok(None);
+        };
+        match value.trim() {
+            "tab" => Ok(Some('\t')),
+            "space" => Ok(Some(' ')),
+            value if value.chars().count() == 1 => Ok(value.chars().next()),
+            value => Err(anyhow!(
+                "invalid {}: \"{}\" is not a single character",
+                option,
+                value
+            )),
+        }
+    };
+    let dialect = CsvDialect {
+        delim: single_char("delim")?.unwrap_or(','),
+        quote: single_char("quote")?.unwrap_or('"'),
+        escape: single_char("escape")?,
+        keepspace: directive.options.contains_key("keepspace"),
+    };
+
+    let text = match directive.options.get("file") {
+        Some(file) if !directive.content.is_empty() => {
+            return Err(anyhow!(
+                "\"file\" option given ({}) but the directive has content too",
+                file.trim()
+            ))
+        }
+        Some(file) => read_include_file(directive, file)?,
+        None => directive.content.join("\n"),
+    };
+    let header = match directive.options.get("header") {
+        Some(header) => dialect.parse(header)?,
+        None => Vec::new(),
+    };
+    Ok(TableData {
+        header,
+        body: dialect.parse(&text)?,
+    })
+}
+
+/// How `csv-table` splits its data, as Python's csv module with
+/// `skipinitialspace` unless `:keepspace:` is given
+struct CsvDialect {
+    delim: char,
+    quote: char,
+    /// Escape character; without one a doubled quote stands for a quote
+    escape: Option<char>,
+    keepspace: bool,
+}
+
+impl CsvDialect {
+    fn parse(&self, text: &str) -> Result<Vec<Vec<String>>> {
+        let mut records = Vec::new();
+        let mut record = Vec::new();
+        let mut field = String::new();
+        let mut field_start = true;
+        let mut in_quotes = false;
+
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            if Some(c) == self.escape {
+                field.extend(chars.next());
+                field_start = false;
+            } else if in_quotes {
+                if c != self.quote {
+                    field.push(c);
+                } else if self.escape.is_none() && chars.peek() == Some(&self.quote) {
+                    field.push(chars.next().unwrap());
+                } else {
+                    in_quotes = false;
+                }
+            } else if field_start && c == ' ' && !self.keepspace {
+                continue;
+            } else if field_start && c == self.quote {
+                in_quotes = true;
+                field_start = false;
+            } else if c == self.delim {
+                record.push(std::mem::take(&mut field));
+                field_start = true;
+            } else if c == '\n' {
+                record.push(std::mem::take(&mut field));
+                // Blank lines hold no record
+                if record.len() > 1 || !record[0].is_empty() {
+                    records.push(std::mem::take(&mut record));
+                } else {
+                    record.clear();
+                }
+                field_start = true;
+            } else {
+                field.push(c);
+                field_start = false;
+            }
+        }
+        if in_quotes {
+            return Err(anyhow!("unexpected end of data inside a quoted field"));
+        }
+        if !record.is_empty() || !field.is_empty() {
+            record.push(field);
+            records.push(record);
+        }
+        Ok(records)
+    }
+}
+
+/// Rows of a `list-table`: a bullet list of rows, each a bullet list of cells
+fn list_table_rows(directive: &Directive) -> Result<Vec<Vec<String>>> {
+    let expected = || anyhow!("a uniform two-level bullet list is expected");
+    let rows = bullet_items(&directive.content).ok_or_else(expected)?;
+
+    let mut table = Vec::with_capacity(rows.len());
+    for (i, row) in rows.iter().enumerate() {
+        let cells = bullet_items(row).ok_or_else(expected)?;
+        if let Some(first) = table.first().map(Vec::len) {
+            if cells.len() != first {
+                return Err(anyhow!(
+                    "row {} has {} item(s) but row 1 has {}",
+                    i + 1,
+                    cells.len(),
+                    first
+                ));
+            }
+        }
+        table.push(cells.iter().map(|cell| cell.join("\n")).collect());
+    }
+    Ok(table)
+}
+
+/// The lines of each item of a bullet list, without its marker and
+/// indentation; `None` if the lines aren't a single bullet list
+fn bullet_items<S: AsRef<str>>(lines: &[S]) -> Option<Vec<Vec<String>>> {
+    let mut items: Vec<Vec<String>> = Vec::new();
+    for line in lines {
+        let line = line.as_ref().trim_end();
+        let item = ["* ", "- ", "+ "]
+            .iter()
+            .find_map(|marker| line.strip_prefix(marker))
+            .or_else(|| ["*", "-", "+"].contains(&line).then_some(""));
+        match (item, items.last_mut()) {
+            (Some(text), _) => items.push(vec![text.to_string()]),
+            (None, _) if line.is_empty() => {
+                if let Some(item) = items.last_mut() {
+                    item.push(String::new());
+                }
+            }
+            (None, Some(item)) if indentation(line) > 0 => {
+                item.push(line[indentation(line).min(2)..].to_string());
+            }
+            (None, _) => return None,
+        }
+    }
+    for item in &mut items {
+        while item.len() > 1 && item.last().is_some_and(String::is_empty) {
+            item.pop();
+        }
+    }
+    (!items.is_empty()).then_some(items)
+}
+
+/// Render a `csv-table` or `list-table` as an HTML table; `render_inline`
+/// renders the title and each paragraph of cell text
+pub fn table_html(
+    directive: &Directive,
+    table: &TableData,
+    mut render_inline: impl FnMut(&str) -> String,
+) -> Result<String> {
+    let columns = table
+        .header
+        .first()
+        .or(table.body.first())
+        .map_or(0, Vec::len);
+
+    let mut classes = vec![format!(
+        "align-{}",
+        directive
+            .options
+            .get("align")
+            .map_or("default", |align| align.trim())
+    )];
+    let mut colgroup = String::new();
+    match directive.options.get("widths").map(|widths| widths.trim()) {
+        None | Some("grid") => {}
+        Some("auto") => classes.push("colwidths-auto".to_string()),
+        Some(widths) => {
+            let widths = widths
+                .split([',', ' '])
+                .filter(|width| !width.is_empty())
+                .map(|width| width.parse::<u32>())
+                .collect::<std::result::Result<Vec<_>, _>>()
+                .map_err(|_| anyhow!("invalid widths: \"{}\"", widths))?;
+            if widths.len() != columns {
+                return Err(anyhow!(
+                    "{} column width(s) given for {} column(s)",
+                    widths.len(),
+                    columns
+                ));
+            }
+            let total = widths.iter().sum::<u32>().max(1);
+            classes.push("colwidths-given".to_string());
+            colgroup.push_str("<colgroup>\n");
+            for width in widths {
+                colgroup.push_str(&format!(
+                    "<col style=\"width: {}%\" />\n",
+                    (width * 100 + total / 2) / total
+                ));
+            }
+            colgroup.push_str("</colgroup>\n");
+        }
+    }
+    if let Some(class) = directive.options.get("class") {
+        classes.extend(class.split_whitespace().map(str::to_string));
+    }
+    let style = directive
+        .options
+        .get("width")
+        .map(|width| width.trim())
+        .map(
+            |width| match width.ends_with(|c: char| c.is_ascii_digit()) {
+                true => format!(" style=\"width: {}px\"", width),
+                false => format!(" style=\"width: {}\"", width),
+            },
+        )
+        .unwrap_or_default();
+
+    let mut html = format!(
+        "<table class=\"docutils {}\"{}{}>\n",
+        html_escape::encode_double_quoted_attribute(&classes.join(" ")),
+        id_attribute(directive),
+        style
+    );
+    let title = directive.arguments.join(" ");
+    if !title.trim().is_empty() {
+        html.push_str(&format!(
+            "<caption><span class=\"caption-text\">{}</span></caption>\n",
+            render_inline(title.trim())
+        ));
+    }
+    html.push_str(&colgroup);
+
+    let stub_columns = match directive.options.get("stub-columns") {
+        Some(columns) => columns.trim().parse::<usize>()?,
+        None => 0,
+    };
+    let mut row_number = 0;
+    for (section, rows) in [("thead", &table.header), ("tbody", &table.body)] {
+        if rows.is_empty() {
+            continue;
+        }
+        html.push_str(&format!("<{}>\n", section));
+        for row in rows {
+            row_number += 1;
+            let parity = if row_number % 2 == 1 { "odd" } else { "even" };
+            html.push_str(&format!("<tr class=\"row-{}\">", parity));
+            for (column, cell) in row.iter().enumerate() {
+                let body: String = cell
+                    .split("\n\n")
+                    .map(str::trim)
+                    .filter(|paragraph| !paragraph.is_empty())
+                    .map(|paragraph| format!("<p>{}</p>", render_inline(paragraph)))
+                    .collect();
+                let (tag, class) = match (section, column < stub_columns) {
+                    ("thead", _) => ("th", " class=\"head\""),
+                    (_, true) => ("th", " class=\"stub\""),
+                    _ => ("td", ""),
+                };
+                html.push_str(&format!("<{}{}>{}</{}>", tag, class, body, tag));
+            }
+            html.push_str("\n</tr>\n");
+        }
+        html.push_str(&format!("</{}>\n", section));
+    }
+    html.push_str("</table>");
+    Ok(html)
+}
+
+/// Tables from CSV data; the renderer interprets the cells' inline markup
+struct CsvTableDirective;
+
+impl DirectiveProcessor for CsvTableDirective {
+    fn process(&self, directive: &Directive) -> Result<String> {
+        table_html(directive, &read_table(directive)?, |text| {
+            html_escape::encode_text(text).into_owned()
+        })
+    }
+
+    fn get_name(&self) -> &str {
+        "csv-table"
+    }
+
+    fn get_option_spec(&self) -> HashMap<String, DirectiveOptionType> {
+        csv_table_option_spec()
+    }
+}
+
+/// Tables from a two-level bullet list; the renderer interprets the cells'
+/// inline markup
+struct ListTableDirective;
+
+impl DirectiveProcessor for ListTableDirective {
+    fn process(&self, directive: &Directive) -> Result<String> {
+        table_html(directive, &read_table(directive)?, |text| {
+            html_escape::encode_text(text).into_owned()
+        })
+    }
+
+    fn get_name(&self) -> &str {
+        "list-table"
+    }
+
+    fn get_option_spec(&self) -> HashMap<String, DirectiveOptionType> {
+        table_option_spec()
+    }
+}
+
 stub_directive!(TableDirective, "table");
-stub_directive!(CsvTableDirective, "csv-table");
-stub_directive!(ListTableDirective, "list-table");
+// The parser splices included files into the document, and the renderer
+// handles `:literal:`/`:code:` includes itself; this only marks leftovers
 stub_directive!(IncludeDirective, "include");
 stub_directive!(RawDirective, "raw");
-stub_directive!(MathDirective, "math");
 stub_directive!(AutoDocDirective, "autodoc");
 stub_directive!(AutoModuleDirective, "automodule");
 stub_directive!(AutoClassDirective, "autoclass");
@@ -474,6 +2542,575 @@ stub_directive!(HighlightsDirective, "highlights");
 stub_directive!(PullQuoteDirective, "pull-quote");
 stub_directive!(CompoundDirective, "compound");
 stub_directive!(ContainerDirective, "container");
-stub_directive!(VersionAddedDirective, "versionadded");
-stub_directive!(VersionChangedDirective, "versionchanged");
-stub_directive!(DeprecatedDirective, "deprecated");
+
+/// Directives noting the version an API changed in
+pub const VERSION_CHANGE_DIRECTIVES: [&str; 3] = ["versionadded", "versionchanged", "deprecated"];
+
+/// `versionadded`, `versionchanged` and `deprecated`: the version, and an
+/// optional explanation
+struct VersionChangeDirective {
+    name: String,
+}
+
+impl VersionChangeDirective {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+        }
+    }
+}
+
+impl DirectiveProcessor for VersionChangeDirective {
+    fn process(&self, directive: &Directive) -> Result<String> {
+        Ok(version_change_html(directive, |text| {
+            html_escape::encode_text(text).into_owned()
+        }))
+    }
+
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    fn get_option_spec(&self) -> HashMap<String, DirectiveOptionType> {
+        HashMap::new()
+    }
+}
+
+/// The admonitions with a title of their own, and the title
+pub const ADMONITION_DIRECTIVES: [(&str, &str); 10] = [
+    ("note", "Note"),
+    ("warning", "Warning"),
+    ("important", "Important"),
+    ("tip", "Tip"),
+    ("caution", "Caution"),
+    ("danger", "Danger"),
+    ("error", "Error"),
+    ("hint", "Hint"),
+    ("attention", "Attention"),
+    ("seealso", "See also"),
+];
+
+/// Whether `name` is an admonition, the generic `admonition` or `acks`: a
+/// directive whose content is reStructuredText rendered inside a `<div>`
+pub fn is_admonition(name: &str) -> bool {
+    matches!(name, "admonition" | "acks") || ADMONITION_DIRECTIVES.iter().any(|(n, _)| *n == name)
+}
+
+/// The reStructuredText content of an admonition; for those with a title of
+/// their own, text on the directive line is the first paragraph
+pub fn admonition_content(directive: &Directive) -> Vec<String> {
+    let arguments = directive.arguments.join(" ");
+    let arguments = arguments.trim();
+    if directive.name == "admonition" || directive.name == "acks" || arguments.is_empty() {
+        return directive.content.clone();
+    }
+    let mut content = vec![arguments.to_string()];
+    if !directive.content.is_empty() {
+        content.push(String::new());
+        content.extend(directive.content.iter().cloned());
+    }
+    content
+}
+
+/// Render an admonition around `body`, its rendered content; `title` is the
+/// rendered title of a generic `admonition`, the others having their own
+pub fn admonition_html(directive: &Directive, title: &str, body: &str) -> String {
+    if directive.name == "acks" {
+        return format!("<div class=\"acks\">\n{}\n</div>", body);
+    }
+    let (class, title) = match ADMONITION_DIRECTIVES
+        .iter()
+        .find(|(name, _)| *name == directive.name)
+    {
+        Some((name, own_title)) => (*name, *own_title),
+        None if title.is_empty() => ("admonition-generic", "Admonition"),
+        None => ("admonition-generic", title),
+    };
+    format!(
+        "<div class=\"admonition {}\"{}>\n<p class=\"admonition-title\">{}</p>\n{}\n</div>",
+        class,
+        id_attribute(directive),
+        title,
+        body
+    )
+}
+
+/// `lines` as HTML paragraphs of escaped text, for content rendered without
+/// inline markup
+fn escaped_paragraphs(lines: &[String]) -> String {
+    lines
+        .split(|line| line.trim().is_empty())
+        .filter(|paragraph| !paragraph.is_empty())
+        .map(|paragraph| {
+            let text: Vec<&str> = paragraph.iter().map(|line| line.trim()).collect();
+            format!("<p>{}</p>", html_escape::encode_text(&text.join(" ")))
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Render a version change as Sphinx does: the label and version lead the
+/// explanation's first paragraph, which is the text after the version on the
+/// directive line or else the content's; `render_text` renders paragraphs
+pub fn version_change_html(
+    directive: &Directive,
+    mut render_text: impl FnMut(&str) -> String,
+) -> String {
+    let (class, label) = match directive.name.as_str() {
+        "versionadded" => ("added", "New in version"),
+        "deprecated" => ("deprecated", "Deprecated since version"),
+        _ => ("changed", "Changed in version"),
+    };
+    let arguments = directive.arguments.join(" ");
+    let (version, text) = arguments
+        .trim()
+        .split_once(char::is_whitespace)
+        .unwrap_or((arguments.trim(), ""));
+
+    let mut paragraphs = vec![text.trim().to_string()];
+    let content = directive.content.join("\n");
+    paragraphs.extend(
+        content
+            .split("\n\n")
+            .map(|paragraph| paragraph.trim().to_string()),
+    );
+    paragraphs.retain(|paragraph| !paragraph.is_empty());
+
+    let version = html_escape::encode_text(version);
+    let mut html = format!("<div class=\"{}\">\n", directive.name);
+    match paragraphs.split_first() {
+        Some((first, rest)) => {
+            html.push_str(&format!(
+                "<p><span class=\"versionmodified {}\">{} {}: </span>{}</p>\n",
+                class,
+                label,
+                version,
+                render_text(first)
+            ));
+            for paragraph in rest {
+                html.push_str(&format!("<p>{}</p>\n", render_text(paragraph)));
+            }
+        }
+        None => html.push_str(&format!(
+            "<p><span class=\"versionmodified {}\">{} {}.</span></p>\n",
+            class, label, version
+        )),
+    }
+    html.push_str("</div>");
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toctree_entry_forms() {
+        assert_eq!(
+            ToctreeEntry::parse("intro"),
+            Some(ToctreeEntry {
+                title: None,
+                target: "intro".to_string(),
+                external: false,
+            })
+        );
+        assert_eq!(
+            ToctreeEntry::parse("Getting started <guide/intro>"),
+            Some(ToctreeEntry {
+                title: Some("Getting started".to_string()),
+                target: "guide/intro".to_string(),
+                external: false,
+            })
+        );
+        assert_eq!(
+            ToctreeEntry::parse("Example <https://example.com>"),
+            Some(ToctreeEntry {
+                title: Some("Example".to_string()),
+                target: "https://example.com".to_string(),
+                external: true,
+            })
+        );
+        assert_eq!(ToctreeEntry::parse(":maxdepth: 2"), None);
+    }
+
+    fn directive(name: &str, arguments: &[&str], content: &[&str]) -> Directive {
+        Directive {
+            name: name.to_string(),
+            arguments: arguments.iter().map(|arg| arg.to_string()).collect(),
+            options: HashMap::new(),
+            content: content.iter().map(|line| line.to_string()).collect(),
+            line_number: 1,
+            source_file: "index.rst".to_string(),
+            source_root: None,
+        }
+    }
+
+    #[test]
+    fn test_registry_enumerates_directives() {
+        let registry = DirectiveRegistry::new();
+        let names = registry.names();
+
+        for name in ["code-block", "note", "toctree", "acks", "autoclass"] {
+            assert!(names.contains(&name.to_string()), "missing {}", name);
+        }
+        assert!(names.windows(2).all(|pair| pair[0] <= pair[1]));
+
+        let toctree = registry.option_spec("toctree").unwrap();
+        assert!(toctree.contains_key("maxdepth"));
+        assert!(!registry.get("toctree").unwrap().is_stub());
+        assert!(registry.get("autoclass").unwrap().is_stub());
+        assert!(registry.option_spec("no-such-directive").is_none());
+    }
+
+    #[test]
+    fn test_seealso_keeps_its_title() {
+        let registry = DirectiveRegistry::new();
+
+        let html = registry
+            .process_directive(&directive("seealso", &["Module", "zipfile"], &[]))
+            .unwrap();
+
+        assert_eq!(
+            html,
+            "<div class=\"admonition seealso\">\n<p class=\"admonition-title\">See also</p>\n<p>Module zipfile</p>\n</div>"
+        );
+    }
+
+    #[test]
+    fn test_version_changes_lead_their_explanation() {
+        let registry = DirectiveRegistry::new();
+
+        for (name, class, label) in [
+            ("versionadded", "added", "New in version"),
+            ("versionchanged", "changed", "Changed in version"),
+            ("deprecated", "deprecated", "Deprecated since version"),
+        ] {
+            let html = registry
+                .process_directive(&directive(name, &["2.1"], &[]))
+                .unwrap();
+            assert_eq!(
+                html,
+                format!(
+                    "<div class=\"{}\">\n<p><span class=\"versionmodified {}\">{} 2.1.</span></p>\n</div>",
+                    name, class, label
+                )
+            );
+
+            let html = registry
+                .process_directive(&directive(
+                    name,
+                    &["2.1 The <b> flag."],
+                    &["More", "details.", "", "Another paragraph."],
+                ))
+                .unwrap();
+            assert_eq!(
+                html,
+                format!(
+                    "<div class=\"{}\">\n<p><span class=\"versionmodified {}\">{} 2.1: </span>\
+                     The &lt;b&gt; flag.</p>\n<p>More\ndetails.</p>\n<p>Another paragraph.</p>\n</div>",
+                    name, class, label
+                )
+            );
+        }
+
+        // Without text on the directive line, the content explains the change
+        let html = registry
+            .process_directive(&directive("versionadded", &["3.0"], &["Use ``run``."]))
+            .unwrap();
+        assert!(html.contains("New in version 3.0: </span>Use ``run``.</p>"));
+    }
+
+    #[test]
+    fn test_deprecated_notes_flag_python_objects() {
+        let function = directive(
+            "py:function",
+            &["old()"],
+            &["Old.", "", ".. deprecated:: 2.0", "   Use new() instead."],
+        );
+
+        let blocks = py_object_content(&function);
+        assert!(matches!(
+            &blocks[1],
+            PyContent::VersionChange(change)
+                if change.name == "deprecated" && change.arguments == ["2.0"]
+        ));
+        let html = py_object_html(
+            &function,
+            Some("demo"),
+            None,
+            &mut PyObject::fullname,
+            &mut |text| text.to_string(),
+        );
+        assert!(html.contains(
+            "<dd><p>Old.</p>\n<div class=\"deprecated\">\n<p><span class=\"versionmodified deprecated\">\
+             Deprecated since version 2.0: </span>Use new() instead.</p>\n</div></dd>"
+        ), "{}", html);
+    }
+
+    #[test]
+    fn test_acks_renders_simple_list() {
+        let registry = DirectiveRegistry::new();
+
+        let html = registry
+            .process_directive(&directive("acks", &[], &["* Alice", "* Bob", ""]))
+            .unwrap();
+
+        assert_eq!(
+            html,
+            "<div class=\"acks\">\n<ul class=\"simple\"><li>Alice</li><li>Bob</li></ul>\n</div>"
+        );
+    }
+
+    #[test]
+    fn test_hidden_toctree_renders_nothing() {
+        let mut options = HashMap::new();
+        options.insert("hidden".to_string(), String::new());
+        let directive = Directive {
+            name: "toctree".to_string(),
+            arguments: Vec::new(),
+            options,
+            content: vec!["intro".to_string()],
+            line_number: 1,
+            source_file: "index.rst".to_string(),
+            source_root: None,
+        };
+
+        let html = DirectiveRegistry::new()
+            .process_directive(&directive)
+            .unwrap();
+
+        assert!(html.is_empty());
+    }
+
+    #[test]
+    fn test_toctree_renders_external_entry_as_link() {
+        let mut options = HashMap::new();
+        options.insert("caption".to_string(), "Contents".to_string());
+        let directive = Directive {
+            name: "toctree".to_string(),
+            arguments: Vec::new(),
+            options,
+            content: vec![
+                "Intro <intro>".to_string(),
+                "Example <https://example.com>".to_string(),
+            ],
+            line_number: 1,
+            source_file: "index.rst".to_string(),
+            source_root: None,
+        };
+
+        let html = DirectiveRegistry::new()
+            .process_directive(&directive)
+            .unwrap();
+
+        assert!(html.contains("<span class=\"caption-text\">Contents</span>"));
+        assert!(html.contains("<a class=\"reference internal\" href=\"intro.html\">Intro</a>"));
+        assert!(html
+            .contains("<a class=\"reference external\" href=\"https://example.com\">Example</a>"));
+    }
+
+    #[test]
+    fn test_option_values_are_checked_against_their_types() {
+        let registry = DirectiveRegistry::new();
+        let directive = |name: &str, options: &[(&str, &str)]| Directive {
+            name: name.to_string(),
+            arguments: vec!["diagram.png".to_string()],
+            options: options
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            content: Vec::new(),
+            line_number: 1,
+            source_file: "index.rst".to_string(),
+            source_root: None,
+        };
+
+        assert_eq!(
+            registry.validate_options(&directive(
+                "figure",
+                &[("align", "top"), ("scale", "half"), ("width", "80%")]
+            )),
+            [
+                "invalid option value: (option: \"align\"; value: \"top\") \
+                 \"top\" unknown; choose from \"left\", \"center\", \"right\"",
+                "invalid option value: (option: \"scale\"; value: \"half\") \
+                 \"half\" is not a percentage",
+            ]
+        );
+        assert!(registry
+            .validate_options(&directive("image", &[("align", "top"), ("scale", "50 %")]))
+            .is_empty());
+
+        assert!(registry
+            .validate_options(&directive("code-block", &[("emphasize-lines", "1,3-5,8-")]))
+            .is_empty());
+        assert_eq!(
+            registry.validate_options(&directive("code-block", &[("emphasize-lines", "1-x")])),
+            [
+                "invalid option value: (option: \"emphasize-lines\"; value: \"1-x\") \
+              \"1-x\" is not a list of line numbers and ranges such as \"1,3-5\""
+            ]
+        );
+    }
+
+    #[test]
+    fn test_csv_table_reads_quoted_fields_with_commas() {
+        let mut table = directive(
+            "csv-table",
+            &["Prices"],
+            &[
+                "\"Apple, red\", 1.50, \"crisp, \"\"sweet\"\"\"",
+                "",
+                "Pear;x, 2",
+            ],
+        );
+        table
+            .options
+            .insert("header".to_string(), "Fruit, Price, Notes".to_string());
+        table
+            .options
+            .insert("widths".to_string(), "2, 1, 1".to_string());
+        table
+            .options
+            .insert("align".to_string(), "center".to_string());
+        table
+            .options
+            .insert("class".to_string(), "longtable".to_string());
+
+        assert_eq!(
+            read_table(&table).unwrap(),
+            TableData {
+                header: vec![vec!["Fruit".into(), "Price".into(), "Notes".into()]],
+                body: vec![
+                    vec![
+                        "Apple, red".into(),
+                        "1.50".into(),
+                        "crisp, \"sweet\"".into()
+                    ],
+                    vec!["Pear;x".into(), "2".into(), String::new()],
+                ],
+            }
+        );
+        let html = DirectiveRegistry::new().process_directive(&table).unwrap();
+        assert_eq!(
+            html,
+            "<table class=\"docutils align-center colwidths-given longtable\">\n\
+             <caption><span class=\"caption-text\">Prices</span></caption>\n\
+             <colgroup>\n<col style=\"width: 50%\" />\n<col style=\"width: 25%\" />\n<col style=\"width: 25%\" />\n</colgroup>\n\
+             <thead>\n<tr class=\"row-odd\"><th class=\"head\"><p>Fruit</p></th><th class=\"head\"><p>Price</p></th><th class=\"head\"><p>Notes</p></th>\n</tr>\n</thead>\n\
+             <tbody>\n<tr class=\"row-even\"><td><p>Apple, red</p></td><td><p>1.50</p></td><td><p>crisp, \"sweet\"</p></td>\n</tr>\n\
+             <tr class=\"row-odd\"><td><p>Pear;x</p></td><td><p>2</p></td><td></td>\n</tr>\n</tbody>\n</table>"
+        );
+
+        table.options.insert("delim".to_string(), ";".to_string());
+        table.options.remove("widths");
+        table.options.remove("header");
+        let rows = read_table(&table).unwrap().body;
+        assert_eq!(rows[1], ["Pear", "x, 2"]);
+
+        table.content = vec!["\"unterminated, field".to_string()];
+        assert!(read_table(&table).is_err());
+    }
+
+    #[test]
+    fn test_list_table_with_multi_row_header() {
+        let mut table = directive(
+            "list-table",
+            &[],
+            &[
+                "* - Name",
+                "  - Size",
+                "* - ",
+                "  - (bytes)",
+                "* - ``a.txt``",
+                "  - 12",
+                "",
+                "    rounded",
+                "* - b.txt",
+                "  - 3",
+            ],
+        );
+        table
+            .options
+            .insert("header-rows".to_string(), "2".to_string());
+        table
+            .options
+            .insert("stub-columns".to_string(), "1".to_string());
+
+        let data = read_table(&table).unwrap();
+        assert_eq!(data.header, [vec!["Name", "Size"], vec!["", "(bytes)"]]);
+        assert_eq!(
+            data.body,
+            [vec!["``a.txt``", "12\n\nrounded"], vec!["b.txt", "3"]]
+        );
+
+        let html = DirectiveRegistry::new().process_directive(&table).unwrap();
+        assert!(html.starts_with("<table class=\"docutils align-default\">\n<thead>\n"));
+        assert!(html.contains(
+            "<tr class=\"row-even\"><th class=\"head\"></th><th class=\"head\"><p>(bytes)</p></th>\n</tr>\n</thead>"
+        ));
+        assert!(html.contains(
+            "<tr class=\"row-odd\"><th class=\"stub\"><p>``a.txt``</p></th><td><p>12</p><p>rounded</p></td>"
+        ));
+
+        table.content.push("* - lonely".to_string());
+        let error = read_table(&table).unwrap_err().to_string();
+        assert_eq!(error, "row 5 has 1 item(s) but row 1 has 2");
+
+        table.content = vec!["Not a list".to_string()];
+        assert!(read_table(&table).is_err());
+    }
+
+    #[test]
+    fn test_code_block_numbers_and_emphasizes_line_ranges() {
+        let mut code = directive(
+            "code-block",
+            &["text"],
+            &[
+                "    a = 1",
+                "    b = 2",
+                "    c = 3",
+                "    d = 4",
+                "    e = 5",
+            ],
+        );
+        for (option, value) in [
+            ("lineno-start", "10"),
+            ("emphasize-lines", "2,4-5"),
+            ("dedent", "4"),
+        ] {
+            code.options.insert(option.to_string(), value.to_string());
+        }
+
+        let mut registry = DirectiveRegistry::new();
+        assert_eq!(
+            registry.process_directive(&code).unwrap(),
+            "<div class=\"highlight-text\"><table class=\"highlighttable\"><tr>\
+             <td class=\"linenos\"><div class=\"linenodiv\"><pre><span class=\"normal\">10</span>\n\
+             <span class=\"normal\">11</span>\n<span class=\"normal\">12</span>\n\
+             <span class=\"normal\">13</span>\n<span class=\"normal\">14</span></pre></div></td>\
+             <td class=\"code\"><pre><code class=\"language-text\">a = 1\n\
+             <span class=\"hll\">b = 2</span>\nc = 3\n<span class=\"hll\">d = 4</span>\n\
+             <span class=\"hll\">e = 5</span></code></pre></td></tr></table></div>"
+        );
+
+        registry.set_linenos_style(LinenosStyle::Inline);
+        code.options.remove("emphasize-lines");
+        assert_eq!(
+            registry.process_directive(&code).unwrap(),
+            "<div class=\"highlight-text\"><pre><code class=\"language-text\">\
+             <span class=\"linenos\">10</span>a = 1\n<span class=\"linenos\">11</span>b = 2\n\
+             <span class=\"linenos\">12</span>c = 3\n<span class=\"linenos\">13</span>d = 4\n\
+             <span class=\"linenos\">14</span>e = 5</code></pre></div>"
+        );
+
+        // Without line numbers the compact markup is kept
+        code.options.remove("lineno-start");
+        code.options.insert("dedent".to_string(), String::new());
+        assert_eq!(
+            registry.process_directive(&code).unwrap(),
+            "<div class=\"highlight-text\"><pre><code class=\"language-text\">\
+             a = 1\nb = 2\nc = 3\nd = 4\ne = 5</code></pre></div>"
+        );
+    }
+}
@@ -38,6 +38,10 @@ pub struct Document {
     /// Document title
     pub title: String,
 
+    /// Heading level the title was taken from; `None` for untitled documents
+    #[serde(default)]
+    pub title_level: Option<usize>,
+
     /// Document content (parsed)
     pub content: DocumentContent,
 
@@ -171,8 +175,9 @@ pub enum RstNode {
         content: String,
         line: usize,
     },
+    /// Bullet (`-`, `*`, `+`) or enumerated (`1.`, `#.`) list
     List {
-        items: Vec<String>,
+        items: Vec<ListItem>,
         ordered: bool,
         line: usize,
     },
@@ -188,9 +193,80 @@ pub enum RstNode {
         content: String,
         line: usize,
     },
+    /// Definition list of terms (with optional ` : classifier`s) and their definitions
+    DefinitionList {
+        items: Vec<DefinitionItem>,
+        line: usize,
+    },
+    /// Field list (`:name: value`) outside the docinfo block
+    FieldList {
+        fields: Vec<(String, String)>,
+        line: usize,
+    },
+    /// Indented block quote with an optional `-- Author` attribution
+    BlockQuote {
+        paragraphs: Vec<String>,
+        attribution: Option<String>,
+        line: usize,
+    },
+    /// Line block (`| ` prefixed lines); each line keeps its extra leading indentation
+    LineBlock {
+        lines: Vec<String>,
+        line: usize,
+    },
+    /// Transition: a standalone line of repeated punctuation between blank lines
+    Transition {
+        line: usize,
+    },
+    /// Footnote definition (`.. [1]`, `.. [#]`, `.. [#name]`, `.. [*]`)
+    Footnote {
+        label: String,
+        content: String,
+        line: usize,
+    },
+    /// Citation definition (`.. [CIT2002]`)
+    Citation {
+        label: String,
+        content: String,
+        line: usize,
+    },
+    /// Internal hyperlink target (`.. _name:`), a label `:ref:` can point at,
+    /// or an external one (`.. _name: https://...`) with its URI
+    Target {
+        name: String,
+        #[serde(default)]
+        refuri: Option<String>,
+        line: usize,
+    },
+    /// Comment: explicit markup (`.. text`) that is no other construct; not rendered
+    Comment {
+        content: String,
+        line: usize,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListItem {
+    /// Text of the item, with its continuation lines
+    pub text: String,
+
+    /// Lists nested in the item
+    pub children: Vec<RstNode>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DefinitionItem {
+    /// Term being defined
+    pub term: String,
+
+    /// Classifiers following the term (`term : classifier`)
+    pub classifiers: Vec<String>,
+
+    /// Paragraphs of the definition
+    pub definition: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum MarkdownNode {
     Heading {
         text: String,
@@ -242,6 +318,7 @@ impl Document {
             source_path,
             output_path,
             title: String::new(),
+            title_level: None,
             content: DocumentContent::PlainText(String::new()),
             metadata: DocumentMetadata::default(),
             html: String::new(),
@@ -252,6 +329,33 @@ impl Document {
         }
     }
 
+    /// Short description of the page: the `meta` directive's `:description:`,
+    /// falling back to the first paragraph
+    pub fn description(&self) -> Option<String> {
+        match &self.content {
+            DocumentContent::RestructuredText(rst) => rst
+                .ast
+                .iter()
+                .find_map(|node| match node {
+                    RstNode::Directive { name, options, .. } if name == "meta" => {
+                        options.get("description").cloned()
+                    }
+                    _ => None,
+                })
+                .or_else(|| {
+                    rst.ast.iter().find_map(|node| match node {
+                        RstNode::Paragraph { content, .. } => Some(content.clone()),
+                        _ => None,
+                    })
+                }),
+            DocumentContent::Markdown(md) => md.ast.iter().find_map(|node| match node {
+                MarkdownNode::Paragraph { content, .. } => Some(content.clone()),
+                _ => None,
+            }),
+            DocumentContent::PlainText(_) => None,
+        }
+    }
+
     #[allow(dead_code)]
     pub fn set_title(&mut self, title: String) {
         self.title = title;
@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 /// Type alias for document relations: (parent, previous, next)
 type DocumentRelations = HashMap<String, (Option<String>, Option<String>, Option<String>)>;
@@ -29,6 +29,9 @@ pub struct BuildEnvironment {
     pub toc_num_entries: HashMap<String, usize>,
     pub dlfiles: HashMap<String, (Option<String>, String)>,
     pub images: HashMap<String, String>,
+    pub anchors: HashMap<String, HashSet<String>>, // docname -> ids in use
+    /// Entries of `.. index::` directives and `:index:` roles, by docname
+    pub index_entries: HashMap<String, Vec<GeneralIndexEntry>>,
 }
 
 use std::collections::HashSet;
@@ -57,6 +60,8 @@ impl BuildEnvironment {
             toc_num_entries: HashMap::new(),
             dlfiles: HashMap::new(),
             images: HashMap::new(),
+            anchors: HashMap::new(),
+            index_entries: HashMap::new(),
         }
     }
 
@@ -71,10 +76,777 @@ impl BuildEnvironment {
         PathBuf::from(format!("{}.rst", docname))
     }
 
-    /// Collect relations between documents
+    /// Record a build's documents as found, with their titles and toctrees;
+    /// all are found before any toctree is read, so `:glob:` sees every one
+    pub fn note_documents<'a>(
+        &mut self,
+        documents: impl IntoIterator<Item = (String, &'a crate::document::Document)>,
+    ) {
+        let documents: Vec<_> = documents.into_iter().collect();
+        self.found_docs
+            .extend(documents.iter().map(|(docname, _)| docname.clone()));
+        for (docname, document) in documents {
+            self.note_title(&docname, document);
+            self.note_toctrees(&docname, document);
+            self.note_images(&docname, document);
+            self.note_labels(&docname, document);
+            self.note_index_entries(&docname, document);
+            self.note_python_objects(&docname, document);
+        }
+    }
+
+    /// Record the images of a document's `image` and `figure` directives in
+    /// `images`, each under a file name of its own in `_images/`
+    ///
+    /// Remote images are linked where they are, not copied.
+    pub fn note_images(&mut self, docname: &str, document: &crate::document::Document) {
+        use crate::document::{DocumentContent, RstNode};
+
+        let DocumentContent::RestructuredText(rst) = &document.content else {
+            return;
+        };
+
+        for node in &rst.ast {
+            let RstNode::Directive { name, args, .. } = node else {
+                continue;
+            };
+            let Some(uri) = args.first().map(|uri| uri.trim()) else {
+                continue;
+            };
+            if !matches!(name.as_str(), "image" | "figure")
+                || crate::directives::is_remote_image(uri)
+            {
+                continue;
+            }
+
+            let path = crate::utils::docname_join(docname, uri);
+            if self.images.contains_key(&path) {
+                continue;
+            }
+            let file_name = Path::new(&path)
+                .file_name()
+                .map_or(path.clone(), |name| name.to_string_lossy().into_owned());
+            let mut dest = file_name.clone();
+            let mut counter = 0;
+            while self.images.values().any(|used| *used == dest) {
+                counter += 1;
+                dest = match file_name.rsplit_once('.') {
+                    Some((stem, extension)) => format!("{}{}.{}", stem, counter, extension),
+                    None => format!("{}{}", file_name, counter),
+                };
+            }
+            self.images.insert(path, dest);
+        }
+    }
+
+    /// Record a document's labels as `label` objects of the `std` domain:
+    /// its `.. _name:` targets, titled by the section they precede, and the
+    /// `:name:`s of its directives, titled by their caption if any
+    ///
+    /// The first definition of a label wins.
+    pub fn note_labels(&mut self, docname: &str, document: &crate::document::Document) {
+        use crate::document::{DocumentContent, RstNode};
+
+        let DocumentContent::RestructuredText(rst) = &document.content else {
+            return;
+        };
+
+        for (i, node) in rst.ast.iter().enumerate() {
+            let (name, title) = match node {
+                RstNode::Target {
+                    name, refuri: None, ..
+                } => {
+                    let section = rst.ast[i + 1..]
+                        .iter()
+                        .find(|node| !matches!(node, RstNode::Target { .. }));
+                    let title = match section {
+                        Some(RstNode::Title { text, .. }) => Some(text.clone()),
+                        _ => None,
+                    };
+                    (name, title)
+                }
+                RstNode::Directive {
+                    name: directive,
+                    args,
+                    options,
+                    content,
+                    ..
+                } => {
+                    let Some(name) = options.get("name") else {
+                        continue;
+                    };
+                    let caption = match directive.as_str() {
+                        "figure" => content.split("\n\n").next().map(str::to_string),
+                        "table" | "csv-table" | "list-table" => Some(args.join(" ")),
+                        _ => options.get("caption").cloned(),
+                    };
+                    (name, caption.filter(|caption| !caption.trim().is_empty()))
+                }
+                _ => continue,
+            };
+
+            let label = normalize_label(name);
+            if self.resolve_label(&label).is_some() {
+                continue;
+            }
+            let mut object = DomainObject::new(
+                label,
+                "label".to_string(),
+                docname.to_string(),
+                Some(crate::directives::name_to_id(name)),
+                -1,
+            );
+            object.display_name = title.map(|title| title.trim().to_string());
+            self.domains
+                .entry("std".to_string())
+                .or_insert_with(|| Domain::new("std"))
+                .add_object("label", object);
+        }
+    }
+
+    /// Record the entries of a document's `.. index::` directives, and of the
+    /// `:index:` roles in its text, under the anchors they render
+    pub fn note_index_entries(&mut self, docname: &str, document: &crate::document::Document) {
+        use crate::document::{DocumentContent, ListItem, RstNode};
+
+        let DocumentContent::RestructuredText(rst) = &document.content else {
+            return;
+        };
+
+        fn list_texts<'a>(items: &'a [ListItem], texts: &mut Vec<&'a str>) {
+            for item in items {
+                texts.push(&item.text);
+                for child in &item.children {
+                    if let RstNode::List { items, .. } = child {
+                        list_texts(items, texts);
+                    }
+                }
+            }
+        }
+
+        let mut entries = Vec::new();
+        for node in &rst.ast {
+            let mut texts: Vec<&str> = Vec::new();
+            let line = match node {
+                RstNode::Directive {
+                    name,
+                    args,
+                    content,
+                    line,
+                    ..
+                } if name == "index" => {
+                    let anchor = crate::directives::index_anchor(*line, None);
+                    for entry in args.iter().chain([content]).flat_map(|text| text.lines()) {
+                        entries.extend(process_index_entry(entry, &anchor));
+                    }
+                    continue;
+                }
+                // Directives whose content is rendered as inline markup
+                RstNode::Directive {
+                    name,
+                    content,
+                    line,
+                    ..
+                } if matches!(
+                    name.as_str(),
+                    "parsed-literal" | "csv-table" | "list-table" | "figure"
+                ) =>
+                {
+                    texts.push(content);
+                    *line
+                }
+                RstNode::Paragraph { content, line } => {
+                    texts.push(content);
+                    *line
+                }
+                RstNode::List { items, line, .. } => {
+                    list_texts(items, &mut texts);
+                    *line
+                }
+                RstNode::Table {
+                    headers,
+                    rows,
+                    line,
+                } => {
+                    texts.extend(
+                        headers
+                            .iter()
+                            .chain(rows.iter().flatten())
+                            .map(String::as_str),
+                    );
+                    *line
+                }
+                RstNode::DefinitionList { items, line } => {
+                    for item in items {
+                        texts.push(&item.term);
+                        texts.extend(item.classifiers.iter().map(String::as_str));
+                        texts.extend(item.definition.iter().map(String::as_str));
+                    }
+                    *line
+                }
+                RstNode::FieldList { fields, line } => {
+                    texts.extend(
+                        fields
+                            .iter()
+                            .flat_map(|(name, value)| [name, value])
+                            .map(String::as_str),
+                    );
+                    *line
+                }
+                RstNode::BlockQuote {
+                    paragraphs,
+                    attribution,
+                    line,
+                } => {
+                    texts.extend(paragraphs.iter().chain(attribution).map(String::as_str));
+                    *line
+                }
+                RstNode::LineBlock { lines, line } => {
+                    texts.extend(lines.iter().map(String::as_str));
+                    *line
+                }
+                _ => continue,
+            };
+
+            for text in texts {
+                for captures in INDEX_ROLE_REGEX.captures_iter(text) {
+                    let role = crate::renderer::parse_role_body("index", &captures[1], line, "");
+                    let anchor = crate::directives::index_anchor(line, Some(&role.target));
+                    entries.extend(index_role_entries(&role, &anchor));
+                }
+            }
+        }
+
+        if !entries.is_empty() {
+            self.index_entries.insert(docname.to_string(), entries);
+        }
+    }
+
+    /// The general index: the terms of all index entries grouped by their
+    /// initial (`Symbols` first), sorted case-insensitively
+    ///
+    /// Pairs are listed under both of their terms and triples under all
+    /// three; `see` and `seealso` entries become unlinked subterms.
+    pub fn create_index(&self) -> Vec<(String, Vec<IndexTerm>)> {
+        let mut terms: Vec<IndexTerm> = Vec::new();
+        let mut add = |name: &str, sub: Option<String>, link: Option<IndexLink>| {
+            let position = match terms.iter().position(|term| term.name == name) {
+                Some(position) => position,
+                None => {
+                    terms.push(IndexTerm {
+                        name: name.to_string(),
+                        ..IndexTerm::default()
+                    });
+                    terms.len() - 1
+                }
+            };
+            let mut term = &mut terms[position];
+            if let Some(sub) = sub {
+                let subterms = &mut term.subterms;
+                let position = match subterms.iter().position(|subterm| subterm.name == sub) {
+                    Some(position) => position,
+                    None => {
+                        subterms.push(IndexTerm {
+                            name: sub,
+                            ..IndexTerm::default()
+                        });
+                        subterms.len() - 1
+                    }
+                };
+                term = &mut subterms[position];
+            }
+            term.links.extend(link);
+        };
+
+        let mut docnames: Vec<&String> = self.index_entries.keys().collect();
+        docnames.sort();
+        for docname in docnames {
+            for entry in &self.index_entries[docname] {
+                let link = IndexLink {
+                    docname: docname.clone(),
+                    anchor: entry.anchor.clone(),
+                    main: entry.main,
+                };
+                let parts: Vec<&str> = entry.value.split(';').map(str::trim).collect();
+                match (entry.entry_type.as_str(), parts.as_slice()) {
+                    ("single", [term]) => add(term, None, Some(link)),
+                    ("single", [term, sub]) => add(term, Some(sub.to_string()), Some(link)),
+                    ("pair", [first, second]) => {
+                        add(first, Some(second.to_string()), Some(link.clone()));
+                        add(second, Some(first.to_string()), Some(link));
+                    }
+                    ("triple", [first, second, third]) => {
+                        add(
+                            first,
+                            Some(format!("{} {}", second, third)),
+                            Some(link.clone()),
+                        );
+                        add(
+                            second,
+                            Some(format!("{}, {}", third, first)),
+                            Some(link.clone()),
+                        );
+                        add(third, Some(format!("{} {}", first, second)), Some(link));
+                    }
+                    ("see", [term, other]) => add(term, Some(format!("see {}", other)), None),
+                    ("seealso", [term, other]) => {
+                        add(term, Some(format!("see also {}", other)), None)
+                    }
+                    // Malformed entries, like a pair of one term, are left out
+                    _ => {}
+                }
+            }
+        }
+
+        let mut groups: Vec<(String, Vec<IndexTerm>)> = Vec::new();
+        terms.sort_by_cached_key(|term| index_sort_key(&term.name));
+        for mut term in terms {
+            term.links.sort_by_key(|link| !link.main);
+            term.subterms
+                .sort_by_cached_key(|subterm| index_sort_key(&subterm.name));
+            for subterm in &mut term.subterms {
+                subterm.links.sort_by_key(|link| !link.main);
+            }
+
+            let heading = match term.name.chars().next() {
+                Some(initial) if initial.is_alphabetic() || initial == '_' => {
+                    initial.to_uppercase().to_string()
+                }
+                _ => "Symbols".to_string(),
+            };
+            match groups.last_mut() {
+                Some((last, group)) if *last == heading => group.push(term),
+                _ => groups.push((heading, vec![term])),
+            }
+        }
+        groups
+    }
+
+    /// Record a document's `py:module` directives as `module` objects of the
+    /// `py` domain, and the module index as the `py-modindex` label
+    ///
+    /// The functions, classes, methods and attributes described after a
+    /// module are recorded under their full dotted names, with an entry in
+    /// the general index. `:noindex:` objects are left out.
+    pub fn note_python_objects(&mut self, docname: &str, document: &crate::document::Document) {
+        use crate::directives::{Directive, PY_OBJECT_DIRECTIVES};
+        use crate::document::{DocumentContent, RstNode};
+
+        let DocumentContent::RestructuredText(rst) = &document.content else {
+            return;
+        };
+
+        // Objects share the document's ids with its headings and targets
+        let used = self.anchors.entry(docname.to_string()).or_default();
+        used.extend(document.toc.iter().map(|entry| entry.anchor.clone()));
+        used.extend(rst.ast.iter().filter_map(|node| match node {
+            RstNode::Target {
+                name, refuri: None, ..
+            } => Some(crate::directives::name_to_id(name)),
+            _ => None,
+        }));
+
+        let mut current_module: Option<String> = None;
+        for node in &rst.ast {
+            let RstNode::Directive {
+                name,
+                args,
+                options,
+                content,
+                line,
+            } = node
+            else {
+                continue;
+            };
+            let module = args.join(" ");
+            let module = module.trim();
+            if PY_OBJECT_DIRECTIVES.contains(&name.as_str()) {
+                let directive = Directive {
+                    name: name.clone(),
+                    arguments: args.clone(),
+                    options: options.clone(),
+                    content: content.lines().map(str::to_string).collect(),
+                    line_number: *line,
+                    source_file: document.source_path.to_string_lossy().to_string(),
+                    source_root: None,
+                };
+                self.note_python_object(docname, &directive, current_module.as_deref(), None);
+                continue;
+            }
+            if !matches!(
+                name.as_str(),
+                "py:module" | "module" | "py:currentmodule" | "currentmodule"
+            ) {
+                continue;
+            }
+            current_module = (!module.is_empty() && module != "None").then(|| module.to_string());
+            if name.ends_with("currentmodule")
+                || module.is_empty()
+                || options.contains_key("noindex")
+            {
+                continue;
+            }
+
+            let mut object = DomainObject::new(
+                module.to_string(),
+                "module".to_string(),
+                docname.to_string(),
+                Some(crate::directives::module_anchor(module)),
+                0,
+            )
+            .with_deprecated(options.contains_key("deprecated"));
+            if let Some(synopsis) = options.get("synopsis").filter(|s| !s.trim().is_empty()) {
+                object = object.with_description(synopsis.trim().to_string());
+            }
+            self.update_domain_object("py", "module", object);
+        }
+
+        if self.resolve_label("py-modindex").is_none() && self.python_modules().next().is_some() {
+            let mut object = DomainObject::new(
+                "py-modindex".to_string(),
+                "label".to_string(),
+                "py-modindex".to_string(),
+                None,
+                -1,
+            );
+            object.display_name = Some("Python Module Index".to_string());
+            self.domains
+                .entry("std".to_string())
+                .or_insert_with(|| Domain::new("std"))
+                .add_object("label", object);
+        }
+    }
+
+    /// Record a Python object directive, and the objects nested in a class's
+    /// content under the class
+    fn note_python_object(
+        &mut self,
+        docname: &str,
+        directive: &crate::directives::Directive,
+        module: Option<&str>,
+        class: Option<&str>,
+    ) {
+        use crate::directives::{py_object_content, PyContent, PyObject};
+
+        let Some(object) = PyObject::new(directive, module, class) else {
+            return;
+        };
+        let content = py_object_content(directive);
+        if !directive.options.contains_key("noindex") {
+            let fullname = object.fullname();
+            let anchor = self.update_domain_object(
+                "py",
+                &object.objtype,
+                DomainObject::new(
+                    fullname.clone(),
+                    object.objtype.clone(),
+                    docname.to_string(),
+                    Some(fullname),
+                    1,
+                )
+                .with_signature(directive.arguments.join(" ").trim().to_string())
+                // A `deprecated` note in the description flags the object
+                .with_deprecated(content.iter().any(|block| {
+                    matches!(block, PyContent::VersionChange(change) if change.name == "deprecated")
+                })),
+            );
+            if let (Some(anchor), false) = (anchor, directive.options.contains_key("noindexentry"))
+            {
+                self.index_entries
+                    .entry(docname.to_string())
+                    .or_default()
+                    .push(GeneralIndexEntry {
+                        entry_type: "single".to_string(),
+                        value: object.index_text(),
+                        anchor,
+                        main: false,
+                    });
+            }
+        }
+
+        let class = match object.objtype.as_str() {
+            "class" => Some(object.qualname.as_str()),
+            _ => class,
+        };
+        for block in content {
+            if let PyContent::Object(nested) = block {
+                self.note_python_object(docname, &nested, object.module.as_deref(), class);
+            }
+        }
+    }
+
+    fn python_modules(&self) -> impl Iterator<Item = &DomainObject> {
+        self.domains
+            .get("py")
+            .and_then(|domain| domain.get_objects_by_type("module"))
+            .into_iter()
+            .flatten()
+    }
+
+    /// The Python module index (mirrors Sphinx's `PythonModuleIndex`), or
+    /// `None` without modules; `uri` gives the link to a docname and anchor
+    ///
+    /// Modules are grouped by their lowercased initial. Submodules nest under
+    /// their top-level package, which gets an unlinked entry if it isn't a
+    /// documented module itself.
+    pub fn python_module_index(&self, uri: impl Fn(&str, &str) -> String) -> Option<DomainIndex> {
+        let mut modules: Vec<&DomainObject> = self.python_modules().collect();
+        if modules.is_empty() {
+            return None;
+        }
+        modules.sort_by_cached_key(|module| module.name.to_lowercase());
+        modules.dedup_by(|a, b| a.name == b.name);
+
+        let entry = |module: &DomainObject| {
+            let extra = match (module.deprecated, &module.description) {
+                (true, Some(synopsis)) => Some(format!("Deprecated: {}", synopsis)),
+                (true, None) => Some("Deprecated".to_string()),
+                (false, synopsis) => synopsis.clone(),
+            };
+            IndexEntry {
+                name: module.name.clone(),
+                subentries: Vec::new(),
+                uri: uri(
+                    &module.docname,
+                    module.anchor.as_deref().unwrap_or_default(),
+                ),
+                display_name: module.name.clone(),
+                extra,
+            }
+        };
+
+        let mut content: Vec<(String, Vec<IndexEntry>)> = Vec::new();
+        let mut toplevels = 0;
+        for module in &modules {
+            let letter = module
+                .name
+                .chars()
+                .take(1)
+                .collect::<String>()
+                .to_lowercase();
+            if content.last().is_none_or(|(last, _)| *last != letter) {
+                content.push((letter, Vec::new()));
+            }
+            let entries = &mut content.last_mut().unwrap().1;
+
+            let package = module.name.split('.').next().unwrap_or_default();
+            if package == module.name {
+                toplevels += 1;
+                entries.push(entry(module));
+                continue;
+            }
+            if entries.last().is_none_or(|last| last.name != package) {
+                entries.push(IndexEntry {
+                    name: package.to_string(),
+                    subentries: Vec::new(),
+                    uri: String::new(),
+                    display_name: package.to_string(),
+                    extra: None,
+                });
+            }
+            entries.last_mut().unwrap().subentries.push(entry(module));
+        }
+
+        Some(DomainIndex {
+            name: "py-modindex".to_string(),
+            localname: "Python Module Index".to_string(),
+            shortname: Some("modules".to_string()),
+            content,
+            // As Sphinx: collapse unless submodules outnumber top-level modules
+            collapse: modules.len() - toplevels < toplevels,
+        })
+    }
+
+    /// The `std` label `:ref:` resolves `label` to, if defined
+    pub fn resolve_label(&self, label: &str) -> Option<&DomainObject> {
+        let label = normalize_label(label);
+        self.domains
+            .get("std")?
+            .get_objects_by_type("label")?
+            .iter()
+            .find(|object| object.name == label)
+    }
+
+    /// URI of an image referenced from `docname` on `docname`'s flat page:
+    /// its copy in `_images/` if noted, else the URI as written
+    pub fn image_uri(&self, docname: &str, uri: &str) -> String {
+        match self
+            .images
+            .get(&crate::utils::docname_join(docname, uri.trim()))
+        {
+            Some(dest) => crate::utils::relative_uri(
+                &format!("{}.html", docname),
+                &format!("_images/{}", dest),
+                "",
+            ),
+            None => uri.to_string(),
+        }
+    }
+
+    /// Record a document's title, for links to it from other pages
+    pub fn note_title(&mut self, docname: &str, document: &crate::document::Document) {
+        self.titles
+            .insert(docname.to_string(), document.title.clone());
+    }
+
+    /// Record the documents included by the toctrees of a document
+    ///
+    /// Hidden toctrees count too: they only suppress the inline list, not navigation.
+    pub fn note_toctrees(&mut self, docname: &str, document: &crate::document::Document) {
+        use crate::document::{DocumentContent, RstNode};
+
+        let DocumentContent::RestructuredText(rst) = &document.content else {
+            return;
+        };
+
+        let mut includes = Vec::new();
+        for node in &rst.ast {
+            if let RstNode::Directive {
+                name,
+                options,
+                content,
+                ..
+            } = node
+            {
+                if name == "toctree" {
+                    let glob = options.contains_key("glob");
+                    if glob {
+                        self.glob_toctrees.insert(docname.to_string());
+                    }
+                    includes.extend(
+                        self.toctree_entries(docname, content.lines(), glob)
+                            .into_iter()
+                            .filter(|entry| !entry.external && entry.target != "self")
+                            .map(|entry| entry.target),
+                    );
+                }
+            }
+        }
+
+        if includes.is_empty() {
+            self.toctree_includes.remove(docname);
+        } else {
+            self.toctree_includes.insert(docname.to_string(), includes);
+        }
+    }
+
+    /// The entries of one toctree of `docname`, with targets resolved to
+    /// docnames
+    ///
+    /// With `glob`, untitled entries containing `*`, `?` or `[` expand to the
+    /// matching found documents, sorted; `*` stays within a directory. A
+    /// document listed explicitly, or matched before, is not matched again.
+    pub fn toctree_entries<'a>(
+        &self,
+        docname: &str,
+        lines: impl IntoIterator<Item = &'a str>,
+        glob: bool,
+    ) -> Vec<crate::directives::ToctreeEntry> {
+        use crate::directives::ToctreeEntry;
+        use crate::utils::docname_join;
+
+        let entries = crate::directives::parse_toctree_entries(lines);
+        let is_pattern = |entry: &ToctreeEntry| {
+            glob && entry.title.is_none() && entry.target.contains(['*', '?', '['])
+        };
+        let mut listed: HashSet<String> = entries
+            .iter()
+            .filter(|entry| !entry.external && !is_pattern(entry))
+            .map(|entry| docname_join(docname, &entry.target))
+            .collect();
+        listed.insert(docname.to_string());
+
+        let mut resolved = Vec::new();
+        for entry in entries {
+            if entry.external || entry.target == "self" {
+                resolved.push(entry);
+            } else if is_pattern(&entry) {
+                let Ok(pattern) = glob::Pattern::new(&docname_join(docname, &entry.target)) else {
+                    continue;
+                };
+                let options = glob::MatchOptions {
+                    require_literal_separator: true,
+                    ..glob::MatchOptions::new()
+                };
+                let mut matches: Vec<&String> = self
+                    .found_docs
+                    .iter()
+                    .filter(|found| pattern.matches_with(found, options))
+                    .collect();
+                matches.sort();
+                for found in matches {
+                    if listed.insert(found.clone()) {
+                        resolved.push(ToctreeEntry {
+                            title: None,
+                            target: found.clone(),
+                            external: false,
+                        });
+                    }
+                }
+            } else {
+                resolved.push(ToctreeEntry {
+                    target: docname_join(docname, &entry.target),
+                    ..entry
+                });
+            }
+        }
+        resolved
+    }
+
+    /// Collect relations between documents (mirrors Sphinx's collect_relations)
+    ///
+    /// Walks the toctree from the root document in pre-order; each document's
+    /// previous/next are its neighbours in that walk.
     pub fn collect_relations(&self) -> DocumentRelations {
-        // TODO: Implement relation collection from toctree
-        HashMap::new()
+        let order = self.toctree_walk();
+
+        let mut relations = HashMap::new();
+        for (index, (parent, docname)) in order.iter().enumerate() {
+            let prev = index
+                .checked_sub(1)
+                .map(|prev_index| order[prev_index].1.clone());
+            let next = order.get(index + 1).map(|(_, next)| next.clone());
+            relations.insert(docname.clone(), (parent.clone(), prev, next));
+        }
+
+        relations
+    }
+
+    /// Docnames in the order of the toctree walk from the root document, as a
+    /// reader following "next" links meets them
+    pub fn toctree_order(&self) -> Vec<String> {
+        self.toctree_walk()
+            .into_iter()
+            .map(|(_, docname)| docname)
+            .collect()
+    }
+
+    /// `(parent, docname)` of every document reached from the root document,
+    /// in pre-order
+    fn toctree_walk(&self) -> Vec<(Option<String>, String)> {
+        let root_doc = self.config.root_doc.as_deref().unwrap_or("index");
+
+        let mut order = Vec::new();
+        let mut traversed = HashSet::new();
+        self.traverse_toctree(None, root_doc, &mut traversed, &mut order);
+        order
+    }
+
+    fn traverse_toctree(
+        &self,
+        parent: Option<&str>,
+        docname: &str,
+        traversed: &mut HashSet<String>,
+        order: &mut Vec<(Option<String>, String)>,
+    ) {
+        if parent == Some(docname) || !traversed.insert(docname.to_string()) {
+            return;
+        }
+
+        order.push((parent.map(str::to_string), docname.to_string()));
+        for child in self.toctree_includes.get(docname).into_iter().flatten() {
+            self.traverse_toctree(Some(docname), child, traversed, order);
+        }
     }
 
     /// Check if document needs to be updated
@@ -100,18 +872,35 @@ impl BuildEnvironment {
         true
     }
 
+    /// Reserve an HTML id within a document, appending a counter on collision
+    /// (`func`, `func-1`, `func-2`, ...)
+    pub fn register_anchor(&mut self, docname: &str, anchor: &str) -> String {
+        let used = self.anchors.entry(docname.to_string()).or_default();
+        crate::utils::unique_anchor(used, anchor)
+    }
+
     /// Update domain object
+    ///
+    /// The object's anchor is de-duplicated within its document and the final
+    /// anchor is returned, so the rendered HTML id matches the inventory entry.
     pub fn update_domain_object(
         &mut self,
         domain_name: &str,
         obj_type: &str,
-        object: DomainObject,
-    ) {
+        mut object: DomainObject,
+    ) -> Option<String> {
+        if let Some(anchor) = object.anchor.take() {
+            object.anchor = Some(self.register_anchor(&object.docname, &anchor));
+        }
+        let anchor = object.anchor.clone();
+
         let domain = self
             .domains
             .entry(domain_name.to_string())
             .or_insert_with(|| Domain::new(domain_name));
         domain.add_object(obj_type, object);
+
+        anchor
     }
 
     /// Get all objects from all domains
@@ -124,6 +913,128 @@ impl BuildEnvironment {
     }
 }
 
+/// `:index:` roles in running text
+static INDEX_ROLE_REGEX: std::sync::LazyLock<regex::Regex> =
+    std::sync::LazyLock::new(|| regex::Regex::new(r":index:`([^`]+)`").unwrap());
+
+/// Entry types an index entry can start with (`pair: a; b`)
+const INDEX_ENTRY_TYPES: &[&str] = &["single", "pair", "triple", "see", "seealso"];
+
+/// Object types that index `type: name` as the pair `type; name`
+const PAIR_INDEX_TYPES: &[&str] = &[
+    "module",
+    "keyword",
+    "operator",
+    "object",
+    "exception",
+    "statement",
+    "builtin",
+];
+
+/// An entry of `.. index::` or `:index:`, as Sphinx records it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GeneralIndexEntry {
+    /// `single`, `pair`, `triple`, `see` or `seealso`
+    pub entry_type: String,
+    /// The terms, separated by `;`
+    pub value: String,
+    /// Id of the target the entry links to
+    pub anchor: String,
+    /// The entry was marked `!`, as the main place its terms are described
+    pub main: bool,
+}
+
+/// A term of the general index
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IndexTerm {
+    pub name: String,
+    /// Where the term is indexed, main places first
+    pub links: Vec<IndexLink>,
+    pub subterms: Vec<IndexTerm>,
+}
+
+/// A place an index term links to
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexLink {
+    pub docname: String,
+    pub anchor: String,
+    pub main: bool,
+}
+
+/// Parse one line of an `.. index::` directive (mirrors Sphinx's
+/// `process_index_entry`)
+///
+/// Untyped lines hold comma-separated single entries.
+pub fn process_index_entry(entry: &str, anchor: &str) -> Vec<GeneralIndexEntry> {
+    let entry = entry.trim();
+    let (entry, main) = match entry.strip_prefix('!') {
+        Some(entry) => (entry.trim_start(), true),
+        None => (entry, false),
+    };
+    let new_entry = |entry_type: &str, value: String| GeneralIndexEntry {
+        entry_type: entry_type.to_string(),
+        value,
+        anchor: anchor.to_string(),
+        main,
+    };
+
+    if let Some((prefix, value)) = entry.split_once(':') {
+        let (prefix, value) = (prefix.trim(), value.trim());
+        if INDEX_ENTRY_TYPES.contains(&prefix) {
+            return vec![new_entry(prefix, value.to_string())];
+        }
+        if PAIR_INDEX_TYPES.contains(&prefix) {
+            return vec![new_entry("pair", format!("{}; {}", prefix, value))];
+        }
+    }
+    entry
+        .split(',')
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(|value| new_entry("single", value.to_string()))
+        .collect()
+}
+
+/// The entries of an `:index:` role: its text as a single entry, or with an
+/// explicit title, its target parsed as an `.. index::` line
+pub fn index_role_entries(role: &crate::roles::Role, anchor: &str) -> Vec<GeneralIndexEntry> {
+    match role.text {
+        Some(_) => process_index_entry(&role.target, anchor),
+        None => {
+            let target = role.target.trim();
+            let (value, main) = match target.strip_prefix('!') {
+                Some(value) => (value, true),
+                None => (target, false),
+            };
+            vec![GeneralIndexEntry {
+                entry_type: "single".to_string(),
+                value: value.to_string(),
+                anchor: anchor.to_string(),
+                main,
+            }]
+        }
+    }
+}
+
+/// Index terms sort case-insensitively, symbols before letters
+fn index_sort_key(name: &str) -> (bool, String) {
+    let key = name.to_lowercase();
+    let letter = key
+        .chars()
+        .next()
+        .is_some_and(|initial| initial.is_alphabetic() || initial == '_');
+    (letter, key)
+}
+
+/// A label as Sphinx compares them: lowercase, with whitespace collapsed
+pub fn normalize_label(label: &str) -> String {
+    label
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
 /// Domain represents a Sphinx domain (py, cpp, js, std, etc.)
 #[derive(Debug, Clone)]
 pub struct Domain {
@@ -239,7 +1150,10 @@ pub struct DomainIndex {
     pub name: String,
     pub localname: String,
     pub shortname: Option<String>,
-    pub entries: Vec<IndexEntry>,
+    /// Entries grouped under their initial
+    pub content: Vec<(String, Vec<IndexEntry>)>,
+    /// Whether sub-entries start out collapsed
+    pub collapse: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -483,6 +1397,85 @@ mod tests {
         assert_eq!(domain.get_objects().len(), 1);
     }
 
+    #[test]
+    fn test_collect_relations_walks_toctree_in_order() {
+        let mut env = BuildEnvironment::new(crate::config::BuildConfig::default());
+        env.toctree_includes.insert(
+            "index".to_string(),
+            vec!["guide/index".to_string(), "api".to_string()],
+        );
+        env.toctree_includes.insert(
+            "guide/index".to_string(),
+            vec!["guide/intro".to_string(), "index".to_string()],
+        );
+
+        let relations = env.collect_relations();
+
+        assert_eq!(relations.len(), 4);
+        assert_eq!(
+            relations["index"],
+            (None, None, Some("guide/index".to_string()))
+        );
+        assert_eq!(
+            relations["guide/intro"],
+            (
+                Some("guide/index".to_string()),
+                Some("guide/index".to_string()),
+                Some("api".to_string())
+            )
+        );
+        assert_eq!(
+            relations["api"],
+            (
+                Some("index".to_string()),
+                Some("guide/intro".to_string()),
+                None
+            )
+        );
+    }
+
+    #[test]
+    fn test_overloaded_objects_get_distinct_anchors() {
+        let mut env = BuildEnvironment::new(crate::config::BuildConfig::default());
+
+        let first = env.update_domain_object(
+            "py",
+            "function",
+            DomainObject::new(
+                "overload".to_string(),
+                "function".to_string(),
+                "api".to_string(),
+                Some("overload".to_string()),
+                1,
+            ),
+        );
+        let second = env.update_domain_object(
+            "py",
+            "function",
+            DomainObject::new(
+                "overload".to_string(),
+                "function".to_string(),
+                "api".to_string(),
+                Some("overload".to_string()),
+                1,
+            ),
+        );
+
+        assert_eq!(first.as_deref(), Some("overload"));
+        assert_eq!(second.as_deref(), Some("overload-1"));
+
+        let mut stored: Vec<_> = env
+            .get_all_objects()
+            .iter()
+            .filter_map(|obj| obj.anchor.clone())
+            .collect();
+        stored.sort();
+        assert_eq!(stored, vec!["overload", "overload-1"]);
+
+        // Anchors are scoped per document
+        assert_eq!(env.register_anchor("other", "overload"), "overload");
+    }
+
     #[test]
     fn test_standard_domains() {
         let domains = create_standard_domains();
@@ -497,4 +1490,69 @@ mod tests {
         assert!(py_domain.object_types.contains_key("function"));
         assert!(py_domain.object_types.contains_key("class"));
     }
+
+    #[test]
+    fn test_python_objects_are_registered_under_their_full_names() {
+        let config = crate::config::BuildConfig::default();
+        let parser = crate::parser::Parser::new(&config).unwrap();
+        let content = "API\n###\n\n.. py:module:: demo\n\n\
+             .. py:function:: greet(name, punctuation='!') -> str\n\n   Say *hello*.\n\n   .. deprecated:: 2.0\n\n\
+             .. py:class:: Greeter(name)\n\n   A greeter.\n\n\
+             \x20  .. py:method:: hello(self)\n\n      Greet.\n\n\
+             \x20  .. py:attribute:: name\n      :type: str\n\n\
+             .. py:function:: secret()\n   :noindex:\n";
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("api.rst");
+        std::fs::write(&path, content).unwrap();
+        let document = parser.parse(&path, content).unwrap();
+        let mut env = BuildEnvironment::new(config.clone());
+        env.note_documents([("api".to_string(), &document)]);
+
+        let mut names: Vec<(&str, &str)> = env
+            .get_all_objects()
+            .into_iter()
+            .filter(|object| object.object_type != "label")
+            .map(|object| (object.name.as_str(), object.object_type.as_str()))
+            .collect();
+        names.sort();
+        assert_eq!(
+            names,
+            [
+                ("demo", "module"),
+                ("demo.Greeter", "class"),
+                ("demo.Greeter.hello", "method"),
+                ("demo.Greeter.name", "attribute"),
+                ("demo.greet", "function"),
+            ]
+        );
+        let deprecated: Vec<&str> = env
+            .get_all_objects()
+            .into_iter()
+            .filter(|object| object.deprecated)
+            .map(|object| object.name.as_str())
+            .collect();
+        assert_eq!(deprecated, ["demo.greet"]);
+        let entries: Vec<&str> = env.index_entries["api"]
+            .iter()
+            .map(|entry| entry.value.as_str())
+            .collect();
+        assert_eq!(
+            entries,
+            [
+                "greet() (in module demo)",
+                "Greeter (class in demo)",
+                "hello() (demo.Greeter method)",
+                "name (demo.Greeter attribute)",
+            ]
+        );
+
+        let html = crate::renderer::HtmlRenderer::new(&config)
+            .render_in_env(&document, "api", &env)
+            .html;
+        assert!(html.contains("<dl class=\"py function\">"), "{}", html);
+        assert!(html.contains("id=\"demo.greet\""), "{}", html);
+        assert!(html.contains("<em>hello</em>"), "{}", html);
+        assert!(html.contains("id=\"demo.Greeter.hello\""), "{}", html);
+        assert!(!html.contains("id=\"secret\""), "{}", html);
+    }
 }
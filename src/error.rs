@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -72,6 +72,11 @@ pub enum WarningType {
     UnusedLabel,
     DuplicateLabel,
     EmptyToctree,
+    UnsupportedDirective,
+    UnknownDirective,
+    UnknownRole,
+    Lint,
+    SizeBudget,
     Other,
 }
 
@@ -82,6 +87,9 @@ pub enum ErrorType {
     FileNotFound,
     TemplateError,
     SyntaxError,
+    BrokenCrossReference,
+    ProcessorPanic,
+    OutputCollision,
     Other,
 }
 
@@ -121,7 +129,66 @@ impl BuildWarning {
         )
     }
 
-    #[allow(dead_code)]
+    pub fn missing_image(file: PathBuf, line: Option<usize>, image: &str) -> Self {
+        Self::new(
+            file,
+            line,
+            format!("image file not found: '{}'", image),
+            WarningType::MissingFile,
+        )
+    }
+
+    pub fn unsupported_directive(file: PathBuf, line: Option<usize>, name: &str) -> Self {
+        Self::new(
+            file,
+            line,
+            format!("directive '{}' is not yet fully supported", name),
+            WarningType::UnsupportedDirective,
+        )
+    }
+
+    pub fn broken_redirect(file: PathBuf, from: &str, to: &str) -> Self {
+        Self::new(
+            file,
+            None,
+            format!(
+                "redirect from '{}' points to '{}', which is not a document",
+                from, to
+            ),
+            WarningType::BrokenCrossReference,
+        )
+    }
+
+    pub fn page_size_budget(file: PathBuf, page: &str, size_kb: u64, budget_kb: u64) -> Self {
+        Self::new(
+            file,
+            None,
+            format!(
+                "rendered page '{}' is {} KB, over the page size budget of {} KB",
+                page, size_kb, budget_kb
+            ),
+            WarningType::SizeBudget,
+        )
+    }
+
+    pub fn unknown_directive(file: PathBuf, line: Option<usize>, name: &str) -> Self {
+        Self::new(
+            file,
+            line,
+            format!("Unknown directive type \"{}\".", name),
+            WarningType::UnknownDirective,
+        )
+    }
+
+    pub fn unknown_role(file: PathBuf, line: Option<usize>, name: &str) -> Self {
+        Self::new(
+            file,
+            line,
+            format!("Unknown interpreted text role \"{}\".", name),
+            WarningType::UnknownRole,
+        )
+    }
+
     pub fn broken_cross_reference(file: PathBuf, line: Option<usize>, reference: &str) -> Self {
         Self::new(
             file,
@@ -133,7 +200,6 @@ impl BuildWarning {
 }
 
 impl BuildErrorReport {
-    #[allow(dead_code)]
     pub fn new(file: PathBuf, line: Option<usize>, message: String, error_type: ErrorType) -> Self {
         Self {
             file,
@@ -142,4 +208,43 @@ impl BuildErrorReport {
             error_type,
         }
     }
+
+    pub fn broken_cross_reference(file: PathBuf, line: Option<usize>, reference: &str) -> Self {
+        Self::new(
+            file,
+            line,
+            format!("cross-reference target not found: '{}'", reference),
+            ErrorType::BrokenCrossReference,
+        )
+    }
+
+    /// `file` maps to the same output as `other`, which is written instead
+    pub fn output_collision(file: PathBuf, other: &Path, output: &Path) -> Self {
+        Self::new(
+            file,
+            None,
+            format!(
+                "output path '{}' is also the output of '{}'; this document is not written",
+                output.display(),
+                other.display()
+            ),
+            ErrorType::OutputCollision,
+        )
+    }
+
+    /// A directive or role processor panicked; `kind` is "directive" or "role"
+    pub fn processor_panic(
+        file: PathBuf,
+        line: Option<usize>,
+        kind: &str,
+        name: &str,
+        message: &str,
+    ) -> Self {
+        Self::new(
+            file,
+            line,
+            format!("\"{}\" {} panicked: {}", name, kind, message),
+            ErrorType::ProcessorPanic,
+        )
+    }
 }
@@ -1,7 +1,7 @@
 use anyhow::Result;
 use serde_json::Value;
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use crate::config::BuildConfig;
 
@@ -369,3 +369,31 @@ impl BuiltinExtensions {
         config
     }
 }
+
+/// Write the files `sphinx.ext.githubpages` adds to the output root: an empty
+/// `.nojekyll`, so GitHub Pages serves `_static` and friends, and a `CNAME` for
+/// a custom domain in `html_baseurl`
+///
+/// A `CNAME` already in the output, e.g. from `html_extra_path`, is kept.
+pub fn write_githubpages_files(outdir: &Path, config: &BuildConfig) -> Result<()> {
+    std::fs::create_dir_all(outdir)?;
+    std::fs::write(outdir.join(".nojekyll"), "")?;
+
+    let cname = outdir.join("CNAME");
+    if let Some(domain) = config.html_baseurl.as_deref().and_then(url_domain) {
+        if !domain.ends_with(".github.io") && !cname.exists() {
+            std::fs::write(cname, domain)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Host name of an absolute URL, without port or credentials
+fn url_domain(url: &str) -> Option<&str> {
+    let (_, rest) = url.split_once("://")?;
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let host = authority.rsplit('@').next()?;
+    let host = host.split(':').next()?;
+    (!host.is_empty()).then_some(host)
+}
@@ -0,0 +1,283 @@
+//! Syntax highlighting of code with syntect
+//!
+//! Tokens are wrapped in spans carrying Pygments' short class names (`k`,
+//! `s2`, `c1`, ...) rather than inline styles, so `pygments.css` applies to
+//! them as it does to Sphinx's output. The stylesheet itself is generated
+//! from one of syntect's bundled themes.
+
+use anyhow::Result;
+use std::fmt::Write;
+use std::ops::Range;
+use std::sync::LazyLock;
+
+use syntect::easy::ScopeRangeIterator;
+use syntect::highlighting::{Color, FontStyle, Highlighter, ThemeSet};
+use syntect::parsing::{ParseState, Scope, ScopeStack, SyntaxReference, SyntaxSet};
+use syntect::util::LinesWithEndings;
+
+use crate::error::BuildError;
+
+/// syntect's bundled syntaxes, loaded on first use
+static SYNTAX_SET: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
+
+/// Selector of the blocks highlighted code is rendered in (`highlight-<lang>`)
+pub const HIGHLIGHT_SELECTOR: &str = "[class^=\"highlight-\"]";
+
+/// Pygments token classes by scope prefix, most specific first
+const TOKEN_CLASSES: &[(&str, &str)] = &[
+    ("comment.line", "c1"),
+    ("comment.block.documentation", "sd"),
+    ("comment", "c"),
+    ("string.quoted.double", "s2"),
+    ("string.quoted.single", "s1"),
+    ("string.regexp", "sr"),
+    ("string", "s"),
+    ("constant.character.escape", "se"),
+    ("constant.numeric.float", "mf"),
+    ("constant.numeric.integer", "mi"),
+    ("constant.numeric", "m"),
+    ("constant.language", "kc"),
+    ("constant", "no"),
+    ("keyword.operator.word", "ow"),
+    ("keyword.operator.logical", "ow"),
+    ("keyword.operator", "o"),
+    ("keyword.control.import", "kn"),
+    ("keyword", "k"),
+    ("storage.type.string", "sa"),
+    ("storage.type.numeric", "kt"),
+    ("storage.modifier", "kd"),
+    ("storage.type", "kd"),
+    ("support.type", "kt"),
+    ("support.function", "nb"),
+    ("support", "nb"),
+    ("entity.name.function.decorator", "nd"),
+    ("meta.annotation", "nd"),
+    ("entity.name.function", "nf"),
+    ("entity.name.class", "nc"),
+    ("entity.name.struct", "nc"),
+    ("entity.name.type", "nc"),
+    ("entity.name.namespace", "nn"),
+    ("entity.name.module", "nn"),
+    ("entity.name.tag", "nt"),
+    ("entity.name.label", "nl"),
+    ("entity.other.attribute-name", "na"),
+    ("variable.language", "bp"),
+    ("markup.heading", "gh"),
+    ("markup.inserted", "gi"),
+    ("markup.deleted", "gd"),
+    ("markup.bold", "gs"),
+    ("markup.italic", "ge"),
+];
+
+/// Generate `pygments.css` from the bundled syntect theme named `theme_name`
+///
+/// Matching is case-insensitive; `github` selects `InspiredGitHub`.
+pub fn theme_css(theme_name: &str) -> Result<String> {
+    let themes = ThemeSet::load_defaults();
+    let wanted = match theme_name.trim().to_lowercase().as_str() {
+        "github" => "inspiredgithub".to_string(),
+        name => name.to_string(),
+    };
+    let theme = themes
+        .themes
+        .iter()
+        .find(|(name, _)| name.to_lowercase() == wanted)
+        .map(|(_, theme)| theme)
+        .ok_or_else(|| {
+            let known: Vec<&str> = themes.themes.keys().map(String::as_str).collect();
+            BuildError::Config(format!(
+                "Unknown highlight_theme: '{}' (available: github, {})",
+                theme_name,
+                known.join(", ")
+            ))
+        })?;
+
+    let color = |color: Color| format!("#{:02x}{:02x}{:02x}", color.r, color.g, color.b);
+    let mut css = format!(
+        "/* Generated from the \"{}\" highlight theme */\n",
+        theme.name.as_deref().unwrap_or(theme_name)
+    );
+    let mut block = Vec::new();
+    if let Some(background) = theme.settings.background {
+        block.push(format!("background: {}", color(background)));
+    }
+    if let Some(foreground) = theme.settings.foreground {
+        block.push(format!("color: {}", color(foreground)));
+    }
+    writeln!(css, "{} {{ {} }}", HIGHLIGHT_SELECTOR, block.join("; "))?;
+    if let Some(line_highlight) = theme.settings.line_highlight {
+        writeln!(
+            css,
+            "{} .hll {{ background-color: {} }}",
+            HIGHLIGHT_SELECTOR,
+            color(line_highlight)
+        )?;
+    }
+
+    // Each class is styled as its most specific scope
+    let highlighter = Highlighter::new(theme);
+    let mut written = Vec::new();
+    for (scope, class) in TOKEN_CLASSES {
+        if written.contains(class) {
+            continue;
+        }
+        written.push(*class);
+        let style = highlighter.style_for_stack(&[Scope::new(scope)?]);
+        let mut rule = vec![format!("color: {}", color(style.foreground))];
+        if style.font_style.contains(FontStyle::BOLD) {
+            rule.push("font-weight: bold".to_string());
+        }
+        if style.font_style.contains(FontStyle::ITALIC) {
+            rule.push("font-style: italic".to_string());
+        }
+        if style.font_style.contains(FontStyle::UNDERLINE) {
+            rule.push("text-decoration: underline".to_string());
+        }
+        writeln!(
+            css,
+            "{} .{} {{ {} }} /* {} */",
+            HIGHLIGHT_SELECTOR,
+            class,
+            rule.join("; "),
+            scope
+        )?;
+    }
+
+    Ok(css)
+}
+
+/// Names Sphinx accepts for a language that syntect knows by another
+fn syntax_token(language: &str) -> &str {
+    match language {
+        "python3" | "py3" | "pycon" | "ipython" => "python",
+        "shell" | "console" | "sh" | "zsh" => "bash",
+        "c++" => "cpp",
+        "js" => "javascript",
+        other => other,
+    }
+}
+
+/// The syntax to highlight `language` with, unless it is unknown or plain text
+fn find_syntax(language: &str) -> Option<&'static SyntaxReference> {
+    let language = language.trim().to_lowercase();
+    match language.as_str() {
+        "" | "text" | "none" | "default" => None,
+        language => SYNTAX_SET.find_syntax_by_token(syntax_token(language)),
+    }
+}
+
+/// The Pygments class of a token with the given scopes, innermost last
+fn token_class(stack: &ScopeStack) -> Option<&'static str> {
+    stack.as_slice().iter().rev().find_map(|scope| {
+        let scope = scope.build_string();
+        TOKEN_CLASSES
+            .iter()
+            .find(|(prefix, _)| {
+                scope == *prefix
+                    || scope
+                        .strip_prefix(prefix)
+                        .is_some_and(|rest| rest.starts_with('.'))
+            })
+            .map(|(_, class)| *class)
+    })
+}
+
+/// Highlight `code` as `language`, escaping it for HTML
+///
+/// Every line closes its own spans, so lines can be wrapped individually
+/// afterwards (as `:emphasize-lines:` does). Unknown languages, and code
+/// syntect fails on, come back as escaped plain text.
+pub fn highlight_code(language: &str, code: &str) -> String {
+    find_syntax(language)
+        .and_then(|syntax| highlight_with(syntax, code))
+        .unwrap_or_else(|| html_escape::encode_text(code).into_owned())
+}
+
+fn highlight_with(syntax: &SyntaxReference, code: &str) -> Option<String> {
+    let mut state = ParseState::new(syntax);
+    let mut stack = ScopeStack::new();
+    let mut html = String::with_capacity(code.len() * 2);
+
+    for line in LinesWithEndings::from(code) {
+        let ops = state.parse_line(line, &SYNTAX_SET).ok()?;
+        // Runs of text sharing a class, merged so each gets a single span
+        let mut tokens: Vec<(Option<&str>, Range<usize>)> = Vec::new();
+        for (range, op) in ScopeRangeIterator::new(&ops, line) {
+            stack.apply(op).ok()?;
+            if range.is_empty() {
+                continue;
+            }
+            let class = token_class(&stack).filter(|_| !line[range.clone()].trim().is_empty());
+            match tokens.last_mut() {
+                Some((last_class, last)) if *last_class == class => last.end = range.end,
+                _ => tokens.push((class, range)),
+            }
+        }
+
+        for (class, range) in tokens {
+            let text = &line[range];
+            let (text, newline) = match text.strip_suffix('\n') {
+                Some(text) => (text, "\n"),
+                None => (text, ""),
+            };
+            let text = html_escape::encode_text(text);
+            match class {
+                Some(class) if !text.is_empty() => {
+                    html.push_str(&format!("<span class=\"{}\">{}</span>", class, text))
+                }
+                _ => html.push_str(&text),
+            }
+            html.push_str(newline);
+        }
+    }
+
+    Some(html)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rust_keywords_are_wrapped_in_token_spans() {
+        let html = highlight_code("rust", "fn main() {\n    let x = 1; // one\n}");
+
+        assert!(html.contains("<span class=\"kd\">fn</span>"), "{}", html);
+        assert!(html.contains("<span class=\"nf\">main</span>"), "{}", html);
+        assert!(html.contains("<span class=\"kd\">let</span>"), "{}", html);
+        assert!(
+            html.contains("<span class=\"c1\">// one</span>"),
+            "{}",
+            html
+        );
+        // Spans never cross lines
+        for line in html.lines() {
+            assert_eq!(
+                line.matches("<span").count(),
+                line.matches("</span>").count()
+            );
+        }
+    }
+
+    #[test]
+    fn test_unknown_languages_are_escaped_plain_text() {
+        assert_eq!(highlight_code("klingon", "a < b"), "a &lt; b");
+        assert_eq!(highlight_code("text", "fn <x>"), "fn &lt;x&gt;");
+    }
+
+    #[test]
+    fn test_theme_css_styles_common_token_classes() {
+        let css = theme_css("github").unwrap();
+
+        assert!(css.starts_with("/* Generated from the \"GitHub\" highlight theme */"));
+        for class in ["k", "kd", "s", "s2", "c", "c1", "mi", "nf", "o"] {
+            let selector = format!("{} .{} {{ color: #", HIGHLIGHT_SELECTOR, class);
+            assert!(css.contains(&selector), "no rule for .{}:\n{}", class, css);
+        }
+        assert_ne!(css, theme_css("Solarized (dark)").unwrap());
+
+        let error = theme_css("no-such-theme").unwrap_err().to_string();
+        assert!(error.contains("Unknown highlight_theme: 'no-such-theme'"));
+        assert!(error.contains("InspiredGitHub"));
+    }
+}
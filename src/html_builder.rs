@@ -1,20 +1,32 @@
 use anyhow::{Context, Result};
+use async_trait::async_trait;
 use log::{debug, info, warn};
 use serde::{Deserialize, Serialize};
-use serde_json::{json, Map, Value as JsonValue};
+use serde_json::{Map, Value as JsonValue};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use tokio::fs;
 
+use crate::builder::PageLayout;
 use crate::config::BuildConfig;
-use crate::document::Document;
+use crate::document::{Document, DocumentContent, RstNode, TocEntry};
+use crate::environment::{BuildEnvironment, IndexTerm};
+use crate::highlight;
 use crate::inventory::InventoryFile;
+use crate::search::SearchIndexBuilder;
 use crate::template::TemplateEngine;
 use crate::utils;
+use crate::writer::DocumentWriter;
 
 /// The filename for the inventory of objects (matches Sphinx)
 pub const INVENTORY_FILENAME: &str = "objects.inv";
 
+/// Directory holding the manifest and shards of a sharded search index
+pub const SHARDED_SEARCHINDEX_DIR: &str = "searchindex";
+
+/// Maximum length of the `og:description` text, in characters
+const OPENGRAPH_DESCRIPTION_LENGTH: usize = 200;
+
 /// HTML Builder that mirrors Sphinx's StandaloneHTMLBuilder
 #[derive(Debug)]
 pub struct HTMLBuilder {
@@ -33,6 +45,8 @@ pub struct HTMLBuilder {
     pub supported_image_types: Vec<String>,
     pub supported_remote_images: bool,
     pub supported_data_uri_images: bool,
+    /// Page layout, from which `out_suffix` and `link_suffix` follow
+    pub layout: PageLayout,
 
     // Directories
     pub outdir: PathBuf,
@@ -63,8 +77,20 @@ pub struct HTMLBuilder {
     // Relations between documents
     pub relations: HashMap<String, DocumentRelation>,
 
+    /// Document titles by docname, for navigation links
+    pub titles: HashMap<String, String>,
+
     // Domain indices
     pub domain_indices: Vec<DomainIndex>,
+
+    /// Canonical URLs of written pages, for sitemap.xml
+    pub sitemap_urls: Vec<String>,
+
+    /// Search index of the documents written through [`DocumentWriter`]
+    pub search_index: SearchIndexBuilder,
+
+    /// Images of the documents written through [`DocumentWriter`]
+    env: BuildEnvironment,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -75,6 +101,9 @@ pub struct CSSFile {
     pub id: Option<String>,
     pub rel: String,
     pub type_: String,
+    /// Order of registration, breaking ties between equal priorities
+    #[serde(skip)]
+    pub insertion_index: usize,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -84,6 +113,9 @@ pub struct JSFile {
     pub loading_method: String,
     pub async_: bool,
     pub defer: bool,
+    /// Order of registration, breaking ties between equal priorities
+    #[serde(skip)]
+    pub insertion_index: usize,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -93,22 +125,7 @@ pub struct DocumentRelation {
     pub next: Option<String>,
 }
 
-#[derive(Debug, Clone)]
-pub struct DomainIndex {
-    pub name: String,
-    pub localname: String,
-    pub shortname: Option<String>,
-    pub content: Vec<IndexEntry>,
-    pub collapse: bool,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct IndexEntry {
-    pub name: String,
-    pub subentries: Vec<IndexEntry>,
-    pub uri: String,
-    pub display_name: String,
-}
+pub use crate::environment::{DomainIndex, IndexEntry};
 
 impl HTMLBuilder {
     pub fn new(config: BuildConfig, srcdir: PathBuf, outdir: PathBuf) -> Result<Self> {
@@ -119,13 +136,19 @@ impl HTMLBuilder {
         let images_dir = outdir.join("_images");
 
         let template_engine = TemplateEngine::new(&config)?;
+        let env = BuildEnvironment::new(config.clone());
+        let search_index =
+            SearchIndexBuilder::new(config.language.clone().unwrap_or_else(|| "en".to_string()));
 
         Ok(Self {
             name: "html".to_string(),
             format: "html".to_string(),
             epilog: "The HTML pages are in %(outdir)s.".to_string(),
             out_suffix: ".html".to_string(),
-            link_suffix: ".html".to_string(),
+            link_suffix: config
+                .html_link_suffix
+                .clone()
+                .unwrap_or_else(|| ".html".to_string()),
             searchindex_filename: "searchindex.js".to_string(),
             allow_parallel: true,
             copysource: true,
@@ -141,6 +164,7 @@ impl HTMLBuilder {
             ],
             supported_remote_images: true,
             supported_data_uri_images: true,
+            layout: PageLayout::Flat,
 
             outdir,
             srcdir,
@@ -163,10 +187,40 @@ impl HTMLBuilder {
 
             global_context: Map::new(),
             relations: HashMap::new(),
+            titles: HashMap::new(),
             domain_indices: Vec::new(),
+            sitemap_urls: Vec::new(),
+            search_index,
+            env,
         })
     }
 
+    /// Lay pages out as `layout` (mirrors choosing Sphinx's `html` or
+    /// `dirhtml` builder); the single-page layout is only written by
+    /// [`SphinxBuilder`](crate::SphinxBuilder)
+    pub fn set_layout(&mut self, layout: PageLayout) -> Result<()> {
+        match layout {
+            PageLayout::Flat => {
+                self.name = "html".to_string();
+                self.link_suffix = self
+                    .config
+                    .html_link_suffix
+                    .clone()
+                    .unwrap_or_else(|| ".html".to_string());
+            }
+            PageLayout::Directory => {
+                self.name = "dirhtml".to_string();
+                self.link_suffix = "/".to_string();
+            }
+            PageLayout::Single => {
+                anyhow::bail!("HTMLBuilder does not support the single-page layout")
+            }
+        }
+        self.out_suffix = ".html".to_string();
+        self.layout = layout;
+        Ok(())
+    }
+
     /// Initialize the builder (mirrors Sphinx's init method)
     pub async fn init(&mut self) -> Result<()> {
         info!("Initializing HTML builder");
@@ -182,12 +236,12 @@ impl HTMLBuilder {
         self.init_css_files()?;
         self.init_js_files()?;
 
-        // Set up global template context
-        self.init_global_context()?;
-
         // Configure use_index based on config
         self.use_index = self.config.html_use_index.unwrap_or(true);
 
+        // Set up global template context
+        self.init_global_context()?;
+
         Ok(())
     }
 
@@ -233,6 +287,10 @@ impl HTMLBuilder {
             self.add_js_file("translations.js", 500, false, false)?;
         }
 
+        if self.config.html_copy_code_button {
+            self.add_js_file("copybutton.js", 500, false, false)?;
+        }
+
         Ok(())
     }
 
@@ -257,10 +315,13 @@ impl HTMLBuilder {
             id: id.map(|s| s.to_string()),
             rel: "stylesheet".to_string(),
             type_: "text/css".to_string(),
+            insertion_index: self.css_files.len(),
         };
 
         if !self.css_files.contains(&css_file) {
             self.css_files.push(css_file);
+            self.css_files
+                .sort_by_key(|file| (file.priority, file.insertion_index));
         }
 
         Ok(())
@@ -286,10 +347,13 @@ impl HTMLBuilder {
             loading_method: "normal".to_string(),
             async_,
             defer,
+            insertion_index: self.js_files.len(),
         };
 
         if !self.js_files.contains(&js_file) {
             self.js_files.push(js_file);
+            self.js_files
+                .sort_by_key(|file| (file.priority, file.insertion_index));
         }
 
         Ok(())
@@ -329,6 +393,7 @@ impl HTMLBuilder {
             "master_doc": self.config.root_doc.as_deref().unwrap_or("index"),
             "root_doc": self.config.root_doc.as_deref().unwrap_or("index"),
             "use_opensearch": self.config.html_use_opensearch.unwrap_or(false),
+            "use_index": self.use_index,
             "docstitle": self.config.html_title.as_deref().unwrap_or(&self.config.project),
             "shorttitle": self.config.html_short_title.as_deref().unwrap_or(&self.config.project),
             "show_copyright": self.config.html_show_copyright.unwrap_or(true),
@@ -348,6 +413,8 @@ impl HTMLBuilder {
             "logo_url": self.config.html_logo.as_deref().unwrap_or(""),
             "favicon_url": self.config.html_favicon.as_deref().unwrap_or(""),
             "html5_doctype": true,
+            "head_extra": self.config.html_head_extra.as_deref().unwrap_or(""),
+            "body_end_extra": self.config.html_body_end_extra.as_deref().unwrap_or(""),
         })
         .as_object()
         .unwrap()
@@ -356,37 +423,211 @@ impl HTMLBuilder {
         Ok(())
     }
 
+    /// Load prev/next/parent relations from the environment's toctrees, and
+    /// the titles of the documents they link
+    pub fn load_relations(&mut self, env: &crate::environment::BuildEnvironment) {
+        self.relations = env
+            .collect_relations()
+            .into_iter()
+            .map(|(docname, (parent, prev, next))| {
+                (docname, DocumentRelation { parent, prev, next })
+            })
+            .collect();
+        self.titles
+            .extend(env.titles.iter().map(|(k, v)| (k.clone(), v.clone())));
+        if let Some(index) = self.python_module_index(env) {
+            self.add_domain_index(index);
+        }
+    }
+
+    /// The Python module index of `env`'s modules, linked from its page
+    fn python_module_index(
+        &self,
+        env: &crate::environment::BuildEnvironment,
+    ) -> Option<DomainIndex> {
+        env.python_module_index(|docname, anchor| {
+            format!(
+                "{}#{}",
+                self.get_relative_uri("py-modindex", docname),
+                anchor
+            )
+        })
+    }
+
+    /// Add a domain index, replacing any of the same name
+    pub fn add_domain_index(&mut self, index: DomainIndex) {
+        self.domain_indices
+            .retain(|existing| existing.name != index.name);
+        self.domain_indices.push(index);
+    }
+
+    /// Title of a document, or its docname when the title isn't known
+    fn doc_title(&self, docname: &str) -> String {
+        self.titles
+            .get(docname)
+            .cloned()
+            .unwrap_or_else(|| docname.to_string())
+    }
+
     /// Write a single document (mirrors Sphinx's write_doc)
     pub async fn write_doc(&mut self, docname: &str, doctree: &Document) -> Result<()> {
         info!("Writing document: {}", docname);
 
-        self.current_docname = docname.to_string();
-        self.imgpath = self.get_relative_uri(docname, "_images");
-        self.dlpath = self.get_relative_uri(docname, "_downloads");
+        let ctx = self.page_context(docname, doctree).await?;
+        self.index_page(docname, doctree, &ctx)?;
 
-        // Render the document to HTML
-        let body = format!(
-            "<div class=\"document\">\n{}\n</div>",
-            html_escape::encode_text(&doctree.content.to_string())
-        );
-        let metatags = format!(
+        // Handle the page
+        self.handle_page(docname, ctx, "page.html").await?;
+
+        Ok(())
+    }
+
+    /// Add a page to the search index from its rendered body, so markup
+    /// doesn't turn up as search terms (mirrors Sphinx's index_page)
+    pub(crate) fn index_page(
+        &mut self,
+        docname: &str,
+        doctree: &Document,
+        ctx: &serde_json::Map<String, serde_json::Value>,
+    ) -> Result<()> {
+        let body = ctx
+            .get("body")
+            .and_then(|body| body.as_str())
+            .unwrap_or_default();
+        self.search_index.add_or_update_document(
+            docname.to_string(),
+            self.get_target_uri(docname),
+            doctree.title.clone(),
+            &utils::html_to_text(body),
+        )
+    }
+
+    /// Template context of a document's page: the global context plus the
+    /// page's body, title, relations, local TOC and metadata
+    pub(crate) async fn page_context(
+        &mut self,
+        docname: &str,
+        doctree: &Document,
+    ) -> Result<serde_json::Map<String, serde_json::Value>> {
+        self.current_docname = docname.to_string();
+        self.titles
+            .insert(docname.to_string(), doctree.title.clone());
+        self.imgpath = utils::relative_uri(&self.get_target_uri(docname), "_images", "");
+        self.dlpath = utils::relative_uri(&self.get_target_uri(docname), "_downloads", "");
+
+        // Documents handed over unrendered are rendered here, against the
+        // environment of the documents written so far
+        let body = if doctree.html.is_empty() {
+            let rendered = crate::renderer::HtmlRenderer::new(&self.config)
+                .render_in_env(doctree, docname, &self.env);
+            format!("<div class=\"document\">\n{}\n</div>", rendered.html)
+        } else {
+            format!("<div class=\"document\">\n{}\n</div>", doctree.html)
+        };
+        let mut metatags = format!(
             "<meta name=\"source\" content=\"{}\" />",
             html_escape::encode_double_quoted_attribute(&doctree.source_path.to_string_lossy())
         );
+        if self.config.html_opengraph.unwrap_or(false) {
+            metatags.push_str(&self.opengraph_metatags(docname, doctree));
+        }
 
         // Get document context
-        let ctx = self.get_doc_context(docname, &body, &metatags).await?;
+        let mut ctx = self
+            .get_doc_context(docname, doctree, &body, &metatags)
+            .await?;
 
-        // Handle the page
-        self.handle_page(docname, ctx, "page.html").await?;
+        if let Some(pageurl) = self.canonical_url(docname) {
+            ctx.insert("pageurl".to_string(), serde_json::json!(pageurl));
+            self.sitemap_urls.push(pageurl);
+        }
 
-        Ok(())
+        // Docinfo fields (author, date, ...) as Sphinx's `meta`
+        ctx.insert(
+            "meta".to_string(),
+            serde_json::json!(doctree.metadata.custom),
+        );
+
+        Ok(ctx)
+    }
+
+    /// OpenGraph and Twitter card meta tags for a page; URLs are only emitted
+    /// when `html_baseurl` makes them absolute
+    fn opengraph_metatags(&self, docname: &str, doctree: &Document) -> String {
+        let mut properties = vec![
+            ("og:title", doctree.title.clone()),
+            ("og:type", "website".to_string()),
+            ("og:site_name", self.config.project.clone()),
+        ];
+        let mut twitter = vec![
+            ("twitter:card", "summary".to_string()),
+            ("twitter:title", doctree.title.clone()),
+        ];
+
+        if let Some(description) = doctree.description() {
+            let description = truncate_description(&description, OPENGRAPH_DESCRIPTION_LENGTH);
+            properties.push(("og:description", description.clone()));
+            twitter.push(("twitter:description", description));
+        }
+        if let Some(url) = self.canonical_url(docname) {
+            properties.push(("og:url", url));
+        }
+        let image = self.config.html_logo.as_deref().and_then(|logo| {
+            if logo.contains("://") {
+                return Some(logo.to_string());
+            }
+            let filename = std::path::Path::new(logo).file_name()?.to_string_lossy();
+            self.absolute_url(&format!("_static/{}", filename))
+        });
+        if let Some(image) = image {
+            properties.push(("og:image", image.clone()));
+            twitter.push(("twitter:image", image));
+        }
+
+        let mut tags = String::new();
+        for (property, content) in properties {
+            tags.push_str(&format!(
+                "\n<meta property=\"{}\" content=\"{}\" />",
+                property,
+                html_escape::encode_double_quoted_attribute(&content)
+            ));
+        }
+        for (name, content) in twitter {
+            tags.push_str(&format!(
+                "\n<meta name=\"{}\" content=\"{}\" />",
+                name,
+                html_escape::encode_double_quoted_attribute(&content)
+            ));
+        }
+        tags
+    }
+
+    /// Canonical URL of a document: under `html_baseurl` with the
+    /// `html_version_path` segment removed, so every version points at one URL
+    fn canonical_url(&self, docname: &str) -> Option<String> {
+        let baseurl = self.config.html_baseurl.as_deref()?.trim_end_matches('/');
+        let baseurl = self
+            .config
+            .html_version_path
+            .as_deref()
+            .map(|segment| segment.trim_matches('/'))
+            .filter(|segment| !segment.is_empty())
+            .and_then(|segment| baseurl.strip_suffix(segment)?.strip_suffix('/'))
+            .unwrap_or(baseurl);
+        Some(format!("{}/{}", baseurl, self.get_target_uri(docname)))
+    }
+
+    /// Absolute URL of an output path under `html_baseurl`
+    fn absolute_url(&self, path: &str) -> Option<String> {
+        let baseurl = self.config.html_baseurl.as_deref()?;
+        Some(format!("{}/{}", baseurl.trim_end_matches('/'), path))
     }
 
     /// Get document context for template (mirrors Sphinx's get_doc_context)
     async fn get_doc_context(
         &self,
         docname: &str,
+        doctree: &Document,
         body: &str,
         metatags: &str,
     ) -> Result<serde_json::Map<String, serde_json::Value>> {
@@ -409,7 +650,7 @@ impl HTMLBuilder {
             if let Some(parent_rel) = self.relations.get(&parent_name) {
                 parents.push(json!({
                     "link": self.get_relative_uri(docname, &parent_name),
-                    "title": parent_name, // TODO: Get actual title
+                    "title": self.doc_title(&parent_name),
                 }));
                 current = parent_rel.parent.clone();
             } else {
@@ -419,7 +660,7 @@ impl HTMLBuilder {
         parents.reverse();
 
         // Title and metadata
-        let title = docname; // TODO: Extract actual title from document
+        let title = &doctree.title;
         let source_suffix = ".rst"; // TODO: Detect actual suffix
         let sourcename = if self.config.html_copy_source.unwrap_or(true) {
             format!(
@@ -434,16 +675,19 @@ impl HTMLBuilder {
             String::new()
         };
 
-        // Local TOC
-        let toc = self.generate_local_toc(docname).await?;
+        // Local TOC, shown when the page has more than its title
+        let toc_entries = self.local_toc_entries(doctree);
+        let display_toc = toc_entries.len() > 1;
+        let toc = render_local_toc(&toc_entries);
 
         ctx.insert("parents".to_string(), json!(parents));
+        ctx.insert("rellinks".to_string(), json!(self.rellinks()));
         if let Some(p) = prev {
             ctx.insert(
                 "prev".to_string(),
                 json!({
                     "link": self.get_relative_uri(docname, &p),
-                    "title": p, // TODO: Get actual title
+                    "title": self.doc_title(&p),
                 }),
             );
         }
@@ -452,7 +696,7 @@ impl HTMLBuilder {
                 "next".to_string(),
                 json!({
                     "link": self.get_relative_uri(docname, &n),
-                    "title": n, // TODO: Get actual title
+                    "title": self.doc_title(&n),
                 }),
             );
         }
@@ -461,16 +705,94 @@ impl HTMLBuilder {
         ctx.insert("metatags".to_string(), json!(metatags));
         ctx.insert("sourcename".to_string(), json!(sourcename));
         ctx.insert("toc".to_string(), json!(toc));
-        ctx.insert("display_toc".to_string(), json!(true));
+        ctx.insert("display_toc".to_string(), json!(display_toc));
+        ctx.insert(
+            "localtoc".to_string(),
+            json!(self.generate_local_toc(doctree, self.config.localtoc_depth)),
+        );
         ctx.insert("page_source_suffix".to_string(), json!(source_suffix));
 
         Ok(ctx)
     }
 
-    /// Generate local table of contents
-    async fn generate_local_toc(&self, _docname: &str) -> Result<String> {
-        // TODO: Implement actual TOC generation
-        Ok("<div class=\"toc\"></div>".to_string())
+    /// Index pages linked from every page's navigation, as Sphinx's
+    /// `(pagename, title, accesskey, link text)` rellinks
+    fn rellinks(&self) -> Vec<(String, String, String, String)> {
+        let mut rellinks = Vec::new();
+        if self.use_index {
+            rellinks.push((
+                "genindex".to_string(),
+                "General Index".to_string(),
+                "I".to_string(),
+                "index".to_string(),
+            ));
+        }
+        for index in self.enabled_domain_indices() {
+            if let Some(shortname) = &index.shortname {
+                rellinks.push((
+                    index.name.clone(),
+                    index.localname.clone(),
+                    String::new(),
+                    shortname.clone(),
+                ));
+            }
+        }
+        rellinks
+    }
+
+    /// Domain indices allowed by `html_domain_indices`
+    fn enabled_domain_indices(&self) -> impl Iterator<Item = &DomainIndex> {
+        self.domain_indices
+            .iter()
+            .filter(|index| self.config.is_domain_index_enabled(&index.name))
+    }
+
+    /// Headings of a page, in order, down to `localtoc_depth` levels below its
+    /// title, with domain objects under their section when `toc_object_entries` is on
+    fn local_toc_entries(&self, doctree: &Document) -> Vec<TocEntry> {
+        let entries = self.toc_entries_with_objects(doctree);
+        match self.config.localtoc_depth {
+            Some(depth) => truncate_toc(entries, depth),
+            None => entries,
+        }
+    }
+
+    /// A page's headings, plus its objects when `toc_object_entries` is on
+    fn toc_entries_with_objects(&self, doctree: &Document) -> Vec<TocEntry> {
+        let object_entries = self.config.toc_object_entries.unwrap_or(true);
+        match (object_entries, &doctree.content) {
+            (true, DocumentContent::RestructuredText(rst)) => {
+                with_object_entries(&doctree.toc, &rst.ast)
+            }
+            _ => doctree.toc.clone(),
+        }
+    }
+
+    /// The sections of a page as a nested `<ul>` in a `<div class="toc">`,
+    /// without the page's own title
+    ///
+    /// `maxdepth` counts levels below the title: 1 lists only the top
+    /// sections. Links use the anchors the parser gave the headings.
+    pub fn generate_local_toc(&self, doctree: &Document, maxdepth: Option<usize>) -> String {
+        let mut entries = self.toc_entries_with_objects(doctree);
+        if let Some(depth) = maxdepth {
+            entries = truncate_toc(entries, depth);
+        }
+        // The title is the first heading; its sections move up a level
+        if entries
+            .first()
+            .is_some_and(|first| entries.iter().all(|entry| entry.level >= first.level))
+        {
+            entries.remove(0);
+        }
+
+        if entries.is_empty() {
+            return "<div class=\"toc\"></div>".to_string();
+        }
+        format!(
+            "<div class=\"toc\">\n{}</div>",
+            render_toc_list(&entries, false)
+        )
     }
 
     /// Handle a page (render and write) - mirrors Sphinx's handle_page
@@ -518,22 +840,32 @@ impl HTMLBuilder {
     }
 
     /// Get output path for a document
-    fn get_output_path(&self, docname: &str) -> PathBuf {
-        self.outdir.join(format!("{}{}", docname, self.out_suffix))
+    ///
+    /// In the directory layout `foo/bar` is written to `foo/bar/index.html`,
+    /// while `index` documents stay where they are.
+    pub(crate) fn get_output_path(&self, docname: &str) -> PathBuf {
+        match self.layout {
+            PageLayout::Directory => self.outdir.join(format!(
+                "{}index{}",
+                utils::target_uri(docname, "/"),
+                self.out_suffix
+            )),
+            _ => self.outdir.join(format!("{}{}", docname, self.out_suffix)),
+        }
     }
 
     /// Get relative URI between two documents
     fn get_relative_uri(&self, from: &str, to: &str) -> String {
-        utils::relative_uri(from, to, &self.link_suffix)
+        utils::relative_uri(&self.get_target_uri(from), &self.get_target_uri(to), "")
     }
 
     /// Get target URI for a document
     pub fn get_target_uri(&self, docname: &str) -> String {
-        format!("{}{}", docname, self.link_suffix)
+        utils::target_uri(docname, &self.link_suffix)
     }
 
     /// Generate indices (mirrors Sphinx's gen_indices)
-    pub async fn gen_indices(&mut self) -> Result<()> {
+    pub async fn gen_indices(&self) -> Result<()> {
         info!("Generating indices");
 
         // Generate general index if enabled
@@ -548,13 +880,53 @@ impl HTMLBuilder {
     }
 
     /// Write general index
+    ///
+    /// The context has Sphinx's shape: each letter's terms are
+    /// `[name, [links, subterms, null]]`, with `links` as `[main, uri]` pairs
+    /// and each subterm as `[name, links]`.
     async fn write_genindex(&self) -> Result<()> {
         info!("Writing general index");
 
-        // TODO: Implement actual index generation
+        let links = |term: &IndexTerm| -> Vec<JsonValue> {
+            term.links
+                .iter()
+                .map(|link| {
+                    let uri = format!(
+                        "{}#{}",
+                        self.get_relative_uri("genindex", &link.docname),
+                        link.anchor
+                    );
+                    serde_json::json!([link.main, uri])
+                })
+                .collect()
+        };
+
+        let index = self.env.create_index();
+        let counts: Vec<usize> = index
+            .iter()
+            .map(|(_, terms)| terms.iter().map(|term| 1 + term.subterms.len()).sum())
+            .collect();
+        let entries: Vec<JsonValue> = index
+            .iter()
+            .map(|(letter, terms)| {
+                let terms: Vec<JsonValue> = terms
+                    .iter()
+                    .map(|term| {
+                        let subterms: Vec<JsonValue> = term
+                            .subterms
+                            .iter()
+                            .map(|subterm| serde_json::json!([subterm.name, links(subterm)]))
+                            .collect();
+                        serde_json::json!([term.name, [links(term), subterms, null]])
+                    })
+                    .collect();
+                serde_json::json!([letter, terms])
+            })
+            .collect();
+
         let genindex_context = serde_json::json!({
-            "genindexentries": [],
-            "genindexcounts": [],
+            "genindexentries": entries,
+            "genindexcounts": counts,
             "split_index": false,
         });
 
@@ -570,7 +942,7 @@ impl HTMLBuilder {
 
     /// Write domain indices
     async fn write_domain_indices(&self) -> Result<()> {
-        for domain_index in &self.domain_indices {
+        for domain_index in self.enabled_domain_indices() {
             info!("Writing domain index: {}", domain_index.name);
 
             let index_context = serde_json::json!({
@@ -608,6 +980,14 @@ impl HTMLBuilder {
         // Create pygments CSS
         self.create_pygments_style_file().await?;
 
+        if self.config.html_copy_code_button {
+            fs::write(
+                self.static_dir.join("copybutton.js"),
+                include_str!("../static/copybutton.js"),
+            )
+            .await?;
+        }
+
         // Copy translations if available
         if self.has_translations() {
             self.copy_translation_js().await?;
@@ -616,15 +996,39 @@ impl HTMLBuilder {
         Ok(())
     }
 
+    /// Copy `html_extra_path` into the output root and write the githubpages
+    /// files; run after every other output step, so these files win
+    pub async fn copy_extra_files(&self) -> Result<()> {
+        for extra_path in &self.config.html_extra_path {
+            let source = self.confdir.join(extra_path);
+            if source.exists() {
+                utils::copy_extra_path(&source, &self.outdir).await?;
+            } else {
+                warn!("html_extra_path entry not found: {}", source.display());
+            }
+        }
+
+        if self
+            .config
+            .extensions
+            .iter()
+            .any(|ext| ext == "sphinx.ext.githubpages")
+        {
+            crate::extensions::write_githubpages_files(&self.outdir, &self.config)?;
+        }
+
+        Ok(())
+    }
+
     /// Copy theme static files
     async fn copy_theme_static_files(&self) -> Result<()> {
         // TODO: Implement theme system
         Ok(())
     }
 
-    /// Create pygments style file
+    /// Create pygments style file from the configured highlight theme
     async fn create_pygments_style_file(&self) -> Result<()> {
-        let css_content = "/* Basic syntax highlighting */\n.highlight { background: #f8f8f8; }\n";
+        let css_content = highlight::theme_css(&self.config.output.highlight_theme)?;
         let css_path = self.static_dir.join("pygments.css");
         fs::write(css_path, css_content).await?;
         Ok(())
@@ -707,34 +1111,38 @@ impl HTMLBuilder {
     }
 
     /// Dump search index
-    pub async fn dump_search_index(
-        &self,
-        _search_index: &crate::search::SearchIndex,
-    ) -> Result<()> {
+    pub async fn dump_search_index(&self, search_index: &crate::search::SearchIndex) -> Result<()> {
         if !self.search {
             return Ok(());
         }
 
-        info!("Dumping search index");
+        write_search_index(
+            &self.outdir,
+            &self.searchindex_filename,
+            search_index,
+            self.config.html_search_shards,
+        )
+        .await
+    }
 
-        // TODO: Implement search index dumping
-        let search_index_path = self.outdir.join(&self.searchindex_filename);
-        let search_data = serde_json::json!({
-            "docnames": [],
-            "filenames": [],
-            "titles": [],
-            "terms": {},
-            "objects": {},
-            "objnames": {},
-            "objtypes": {},
-        });
+    /// Write sitemap.xml listing the canonical URL of every written page
+    pub async fn write_sitemap(&self) -> Result<()> {
+        if self.sitemap_urls.is_empty() {
+            return Ok(());
+        }
 
-        fs::write(
-            search_index_path,
-            serde_json::to_string_pretty(&search_data)?,
-        )
-        .await?;
+        let mut sitemap = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+        );
+        for url in &self.sitemap_urls {
+            sitemap.push_str(&format!(
+                "  <url><loc>{}</loc></url>\n",
+                html_escape::encode_text(url)
+            ));
+        }
+        sitemap.push_str("</urlset>\n");
 
+        fs::write(self.outdir.join("sitemap.xml"), sitemap).await?;
         Ok(())
     }
 
@@ -763,28 +1171,76 @@ impl HTMLBuilder {
     }
 
     /// Finish the build process
+    ///
+    /// The steps write disjoint files and only borrow the builder immutably,
+    /// so they run concurrently. `html_extra_path` files may replace any of
+    /// those files, so they are copied once all steps are done.
     pub async fn finish(
-        &mut self,
+        &self,
         env: &crate::environment::BuildEnvironment,
         search_index: &crate::search::SearchIndex,
     ) -> Result<()> {
         info!("Finishing HTML build");
 
-        // Generate indices
-        self.gen_indices().await?;
+        tokio::try_join!(
+            // Generate indices
+            self.gen_indices(),
+            // Copy static files and the images pages show
+            self.copy_static_files(),
+            self.copy_image_files(&env.images),
+            // Dump inventory and search index
+            self.dump_inventory(env),
+            self.dump_search_index(search_index),
+            // Write build info
+            self.write_build_info(),
+            // Write sitemap of canonical page URLs
+            self.write_sitemap(),
+        )?;
+        self.copy_extra_files().await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl DocumentWriter for HTMLBuilder {
+    fn name(&self) -> &str {
+        &self.name
+    }
 
-        // Copy static files
-        self.copy_static_files().await?;
+    async fn init(&mut self) -> Result<()> {
+        HTMLBuilder::init(self).await
+    }
 
-        // Dump inventory and search index
-        self.dump_inventory(env).await?;
-        self.dump_search_index(search_index).await?;
+    async fn write_doc(&mut self, docname: &str, doctree: &Document) -> Result<()> {
+        self.env.note_images(docname, doctree);
+        self.env.note_labels(docname, doctree);
+        self.env.note_index_entries(docname, doctree);
+        self.env.note_python_objects(docname, doctree);
+        HTMLBuilder::write_doc(self, docname, doctree).await?;
+        self.search_index.add_document_objects(docname, &self.env)
+    }
 
-        // Write build info
-        self.write_build_info().await?;
+    async fn finish(&mut self) -> Result<()> {
+        if let Some(index) = self.python_module_index(&self.env) {
+            self.add_domain_index(index);
+        }
+        HTMLBuilder::finish(self, &self.env, self.search_index.index()).await
+    }
+}
 
-        Ok(())
+/// Shorten a description to `max_chars`, cutting at a word boundary and adding an ellipsis
+fn truncate_description(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
     }
+
+    let cut: String = text.chars().take(max_chars).collect();
+    let cut = match cut.rfind(' ') {
+        Some(space) => &cut[..space],
+        None => &cut,
+    };
+    format!("{}...", cut.trim_end())
 }
 
 impl PartialEq for CSSFile {
@@ -798,3 +1254,1052 @@ impl PartialEq for JSFile {
         self.filename == other.filename
     }
 }
+
+/// Domain object directives, such as `py:function`, that get a local TOC entry
+fn is_object_directive(name: &str) -> bool {
+    match name.split_once(':') {
+        Some((domain, objtype)) => {
+            matches!(domain, "py" | "js" | "c" | "cpp" | "rst")
+                && !matches!(objtype, "module" | "currentmodule" | "namespace")
+        }
+        None => false,
+    }
+}
+
+/// Interleave a page's headings with its domain objects, each object one level
+/// below the section it appears in
+fn with_object_entries(headings: &[TocEntry], nodes: &[RstNode]) -> Vec<TocEntry> {
+    let mut entries = Vec::new();
+    let mut headings = headings.iter();
+    let mut level = 1;
+    let mut module: Option<String> = None;
+
+    for node in nodes {
+        match node {
+            RstNode::Title { .. } => {
+                if let Some(heading) = headings.next() {
+                    level = heading.level;
+                    entries.push(heading.clone());
+                }
+            }
+            RstNode::Directive {
+                name, args, line, ..
+            } => {
+                let signature = args.join(" ");
+                if matches!(name.as_str(), "py:module" | "py:currentmodule") {
+                    module = Some(signature.trim().to_string()).filter(|m| !m.is_empty());
+                    continue;
+                }
+                if !is_object_directive(name) || signature.trim().is_empty() {
+                    continue;
+                }
+
+                let (object, callable) = match signature.split_once('(') {
+                    Some((object, _)) => (object.trim(), true),
+                    None => (signature.trim(), false),
+                };
+                let anchor = match (&module, name.starts_with("py:")) {
+                    (Some(module), true) => format!("{}.{}", module, object),
+                    _ => object.to_string(),
+                };
+                let title = if callable {
+                    format!("{}()", object)
+                } else {
+                    object.to_string()
+                };
+                entries.push(TocEntry::new(title, level + 1, anchor, *line));
+            }
+            _ => {}
+        }
+    }
+    entries
+}
+
+/// Keep the TOC entries nested at most `depth` levels below the first
+fn truncate_toc(mut entries: Vec<TocEntry>, depth: usize) -> Vec<TocEntry> {
+    // Adornment levels may skip numbers, so count the nesting instead
+    let mut open_levels: Vec<usize> = Vec::new();
+    entries.retain(|entry| {
+        while open_levels
+            .last()
+            .is_some_and(|&level| level >= entry.level)
+        {
+            open_levels.pop();
+        }
+        let nesting = open_levels.len();
+        open_levels.push(entry.level);
+        nesting <= depth
+    });
+    entries
+}
+
+/// Render flat TOC entries as nested lists following their levels; the first
+/// entry, the page title, links to the top of the page
+fn render_local_toc(entries: &[TocEntry]) -> String {
+    render_toc_list(entries, true)
+}
+
+/// Render flat TOC entries as nested lists following their levels, the first
+/// linking to the top of the page when `title_first` is set
+fn render_toc_list(entries: &[TocEntry], title_first: bool) -> String {
+    let mut html = String::new();
+    let mut levels: Vec<usize> = Vec::new();
+
+    for (i, entry) in entries.iter().enumerate() {
+        match levels.last() {
+            Some(&last) if entry.level > last => {
+                html.push_str("<ul>\n");
+                levels.push(entry.level);
+            }
+            Some(_) => {
+                while levels.len() > 1 && levels[levels.len() - 2] >= entry.level {
+                    html.push_str("</li>\n</ul>\n");
+                    levels.pop();
+                }
+                html.push_str("</li>\n");
+                *levels.last_mut().unwrap() = entry.level;
+            }
+            None => {
+                html.push_str("<ul>\n");
+                levels.push(entry.level);
+            }
+        }
+        let href = if i == 0 && title_first {
+            "#".to_string()
+        } else {
+            format!("#{}", entry.anchor)
+        };
+        html.push_str(&format!(
+            "<li><a class=\"reference internal\" href=\"{}\">{}</a>",
+            html_escape::encode_double_quoted_attribute(&href),
+            html_escape::encode_text(&entry.title)
+        ));
+    }
+    for _ in &levels {
+        html.push_str("</li>\n</ul>\n");
+    }
+    html
+}
+
+/// Write the search index into `outdir`: as `filename` in Sphinx's
+/// `searchindex.js` format, or, with more than one shard, as
+/// `searchindex/manifest.json` plus `searchindex/shard-<n>.json` files
+/// (see `SearchIndex::to_shards`)
+pub async fn write_search_index(
+    outdir: &Path,
+    filename: &str,
+    search_index: &crate::search::SearchIndex,
+    shards: Option<usize>,
+) -> Result<()> {
+    let Some(shard_count) = shards.filter(|&n| n > 1) else {
+        info!("Dumping search index");
+        fs::write(outdir.join(filename), search_index.to_js()?).await?;
+        return Ok(());
+    };
+
+    info!("Dumping search index in {} shards", shard_count);
+
+    let index_dir = outdir.join(SHARDED_SEARCHINDEX_DIR);
+    utils::ensure_dir(&index_dir).await?;
+
+    let (manifest, shards) = search_index.to_shards(shard_count);
+    fs::write(
+        index_dir.join("manifest.json"),
+        serde_json::to_string(&manifest)?,
+    )
+    .await?;
+    for (i, shard) in shards.iter().enumerate() {
+        fs::write(
+            index_dir.join(format!("shard-{}.json", i)),
+            serde_json::to_string(shard)?,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::environment::BuildEnvironment;
+
+    #[tokio::test]
+    async fn test_hidden_toctree_drives_prev_next() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let srcdir = temp_dir.path().join("source");
+        let outdir = temp_dir.path().join("build");
+        std::fs::create_dir_all(&srcdir).unwrap();
+
+        let index_path = srcdir.join("index.rst");
+        let index = "Index\n#####\n\n.. toctree::\n   :hidden:\n\n   intro\n   usage\n";
+        std::fs::write(&index_path, index).unwrap();
+
+        let config = BuildConfig::default();
+        let parser = crate::parser::Parser::new(&config).unwrap();
+        let document = parser.parse(&index_path, index).unwrap();
+
+        let mut env = BuildEnvironment::new(config.clone());
+        env.note_toctrees("index", &document);
+
+        let mut builder = HTMLBuilder::new(config, srcdir, outdir.clone()).unwrap();
+        builder.load_relations(&env);
+
+        let intro = &builder.relations["intro"];
+        assert_eq!(intro.parent.as_deref(), Some("index"));
+        assert_eq!(intro.prev.as_deref(), Some("index"));
+        assert_eq!(intro.next.as_deref(), Some("usage"));
+
+        builder.write_doc("index", &document).await.unwrap();
+        let html = std::fs::read_to_string(outdir.join("index.html")).unwrap();
+        assert!(html.contains(r#"<link rel="next" title="intro" href="intro.html" />"#));
+    }
+
+    #[test]
+    fn test_glob_toctree_links_documents_in_sorted_order() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let index_path = temp_dir.path().join("index.rst");
+        let index = "Index\n#####\n\n.. toctree::\n   :glob:\n\n   *\n";
+        std::fs::write(&index_path, index).unwrap();
+
+        let config = BuildConfig::default();
+        let parser = crate::parser::Parser::new(&config).unwrap();
+        let document = parser.parse(&index_path, index).unwrap();
+
+        let mut env = BuildEnvironment::new(config.clone());
+        for docname in ["usage", "index", "intro", "api/reference"] {
+            env.add_document(docname.to_string(), 0.0);
+        }
+        env.note_toctrees("index", &document);
+        assert!(env.glob_toctrees.contains("index"));
+
+        let mut builder = HTMLBuilder::new(
+            config,
+            temp_dir.path().join("source"),
+            temp_dir.path().join("build"),
+        )
+        .unwrap();
+        builder.load_relations(&env);
+
+        let relation = |docname: &str| {
+            let relation = &builder.relations[docname];
+            (
+                relation.parent.as_deref(),
+                relation.prev.as_deref(),
+                relation.next.as_deref(),
+            )
+        };
+        assert_eq!(builder.relations.len(), 3);
+        assert_eq!(relation("index"), (None, None, Some("intro")));
+        assert_eq!(
+            relation("intro"),
+            (Some("index"), Some("index"), Some("usage"))
+        );
+        assert_eq!(relation("usage"), (Some("index"), Some("intro"), None));
+    }
+
+    #[tokio::test]
+    async fn test_pages_and_links_use_document_titles() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let srcdir = temp_dir.path().join("source");
+        let outdir = temp_dir.path().join("build");
+        std::fs::create_dir_all(&srcdir).unwrap();
+
+        let config = BuildConfig::default();
+        let parser = crate::parser::Parser::new(&config).unwrap();
+        let mut env = BuildEnvironment::new(config.clone());
+        let mut documents = Vec::new();
+        for (docname, content) in [
+            (
+                "index",
+                "Welcome Home\n############\n\n.. toctree::\n\n   intro\n",
+            ),
+            ("intro", "Getting Started\n###############\n\nHello.\n"),
+        ] {
+            let path = srcdir.join(format!("{}.rst", docname));
+            std::fs::write(&path, content).unwrap();
+            let document = parser.parse(&path, content).unwrap();
+            env.note_toctrees(docname, &document);
+            env.note_title(docname, &document);
+            documents.push((docname, document));
+        }
+
+        let mut builder = HTMLBuilder::new(config, srcdir, outdir.clone()).unwrap();
+        builder.init().await.unwrap();
+        builder.load_relations(&env);
+        for (docname, document) in &documents {
+            builder.write_doc(docname, document).await.unwrap();
+        }
+
+        let index = std::fs::read_to_string(outdir.join("index.html")).unwrap();
+        let intro = std::fs::read_to_string(outdir.join("intro.html")).unwrap();
+        assert!(index.contains("<title>Welcome Home &#8212; "));
+        assert!(intro.contains("<title>Getting Started &#8212; "));
+        assert!(index.contains(r#"<link rel="next" title="Getting Started" href="intro.html" />"#));
+        assert!(intro.contains(r#"<link rel="prev" title="Welcome Home" href="index.html" />"#));
+    }
+
+    #[tokio::test]
+    async fn test_head_extra_is_added_to_pages() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let srcdir = temp_dir.path().join("source");
+        let outdir = temp_dir.path().join("build");
+        std::fs::create_dir_all(&srcdir).unwrap();
+
+        let path = srcdir.join("index.rst");
+        let content = "Index\n#####\n\nHello.\n";
+        std::fs::write(&path, content).unwrap();
+
+        let config = BuildConfig {
+            html_head_extra: Some(
+                "<link rel=\"preconnect\" href=\"https://fonts.example.com\" />".to_string(),
+            ),
+            html_body_end_extra: Some("<script>track();</script>".to_string()),
+            ..BuildConfig::default()
+        };
+        let parser = crate::parser::Parser::new(&config).unwrap();
+        let document = parser.parse(&path, content).unwrap();
+
+        let mut builder = HTMLBuilder::new(config, srcdir, outdir.clone()).unwrap();
+        builder.init().await.unwrap();
+        builder.write_doc("index", &document).await.unwrap();
+        let html = std::fs::read_to_string(outdir.join("index.html")).unwrap();
+
+        let head_end = html.find("</head>").unwrap();
+        let extra = html
+            .find("<link rel=\"preconnect\" href=\"https://fonts.example.com\" />")
+            .unwrap();
+        assert!(extra < head_end);
+        assert!(html.contains("<script>track();</script>\n</body>"));
+    }
+
+    #[tokio::test]
+    async fn test_opengraph_meta_tags() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let srcdir = temp_dir.path().join("source");
+        let outdir = temp_dir.path().join("build");
+        std::fs::create_dir_all(srcdir.join("guide")).unwrap();
+
+        let path = srcdir.join("guide").join("intro.rst");
+        let content = "Getting Started\n###############\n\nLearn the \"basics\" here.\n";
+        std::fs::write(&path, content).unwrap();
+
+        let config = BuildConfig {
+            html_opengraph: Some(true),
+            html_baseurl: Some("https://docs.example.com/en/".to_string()),
+            html_logo: Some("_static/logo.png".to_string()),
+            ..BuildConfig::default()
+        };
+        let parser = crate::parser::Parser::new(&config).unwrap();
+        let document = parser.parse(&path, content).unwrap();
+
+        let mut builder = HTMLBuilder::new(config, srcdir, outdir.clone()).unwrap();
+        builder.write_doc("guide/intro", &document).await.unwrap();
+        let html = std::fs::read_to_string(outdir.join("guide/intro.html")).unwrap();
+
+        assert!(html.contains(r#"<meta property="og:title" content="Getting Started" />"#));
+        assert!(html.contains(
+            r#"<meta property="og:url" content="https://docs.example.com/en/guide/intro.html" />"#
+        ));
+        assert!(html.contains(
+            r#"<meta property="og:description" content="Learn the &quot;basics&quot; here." />"#
+        ));
+        assert!(html.contains(
+            r#"<meta property="og:image" content="https://docs.example.com/en/_static/logo.png" />"#
+        ));
+        assert!(html.contains(r#"<meta name="twitter:card" content="summary" />"#));
+    }
+
+    #[tokio::test]
+    async fn test_canonical_url_drops_version_segment() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let srcdir = temp_dir.path().join("source");
+        let outdir = temp_dir.path().join("build");
+        std::fs::create_dir_all(&srcdir).unwrap();
+
+        let path = srcdir.join("intro.rst");
+        let content = "Intro\n#####\n\nText.\n";
+        std::fs::write(&path, content).unwrap();
+
+        let config = BuildConfig {
+            html_baseurl: Some("https://docs.example.com/v1.2/".to_string()),
+            html_version_path: Some("v1.2".to_string()),
+            html_opengraph: Some(true),
+            html_logo: Some("logo.png".to_string()),
+            ..BuildConfig::default()
+        };
+        let parser = crate::parser::Parser::new(&config).unwrap();
+        let document = parser.parse(&path, content).unwrap();
+
+        let mut builder = HTMLBuilder::new(config, srcdir, outdir.clone()).unwrap();
+        builder.relations.insert(
+            "intro".to_string(),
+            DocumentRelation {
+                parent: None,
+                prev: None,
+                next: Some("usage".to_string()),
+            },
+        );
+        builder.write_doc("intro", &document).await.unwrap();
+        builder.write_sitemap().await.unwrap();
+
+        let html = std::fs::read_to_string(outdir.join("intro.html")).unwrap();
+        assert!(
+            html.contains(r#"<link rel="canonical" href="https://docs.example.com/intro.html" />"#)
+        );
+        assert!(html.contains(
+            r#"<meta property="og:url" content="https://docs.example.com/intro.html" />"#
+        ));
+        // Links within the versioned site keep pointing at this version
+        assert!(html.contains(r#"<link rel="next" title="usage" href="usage.html" />"#));
+        assert!(html.contains(
+            r#"<meta property="og:image" content="https://docs.example.com/v1.2/_static/logo.png" />"#
+        ));
+
+        let sitemap = std::fs::read_to_string(outdir.join("sitemap.xml")).unwrap();
+        assert!(sitemap.contains("<url><loc>https://docs.example.com/intro.html</loc></url>"));
+    }
+
+    #[tokio::test]
+    async fn test_finish_writes_all_outputs() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let srcdir = temp_dir.path().join("source");
+        let outdir = temp_dir.path().join("build");
+        std::fs::create_dir_all(srcdir.join("_static")).unwrap();
+        std::fs::write(srcdir.join("_static").join("custom.css"), "body {}").unwrap();
+        std::fs::create_dir_all(srcdir.join("_extra")).unwrap();
+        std::fs::write(srcdir.join("_extra").join(".nojekyll"), "").unwrap();
+        std::fs::write(srcdir.join("_extra").join("CNAME"), "docs.example.com").unwrap();
+        std::fs::write(srcdir.join("robots.txt"), "User-agent: *").unwrap();
+        // Extra files replace generated ones
+        std::fs::write(srcdir.join("_extra").join("sitemap.xml"), "<urlset/>").unwrap();
+        std::fs::write(srcdir.join("_extra").join("genindex.html"), "custom").unwrap();
+
+        let config = BuildConfig {
+            html_baseurl: Some("https://docs.example.com/".to_string()),
+            html_extra_path: vec![PathBuf::from("_extra"), PathBuf::from("robots.txt")],
+            ..BuildConfig::default()
+        };
+        let env = BuildEnvironment::new(config.clone());
+        let search_index = crate::search::SearchIndex::new("en".to_string());
+
+        let mut builder = HTMLBuilder::new(config, srcdir, outdir.clone()).unwrap();
+        builder.init().await.unwrap();
+        builder
+            .sitemap_urls
+            .push("https://docs.example.com/index.html".to_string());
+        builder.finish(&env, &search_index).await.unwrap();
+
+        for file in [
+            "genindex.html",
+            "_static/custom.css",
+            ".nojekyll",
+            "CNAME",
+            "robots.txt",
+            "_static/pygments.css",
+            INVENTORY_FILENAME,
+            "searchindex.js",
+            ".buildinfo",
+            "sitemap.xml",
+        ] {
+            assert!(outdir.join(file).exists(), "missing {}", file);
+        }
+        assert_eq!(
+            std::fs::read_to_string(outdir.join("sitemap.xml")).unwrap(),
+            "<urlset/>"
+        );
+        assert_eq!(
+            std::fs::read_to_string(outdir.join("genindex.html")).unwrap(),
+            "custom"
+        );
+    }
+
+    async fn build_with_index_config(config: BuildConfig) -> (tempfile::TempDir, String) {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let srcdir = temp_dir.path().join("source");
+        let outdir = temp_dir.path().join("build");
+        std::fs::create_dir_all(&srcdir).unwrap();
+
+        let path = srcdir.join("index.rst");
+        let content = "Index\n#####\n\nText.\n";
+        std::fs::write(&path, content).unwrap();
+        let document = crate::parser::Parser::new(&config)
+            .unwrap()
+            .parse(&path, content)
+            .unwrap();
+
+        let mut builder = HTMLBuilder::new(config, srcdir, outdir.clone()).unwrap();
+        builder.init().await.unwrap();
+        builder.domain_indices.push(DomainIndex {
+            name: "py-modindex".to_string(),
+            localname: "Python Module Index".to_string(),
+            shortname: Some("modules".to_string()),
+            content: Vec::new(),
+            collapse: false,
+        });
+        builder.write_doc("index", &document).await.unwrap();
+        builder.gen_indices().await.unwrap();
+
+        let html = std::fs::read_to_string(outdir.join("index.html")).unwrap();
+        (temp_dir, html)
+    }
+
+    #[tokio::test]
+    async fn test_indices_are_generated_and_linked_by_default() {
+        let (temp_dir, html) = build_with_index_config(BuildConfig::default()).await;
+        let outdir = temp_dir.path().join("build");
+
+        assert!(outdir.join("genindex.html").exists());
+        assert!(outdir.join("py-modindex.html").exists());
+        assert!(html.contains(r#"<link rel="index" title="Index" href="genindex.html" />"#));
+        assert!(
+            html.contains(r#"href="genindex.html" title="General Index" accesskey="I">index</a>"#)
+        );
+        assert!(html.contains(r#"href="py-modindex.html" title="Python Module Index">modules</a>"#));
+    }
+
+    #[tokio::test]
+    async fn test_disabled_indices_are_not_generated_or_linked() {
+        let config = BuildConfig {
+            html_use_index: Some(false),
+            html_domain_indices: Some(crate::config::DomainIndices::All(false)),
+            ..BuildConfig::default()
+        };
+        let (temp_dir, html) = build_with_index_config(config).await;
+        let outdir = temp_dir.path().join("build");
+
+        assert!(!outdir.join("genindex.html").exists());
+        assert!(!outdir.join("py-modindex.html").exists());
+        assert!(!html.contains("genindex"));
+        assert!(!html.contains("py-modindex"));
+    }
+
+    #[test]
+    fn test_truncate_description_at_word_boundary() {
+        assert_eq!(truncate_description("short text", 20), "short text");
+        assert_eq!(
+            truncate_description("a sentence that is too long", 12),
+            "a sentence..."
+        );
+    }
+
+    #[tokio::test]
+    async fn test_localtoc_depth_and_object_entries() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let srcdir = temp_dir.path().join("source");
+        std::fs::create_dir_all(&srcdir).unwrap();
+
+        let path = srcdir.join("api.rst");
+        let content = "API\n###\n\nUsage\n=====\n\nBasics\n------\n\nDetails\n~~~~~~~\n\n\
+                       Reference\n=========\n\n.. py:module:: demo\n\n.. py:function:: greet(name)\n\n   Say hi.\n";
+        std::fs::write(&path, content).unwrap();
+
+        let render = |depth: usize, objects: bool| {
+            let config = BuildConfig {
+                localtoc_depth: Some(depth),
+                toc_object_entries: Some(objects),
+                ..BuildConfig::default()
+            };
+            let parser = crate::parser::Parser::new(&config).unwrap();
+            let document = parser.parse(&path, content).unwrap();
+            let builder =
+                HTMLBuilder::new(config, srcdir.clone(), temp_dir.path().join("build")).unwrap();
+            render_local_toc(&builder.local_toc_entries(&document))
+        };
+
+        let shallow = render(2, false);
+        assert!(shallow.contains("href=\"#basics\">Basics</a>"));
+        assert!(!shallow.contains("Details"));
+        assert!(!shallow.contains("greet"));
+
+        let deep = render(3, true);
+        assert_eq!(
+            deep,
+            "<ul>\n<li><a class=\"reference internal\" href=\"#\">API</a><ul>\n\
+             <li><a class=\"reference internal\" href=\"#usage\">Usage</a><ul>\n\
+             <li><a class=\"reference internal\" href=\"#basics\">Basics</a><ul>\n\
+             <li><a class=\"reference internal\" href=\"#details\">Details</a></li>\n</ul>\n\
+             </li>\n</ul>\n</li>\n\
+             <li><a class=\"reference internal\" href=\"#reference\">Reference</a><ul>\n\
+             <li><a class=\"reference internal\" href=\"#demo.greet\">greet()</a></li>\n</ul>\n\
+             </li>\n</ul>\n</li>\n</ul>\n"
+        );
+    }
+
+    #[test]
+    fn test_generate_local_toc_nests_sections_below_the_title() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let builder = HTMLBuilder::new(
+            BuildConfig::default(),
+            temp_dir.path().join("source"),
+            temp_dir.path().join("build"),
+        )
+        .unwrap();
+        let entry = |title: &str, level: usize, anchor: &str| {
+            TocEntry::new(title.to_string(), level, anchor.to_string(), 1)
+        };
+        let mut document = Document::new(temp_dir.path().join("source/guide.rst"), PathBuf::new());
+        document.toc = vec![
+            entry("Guide", 1, "guide"),
+            entry("Install", 2, "install"),
+            entry("Linux", 3, "linux"),
+            entry("Debian", 4, "debian"),
+            entry("Usage", 2, "usage"),
+        ];
+
+        assert_eq!(
+            builder.generate_local_toc(&document, None),
+            "<div class=\"toc\">\n<ul>\n\
+             <li><a class=\"reference internal\" href=\"#install\">Install</a><ul>\n\
+             <li><a class=\"reference internal\" href=\"#linux\">Linux</a><ul>\n\
+             <li><a class=\"reference internal\" href=\"#debian\">Debian</a></li>\n</ul>\n\
+             </li>\n</ul>\n</li>\n\
+             <li><a class=\"reference internal\" href=\"#usage\">Usage</a></li>\n</ul>\n</div>"
+        );
+
+        let shallow = builder.generate_local_toc(&document, Some(1));
+        assert!(!shallow.contains("href=\"#guide\""));
+        assert!(shallow.contains("href=\"#install\""));
+        assert!(shallow.contains("href=\"#usage\""));
+        assert!(!shallow.contains("Linux"));
+        assert!(builder
+            .generate_local_toc(&document, Some(2))
+            .contains("href=\"#linux\""));
+
+        document.toc.truncate(1);
+        assert_eq!(
+            builder.generate_local_toc(&document, None),
+            "<div class=\"toc\"></div>"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_assets_sorted_by_priority_then_insertion() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let srcdir = temp_dir.path().join("source");
+        std::fs::create_dir_all(srcdir.join("locale/de/LC_MESSAGES")).unwrap();
+        std::fs::write(srcdir.join("locale/de/LC_MESSAGES/sphinx.js"), "").unwrap();
+
+        let config = BuildConfig {
+            html_css_files: vec!["zeta.css".to_string(), "alpha.css".to_string()],
+            html_js_files: vec!["custom.js".to_string()],
+            language: Some("de".to_string()),
+            ..BuildConfig::default()
+        };
+
+        for _ in 0..3 {
+            let mut builder = HTMLBuilder::new(
+                config.clone(),
+                srcdir.clone(),
+                temp_dir.path().join("build"),
+            )
+            .unwrap();
+            builder.init().await.unwrap();
+
+            let css: Vec<&str> = builder
+                .css_files
+                .iter()
+                .map(|f| f.filename.as_str())
+                .collect();
+            assert_eq!(
+                css,
+                [
+                    "_static/pygments.css",
+                    "_static/sphinx_rtd_theme.css",
+                    "_static/zeta.css",
+                    "_static/alpha.css"
+                ]
+            );
+            // translations.js is registered last but has a lower priority
+            let js: Vec<&str> = builder
+                .js_files
+                .iter()
+                .map(|f| f.filename.as_str())
+                .collect();
+            assert_eq!(
+                js,
+                [
+                    "_static/documentation_options.js",
+                    "_static/doctools.js",
+                    "_static/sphinx_highlight.js",
+                    "_static/translations.js",
+                    "_static/custom.js",
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn test_flat_layout_paths_and_uris() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let outdir = temp_dir.path().join("build");
+        let mut builder = HTMLBuilder::new(
+            BuildConfig::default(),
+            temp_dir.path().into(),
+            outdir.clone(),
+        )
+        .unwrap();
+        builder.set_layout(PageLayout::Flat).unwrap();
+
+        assert_eq!(builder.get_output_path("index"), outdir.join("index.html"));
+        assert_eq!(
+            builder.get_output_path("foo/bar"),
+            outdir.join("foo/bar.html")
+        );
+        assert_eq!(builder.get_target_uri("index"), "index.html");
+        assert_eq!(builder.get_target_uri("foo/bar"), "foo/bar.html");
+        assert_eq!(
+            builder.get_relative_uri("foo/bar", "foo/index"),
+            "index.html"
+        );
+    }
+
+    #[test]
+    fn test_directory_layout_paths_and_uris() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let outdir = temp_dir.path().join("build");
+        let mut builder = HTMLBuilder::new(
+            BuildConfig::default(),
+            temp_dir.path().into(),
+            outdir.clone(),
+        )
+        .unwrap();
+        builder.set_layout(PageLayout::Directory).unwrap();
+
+        assert_eq!(builder.name, "dirhtml");
+        assert_eq!(builder.out_suffix, ".html");
+        assert_eq!(builder.link_suffix, "/");
+        assert_eq!(builder.get_output_path("index"), outdir.join("index.html"));
+        assert_eq!(
+            builder.get_output_path("foo/bar"),
+            outdir.join("foo/bar/index.html")
+        );
+        assert_eq!(
+            builder.get_output_path("foo/index"),
+            outdir.join("foo/index.html")
+        );
+        assert_eq!(builder.get_target_uri("index"), "");
+        assert_eq!(builder.get_target_uri("foo/bar"), "foo/bar/");
+        assert_eq!(builder.get_target_uri("foo/index"), "foo/");
+        assert_eq!(builder.get_relative_uri("foo/bar", "foo/index"), "../");
+        assert_eq!(builder.get_relative_uri("foo/index", "foo/bar"), "bar/");
+
+        assert!(builder.set_layout(PageLayout::Single).is_err());
+        assert_eq!(builder.layout, PageLayout::Directory);
+    }
+
+    #[tokio::test]
+    async fn test_written_document_images_are_copied_on_finish() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let srcdir = temp_dir.path().join("source");
+        let outdir = temp_dir.path().join("build");
+        std::fs::create_dir_all(srcdir.join("guide")).unwrap();
+        std::fs::write(srcdir.join("guide/diagram.svg"), "<svg/>").unwrap();
+        let path = srcdir.join("guide/intro.rst");
+        let content =
+            "Intro\n#####\n\n.. image:: diagram.svg\n\n.. image:: https://example.com/x.png\n";
+        std::fs::write(&path, content).unwrap();
+
+        let config = BuildConfig::default();
+        let document = crate::parser::Parser::new(&config)
+            .unwrap()
+            .parse(&path, content)
+            .unwrap();
+        let mut builder = HTMLBuilder::new(config, srcdir, outdir.clone()).unwrap();
+        DocumentWriter::init(&mut builder).await.unwrap();
+        DocumentWriter::write_doc(&mut builder, "guide/intro", &document)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            builder.env.images,
+            HashMap::from([("guide/diagram.svg".to_string(), "diagram.svg".to_string())])
+        );
+        assert_eq!(
+            builder.env.image_uri("guide/intro", "diagram.svg"),
+            "../_images/diagram.svg"
+        );
+        DocumentWriter::finish(&mut builder).await.unwrap();
+        assert!(outdir.join("_images/diagram.svg").exists());
+    }
+
+    #[tokio::test]
+    async fn test_search_index_holds_rendered_text() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let srcdir = temp_dir.path().join("source");
+        let outdir = temp_dir.path().join("build");
+        std::fs::create_dir_all(&srcdir).unwrap();
+        let path = srcdir.join("index.rst");
+        let content = "Index\n#####\n\n.. _zoo:\n\nZebras bite, see :ref:`zoo`.\n\n\
+                       .. code-block:: python\n\n   print(42)\n";
+        std::fs::write(&path, content).unwrap();
+
+        let config = BuildConfig::default();
+        let document = crate::parser::Parser::new(&config)
+            .unwrap()
+            .parse(&path, content)
+            .unwrap();
+        let mut builder = HTMLBuilder::new(config, srcdir, outdir).unwrap();
+        DocumentWriter::init(&mut builder).await.unwrap();
+        DocumentWriter::write_doc(&mut builder, "index", &document)
+            .await
+            .unwrap();
+
+        let index = builder.search_index.index();
+        assert!(index.terms.contains_key("zebra"));
+        assert!(index.terms.contains_key("print"));
+        for markup in ["ref", "code-block", "_zoo"] {
+            assert!(!index.terms.contains_key(markup), "{}", markup);
+        }
+        assert!(!index.contents[0].contains(":ref:"));
+    }
+
+    /// Every file below `root` with its contents, by relative path
+    fn read_tree(root: &std::path::Path) -> std::collections::BTreeMap<PathBuf, Vec<u8>> {
+        walkdir::WalkDir::new(root)
+            .into_iter()
+            .map(Result::unwrap)
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| {
+                let path = entry.path().strip_prefix(root).unwrap().to_path_buf();
+                (path, std::fs::read(entry.path()).unwrap())
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_finish_matches_sequential_output() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let srcdir = temp_dir.path().join("source");
+        std::fs::create_dir_all(srcdir.join("_static")).unwrap();
+        std::fs::write(srcdir.join("_static/custom.css"), "body {}").unwrap();
+        std::fs::create_dir_all(srcdir.join("_extra")).unwrap();
+        std::fs::write(srcdir.join("_extra/robots.txt"), "User-agent: *").unwrap();
+
+        let config = BuildConfig {
+            html_baseurl: Some("https://docs.example.com/".to_string()),
+            html_extra_path: vec![PathBuf::from("_extra")],
+            ..BuildConfig::default()
+        };
+        let parser = crate::parser::Parser::new(&config).unwrap();
+        let documents: Vec<(String, Document)> = (0..20)
+            .map(|i| {
+                let docname = format!("page{}", i);
+                let content = format!(
+                    "Page {i}\n#######\n\n.. index:: topic{i}\n\n.. py:module:: mod{i}\n\n\
+                     .. py:function:: run{i}(x)\n\n   Runs step {i}.\n"
+                );
+                let path = srcdir.join(format!("{}.rst", docname));
+                std::fs::write(&path, &content).unwrap();
+                let document = parser.parse(&path, &content).unwrap();
+                (docname, document)
+            })
+            .collect();
+
+        let mut outputs = Vec::new();
+        for concurrent in [true, false] {
+            let outdir = temp_dir.path().join(format!("build-{}", concurrent));
+            let mut builder =
+                HTMLBuilder::new(config.clone(), srcdir.clone(), outdir.clone()).unwrap();
+            DocumentWriter::init(&mut builder).await.unwrap();
+            for (docname, document) in &documents {
+                DocumentWriter::write_doc(&mut builder, docname, document)
+                    .await
+                    .unwrap();
+            }
+            let (env, search_index) = (&builder.env, builder.search_index.index());
+            if concurrent {
+                builder.finish(env, search_index).await.unwrap();
+            } else {
+                // The steps of finish, one after the other
+                builder.gen_indices().await.unwrap();
+                builder.copy_static_files().await.unwrap();
+                builder.copy_image_files(&env.images).await.unwrap();
+                builder.dump_inventory(env).await.unwrap();
+                builder.dump_search_index(search_index).await.unwrap();
+                builder.write_build_info().await.unwrap();
+                builder.write_sitemap().await.unwrap();
+                builder.copy_extra_files().await.unwrap();
+            }
+            outputs.push(read_tree(&outdir));
+        }
+
+        assert!(outputs[0].contains_key(&PathBuf::from("robots.txt")));
+        assert_eq!(
+            outputs[0].keys().collect::<Vec<_>>(),
+            outputs[1].keys().collect::<Vec<_>>()
+        );
+        for (path, contents) in &outputs[0] {
+            assert!(outputs[1][path] == *contents, "{} differs", path.display());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_genindex_groups_index_entries_by_letter() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let srcdir = temp_dir.path().join("source");
+        let outdir = temp_dir.path().join("build");
+        std::fs::create_dir_all(&srcdir).unwrap();
+
+        let config = BuildConfig::default();
+        let parser = crate::parser::Parser::new(&config).unwrap();
+        let renderer = crate::renderer::HtmlRenderer::new(&config);
+        let mut builder = HTMLBuilder::new(config.clone(), srcdir.clone(), outdir.clone()).unwrap();
+        builder.init().await.unwrap();
+        for (docname, content) in [
+            (
+                "intro",
+                "Intro\n#####\n\n.. index::\n   single: parser; options\n   pair: build; cache\n\n\
+                 Crossing :index:`Zebra` and :index:`the heap <!heap>`.\n",
+            ),
+            (
+                "api",
+                "API\n###\n\n.. index:: apple, Banana\n\n.. index:: @decorator\n\n\
+                 .. index:: seealso: cache; heap\n",
+            ),
+        ] {
+            let path = srcdir.join(format!("{}.rst", docname));
+            std::fs::write(&path, content).unwrap();
+            let mut document = parser.parse(&path, content).unwrap();
+            document.html = renderer.render(&document).html;
+            DocumentWriter::write_doc(&mut builder, docname, &document)
+                .await
+                .unwrap();
+        }
+        builder.gen_indices().await.unwrap();
+
+        let intro = std::fs::read_to_string(outdir.join("intro.html")).unwrap();
+        assert!(intro.contains(r#"<span id="index-4"></span>"#), "{}", intro);
+        assert!(intro.contains(r#"<span class="target" id="index-8-zebra"></span>Zebra"#));
+        assert!(intro.contains(r#"<span class="target" id="index-8-heap"></span>the heap"#));
+
+        let genindex = std::fs::read_to_string(outdir.join("genindex.html")).unwrap();
+        let headings: Vec<&str> = genindex
+            .match_indices("<h2 id=\"")
+            .map(|(start, _)| {
+                let rest = &genindex[start + 8..];
+                &rest[..rest.find('"').unwrap()]
+            })
+            .collect();
+        assert_eq!(headings, ["Symbols", "A", "B", "C", "H", "P", "Z"]);
+        for expected in [
+            r#"<a href="api.html#index-6">@decorator</a>"#,
+            r#"<a href="api.html#index-4">apple</a>"#,
+            r#"<a href="api.html#index-4">Banana</a>"#,
+            r#"<a href="intro.html#index-8-heap"><strong>heap</strong></a>"#,
+            r#"<a href="intro.html#index-8-zebra">Zebra</a>"#,
+            r#"<a href="intro.html#index-4">options</a>"#,
+            r#"<a href="intro.html#index-4">build</a>"#,
+            r#"<a href="intro.html#index-4">cache</a>"#,
+        ] {
+            assert!(
+                genindex.contains(expected),
+                "missing {}:\n{}",
+                expected,
+                genindex
+            );
+        }
+        // Pair entries index both terms; `seealso` adds an unlinked subterm
+        let cache = &genindex[genindex.find("<h2 id=\"C\">").unwrap()..];
+        let cache = &cache[..cache[1..].find("<h2").unwrap()];
+        assert!(cache.contains("see also heap"), "{}", cache);
+        assert!(!cache.contains("<a href=\"api.html"));
+    }
+
+    #[tokio::test]
+    async fn test_python_module_index_nests_submodules() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let srcdir = temp_dir.path().join("source");
+        let outdir = temp_dir.path().join("build");
+        std::fs::create_dir_all(srcdir.join("api")).unwrap();
+
+        let config = BuildConfig::default();
+        let parser = crate::parser::Parser::new(&config).unwrap();
+        let renderer = crate::renderer::HtmlRenderer::new(&config);
+        let mut documents = Vec::new();
+        for (docname, content) in [
+            (
+                "api/core",
+                "Core\n####\n\n.. py:module:: acme\n   :synopsis: The core\n\n\
+                 .. py:module:: acme.io\n\n.. py:module:: acme.legacy\n   :deprecated:\n",
+            ),
+            (
+                "tools",
+                "Tools\n#####\n\n.. module:: build_tools\n\n\
+                 .. py:module:: zlibx.stream\n   :synopsis: Streams\n\n\
+                 .. py:module:: hidden\n   :noindex:\n\nSee :ref:`py-modindex`.\n",
+            ),
+        ] {
+            let path = srcdir.join(format!("{}.rst", docname));
+            std::fs::write(&path, content).unwrap();
+            documents.push((docname.to_string(), parser.parse(&path, content).unwrap()));
+        }
+        let mut env = BuildEnvironment::new(config.clone());
+        env.note_documents(
+            documents
+                .iter()
+                .map(|(docname, doc)| (docname.clone(), doc)),
+        );
+
+        let mut builder = HTMLBuilder::new(config, srcdir, outdir.clone()).unwrap();
+        builder.init().await.unwrap();
+        builder.load_relations(&env);
+        for (docname, document) in &mut documents {
+            document.html = renderer.render_in_env(document, docname, &env).html;
+            DocumentWriter::write_doc(&mut builder, docname, document)
+                .await
+                .unwrap();
+        }
+        DocumentWriter::finish(&mut builder).await.unwrap();
+
+        let index = &builder.domain_indices[0];
+        assert_eq!(index.name, "py-modindex");
+        assert!(!index.collapse);
+        // Each entry as "letter: name -> uri (extra) [subentries]"
+        let summary: Vec<String> = index
+            .content
+            .iter()
+            .flat_map(|(letter, entries)| {
+                entries.iter().map(move |entry| {
+                    let subentries: Vec<&str> = entry
+                        .subentries
+                        .iter()
+                        .map(|sub| sub.name.as_str())
+                        .collect();
+                    format!(
+                        "{}: {} -> {} ({}) [{}]",
+                        letter,
+                        entry.name,
+                        entry.uri,
+                        entry.extra.as_deref().unwrap_or_default(),
+                        subentries.join(", ")
+                    )
+                })
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                "a: acme -> api/core.html#module-acme (The core) [acme.io, acme.legacy]",
+                "b: build_tools -> tools.html#module-build_tools () []",
+                "z: zlibx ->  () [zlibx.stream]",
+            ]
+        );
+        assert_eq!(
+            index.content[0].1[0].subentries[1].extra.as_deref(),
+            Some("Deprecated")
+        );
+
+        let page = std::fs::read_to_string(outdir.join("py-modindex.html")).unwrap();
+        assert!(page
+            .contains(r#"<a href="api/core.html#module-acme"><code class="xref">acme</code></a>"#));
+        assert!(page.contains(r#"<code class="xref">zlibx</code>"#));
+        assert!(!page.contains("hidden"));
+
+        let tools = std::fs::read_to_string(outdir.join("tools.html")).unwrap();
+        assert!(tools.contains(r#"<span id="module-zlibx.stream"></span>"#));
+        assert!(tools.contains(
+            r#"<a class="reference internal" href="py-modindex.html">Python Module Index</a>"#
+        ));
+    }
+}
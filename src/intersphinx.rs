@@ -0,0 +1,335 @@
+//! Loading other projects' inventories, as `sphinx.ext.intersphinx` does
+//!
+//! Remote inventories are fetched over HTTP(S), honoring `tls_verify`,
+//! `tls_cacerts` and `user_agent`, and cached under the build cache so that
+//! rebuilds within `intersphinx_cache_limit` days stay offline. A project
+//! whose inventory can't be fetched is reported and left out; it never fails
+//! the build.
+
+use anyhow::{bail, Context, Result};
+use log::{debug, info};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::fs;
+use tokio::task::JoinSet;
+
+use crate::config::{BuildConfig, IntersphinxTarget, TlsCacerts};
+use crate::inventory::{Inventory, InventoryFile};
+
+/// Subdirectory of the build cache fetched inventories are kept in
+const CACHE_SUBDIR: &str = "intersphinx";
+
+/// Fetches inventories and keeps them in the build cache
+#[derive(Debug, Clone)]
+pub struct InventoryFetcher {
+    /// Directory local inventory paths are relative to
+    source_dir: PathBuf,
+    cache_dir: PathBuf,
+    cache_limit: i64,
+    timeout: Option<f64>,
+    tls_verify: bool,
+    tls_cacerts: Option<TlsCacerts>,
+    user_agent: String,
+}
+
+impl InventoryFetcher {
+    pub fn new(config: &BuildConfig, source_dir: PathBuf, cache_dir: &Path) -> Self {
+        Self {
+            source_dir,
+            cache_dir: cache_dir.join(CACHE_SUBDIR),
+            cache_limit: config.intersphinx_cache_limit,
+            timeout: config.intersphinx_timeout,
+            tls_verify: config.tls_verify,
+            tls_cacerts: config.tls_cacerts.clone(),
+            user_agent: config
+                .user_agent
+                .clone()
+                .unwrap_or_else(|| format!("sphinx-ultra/{}", env!("CARGO_PKG_VERSION"))),
+        }
+    }
+
+    /// Where the inventory fetched from `url` is cached
+    pub fn cache_path(&self, url: &str) -> PathBuf {
+        let hash = blake3::hash(url.as_bytes());
+        self.cache_dir.join(format!("{}.inv", &hash.to_hex()[..32]))
+    }
+
+    /// Load the inventory of `target`, whose entries link below its URI
+    pub async fn load(&self, target: &IntersphinxTarget) -> Result<Inventory> {
+        let location = match &target.inventory {
+            Some(location) => location.clone(),
+            None => format!("{}/objects.inv", target.uri.trim_end_matches('/')),
+        };
+        if !is_remote(&location)? {
+            return InventoryFile::load(self.source_dir.join(&location), &target.uri).await;
+        }
+
+        let content = self.fetch(&location).await?;
+        InventoryFile::loads(&content, &target.uri)
+            .with_context(|| format!("{} is not a valid inventory", location))
+    }
+
+    /// The inventory at `url`, from the cache while it is fresh
+    ///
+    /// A cached copy past its limit is still used when fetching fails.
+    pub async fn fetch(&self, url: &str) -> Result<Vec<u8>> {
+        let cache_path = self.cache_path(url);
+        let cached_age = fs::metadata(&cache_path)
+            .await
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .map(|modified| modified.elapsed().unwrap_or_default());
+        if let Some(age) = cached_age {
+            if self.is_fresh(age) {
+                debug!("Using cached inventory of {}", url);
+                return Ok(fs::read(&cache_path).await?);
+            }
+        }
+
+        match self.download(url).await {
+            Ok(content) => {
+                fs::create_dir_all(&self.cache_dir).await?;
+                fs::write(&cache_path, &content).await?;
+                Ok(content)
+            }
+            Err(error) if cached_age.is_some() => {
+                info!("Using stale cached inventory of {}: {:#}", url, error);
+                Ok(fs::read(&cache_path).await?)
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    fn is_fresh(&self, age: Duration) -> bool {
+        self.cache_limit < 0 || age.as_secs() < self.cache_limit as u64 * 24 * 60 * 60
+    }
+
+    async fn download(&self, url: &str) -> Result<Vec<u8>> {
+        info!("Fetching inventory {}", url);
+        let mut client = reqwest::Client::builder()
+            .user_agent(&self.user_agent)
+            .danger_accept_invalid_certs(!self.tls_verify);
+        if let Some(cacerts) = self.tls_cacerts.as_ref().and_then(|c| c.for_url(url)) {
+            // Like `requests`, a CA bundle replaces the built-in roots
+            let pem = fs::read(cacerts)
+                .await
+                .with_context(|| format!("Failed to read CA certificates {}", cacerts))?;
+            client = client.tls_built_in_root_certs(false);
+            for certificate in reqwest::Certificate::from_pem_bundle(&pem)
+                .with_context(|| format!("Invalid CA certificates in {}", cacerts))?
+            {
+                client = client.add_root_certificate(certificate);
+            }
+        }
+        if let Some(timeout) = self.timeout {
+            client = client.timeout(Duration::from_secs_f64(timeout));
+        }
+
+        let response = client.build()?.get(url).send().await?.error_for_status()?;
+        Ok(response.bytes().await?.to_vec())
+    }
+}
+
+/// Whether `location` is fetched over the network rather than read from disk
+///
+/// Only `http` and `https` are fetched; any other scheme is an error.
+fn is_remote(location: &str) -> Result<bool> {
+    match location.split_once("://") {
+        None => Ok(false),
+        Some((scheme, _)) if scheme.eq_ignore_ascii_case("http") => Ok(true),
+        Some((scheme, _)) if scheme.eq_ignore_ascii_case("https") => Ok(true),
+        Some((scheme, _)) => bail!("unsupported URL scheme '{}' in {}", scheme, location),
+    }
+}
+
+/// Load every inventory of `intersphinx_mapping` concurrently
+///
+/// Returns the inventories by project name, and a message for each project
+/// whose inventory couldn't be loaded.
+pub async fn load_inventories(
+    config: &BuildConfig,
+    source_dir: PathBuf,
+    cache_dir: &Path,
+) -> (HashMap<String, Inventory>, Vec<String>) {
+    let fetcher = InventoryFetcher::new(config, source_dir, cache_dir);
+    let mut tasks = JoinSet::new();
+    for (name, target) in &config.intersphinx_mapping {
+        let (fetcher, name, target) = (fetcher.clone(), name.clone(), target.clone());
+        tasks.spawn(async move {
+            let inventory = fetcher.load(&target).await;
+            (name, target, inventory)
+        });
+    }
+
+    let mut inventories = HashMap::new();
+    let mut failures = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        match joined {
+            Ok((name, _, Ok(inventory))) => {
+                inventories.insert(name, inventory);
+            }
+            Ok((name, target, Err(error))) => failures.push(format!(
+                "intersphinx inventory '{}' ({}) not fetchable: {:#}",
+                name, target.uri, error
+            )),
+            Err(error) => failures.push(format!("intersphinx task failed: {}", error)),
+        }
+    }
+    failures.sort();
+
+    (inventories, failures)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Serve `body` over HTTP to every connection, counting requests
+    async fn serve(body: Vec<u8>) -> (String, std::sync::Arc<std::sync::atomic::AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let requests = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                let mut request = [0; 1024];
+                let _ = stream.read(&mut request).await;
+                let head = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    body.len()
+                );
+                let _ = stream.write_all(head.as_bytes()).await;
+                let _ = stream.write_all(&body).await;
+            }
+        });
+        (format!("http://{}", address), requests)
+    }
+
+    #[tokio::test]
+    async fn test_remote_inventories_are_fetched_and_cached() {
+        let inventory = b"# Sphinx inventory version 1\n\
+            # Project: Other\n\
+            # Version: 2.0\n\
+            other.module mod other.html\n"
+            .to_vec();
+        let (base, requests) = serve(inventory).await;
+        let temp_dir = tempfile::tempdir().unwrap();
+
+        let mut config = BuildConfig::default();
+        config.intersphinx_mapping.insert(
+            "other".to_string(),
+            IntersphinxTarget {
+                uri: format!("{}/docs", base),
+                inventory: None,
+            },
+        );
+        config.intersphinx_mapping.insert(
+            "gone".to_string(),
+            IntersphinxTarget {
+                uri: "http://127.0.0.1:1/docs".to_string(),
+                inventory: None,
+            },
+        );
+        config.intersphinx_timeout = Some(5.0);
+
+        let cache_dir = temp_dir.path().join("cache");
+        let (inventories, failures) =
+            load_inventories(&config, temp_dir.path().to_path_buf(), &cache_dir).await;
+
+        let item = inventories["other"]
+            .get("py:module", "other.module")
+            .unwrap();
+        assert_eq!(
+            item.uri,
+            format!("{}/docs/other.html#module-other.module", base)
+        );
+        assert_eq!(failures.len(), 1, "{:?}", failures);
+        assert!(failures[0].starts_with("intersphinx inventory 'gone'"));
+        let fetcher = InventoryFetcher::new(&config, temp_dir.path().to_path_buf(), &cache_dir);
+        assert!(fetcher
+            .cache_path(&format!("{}/docs/objects.inv", base))
+            .exists());
+
+        // The cached copy is used while it is fresh
+        load_inventories(&config, temp_dir.path().to_path_buf(), &cache_dir).await;
+        assert_eq!(requests.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_only_http_and_https_inventories_are_fetched() {
+        assert!(!is_remote("objects.inv").unwrap());
+        assert!(is_remote("https://docs.example.org/objects.inv").unwrap());
+        assert!(is_remote("HTTP://docs.example.org/objects.inv").unwrap());
+        assert!(is_remote("file:///etc/passwd").is_err());
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let fetcher = InventoryFetcher::new(
+            &BuildConfig::default(),
+            temp_dir.path().to_path_buf(),
+            temp_dir.path(),
+        );
+        let error = fetcher
+            .load(&IntersphinxTarget {
+                uri: "https://docs.example.org".to_string(),
+                inventory: Some("scp://host/objects.inv".to_string()),
+            })
+            .await
+            .unwrap_err();
+        assert!(
+            error.to_string().contains("unsupported URL scheme 'scp'"),
+            "{}",
+            error
+        );
+    }
+
+    #[tokio::test]
+    async fn test_missing_ca_bundle_is_reported() {
+        let (base, requests) = serve(Vec::new()).await;
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config = BuildConfig {
+            tls_cacerts: Some(
+                serde_json::from_value(serde_json::json!("/nonexistent/ca.pem")).unwrap(),
+            ),
+            ..BuildConfig::default()
+        };
+        let fetcher =
+            InventoryFetcher::new(&config, temp_dir.path().to_path_buf(), temp_dir.path());
+
+        let error = fetcher
+            .fetch(&format!("{}/objects.inv", base))
+            .await
+            .unwrap_err();
+        assert!(
+            format!("{:#}", error).contains("Failed to read CA certificates"),
+            "{:#}",
+            error
+        );
+        assert_eq!(requests.load(std::sync::atomic::Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_tls_cacerts_are_chosen_by_host() {
+        let per_host: TlsCacerts = serde_json::from_value(serde_json::json!({
+            "docs.example.org": "/certs/example.pem",
+            "internal:8443": "/certs/internal.pem",
+        }))
+        .unwrap();
+        assert_eq!(
+            per_host.for_url("https://docs.example.org/en/objects.inv"),
+            Some("/certs/example.pem")
+        );
+        assert_eq!(
+            per_host.for_url("https://internal:8443/objects.inv"),
+            Some("/certs/internal.pem")
+        );
+        assert_eq!(per_host.for_url("https://other.org/objects.inv"), None);
+
+        let bundle: TlsCacerts =
+            serde_json::from_value(serde_json::json!("/certs/all.pem")).unwrap();
+        assert_eq!(bundle.for_url("https://other.org/"), Some("/certs/all.pem"));
+    }
+}
@@ -64,22 +64,43 @@ impl Inventory {
     }
 }
 
+/// Split raw inventory data after its first line, dropping the `\n`
+fn split_line(data: &[u8]) -> (&[u8], &[u8]) {
+    match data.iter().position(|&byte| byte == b'\n') {
+        Some(end) => (&data[..end], &data[end + 1..]),
+        None => (data, &[]),
+    }
+}
+
+/// Project name and version from the `# Project:` and `# Version:` header lines
+fn parse_header<'a>(project_line: &'a str, version_line: &'a str) -> Result<(&'a str, &'a str)> {
+    match (
+        project_line.strip_prefix("# Project: "),
+        version_line.strip_prefix("# Version: "),
+    ) {
+        (Some(project_name), Some(version)) => Ok((project_name.trim(), version.trim())),
+        _ => anyhow::bail!("Invalid inventory header: missing project name or version"),
+    }
+}
+
 /// Inventory file handler - mirrors Sphinx's InventoryFile class
 pub struct InventoryFile;
 
 impl InventoryFile {
     /// Load inventory from bytes (mirrors Sphinx's loads method)
+    ///
+    /// Only the header lines are decoded as text; a version 2 body is zlib data
+    /// and stays raw bytes until it is inflated.
     pub fn loads(content: &[u8], uri: &str) -> Result<Inventory> {
-        let content_str = String::from_utf8_lossy(content);
-        let mut lines = content_str.lines();
-
         // Parse header
-        let format_line = lines.next().unwrap_or("").trim();
+        let (format_line, rest) = split_line(content);
+        let format_line = String::from_utf8_lossy(format_line);
+        let format_line = format_line.trim();
 
         if format_line == "# Sphinx inventory version 2" {
-            Self::loads_v2(&mut lines, uri)
+            Self::loads_v2(rest, uri)
         } else if format_line == "# Sphinx inventory version 1" {
-            Self::loads_v1(&mut lines, uri)
+            Self::loads_v1(&mut String::from_utf8_lossy(rest).lines(), uri)
         } else if let Some(version) = format_line.strip_prefix("# Sphinx inventory version ") {
             anyhow::bail!("Unknown or unsupported inventory version: {}", version);
         } else {
@@ -98,12 +119,7 @@ impl InventoryFile {
             .next()
             .ok_or_else(|| anyhow::anyhow!("Missing project version"))?;
 
-        if !project_line.starts_with("# Project: ") || !version_line.starts_with("# Version: ") {
-            anyhow::bail!("Invalid inventory header: missing project name or version");
-        }
-
-        let project_name = project_line[11..].trim();
-        let version = version_line[11..].trim();
+        let (project_name, version) = parse_header(project_line, version_line)?;
 
         for line in lines {
             let line = line.trim();
@@ -147,25 +163,25 @@ impl InventoryFile {
     }
 
     /// Load inventory from version 2 format
-    fn loads_v2(lines: &mut std::str::Lines, uri: &str) -> Result<Inventory> {
+    fn loads_v2(content: &[u8], uri: &str) -> Result<Inventory> {
         let mut inv = Inventory::new();
 
-        let project_line = lines
-            .next()
-            .ok_or_else(|| anyhow::anyhow!("Missing project name"))?;
-        let version_line = lines
-            .next()
-            .ok_or_else(|| anyhow::anyhow!("Missing project version"))?;
-        let compression_line = lines
-            .next()
-            .ok_or_else(|| anyhow::anyhow!("Missing compression info"))?;
-
-        if !project_line.starts_with("# Project: ") || !version_line.starts_with("# Version: ") {
-            anyhow::bail!("Invalid inventory header: missing project name or version");
+        let mut header_lines = Vec::with_capacity(3);
+        let mut body = content;
+        for missing in [
+            "Missing project name",
+            "Missing project version",
+            "Missing compression info",
+        ] {
+            if body.is_empty() {
+                anyhow::bail!(missing);
+            }
+            let (line, rest) = split_line(body);
+            header_lines.push(String::from_utf8_lossy(line).into_owned());
+            body = rest;
         }
-
-        let project_name = project_line[11..].trim();
-        let version = version_line[11..].trim();
+        let (project_name, version) = parse_header(&header_lines[0], &header_lines[1])?;
+        let compression_line = &header_lines[2];
 
         if !compression_line.contains("zlib") {
             anyhow::bail!(
@@ -174,14 +190,13 @@ impl InventoryFile {
             );
         }
 
-        // Read the rest as compressed data
-        let remaining_content: String = lines.collect::<Vec<_>>().join("\n");
+        // The rest is compressed data, possibly base64-encoded
         let compressed_data = {
             use base64::prelude::*;
-            BASE64_STANDARD.decode(&remaining_content).or_else(|_| {
-                // If base64 decode fails, try treating as raw bytes
-                Ok::<Vec<u8>, base64::DecodeError>(remaining_content.as_bytes().to_vec())
-            })?
+            let text = String::from_utf8_lossy(body);
+            BASE64_STANDARD
+                .decode(text.trim())
+                .unwrap_or_else(|_| body.to_vec())
         };
 
         // Decompress using zlib
@@ -213,8 +228,8 @@ impl InventoryFile {
             }
 
             // Handle location anchors
-            if location.ends_with('$') {
-                location = location[..location.len() - 1].to_string() + name;
+            if let Some(prefix) = location.strip_suffix('$') {
+                location = format!("{}{}", prefix, name);
             }
 
             let full_location = if uri.is_empty() {
@@ -308,10 +323,9 @@ impl InventoryFile {
                 // Build URI
                 let mut uri = builder.get_target_uri(docname);
                 if !anchor.is_empty() {
-                    if anchor.ends_with(fullname) {
-                        // Optimize by using $ suffix
-                        let prefix = &anchor[..anchor.len() - fullname.len()];
-                        uri = format!("{}{}$", uri, prefix);
+                    if let Some(prefix) = anchor.strip_suffix(fullname.as_str()) {
+                        // Optimize by using $ suffix (expanded back to the name on load)
+                        uri = format!("{}#{}$", uri, prefix);
                     } else {
                         uri = format!("{}#{}", uri, anchor);
                     }
@@ -421,6 +435,97 @@ mod tests {
         assert_eq!(parts[4], "Test Function");
     }
 
+    #[tokio::test]
+    async fn test_dump_keeps_overload_anchors_distinct() {
+        use crate::environment::{BuildEnvironment, DomainObject};
+
+        let config = crate::config::BuildConfig::default();
+        let mut env = BuildEnvironment::new(config.clone());
+        for _ in 0..2 {
+            env.update_domain_object(
+                "py",
+                "function",
+                DomainObject::new(
+                    "overload".to_string(),
+                    "function".to_string(),
+                    "api".to_string(),
+                    Some("overload".to_string()),
+                    1,
+                ),
+            );
+        }
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let builder = crate::html_builder::HTMLBuilder::new(
+            config,
+            temp_dir.path().to_path_buf(),
+            temp_dir.path().to_path_buf(),
+        )
+        .unwrap();
+        let inventory_path = temp_dir.path().join("objects.inv");
+        InventoryFile::dump(&inventory_path, &env, &builder)
+            .await
+            .unwrap();
+
+        // Skip the four header lines and inflate the body
+        let raw = std::fs::read(&inventory_path).unwrap();
+        let body_start = raw
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| **b == b'\n')
+            .nth(3)
+            .map(|(i, _)| i + 1)
+            .unwrap();
+        let body = InventoryFile::decompress_zlib(&raw[body_start..]).unwrap();
+        let body = String::from_utf8(body).unwrap();
+
+        assert!(body.contains("overload py:function 1 api.html#$ -"));
+        assert!(body.contains("overload py:function 1 api.html#overload-1 -"));
+    }
+
+    #[tokio::test]
+    async fn test_unicode_project_round_trips() {
+        use crate::environment::{BuildEnvironment, DomainObject};
+
+        let config = crate::config::BuildConfig {
+            project: "Café Ünïcode".to_string(),
+            version: Some("1.0 – beta".to_string()),
+            ..Default::default()
+        };
+        let mut env = BuildEnvironment::new(config.clone());
+        env.update_domain_object(
+            "py",
+            "function",
+            DomainObject::new(
+                "grüße".to_string(),
+                "function".to_string(),
+                "api".to_string(),
+                Some("grüße".to_string()),
+                1,
+            ),
+        );
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let builder = crate::html_builder::HTMLBuilder::new(
+            config,
+            temp_dir.path().to_path_buf(),
+            temp_dir.path().to_path_buf(),
+        )
+        .unwrap();
+        let inventory_path = temp_dir.path().join("objects.inv");
+        InventoryFile::dump(&inventory_path, &env, &builder)
+            .await
+            .unwrap();
+
+        let inv = InventoryFile::load(&inventory_path, "https://example.org/")
+            .await
+            .unwrap();
+        let item = inv.get("py:function", "grüße").unwrap();
+        assert_eq!(item.project_name, "Café Ünïcode");
+        assert_eq!(item.project_version, "1.0 – beta");
+        assert_eq!(item.uri, "https://example.org/api.html#grüße");
+    }
+
     #[test]
     fn test_escape_string() {
         assert_eq!(
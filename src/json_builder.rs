@@ -0,0 +1,243 @@
+//! JSON output for custom front-ends, mirroring Sphinx's `json` builder
+//!
+//! Every document becomes a `<docname>.fjson` file holding its page context
+//! (title, body, local TOC, relations, metadata); the context shared by all
+//! pages goes to `globalcontext.json` and the search index to
+//! `searchindex.json`.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use log::info;
+use serde_json::{Map, Value as JsonValue};
+use std::path::PathBuf;
+use tokio::fs;
+
+use crate::config::BuildConfig;
+use crate::document::Document;
+use crate::environment::BuildEnvironment;
+use crate::html_builder::HTMLBuilder;
+use crate::utils;
+use crate::writer::DocumentWriter;
+
+/// The filename of the context shared by all pages (matches Sphinx)
+pub const GLOBALCONTEXT_FILENAME: &str = "globalcontext.json";
+
+/// JSON Builder that mirrors Sphinx's JSONHTMLBuilder
+///
+/// Page contexts are assembled by an [`HTMLBuilder`], as Sphinx's serializing
+/// builders extend its HTML builder, and dumped instead of rendered.
+#[derive(Debug)]
+pub struct JSONBuilder {
+    pub name: String,
+    pub format: String,
+    pub out_suffix: String,
+    pub globalcontext_filename: String,
+    pub searchindex_filename: String,
+
+    /// Assembles the page and global contexts
+    pub html: HTMLBuilder,
+
+    /// Documents handed over through [`DocumentWriter`], written on finish
+    /// once the relations between all of them are known
+    pending: Vec<(String, Document)>,
+}
+
+impl JSONBuilder {
+    pub fn new(config: BuildConfig, srcdir: PathBuf, outdir: PathBuf) -> Result<Self> {
+        let out_suffix = ".fjson".to_string();
+        let searchindex_filename = "searchindex.json".to_string();
+
+        let mut html = HTMLBuilder::new(config, srcdir, outdir)?;
+        html.name = "json".to_string();
+        html.format = "json".to_string();
+        html.epilog = "You can now process the JSON files in %(outdir)s.".to_string();
+        html.out_suffix = out_suffix.clone();
+        // Front-ends route pages by directory-style URIs
+        html.link_suffix = "/".to_string();
+        html.searchindex_filename = searchindex_filename.clone();
+
+        Ok(Self {
+            name: "json".to_string(),
+            format: "json".to_string(),
+            out_suffix,
+            globalcontext_filename: GLOBALCONTEXT_FILENAME.to_string(),
+            searchindex_filename,
+            html,
+            pending: Vec::new(),
+        })
+    }
+
+    /// Initialize the builder
+    pub async fn init(&mut self) -> Result<()> {
+        info!("Initializing JSON builder");
+        self.html.init().await
+    }
+
+    /// Load prev/next/parent relations and titles from the environment
+    pub fn load_relations(&mut self, env: &BuildEnvironment) {
+        self.html.load_relations(env);
+    }
+
+    /// Write a document's page context as `<docname>.fjson`
+    ///
+    /// Entries of the global context are left out unless the page changes
+    /// them; they are written once, to `globalcontext.json`.
+    pub async fn write_doc(&mut self, docname: &str, doctree: &Document) -> Result<()> {
+        info!("Writing document: {}", docname);
+
+        let ctx = self.html.page_context(docname, doctree).await?;
+        self.html.index_page(docname, doctree, &ctx)?;
+        let mut ctx: Map<String, JsonValue> = ctx
+            .into_iter()
+            .filter(|(key, value)| self.html.global_context.get(key) != Some(value))
+            .collect();
+        ctx.insert(
+            "current_page_name".to_string(),
+            JsonValue::String(docname.to_string()),
+        );
+
+        let output_path = self.html.get_output_path(docname);
+        utils::ensure_dir(output_path.parent().unwrap()).await?;
+        fs::write(&output_path, serde_json::to_string(&ctx)?)
+            .await
+            .with_context(|| format!("Failed to write page: {}", output_path.display()))?;
+
+        Ok(())
+    }
+
+    /// Dump the context shared by all pages
+    pub async fn dump_global_context(&self) -> Result<()> {
+        let path = self.html.outdir.join(&self.globalcontext_filename);
+        fs::write(path, serde_json::to_string(&self.html.global_context)?).await?;
+        Ok(())
+    }
+
+    /// Dump the search index as plain JSON, without `searchindex.js`'s wrapper
+    pub async fn dump_search_index(&self, search_index: &crate::search::SearchIndex) -> Result<()> {
+        info!("Dumping search index");
+        let path = self.html.outdir.join(&self.searchindex_filename);
+        fs::write(path, search_index.to_json()?).await?;
+        Ok(())
+    }
+
+    /// Finish the build process
+    pub async fn finish(&self, search_index: &crate::search::SearchIndex) -> Result<()> {
+        info!("Finishing JSON build");
+
+        tokio::try_join!(
+            self.dump_global_context(),
+            self.dump_search_index(search_index),
+            self.html.copy_static_files(),
+        )?;
+        self.html.copy_extra_files().await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl DocumentWriter for JSONBuilder {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn init(&mut self) -> Result<()> {
+        JSONBuilder::init(self).await
+    }
+
+    async fn write_doc(&mut self, docname: &str, doctree: &Document) -> Result<()> {
+        self.pending.push((docname.to_string(), doctree.clone()));
+        Ok(())
+    }
+
+    async fn finish(&mut self) -> Result<()> {
+        let pending = std::mem::take(&mut self.pending);
+        let mut env = BuildEnvironment::new(self.html.config.clone());
+        env.note_documents(
+            pending
+                .iter()
+                .map(|(docname, document)| (docname.clone(), document)),
+        );
+        self.load_relations(&env);
+
+        for (docname, document) in &pending {
+            JSONBuilder::write_doc(self, docname, document).await?;
+            self.html.search_index.add_document_objects(docname, &env)?;
+        }
+        self.html.copy_image_files(&env.images).await?;
+        JSONBuilder::finish(self, self.html.search_index.index()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SphinxBuilder;
+
+    #[tokio::test]
+    async fn test_json_builder_writes_page_and_global_contexts() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        let output_dir = temp_dir.path().join("build");
+        std::fs::create_dir_all(&source_dir).unwrap();
+        for (name, content) in [
+            (
+                "index.rst",
+                "Index\n#####\n\n.. toctree::\n\n   intro\n   usage\n",
+            ),
+            (
+                "intro.rst",
+                "Introduction\n############\n\nSome **bold** words.\n\nDetails\n=======\n\nMore.\n",
+            ),
+            ("usage.rst", "Usage\n#####\n\nRun it.\n"),
+        ] {
+            std::fs::write(source_dir.join(name), content).unwrap();
+        }
+
+        let config = BuildConfig {
+            project: "Demo".to_string(),
+            ..BuildConfig::default()
+        };
+        let mut builder =
+            SphinxBuilder::new(config.clone(), source_dir.clone(), output_dir.clone()).unwrap();
+        builder.add_writer(Box::new(
+            JSONBuilder::new(config, source_dir, output_dir.clone()).unwrap(),
+        ));
+        builder.build().await.unwrap();
+
+        let read_json = |name: &str| -> JsonValue {
+            serde_json::from_str(&std::fs::read_to_string(output_dir.join(name)).unwrap()).unwrap()
+        };
+
+        let intro = read_json("intro.fjson");
+        assert_eq!(intro["title"], "Introduction");
+        assert_eq!(intro["current_page_name"], "intro");
+        assert!(intro["body"]
+            .as_str()
+            .unwrap()
+            .contains("<strong>bold</strong>"));
+        assert!(intro["toc"].as_str().unwrap().contains("Details"));
+        assert_eq!(
+            intro["prev"],
+            serde_json::json!({"link": "../", "title": "Index"})
+        );
+        assert_eq!(
+            intro["next"],
+            serde_json::json!({"link": "../usage/", "title": "Usage"})
+        );
+        assert!(intro["meta"].is_object());
+        assert!(intro.get("project").is_none());
+
+        let global = read_json(GLOBALCONTEXT_FILENAME);
+        assert_eq!(global["project"], "Demo");
+
+        let search: crate::search::SphinxSearchData =
+            serde_json::from_value(read_json("searchindex.json")).unwrap();
+        let mut docnames = search.docnames.clone();
+        docnames.sort();
+        assert_eq!(docnames, ["index", "intro", "usage"]);
+        assert!(search.terms.contains_key("bold"));
+        assert!(!search.terms.contains_key("toctre"));
+        assert!(!search.terms.contains_key("toctree"));
+    }
+}
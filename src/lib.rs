@@ -10,16 +10,24 @@ pub mod document;
 pub mod environment;
 pub mod error;
 pub mod extensions;
+pub mod highlight;
 pub mod html_builder;
+pub mod intersphinx;
 pub mod inventory;
+pub mod json_builder;
+pub mod lint;
 pub mod parser;
 pub mod python_config;
+pub mod query;
+pub mod renderer;
 pub mod roles;
 pub mod search;
+pub mod tags;
 pub mod template;
 pub mod utils;
+pub mod writer;
 
-pub use builder::{BuildStats, SphinxBuilder};
+pub use builder::{BuildStats, PageLayout, SphinxBuilder};
 pub use config::BuildConfig;
 pub use directives::{Directive, DirectiveRegistry};
 pub use document::Document;
@@ -28,9 +36,13 @@ pub use error::BuildError;
 pub use extensions::{ExtensionLoader, SphinxApp, SphinxExtension};
 pub use html_builder::HTMLBuilder;
 pub use inventory::{InventoryFile, InventoryItem};
+pub use json_builder::JSONBuilder;
 pub use parser::Parser;
 pub use python_config::{ConfPyConfig, PythonConfigParser};
+pub use query::DocumentQuery;
+pub use renderer::HtmlRenderer;
 pub use roles::{Role, RoleRegistry};
 pub use search::SearchIndex;
 pub use template::TemplateEngine;
 pub use utils::{analyze_project, ProjectStats};
+pub use writer::DocumentWriter;
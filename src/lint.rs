@@ -0,0 +1,147 @@
+use std::path::Path;
+
+use crate::error::{BuildWarning, WarningType};
+
+/// Default maximum line length for the source linter
+pub const DEFAULT_MAX_LINE_LENGTH: usize = 120;
+
+/// Characters that can adorn an RST section title
+const ADORNMENT_CHARS: &str = "=-~^\"'*+#<>";
+
+/// Lint a source file for issues the parser is sensitive to
+///
+/// Reports trailing whitespace and over-long lines for every source, plus tabs in
+/// directive bodies and too-short title underlines for reStructuredText.
+pub fn lint_source(file_path: &Path, content: &str, max_line_length: usize) -> Vec<BuildWarning> {
+    let is_rst = file_path.extension().is_some_and(|ext| ext == "rst");
+    let lines: Vec<&str> = content.lines().collect();
+    let mut warnings = Vec::new();
+    let mut in_directive = false;
+
+    let mut warn = |line: usize, message: String| {
+        warnings.push(BuildWarning::new(
+            file_path.to_path_buf(),
+            Some(line),
+            message,
+            WarningType::Lint,
+        ));
+    };
+
+    for (index, line) in lines.iter().enumerate() {
+        let line_number = index + 1;
+
+        if line.len() != line.trim_end().len() {
+            warn(line_number, "trailing whitespace".to_string());
+        }
+
+        let length = line.chars().count();
+        if length > max_line_length && line.trim().contains(char::is_whitespace) {
+            warn(
+                line_number,
+                format!(
+                    "line too long ({} > {} characters)",
+                    length, max_line_length
+                ),
+            );
+        }
+
+        if !is_rst {
+            continue;
+        }
+
+        // Track directive bodies: the indented block following `.. name::`
+        if line.starts_with(".. ") && line.contains("::") {
+            in_directive = true;
+            continue;
+        }
+        if in_directive && !line.trim().is_empty() && !line.starts_with([' ', '\t']) {
+            in_directive = false;
+        }
+        if in_directive && line.contains('\t') {
+            warn(line_number, "tab character in directive body".to_string());
+        }
+
+        if let Some(underline) = lines.get(index + 1) {
+            let title = line.trim_end();
+            if is_short_underline(title, underline.trim_end()) {
+                warn(
+                    line_number + 1,
+                    format!(
+                        "title underline too short ({} < {} characters)",
+                        underline.trim_end().chars().count(),
+                        title.chars().count()
+                    ),
+                );
+            }
+        }
+    }
+
+    warnings
+}
+
+/// Whether `underline` adorns `title` but is shorter than it
+fn is_short_underline(title: &str, underline: &str) -> bool {
+    let Some(first) = underline.chars().next() else {
+        return false;
+    };
+
+    if title.is_empty()
+        || title.starts_with(char::is_whitespace)
+        || is_adornment(title)
+        || underline.chars().count() < 2
+        || !ADORNMENT_CHARS.contains(first)
+        || !underline.chars().all(|c| c == first)
+    {
+        return false;
+    }
+
+    underline.chars().count() < title.chars().count()
+}
+
+fn is_adornment(line: &str) -> bool {
+    let mut chars = line.chars();
+    match chars.next() {
+        Some(first) => ADORNMENT_CHARS.contains(first) && chars.all(|c| c == first),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_short_title_underline_is_reported() {
+        let content = "Getting Started\n=====\n\nSome text.\n";
+
+        let warnings = lint_source(Path::new("intro.rst"), content, DEFAULT_MAX_LINE_LENGTH);
+
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].line, Some(2));
+        assert_eq!(
+            warnings[0].message,
+            "title underline too short (5 < 15 characters)"
+        );
+    }
+
+    #[test]
+    fn test_whitespace_tabs_and_long_lines() {
+        let content =
+            ".. note::\n\n\tTabbed body.\n\nTrailing space \nA line that is far too long\n";
+
+        let warnings = lint_source(Path::new("intro.rst"), content, 20);
+        let messages: Vec<(Option<usize>, &str)> = warnings
+            .iter()
+            .map(|warning| (warning.line, warning.message.as_str()))
+            .collect();
+
+        assert_eq!(
+            messages,
+            vec![
+                (Some(3), "tab character in directive body"),
+                (Some(5), "trailing whitespace"),
+                (Some(6), "line too long (27 > 20 characters)"),
+            ]
+        );
+    }
+}
@@ -1,11 +1,14 @@
 use anyhow::Result;
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 use log::{info, warn};
 use std::fs::OpenOptions;
-use std::io::Write;
+use std::io::{IsTerminal, Write};
 use std::path::PathBuf;
 
-use sphinx_ultra::{analyze_project, BuildConfig, SphinxBuilder};
+use sphinx_ultra::{
+    analyze_project, BuildConfig, DirectiveRegistry, JSONBuilder, PageLayout, RoleRegistry,
+    SphinxBuilder,
+};
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -13,13 +16,69 @@ struct Cli {
     #[command(subcommand)]
     command: Commands,
 
-    /// Enable verbose logging
-    #[arg(short, long)]
-    verbose: bool,
+    /// Enable verbose logging; repeat (-vv) for a per-document trace of the
+    /// directives and roles rendered
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    verbose: u8,
 
     /// Configuration file path
     #[arg(short, long)]
     config: Option<PathBuf>,
+
+    /// Read conf.py's plain assignments instead of executing it with Python
+    #[arg(long, global = true)]
+    static_conf: bool,
+
+    /// Color the log and the build's warnings, errors and summary
+    #[arg(long, value_enum, global = true, default_value_t = ColorDiagnostics::Auto)]
+    color_diagnostics: ColorDiagnostics,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ColorDiagnostics {
+    /// Color when stderr is a terminal and NO_COLOR is unset
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputBuilder {
+    /// HTML pages, one `<docname>.html` per document
+    Html,
+    /// HTML pages at `<docname>/index.html`, linked as directories
+    #[value(name = "dirhtml")]
+    Dirhtml,
+    /// All documents on a single HTML page
+    #[value(name = "singlehtml")]
+    Singlehtml,
+    /// HTML pages plus `.fjson` page contexts, `globalcontext.json` and
+    /// `searchindex.json`, as Sphinx's json builder
+    Json,
+}
+
+impl OutputBuilder {
+    /// Sphinx's name and format for the builder, which become build tags
+    fn name_and_format(self) -> (&'static str, &'static str) {
+        match self {
+            OutputBuilder::Html => ("html", "html"),
+            OutputBuilder::Dirhtml => ("dirhtml", "html"),
+            OutputBuilder::Singlehtml => ("singlehtml", "html"),
+            OutputBuilder::Json => ("json", "html"),
+        }
+    }
+}
+
+impl ColorDiagnostics {
+    fn enabled(self) -> bool {
+        match self {
+            ColorDiagnostics::Auto => {
+                std::io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none()
+            }
+            ColorDiagnostics::Always => true,
+            ColorDiagnostics::Never => false,
+        }
+    }
 }
 
 #[derive(Subcommand)]
@@ -53,6 +112,58 @@ enum Commands {
         /// Write warnings (and errors) to given file
         #[arg(short = 'w', long)]
         warning_file: Option<PathBuf>,
+
+        /// Report source lint warnings (tabs, short title underlines, long lines)
+        #[arg(long)]
+        lint: bool,
+
+        /// Fail the build on unresolved :ref:/:doc: targets, independent of -W
+        #[arg(long)]
+        strict_refs: bool,
+
+        /// Define a build tag for `only` conditions (repeatable)
+        #[arg(short = 't', long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
+
+        /// Only render documents matching this docname glob, e.g. "api/*" (repeatable)
+        #[arg(long = "only", value_name = "GLOB")]
+        only: Vec<String>,
+
+        /// Also copy the finished site to this directory (repeatable)
+        #[arg(long = "also-output", value_name = "DIR")]
+        also_output: Vec<PathBuf>,
+
+        /// Open the built index.html in the default browser (skipped on CI or without a display)
+        #[arg(long, overrides_with = "no_open")]
+        open: bool,
+
+        /// Don't open a browser, overriding an earlier --open
+        #[arg(long, overrides_with = "open")]
+        no_open: bool,
+
+        /// Builder to run: html, dirhtml, singlehtml or json
+        #[arg(short, long, value_enum, default_value_t = OutputBuilder::Html)]
+        builder: OutputBuilder,
+    },
+
+    /// Parse all documents into the cache without rendering, for a following
+    /// `build --incremental`
+    Prebuild {
+        /// Source directory
+        #[arg(short, long, default_value = ".")]
+        source: PathBuf,
+
+        /// Output directory (the cache lives inside it)
+        #[arg(short, long, default_value = "_build")]
+        output: PathBuf,
+
+        /// Number of parallel jobs
+        #[arg(short, long)]
+        jobs: Option<usize>,
+
+        /// Report source lint warnings (tabs, short title underlines, long lines)
+        #[arg(long)]
+        lint: bool,
     },
 
     /// Clean build artifacts
@@ -68,6 +179,9 @@ enum Commands {
         #[arg(short, long, default_value = ".")]
         source: PathBuf,
     },
+
+    /// List the supported directives and roles
+    ListDirectives,
 }
 
 #[tokio::main]
@@ -75,9 +189,20 @@ async fn main() -> Result<()> {
     let cli = Cli::parse();
 
     // Initialize logging
-    let log_level = if cli.verbose { "debug" } else { "info" };
+    let log_level = match cli.verbose {
+        0 => "info",
+        1 => "debug",
+        _ => "debug,sphinx_ultra=trace",
+    };
     std::env::set_var("RUST_LOG", log_level);
-    env_logger::init();
+    let color = cli.color_diagnostics.enabled();
+    env_logger::Builder::from_default_env()
+        .write_style(if color {
+            env_logger::WriteStyle::Always
+        } else {
+            env_logger::WriteStyle::Never
+        })
+        .init();
 
     info!("Sphinx Ultra Builder v{}", env!("CARGO_PKG_VERSION"));
 
@@ -90,23 +215,51 @@ async fn main() -> Result<()> {
             incremental,
             fail_on_warning,
             warning_file,
+            lint,
+            strict_refs,
+            tags,
+            only,
+            also_output,
+            open,
+            no_open: _,
+            builder: output_builder,
         } => {
             let mut config = if let Some(ref config_path) = cli.config {
                 BuildConfig::from_file(config_path)?
             } else {
                 // Try to auto-detect configuration (including conf.py)
-                BuildConfig::auto_detect(&source)?
+                BuildConfig::auto_detect_with(&source, cli.static_conf)?
             };
 
             // Override config with CLI arguments
             if fail_on_warning {
                 config.fail_on_warning = true;
             }
+            config.tags.extend(tags);
 
             // Save the fail_on_warning flag before moving config
             let should_fail_on_warning = config.fail_on_warning;
 
+            let index_page = output.join("index.html");
+            let json_builder = match output_builder {
+                OutputBuilder::Html | OutputBuilder::Dirhtml | OutputBuilder::Singlehtml => None,
+                OutputBuilder::Json => Some(JSONBuilder::new(
+                    config.clone(),
+                    source.clone(),
+                    output.clone(),
+                )?),
+            };
             let mut builder = SphinxBuilder::new(config, source, output)?;
+            if let Some(json_builder) = json_builder {
+                builder.add_writer(Box::new(json_builder));
+            }
+            builder.set_page_layout(match output_builder {
+                OutputBuilder::Dirhtml => PageLayout::Directory,
+                OutputBuilder::Singlehtml => PageLayout::Single,
+                OutputBuilder::Html | OutputBuilder::Json => PageLayout::Flat,
+            });
+            let (name, format) = output_builder.name_and_format();
+            builder.set_builder_tags(name, format);
 
             if let Some(jobs) = jobs {
                 builder.set_parallel_jobs(jobs);
@@ -120,99 +273,87 @@ async fn main() -> Result<()> {
                 builder.enable_incremental();
             }
 
-            let stats = builder.build().await?;
-
-            // Handle warning file output if specified
-            let mut warning_file_handle = if let Some(ref warning_file_path) = warning_file {
-                // Create parent directories if they don't exist
-                if let Some(parent) = warning_file_path.parent() {
-                    std::fs::create_dir_all(parent)?;
-                }
-                Some(
-                    OpenOptions::new()
-                        .create(true)
-                        .write(true)
-                        .truncate(true)
-                        .open(warning_file_path)?,
-                )
-            } else {
-                None
-            };
-
-            // Print warnings in Sphinx-like format
-            for warning in &stats.warning_details {
-                let file_path = warning.file.display();
-                let line_info = if let Some(line) = warning.line {
-                    format!(":{}", line)
-                } else {
-                    String::new()
-                };
-                let warning_msg =
-                    format!("{}{}: WARNING: {}", file_path, line_info, warning.message);
-
-                // Write to warning file if specified
-                if let Some(ref mut file) = warning_file_handle {
-                    writeln!(file, "{}", warning_msg)?;
-                }
-
-                warn!("{}", warning_msg);
+            if lint {
+                builder.enable_lint();
             }
 
-            // Print errors in Sphinx-like format
-            for error in &stats.error_details {
-                let file_path = error.file.display();
-                let line_info = if let Some(line) = error.line {
-                    format!(":{}", line)
-                } else {
-                    String::new()
-                };
-                let error_msg = format!("{}{}: ERROR: {}", file_path, line_info, error.message);
-
-                // Write to warning file if specified (errors also go to warning file in Sphinx)
-                if let Some(ref mut file) = warning_file_handle {
-                    writeln!(file, "{}", error_msg)?;
-                }
-
-                eprintln!("{}", error_msg);
+            if strict_refs {
+                builder.enable_strict_refs();
             }
 
-            // Flush and close the warning file
-            if let Some(mut file) = warning_file_handle {
-                file.flush()?;
+            if !only.is_empty() {
+                builder.set_only(&only)?;
             }
 
-            // Check for fail-on-warning condition
-            if should_fail_on_warning && stats.warnings > 0 {
-                eprintln!("Build failed due to warnings (caused by --fail-on-warning)");
-                std::process::exit(1);
+            for dir in also_output {
+                builder.add_output_dir(dir);
             }
 
-            // Print final summary
-            if stats.warnings > 0 || stats.errors > 0 {
-                let status_msg = if stats.errors > 0 {
-                    "build succeeded with problems"
-                } else {
-                    "build succeeded"
-                };
-
-                if stats.warnings > 0 && stats.errors > 0 {
-                    warn!(
-                        "{}, {} warnings, {} errors.",
-                        status_msg, stats.warnings, stats.errors
-                    );
-                } else if stats.warnings > 0 {
-                    warn!("{}, {} warnings.", status_msg, stats.warnings);
-                } else if stats.errors > 0 {
-                    warn!("{}, {} errors.", status_msg, stats.errors);
-                }
-            }
+            let stats = builder.build().await?;
 
-            info!("Build completed successfully!");
             info!("Files processed: {}", stats.files_processed);
             info!("Files skipped: {}", stats.files_skipped);
             info!("Cache hits: {}", stats.cache_hits);
             info!("Build time: {:?}", stats.build_time);
             info!("Output size: {} MB", stats.output_size_mb);
+
+            // Write warnings and errors to the warning file if specified
+            // (errors also go to the warning file in Sphinx)
+            if let Some(ref warning_file_path) = warning_file {
+                // Create parent directories if they don't exist
+                if let Some(parent) = warning_file_path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                let mut file = OpenOptions::new()
+                    .create(true)
+                    .write(true)
+                    .truncate(true)
+                    .open(warning_file_path)?;
+                stats.write_diagnostics(&mut file, false)?;
+                file.flush()?;
+            }
+
+            // Warnings, errors and the summary go to stderr in one piece, so
+            // they don't interleave with the log
+            {
+                let mut stderr = std::io::stderr().lock();
+                stats.write_report(&mut stderr, color)?;
+
+                // Check for fail-on-warning condition
+                if should_fail_on_warning && stats.warnings > 0 {
+                    writeln!(
+                        stderr,
+                        "Build failed due to warnings (caused by --fail-on-warning)"
+                    )?;
+                    std::process::exit(1);
+                }
+
+                if stats.has_broken_references() {
+                    writeln!(
+                        stderr,
+                        "Build failed due to unresolved references (caused by --strict-refs)"
+                    )?;
+                    std::process::exit(1);
+                }
+            }
+
+            if sphinx_ultra::utils::should_open_browser(open, |name| std::env::var(name).ok()) {
+                match index_page.canonicalize() {
+                    Ok(index_page) => {
+                        let url = sphinx_ultra::utils::file_url(&index_page);
+                        info!("Opening {}", url);
+                        if let Err(e) = sphinx_ultra::utils::open_in_browser(&url) {
+                            warn!("{}", e);
+                        }
+                    }
+                    Err(_) => warn!(
+                        "Not opening a browser: {} was not built",
+                        index_page.display()
+                    ),
+                }
+            } else if open {
+                info!("Not opening a browser on CI or without a display");
+            }
         }
 
         Commands::Clean { output } => {
@@ -236,6 +377,63 @@ async fn main() -> Result<()> {
             println!("  Directory depth: {}", stats.max_depth);
             println!("  Cross-references: {}", stats.cross_references);
         }
+
+        Commands::Prebuild {
+            source,
+            output,
+            jobs,
+            lint,
+        } => {
+            let config = if let Some(ref config_path) = cli.config {
+                BuildConfig::from_file(config_path)?
+            } else {
+                BuildConfig::auto_detect_with(&source, cli.static_conf)?
+            };
+
+            let mut builder = SphinxBuilder::new(config, source, output)?;
+            if let Some(jobs) = jobs {
+                builder.set_parallel_jobs(jobs);
+            }
+            if lint {
+                builder.enable_lint();
+            }
+
+            let stats = builder.prebuild().await?;
+            stats.write_diagnostics(&mut std::io::stderr().lock(), color)?;
+            info!(
+                "Cached {} documents ({} unchanged)",
+                stats.files_processed, stats.files_skipped
+            );
+        }
+
+        Commands::ListDirectives => {
+            let directives = DirectiveRegistry::new();
+            println!("Directives:");
+            for name in directives.names() {
+                let mut options: Vec<String> = directives
+                    .option_spec(&name)
+                    .unwrap_or_default()
+                    .into_keys()
+                    .collect();
+                options.sort();
+
+                let stub = if directives.get(&name).is_some_and(|d| d.is_stub()) {
+                    " (stub)"
+                } else {
+                    ""
+                };
+                if options.is_empty() {
+                    println!("  {}{}", name, stub);
+                } else {
+                    println!("  {}{} [{}]", name, stub, options.join(", "));
+                }
+            }
+
+            println!("Roles:");
+            for name in RoleRegistry::new().names() {
+                println!("  {}", name);
+            }
+        }
     }
 
     Ok(())
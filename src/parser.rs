@@ -1,21 +1,35 @@
 use anyhow::Result;
-use log::debug;
-use pulldown_cmark::{Event, Parser as MarkdownParser, Tag};
+use chrono::{DateTime, NaiveDate, Utc};
+use encoding_rs::{Encoding, UTF_8};
+use log::{debug, error, warn};
+use pulldown_cmark::{CodeBlockKind, Event, Options, Parser as MarkdownParser, Tag, TagEnd};
 use regex::Regex;
-use std::collections::HashMap;
-use std::path::Path;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 
-use crate::config::BuildConfig;
-use crate::directives::DirectiveRegistry;
+use crate::config::{self, BuildConfig};
+use crate::directives::{
+    name_to_id, read_include_file, resolve_include_path, select_include_lines, Directive,
+    DirectiveRegistry,
+};
 use crate::document::{
-    CrossReference, Document, DocumentContent, MarkdownContent, MarkdownNode, RstContent,
-    RstDirective, RstNode, TocEntry,
+    CrossReference, DefinitionItem, Document, DocumentContent, DocumentMetadata, ListItem,
+    MarkdownContent, MarkdownNode, RstContent, RstDirective, RstNode, TocEntry,
 };
+use crate::error::{BuildError, BuildErrorReport, BuildWarning, ErrorType, WarningType};
 use crate::roles::RoleRegistry;
 use crate::utils;
 
 pub struct Parser {
+    source_encoding: Option<&'static Encoding>,
+    source_suffix: HashMap<String, String>,
+    /// Root of the documentation source, against which `/`-prefixed include
+    /// paths resolve
+    source_root: Option<PathBuf>,
     rst_directive_regex: Regex,
+    field_regex: Regex,
+    note_regex: Regex,
     cross_ref_regex: Regex,
     #[allow(dead_code)]
     directive_registry: DirectiveRegistry,
@@ -23,40 +37,197 @@ pub struct Parser {
     role_registry: RoleRegistry,
 }
 
+/// The include chain of a reStructuredText parse and what it turned up
+struct Includes<'a> {
+    /// Canonical paths of the document and the files being included, outermost
+    /// first, to detect circular inclusion
+    stack: Vec<PathBuf>,
+    /// Text of every included file
+    texts: Vec<String>,
+    /// Underline characters of the section titles so far, in order of first
+    /// appearance; a title's level is its character's position, as in docutils
+    title_styles: Vec<char>,
+    /// Whether a field list before any body element is docinfo, which it is
+    /// only at the start of a document
+    docinfo: bool,
+    warnings: &'a mut Vec<BuildWarning>,
+    errors: &'a mut Vec<BuildErrorReport>,
+}
+
+/// Look up a text encoding, accepting Python codec spellings such as "latin-1"
+/// or "utf_8" too
+pub fn encoding_for_label(label: &str) -> Option<&'static Encoding> {
+    Encoding::for_label(label.as_bytes())
+        .or_else(|| Encoding::for_label(label.replace(['-', '_'], "").as_bytes()))
+}
+
 impl Parser {
-    pub fn new(_config: &BuildConfig) -> Result<Self> {
-        let rst_directive_regex = Regex::new(r"^\s*\.\.\s+(\w+)::\s*(.*?)$")?;
+    pub fn new(config: &BuildConfig) -> Result<Self> {
+        // utf-8 is read natively; other encodings are transcoded on read
+        let source_encoding = match config.source_encoding.as_deref() {
+            None => None,
+            Some(label) => {
+                let label = label.trim();
+                let encoding = encoding_for_label(label).ok_or_else(|| {
+                    BuildError::Config(format!("Unknown source_encoding: '{}'", label))
+                })?;
+                (encoding != UTF_8).then_some(encoding)
+            }
+        };
+
+        let rst_directive_regex =
+            Regex::new(r"^\s*\.\.\s+([A-Za-z0-9][\w.+-]*(?::[\w.+-]+)*)::\s*(.*?)$")?;
+        let field_regex = Regex::new(r"^:([^:\s][^:]*):(?:\s+(.*))?$")?;
+        let note_regex = Regex::new(r"^\.\.\s+\[([^\]\s]+)\](?:\s+(.*))?$")?;
         let cross_ref_regex = Regex::new(r":(\w+):`([^`]+)`")?;
         let directive_registry = DirectiveRegistry::new();
         let role_registry = RoleRegistry::new();
 
         Ok(Self {
+            source_encoding,
+            source_suffix: config.source_suffix.clone(),
+            source_root: None,
             rst_directive_regex,
+            field_regex,
+            note_regex,
             cross_ref_regex,
             directive_registry,
             role_registry,
         })
     }
 
+    /// Resolve `/`-prefixed include paths against `root`
+    pub fn set_source_root(&mut self, root: PathBuf) {
+        self.source_root = Some(root);
+    }
+
+    /// Highlight `code` as `language` into Pygments-classed token spans,
+    /// escaped for HTML; unknown languages stay plain text
+    pub fn highlight_code(&self, language: &str, code: &str) -> String {
+        crate::highlight::highlight_code(language, code)
+    }
+
+    /// Read a source file, decoding it with the configured `source_encoding`
+    pub fn read_source(&self, file_path: &Path) -> Result<String> {
+        let bytes = std::fs::read(file_path)?;
+
+        match self.source_encoding {
+            None => String::from_utf8(bytes).map_err(|e| {
+                let valid = &e.as_bytes()[..e.utf8_error().valid_up_to()];
+                let line = valid.iter().filter(|&&b| b == b'\n').count() + 1;
+                BuildError::Parse {
+                    file: file_path.display().to_string(),
+                    message: format!(
+                        "invalid utf-8 at line {} (byte offset {}); set source_encoding if this file uses another encoding",
+                        line,
+                        valid.len()
+                    ),
+                }
+                .into()
+            }),
+            Some(encoding) => encoding
+                .decode_without_bom_handling_and_without_replacement(&bytes)
+                .map(|content| content.into_owned())
+                .ok_or_else(|| {
+                    BuildError::Parse {
+                        file: file_path.display().to_string(),
+                        message: format!("invalid {} byte sequence", encoding.name()),
+                    }
+                    .into()
+                }),
+        }
+    }
+
     pub fn parse(&self, file_path: &Path, content: &str) -> Result<Document> {
+        let (document, warnings, errors) = self.parse_with_diagnostics(file_path, content)?;
+        for warning in warnings {
+            warn!(
+                "{}:{}: {}",
+                warning.file.display(),
+                warning.line.unwrap_or(1),
+                warning.message
+            );
+        }
+        for error in errors {
+            error!(
+                "{}:{}: {}",
+                error.file.display(),
+                error.line.unwrap_or(1),
+                error.message
+            );
+        }
+        Ok(document)
+    }
+
+    /// Parse a source file, returning the warnings and errors found in it
+    /// alongside the document rather than logging them
+    pub fn parse_with_diagnostics(
+        &self,
+        file_path: &Path,
+        content: &str,
+    ) -> Result<(Document, Vec<BuildWarning>, Vec<BuildErrorReport>)> {
+        let mut warnings = Vec::new();
+        let mut errors = Vec::new();
+        let content = normalize_source(content);
+        let content = content.as_ref();
         let output_path = self.get_output_path(file_path)?;
         let mut document = Document::new(file_path.to_path_buf(), output_path);
 
         // Set source modification time
         document.source_mtime = utils::get_file_mtime(file_path)?;
 
-        // Determine file type and parse accordingly
-        let extension = file_path
-            .extension()
-            .and_then(|ext| ext.to_str())
-            .unwrap_or("");
+        // Determine file type from the configured suffixes, or else the extension
+        let file_type = match config::match_source_suffix(&self.source_suffix, file_path) {
+            Some((_, file_type)) => match file_type.as_str() {
+                "restructuredtext" | "rst" => "rst",
+                "markdown" | "md" | "myst" => "md",
+                _ => "",
+            },
+            None => file_path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .unwrap_or(""),
+        };
 
-        match extension {
+        let mut included = Vec::new();
+        match file_type {
             "rst" => {
-                document.content = self.parse_rst(content)?;
+                let mut includes = Includes {
+                    stack: vec![file_path
+                        .canonicalize()
+                        .unwrap_or_else(|_| file_path.to_path_buf())],
+                    texts: Vec::new(),
+                    title_styles: Vec::new(),
+                    docinfo: true,
+                    warnings: &mut warnings,
+                    errors: &mut errors,
+                };
+                let (content, docinfo) = self.parse_rst(file_path, content, &mut includes)?;
+                included = includes.texts;
+                document.content = DocumentContent::RestructuredText(content);
+                apply_docinfo(&mut document.metadata, docinfo);
+                collect_authors(&mut document);
             }
             "md" => {
-                document.content = self.parse_markdown(content)?;
+                let (yaml, body) = split_front_matter(content);
+                let front_matter = yaml.and_then(|yaml| {
+                    serde_yaml::from_str::<serde_yaml::Value>(yaml)
+                        .map_err(|e| {
+                            // The YAML starts on the line after the opening `---`
+                            let line = e.location().map_or(1, |location| location.line()) + 1;
+                            warnings.push(BuildWarning::new(
+                                file_path.to_path_buf(),
+                                Some(line),
+                                format!("invalid YAML front matter: {}", e),
+                                WarningType::Other,
+                            ));
+                        })
+                        .ok()
+                });
+                if let Some(front_matter) = &front_matter {
+                    apply_front_matter(&mut document.metadata, front_matter);
+                }
+                document.content = self.parse_markdown(content, body, front_matter)?;
             }
             _ => {
                 document.content = DocumentContent::PlainText(content.to_string());
@@ -64,13 +235,18 @@ impl Parser {
         }
 
         // Extract title from content
-        document.title = self.extract_title(&document.content);
+        let (title, title_level) = self.extract_title(&document.content);
+        document.title = title;
+        document.title_level = title_level;
 
         // Extract table of contents
         document.toc = self.extract_toc(&document.content);
 
-        // Extract cross-references
+        // Extract cross-references, including those of included files
         document.cross_refs = self.extract_cross_refs(content);
+        for text in &included {
+            document.cross_refs.extend(self.extract_cross_refs(text));
+        }
 
         debug!(
             "Parsed document: {} ({} chars)",
@@ -78,12 +254,57 @@ impl Parser {
             content.len()
         );
 
-        Ok(document)
+        Ok((document, warnings, errors))
     }
 
-    fn parse_rst(&self, content: &str) -> Result<DocumentContent> {
+    /// Parse a fragment of reStructuredText from `file_path`, such as a
+    /// directive's content, whose first line is line `first_line` of the file
+    pub fn parse_rst_fragment(
+        &self,
+        file_path: &Path,
+        content: &str,
+        first_line: usize,
+    ) -> (Vec<RstNode>, Vec<BuildWarning>, Vec<BuildErrorReport>) {
+        let mut warnings = Vec::new();
+        let mut errors = Vec::new();
+        let mut includes = Includes {
+            stack: vec![file_path
+                .canonicalize()
+                .unwrap_or_else(|_| file_path.to_path_buf())],
+            texts: Vec::new(),
+            title_styles: Vec::new(),
+            docinfo: false,
+            warnings: &mut warnings,
+            errors: &mut errors,
+        };
+        // Blank lines in front give the nodes the line numbers of the file
+        let content = "\n".repeat(first_line.saturating_sub(1)) + content;
+        let nodes = match self.parse_rst(file_path, &content, &mut includes) {
+            Ok((rst, _)) => rst.ast,
+            Err(e) => {
+                warnings.push(BuildWarning::new(
+                    file_path.to_path_buf(),
+                    Some(first_line),
+                    e.to_string(),
+                    WarningType::Other,
+                ));
+                Vec::new()
+            }
+        };
+        (nodes, warnings, errors)
+    }
+
+    /// Parse reStructuredText from `file_path`, returning the content and its
+    /// docinfo fields; `include`d files are parsed and spliced in
+    fn parse_rst(
+        &self,
+        file_path: &Path,
+        content: &str,
+        includes: &mut Includes,
+    ) -> Result<(RstContent, Vec<(String, String)>)> {
         let mut nodes = Vec::new();
         let mut directives = Vec::new();
+        let mut docinfo = Vec::new();
         let lines: Vec<&str> = content.lines().collect();
 
         let mut i = 0;
@@ -96,6 +317,89 @@ impl Parser {
                 continue;
             }
 
+            // A field list before any body element is the document's docinfo
+            let in_preamble = nodes
+                .iter()
+                .all(|node| matches!(node, RstNode::Title { .. }));
+            if includes.docinfo
+                && in_preamble
+                && docinfo.is_empty()
+                && self.field_regex.is_match(line)
+            {
+                i += self.parse_field_list(&lines[i..], &mut docinfo);
+                continue;
+            }
+
+            // Check for field list
+            if self.field_regex.is_match(line) {
+                let mut fields = Vec::new();
+                let consumed_lines = self.parse_field_list(&lines[i..], &mut fields);
+                nodes.push(RstNode::FieldList {
+                    fields,
+                    line: i + 1,
+                });
+                i += consumed_lines;
+                continue;
+            }
+
+            // Check for an internal hyperlink target, unless its URI follows
+            // on an indented line
+            if let Some(name) = internal_target(line)
+                .filter(|_| !lines.get(i + 1).is_some_and(|next| is_indented(next)))
+            {
+                nodes.push(RstNode::Target {
+                    name: name.to_string(),
+                    refuri: None,
+                    line: i + 1,
+                });
+                i += 1;
+                continue;
+            }
+
+            // Check for an external hyperlink target, whose URI may continue
+            // on indented lines
+            let target =
+                external_target(line).or_else(|| internal_target(line).map(|name| (name, "")));
+            if let Some((name, uri)) = target {
+                let mut refuri = uri.to_string();
+                let mut consumed_lines = 1;
+                while i + consumed_lines < lines.len() && is_indented(lines[i + consumed_lines]) {
+                    refuri.push_str(lines[i + consumed_lines].trim());
+                    consumed_lines += 1;
+                }
+                nodes.push(RstNode::Target {
+                    name: name.to_string(),
+                    refuri: Some(refuri),
+                    line: i + 1,
+                });
+                i += consumed_lines;
+                continue;
+            }
+
+            // Check for footnote or citation definition
+            if let Some(captures) = self.note_regex.captures(line) {
+                let label = captures[1].to_string();
+                let first = captures.get(2).map_or("", |m| m.as_str());
+                let (content, consumed_lines) = self.parse_note_body(first, &lines[i + 1..]);
+
+                nodes.push(if is_footnote_label(&label) {
+                    RstNode::Footnote {
+                        label,
+                        content,
+                        line: i + 1,
+                    }
+                } else {
+                    RstNode::Citation {
+                        label,
+                        content,
+                        line: i + 1,
+                    }
+                });
+
+                i += consumed_lines + 1;
+                continue;
+            }
+
             // Check for RST directive
             if let Some(captures) = self.rst_directive_regex.captures(line) {
                 let directive_name = captures.get(1).unwrap().as_str();
@@ -104,6 +408,19 @@ impl Parser {
                 let (directive, consumed_lines) =
                     self.parse_rst_directive(&lines[i..], directive_name, directive_args, i + 1)?;
 
+                // Verbatim includes are rendered, not parsed
+                if directive.name == "include"
+                    && !directive.options.contains_key("literal")
+                    && !directive.options.contains_key("code")
+                {
+                    if let Some(included) = self.include_rst(file_path, &directive, includes)? {
+                        nodes.extend(included.ast);
+                        directives.extend(included.directives);
+                    }
+                    i += consumed_lines;
+                    continue;
+                }
+
                 directives.push(directive.clone());
                 nodes.push(RstNode::Directive {
                     name: directive.name,
@@ -117,14 +434,73 @@ impl Parser {
                 continue;
             }
 
+            // Check for comment (explicit markup that matched nothing above)
+            if let Some(first) = comment_start(line) {
+                // An empty comment followed by a blank line is just that line
+                let (content, consumed_lines) = if first.is_empty()
+                    && lines.get(i + 1).is_some_and(|next| next.trim().is_empty())
+                {
+                    (String::new(), 0)
+                } else {
+                    self.parse_note_body(first, &lines[i + 1..])
+                };
+                nodes.push(RstNode::Comment {
+                    content,
+                    line: i + 1,
+                });
+                i += consumed_lines + 1;
+                continue;
+            }
+
+            // Check for line block (`|` prefixed lines)
+            if is_line_block_line(line) {
+                let (block_lines, consumed_lines) = self.parse_line_block(&lines[i..]);
+                nodes.push(RstNode::LineBlock {
+                    lines: block_lines,
+                    line: i + 1,
+                });
+                i += consumed_lines;
+                continue;
+            }
+
+            // Check for block quote (indented text not belonging to another construct)
+            if is_indented(line) {
+                let (paragraphs, attribution, consumed_lines) = self.parse_block_quote(&lines[i..]);
+                nodes.push(RstNode::BlockQuote {
+                    paragraphs,
+                    attribution,
+                    line: i + 1,
+                });
+                i += consumed_lines;
+                continue;
+            }
+
+            // Check for transition (a punctuation rule with blank lines around it);
+            // blank lines are skipped above, so only the line after needs checking
+            if is_transition_line(line)
+                && lines.get(i + 1).is_none_or(|next| next.trim().is_empty())
+            {
+                nodes.push(RstNode::Transition { line: i + 1 });
+                i += 1;
+                continue;
+            }
+
             // Check for title (underlined with =, -, ~, etc.)
             if i + 1 < lines.len() {
                 let next_line = lines[i + 1];
                 if !next_line.trim().is_empty()
                     && next_line.chars().all(|c| "=-~^\"'*+#<>".contains(c))
-                    && next_line.len() >= trimmed.len()
+                    && next_line.chars().count() >= trimmed.chars().count()
                 {
-                    let level = self.get_rst_title_level(next_line.chars().next().unwrap());
+                    let style = next_line.chars().next().unwrap();
+                    let styles = &mut includes.title_styles;
+                    let level = match styles.iter().position(|&known| known == style) {
+                        Some(position) => position + 1,
+                        None => {
+                            styles.push(style);
+                            styles.len()
+                        }
+                    };
                     nodes.push(RstNode::Title {
                         text: trimmed.to_string(),
                         level,
@@ -136,73 +512,576 @@ impl Parser {
                 }
             }
 
-            // Check for code block (indented text after ::)
-            if line.ends_with("::") {
-                let (code_content, consumed_lines) = self.parse_code_block(&lines[i + 1..]);
-                nodes.push(RstNode::CodeBlock {
-                    language: None,
-                    content: code_content,
-                    line: i + 1,
-                });
-                i += consumed_lines + 1;
+            // Check for bullet or enumerated list
+            if list_marker(line).is_some() {
+                let (list, consumed_lines) = self.parse_list(&lines[i..], i + 1);
+                nodes.push(list);
+                i += consumed_lines;
+                continue;
+            }
+
+            // Check for definition list (a term directly followed by an indented definition)
+            if self.is_definition_term(&lines[i..]) {
+                let (items, consumed_lines) = self.parse_definition_list(&lines[i..]);
+                nodes.push(RstNode::DefinitionList { items, line: i + 1 });
+                i += consumed_lines;
                 continue;
             }
 
             // Default to paragraph
-            let (paragraph_content, consumed_lines) = self.parse_paragraph(&lines[i..]);
-            nodes.push(RstNode::Paragraph {
-                content: paragraph_content,
-                line: i + 1,
-            });
+            let (paragraph_content, consumed_lines, literal_follows) =
+                self.parse_paragraph(&lines[i..]);
+            if !paragraph_content.is_empty() {
+                nodes.push(RstNode::Paragraph {
+                    content: paragraph_content,
+                    line: i + 1,
+                });
+            }
             i += consumed_lines;
+
+            // Check for code block (indented text after ::)
+            if literal_follows {
+                let (code_content, consumed_lines) = self.parse_code_block(&lines[i..]);
+                if !code_content.is_empty() {
+                    nodes.push(RstNode::CodeBlock {
+                        language: None,
+                        content: code_content,
+                        line: i,
+                    });
+                }
+                i += consumed_lines;
+            }
         }
 
-        Ok(DocumentContent::RestructuredText(RstContent {
-            raw: content.to_string(),
-            ast: nodes,
-            directives,
-        }))
+        Ok((
+            RstContent {
+                raw: content.to_string(),
+                ast: nodes,
+                directives,
+            },
+            docinfo,
+        ))
+    }
+
+    /// Parse the file an `include` directive in `file_path` names, for
+    /// splicing into the including document
+    ///
+    /// Unreadable files are warned about and circular inclusion is reported
+    /// as an error; either way nothing is included.
+    fn include_rst(
+        &self,
+        file_path: &Path,
+        directive: &RstDirective,
+        includes: &mut Includes,
+    ) -> Result<Option<RstContent>> {
+        let include = Directive {
+            name: directive.name.clone(),
+            arguments: directive.args.clone(),
+            options: directive.options.clone(),
+            content: Vec::new(),
+            line_number: directive.line,
+            source_file: file_path.to_string_lossy().into_owned(),
+            source_root: self.source_root.clone(),
+        };
+        let warn = |includes: &mut Includes, message: String| {
+            includes.warnings.push(BuildWarning::new(
+                file_path.to_path_buf(),
+                Some(directive.line),
+                format!("error in \"include\" directive: {}", message),
+                WarningType::Other,
+            ));
+        };
+
+        let Some(name) = directive.args.first().map(|name| name.trim()) else {
+            warn(includes, "missing file name".to_string());
+            return Ok(None);
+        };
+        // `<name>` includes come with docutils, not the project
+        if name.starts_with('<') {
+            warn(
+                includes,
+                format!("standard include '{}' is not available", name),
+            );
+            return Ok(None);
+        }
+
+        let path = resolve_include_path(&include, name);
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.clone());
+        if includes.stack.contains(&canonical) {
+            let chain: Vec<String> = std::iter::once(&canonical)
+                .chain(includes.stack.iter().rev())
+                .map(|path| path.display().to_string())
+                .collect();
+            includes.errors.push(BuildErrorReport::new(
+                file_path.to_path_buf(),
+                Some(directive.line),
+                format!(
+                    "circular inclusion in \"include\" directive: {}",
+                    chain.join(" < ")
+                ),
+                ErrorType::ParseError,
+            ));
+            return Ok(None);
+        }
+
+        // Included files share the document's encoding unless they name theirs
+        let text = if directive.options.contains_key("encoding") {
+            read_include_file(&include, name)
+        } else {
+            self.read_source(&path)
+                .map_err(|e| anyhow::anyhow!("cannot read {}: {}", path.display(), e))
+        };
+        let text = match text {
+            Ok(text) => select_include_lines(&normalize_source(&text), &directive.options),
+            Err(e) => {
+                warn(includes, e.to_string());
+                return Ok(None);
+            }
+        };
+
+        includes.stack.push(canonical);
+        let parsed = self.parse_rst(&path, &text, includes);
+        includes.stack.pop();
+        includes.texts.push(text);
+        let (content, _docinfo) = parsed?;
+        Ok(Some(content))
+    }
+
+    /// Parse a list starting at its first item on line `line`, returning the list
+    /// and lines consumed; more deeply indented items form nested lists
+    fn parse_list(&self, lines: &[&str], line: usize) -> (RstNode, usize) {
+        let first = list_marker(lines[0]).expect("list starts with an item");
+        let mut items = Vec::new();
+        let mut i = 0;
+
+        while let Some(marker) = lines.get(i).and_then(|line| list_marker(line)) {
+            if marker.indent != first.indent || marker.bullet != first.bullet {
+                break;
+            }
+            let mut item = ListItem {
+                text: lines[i][marker.text_offset..].trim().to_string(),
+                children: Vec::new(),
+            };
+            i += 1;
+
+            // The item's body: anything indented past its marker, blank lines included
+            while let Some(next) = lines.get(i) {
+                if next.trim().is_empty() {
+                    let continues = lines[i..]
+                        .iter()
+                        .find(|line| !line.trim().is_empty())
+                        .is_some_and(|line| indentation(line) > marker.indent);
+                    if !continues {
+                        break;
+                    }
+                    i += 1;
+                } else if indentation(next) <= marker.indent {
+                    break;
+                } else if list_marker(next).is_some() {
+                    let (nested, consumed_lines) = self.parse_list(&lines[i..], line + i);
+                    item.children.push(nested);
+                    i += consumed_lines;
+                } else {
+                    if !item.text.is_empty() {
+                        item.text.push(' ');
+                    }
+                    item.text.push_str(next.trim());
+                    i += 1;
+                }
+            }
+            items.push(item);
+
+            // Blank lines may separate the items
+            let next_item = (i..lines.len()).find(|&j| !lines[j].trim().is_empty());
+            match next_item.and_then(|j| list_marker(lines[j]).map(|marker| (j, marker))) {
+                Some((j, marker))
+                    if marker.indent == first.indent && marker.bullet == first.bullet =>
+                {
+                    i = j
+                }
+                _ => break,
+            }
+        }
+
+        let list = RstNode::List {
+            items,
+            ordered: first.bullet.is_none(),
+            line,
+        };
+        (list, i)
+    }
+
+    /// Collect the indented body of a footnote or citation, returning the text and lines consumed
+    fn parse_note_body(&self, first: &str, lines: &[&str]) -> (String, usize) {
+        let mut content = first.trim().to_string();
+        let mut consumed_lines = 0;
+
+        while let Some(line) = lines.get(consumed_lines) {
+            let blank_before_indented = line.trim().is_empty()
+                && lines
+                    .get(consumed_lines + 1)
+                    .is_some_and(|next| is_indented(next));
+
+            if is_indented(line) {
+                if !content.is_empty() {
+                    content.push(' ');
+                }
+                content.push_str(line.trim());
+            } else if !blank_before_indented {
+                break;
+            }
+            consumed_lines += 1;
+        }
+
+        (content, consumed_lines)
+    }
+
+    /// Collect a line block, returning its lines and the lines consumed
+    ///
+    /// Indented lines without a `|` continue the previous line.
+    fn parse_line_block(&self, lines: &[&str]) -> (Vec<String>, usize) {
+        let mut block: Vec<String> = Vec::new();
+        let mut consumed_lines = 0;
+
+        for line in lines {
+            if is_line_block_line(line) {
+                let text = &line[1..];
+                block.push(
+                    text.strip_prefix(' ')
+                        .unwrap_or(text)
+                        .trim_end()
+                        .to_string(),
+                );
+            } else if is_indented(line) && !block.is_empty() {
+                let last = block.last_mut().unwrap();
+                last.push(' ');
+                last.push_str(line.trim());
+            } else {
+                break;
+            }
+            consumed_lines += 1;
+        }
+
+        (block, consumed_lines)
+    }
+
+    /// Collect an indented block quote, returning its paragraphs, attribution and lines consumed
+    fn parse_block_quote(&self, lines: &[&str]) -> (Vec<String>, Option<String>, usize) {
+        let mut paragraphs = vec![String::new()];
+        let mut consumed_lines = 0;
+
+        while let Some(line) = lines.get(consumed_lines) {
+            if is_indented(line) {
+                let paragraph = paragraphs.last_mut().unwrap();
+                if !paragraph.is_empty() {
+                    paragraph.push(' ');
+                }
+                paragraph.push_str(line.trim());
+            } else if line.trim().is_empty()
+                && lines
+                    .get(consumed_lines + 1)
+                    .is_some_and(|next| is_indented(next))
+            {
+                paragraphs.push(String::new());
+            } else {
+                break;
+            }
+            consumed_lines += 1;
+        }
+        paragraphs.retain(|paragraph| !paragraph.is_empty());
+
+        // A final paragraph starting with "--", "---" or an em dash is the attribution
+        let attribution = paragraphs.last().and_then(|last| {
+            ["---", "--", "\u{2014}"]
+                .iter()
+                .find_map(|dash| last.strip_prefix(dash))
+                .filter(|rest| rest.starts_with(' '))
+                .map(|rest| rest.trim().to_string())
+        });
+        if attribution.is_some() {
+            paragraphs.pop();
+        }
+
+        (paragraphs, attribution, consumed_lines)
+    }
+
+    /// Whether `lines` start with a definition list term: an unindented line that is
+    /// not another construct, followed directly by an indented definition
+    fn is_definition_term(&self, lines: &[&str]) -> bool {
+        let Some(term) = lines.first() else {
+            return false;
+        };
+
+        !term.trim().is_empty()
+            && !is_indented(term)
+            && !term.starts_with("..")
+            && !term.trim_end().ends_with("::")
+            && !self.field_regex.is_match(term)
+            && lines.get(1).is_some_and(|next| is_indented(next))
+    }
+
+    /// Collect consecutive definition list items, returning them and the lines consumed
+    fn parse_definition_list(&self, lines: &[&str]) -> (Vec<DefinitionItem>, usize) {
+        let mut items = Vec::new();
+        let mut consumed_lines = 0;
+        let mut i = 0;
+
+        while self.is_definition_term(&lines[i..]) {
+            let mut parts = lines[i].trim().split(" : ").map(str::trim);
+            let term = parts.next().unwrap_or_default().to_string();
+            let classifiers = parts.map(str::to_string).collect();
+
+            let mut definition = vec![String::new()];
+            i += 1;
+            while let Some(line) = lines.get(i) {
+                if is_indented(line) {
+                    let paragraph = definition.last_mut().unwrap();
+                    if !paragraph.is_empty() {
+                        paragraph.push(' ');
+                    }
+                    paragraph.push_str(line.trim());
+                } else if lines.get(i + 1).is_some_and(|next| is_indented(next))
+                    && line.trim().is_empty()
+                {
+                    definition.push(String::new());
+                } else {
+                    break;
+                }
+                i += 1;
+            }
+            definition.retain(|paragraph| !paragraph.is_empty());
+
+            items.push(DefinitionItem {
+                term,
+                classifiers,
+                definition,
+            });
+            consumed_lines = i;
+
+            // Items may be separated by blank lines
+            while lines.get(i).is_some_and(|line| line.trim().is_empty()) {
+                i += 1;
+            }
+        }
+
+        (items, consumed_lines)
+    }
+
+    /// Collect a field list (with indented continuation lines), returning the lines consumed
+    fn parse_field_list(&self, lines: &[&str], fields: &mut Vec<(String, String)>) -> usize {
+        let mut consumed = 0;
+
+        for line in lines {
+            if let Some(captures) = self.field_regex.captures(line) {
+                let name = captures[1].trim().to_string();
+                let value = captures.get(2).map_or("", |m| m.as_str()).trim();
+                fields.push((name, value.to_string()));
+            } else if line.starts_with([' ', '\t']) && !line.trim().is_empty() {
+                match fields.last_mut() {
+                    Some((_, value)) => {
+                        if !value.is_empty() {
+                            value.push(' ');
+                        }
+                        value.push_str(line.trim());
+                    }
+                    None => break,
+                }
+            } else {
+                break;
+            }
+            consumed += 1;
+        }
+
+        consumed
     }
 
-    fn parse_markdown(&self, content: &str) -> Result<DocumentContent> {
+    /// Parse Markdown whose `body` follows the already parsed front matter
+    fn parse_markdown(
+        &self,
+        content: &str,
+        body: &str,
+        front_matter: Option<serde_yaml::Value>,
+    ) -> Result<DocumentContent> {
+        // The body starts after the front matter
+        let first_line = content[..content.len() - body.len()].lines().count() + 1;
+        let line_at = |offset: usize| first_line + body[..offset].matches('\n').count();
         let mut nodes = Vec::new();
-        let parser = MarkdownParser::new(content);
-        let current_line = 1;
 
-        for event in parser {
+        // Blocks being read: a heading's text, a code block's language and
+        // content, a list's items and a table's cells
+        let mut heading: Option<(usize, String, usize)> = None;
+        let mut code: Option<(Option<String>, String, usize)> = None;
+        let mut list: Option<(bool, Vec<String>, usize)> = None;
+        let mut table: Option<(Vec<String>, Vec<Vec<String>>, usize)> = None;
+        let (mut list_depth, mut quote_depth, mut in_table_head) = (0, 0, false);
+        // Source span of the inline markup of the paragraph, item or cell
+        // being read, kept as written so the renderer formats it
+        let mut inline: Option<std::ops::Range<usize>> = None;
+        let mut paragraph_line = first_line;
+
+        let inline_source = |span: Option<std::ops::Range<usize>>, quoted: bool| {
+            let Some(span) = span else {
+                return String::new();
+            };
+            body[span]
+                .lines()
+                .map(|line| match quoted {
+                    true => line.trim().trim_start_matches('>').trim(),
+                    false => line.trim(),
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+
+        let options = Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH;
+        for (event, range) in MarkdownParser::new_ext(body, options).into_offset_iter() {
             match event {
-                Event::Start(Tag::Heading { .. }) => {
-                    // We'll handle this in the text event
+                Event::Start(Tag::Heading { level, .. }) => {
+                    heading = Some((level as usize, String::new(), line_at(range.start)));
                 }
-                Event::End(_) => {
-                    // Handle end tags generically
+                Event::End(TagEnd::Heading(_)) => {
+                    if let Some((level, text, line)) = heading.take() {
+                        nodes.push(MarkdownNode::Heading {
+                            text: text.trim().to_string(),
+                            level,
+                            line,
+                        });
+                    }
                 }
-                Event::Start(Tag::Paragraph) => {
-                    // Start of paragraph
+                Event::Start(Tag::CodeBlock(kind)) => {
+                    let language = match kind {
+                        CodeBlockKind::Fenced(info) => {
+                            info.split_whitespace().next().map(str::to_string)
+                        }
+                        CodeBlockKind::Indented => None,
+                    };
+                    code = Some((language, String::new(), line_at(range.start)));
                 }
-                Event::Start(Tag::CodeBlock(_)) => {
-                    // Start of code block
+                Event::End(TagEnd::CodeBlock) => {
+                    if let Some((language, content, line)) = code.take() {
+                        nodes.push(MarkdownNode::CodeBlock {
+                            language,
+                            content: content.trim_end_matches('\n').to_string(),
+                            line,
+                        });
+                    }
                 }
-                Event::Text(text) => {
-                    // Handle text content based on context
-                    nodes.push(MarkdownNode::Paragraph {
-                        content: text.to_string(),
-                        line: current_line,
-                    });
+                Event::Text(text) if code.is_some() => {
+                    if let Some((_, content, _)) = &mut code {
+                        content.push_str(&text);
+                    }
+                }
+                Event::Text(text) | Event::Code(text) if heading.is_some() => {
+                    if let Some((_, heading_text, _)) = &mut heading {
+                        heading_text.push_str(&text);
+                    }
+                }
+                Event::SoftBreak if heading.is_some() => {
+                    if let Some((_, heading_text, _)) = &mut heading {
+                        heading_text.push(' ');
+                    }
+                }
+                Event::Start(Tag::Paragraph) if list_depth == 0 => {
+                    inline = None;
+                    paragraph_line = line_at(range.start);
+                }
+                Event::End(TagEnd::Paragraph) if list_depth == 0 => {
+                    if inline.is_some() {
+                        nodes.push(MarkdownNode::Paragraph {
+                            content: inline_source(inline.take(), quote_depth > 0),
+                            line: paragraph_line,
+                        });
+                    }
+                }
+                Event::Start(Tag::BlockQuote(_)) => quote_depth += 1,
+                Event::End(TagEnd::BlockQuote(_)) => quote_depth -= 1,
+                // Nested lists are flattened into the items of the outermost
+                Event::Start(Tag::List(start)) => {
+                    list_depth += 1;
+                    match &mut list {
+                        Some((_, items, _)) if inline.is_some() => {
+                            items.push(inline_source(inline.take(), quote_depth > 0));
+                        }
+                        Some(_) => {}
+                        None => list = Some((start.is_some(), Vec::new(), line_at(range.start))),
+                    }
+                }
+                Event::End(TagEnd::List(_)) => {
+                    list_depth -= 1;
+                    if list_depth == 0 {
+                        if let Some((ordered, items, line)) = list.take() {
+                            nodes.push(MarkdownNode::List {
+                                items,
+                                ordered,
+                                line,
+                            });
+                        }
+                    }
+                }
+                Event::Start(Tag::Item) | Event::Start(Tag::TableCell) => inline = None,
+                Event::End(TagEnd::Item) => {
+                    if let (Some((_, items, _)), Some(_)) = (&mut list, &inline) {
+                        items.push(inline_source(inline.take(), quote_depth > 0));
+                    }
+                }
+                Event::Start(Tag::Table(_)) => {
+                    table = Some((Vec::new(), Vec::new(), line_at(range.start)));
+                }
+                Event::End(TagEnd::Table) => {
+                    if let Some((headers, rows, line)) = table.take() {
+                        nodes.push(MarkdownNode::Table {
+                            headers,
+                            rows,
+                            line,
+                        });
+                    }
                 }
-                Event::Code(_code) => {
-                    // Inline code
+                Event::Start(Tag::TableHead) => in_table_head = true,
+                Event::End(TagEnd::TableHead) => in_table_head = false,
+                Event::Start(Tag::TableRow) => {
+                    if let Some((_, rows, _)) = &mut table {
+                        rows.push(Vec::new());
+                    }
+                }
+                Event::End(TagEnd::TableCell) => {
+                    let cell = inline_source(inline.take(), false);
+                    match (&mut table, in_table_head) {
+                        (Some((headers, _, _)), true) => headers.push(cell),
+                        (Some((_, rows, _)), false) => {
+                            if let Some(row) = rows.last_mut() {
+                                row.push(cell);
+                            }
+                        }
+                        (None, _) => {}
+                    }
                 }
-                _ => {
-                    // Handle other events as needed
+                // Inline markup, kept by the span of source it covers
+                Event::Start(
+                    Tag::Emphasis
+                    | Tag::Strong
+                    | Tag::Strikethrough
+                    | Tag::Link { .. }
+                    | Tag::Image { .. },
+                )
+                | Event::Text(_)
+                | Event::Code(_)
+                | Event::InlineMath(_)
+                | Event::InlineHtml(_)
+                | Event::SoftBreak
+                | Event::HardBreak => {
+                    inline = Some(match inline {
+                        Some(span) => span.start.min(range.start)..span.end.max(range.end),
+                        None => range,
+                    });
                 }
+                _ => {}
             }
         }
 
         Ok(DocumentContent::Markdown(MarkdownContent {
             raw: content.to_string(),
             ast: nodes,
-            front_matter: None, // TODO: Parse YAML front matter
+            front_matter,
         }))
     }
 
@@ -218,31 +1097,32 @@ impl Parser {
         let mut consumed_lines = 1;
         let mut i = 1;
 
-        // Parse options (lines starting with :option:)
+        // Parse options: indented `:name: value` lines directly after the
+        // directive line, up to the first blank line; at any indentation, so a
+        // misindented option isn't mistaken for content
         while i < lines.len() {
             let line = lines[i];
-            if line.trim().is_empty() {
-                i += 1;
-                consumed_lines += 1;
-                continue;
-            }
+            let Some(stripped) = line
+                .strip_prefix([' ', '\t'])
+                .and_then(|rest| rest.trim_start().strip_prefix(':'))
+            else {
+                break;
+            };
 
-            if let Some(stripped) = line.strip_prefix("   :") {
-                // This is an option
-                if let Some(colon_pos) = stripped.find(':') {
+            match stripped.find(':') {
+                Some(colon_pos) => {
                     let option_name = &stripped[..colon_pos];
                     let option_value = stripped[colon_pos + 1..].trim();
                     options.insert(option_name.to_string(), option_value.to_string());
                 }
-                i += 1;
-                consumed_lines += 1;
-            } else if line.starts_with("   ") || line.starts_with("\t") {
-                // This is content
-                break;
-            } else {
-                // End of directive
-                break;
+                // Keep a malformed option line (`:name` without its closing
+                // colon) under its raw text, so validation can report it
+                None => {
+                    options.insert(format!(":{}", stripped.trim_end()), String::new());
+                }
             }
+            i += 1;
+            consumed_lines += 1;
         }
 
         // Parse content (indented lines)
@@ -254,7 +1134,10 @@ impl Parser {
                 i += 1;
                 consumed_lines += 1;
             } else if line.trim().is_empty() {
-                content.push('\n');
+                // Blank lines before the content separate it from the options
+                if !content.is_empty() {
+                    content.push('\n');
+                }
                 i += 1;
                 consumed_lines += 1;
             } else {
@@ -277,36 +1160,35 @@ impl Parser {
         Ok((directive, consumed_lines))
     }
 
-    fn get_rst_title_level(&self, char: char) -> usize {
-        match char {
-            '#' => 1,
-            '*' => 2,
-            '=' => 3,
-            '-' => 4,
-            '^' => 5,
-            '"' => 6,
-            _ => 7,
-        }
-    }
-
+    /// The indented block starting at `lines`, dedented, and how many lines it
+    /// (and the blank lines around it) took up
     fn parse_code_block(&self, lines: &[&str]) -> (String, usize) {
-        let mut content = String::new();
-        let mut consumed_lines = 0;
-
-        for line in lines {
-            if line.starts_with("   ") || line.starts_with("\t") || line.trim().is_empty() {
-                content.push_str(line);
-                content.push('\n');
-                consumed_lines += 1;
-            } else {
-                break;
-            }
-        }
+        let consumed_lines = lines
+            .iter()
+            .take_while(|line| is_indented(line) || line.trim().is_empty())
+            .count();
+        let block = &lines[..consumed_lines];
+        let width = block
+            .iter()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| indentation(line))
+            .min()
+            .unwrap_or(0);
+        let content = block
+            .iter()
+            .map(|line| line.get(width..).unwrap_or("").trim_end())
+            .collect::<Vec<_>>()
+            .join("\n");
 
-        (content.trim().to_string(), consumed_lines)
+        (content.trim_matches('\n').to_string(), consumed_lines)
     }
 
-    fn parse_paragraph(&self, lines: &[&str]) -> (String, usize) {
+    /// The paragraph starting at `lines`, how many lines it took up, and
+    /// whether it ends in `::` and so introduces a literal block
+    ///
+    /// As in docutils, the `::` becomes a single `:`, or disappears when it
+    /// stands apart from the text.
+    fn parse_paragraph(&self, lines: &[&str]) -> (String, usize, bool) {
         let mut content = String::new();
         let mut consumed_lines = 0;
 
@@ -321,39 +1203,81 @@ impl Parser {
             consumed_lines += 1;
         }
 
-        (content.trim().to_string(), consumed_lines)
+        let content = content.trim();
+        match content.strip_suffix("::") {
+            Some(text) if text.is_empty() || text.ends_with(char::is_whitespace) => {
+                (text.trim_end().to_string(), consumed_lines, true)
+            }
+            Some(text) => (format!("{}:", text), consumed_lines, true),
+            None => (content.to_string(), consumed_lines, false),
+        }
     }
 
-    fn extract_title(&self, content: &DocumentContent) -> String {
-        match content {
-            DocumentContent::RestructuredText(rst) => {
-                for node in &rst.ast {
-                    if let RstNode::Title { text, level: 1, .. } = node {
-                        return text.clone();
-                    }
-                }
-            }
-            DocumentContent::Markdown(md) => {
-                for node in &md.ast {
-                    if let MarkdownNode::Heading { text, level: 1, .. } = node {
-                        return text.clone();
-                    }
-                }
+    /// The first level-1 heading, or else the first heading of any level (for
+    /// section-style pages), with its level
+    fn extract_title(&self, content: &DocumentContent) -> (String, Option<usize>) {
+        // A front matter title takes the place of the first heading
+        if let DocumentContent::Markdown(md) = content {
+            let title = md
+                .front_matter
+                .as_ref()
+                .and_then(|front_matter| front_matter.get("title"))
+                .and_then(yaml_scalar);
+            if let Some(title) = title {
+                return (title, Some(1));
             }
-            DocumentContent::PlainText(_) => {}
         }
 
-        "Untitled".to_string()
+        let headings: Vec<(&String, usize)> = match content {
+            DocumentContent::RestructuredText(rst) => rst
+                .ast
+                .iter()
+                .filter_map(|node| match node {
+                    RstNode::Title { text, level, .. } => Some((text, *level)),
+                    _ => None,
+                })
+                .collect(),
+            DocumentContent::Markdown(md) => md
+                .ast
+                .iter()
+                .filter_map(|node| match node {
+                    MarkdownNode::Heading { text, level, .. } => Some((text, *level)),
+                    _ => None,
+                })
+                .collect(),
+            DocumentContent::PlainText(_) => Vec::new(),
+        };
+
+        match headings
+            .iter()
+            .find(|(_, level)| *level == 1)
+            .or(headings.first())
+        {
+            Some((text, level)) => (text.to_string(), Some(*level)),
+            None => ("Untitled".to_string(), None),
+        }
     }
 
+    /// The document's headings, with anchors unique among themselves and the
+    /// ids of its internal hyperlink targets, which keep their own names
     fn extract_toc(&self, content: &DocumentContent) -> Vec<TocEntry> {
         let mut toc = Vec::new();
+        let mut used_anchors = HashSet::new();
 
         match content {
             DocumentContent::RestructuredText(rst) => {
+                used_anchors.extend(rst.ast.iter().filter_map(|node| match node {
+                    RstNode::Target {
+                        name, refuri: None, ..
+                    } => Some(name_to_id(name)),
+                    _ => None,
+                }));
                 for node in &rst.ast {
                     if let RstNode::Title { text, level, line } = node {
-                        let anchor = text.to_lowercase().replace(' ', "-");
+                        let anchor = utils::unique_anchor(
+                            &mut used_anchors,
+                            &text.to_lowercase().replace(' ', "-"),
+                        );
                         toc.push(TocEntry::new(text.clone(), *level, anchor, *line));
                     }
                 }
@@ -361,7 +1285,10 @@ impl Parser {
             DocumentContent::Markdown(md) => {
                 for node in &md.ast {
                     if let MarkdownNode::Heading { text, level, line } = node {
-                        let anchor = text.to_lowercase().replace(' ', "-");
+                        let anchor = utils::unique_anchor(
+                            &mut used_anchors,
+                            &text.to_lowercase().replace(' ', "-"),
+                        );
                         toc.push(TocEntry::new(text.clone(), *level, anchor, *line));
                     }
                 }
@@ -398,3 +1325,576 @@ impl Parser {
         Ok(output_path)
     }
 }
+
+/// The text after `..` of a comment line, or `None` if the line is not a
+/// comment; hyperlink targets (`.. _name:`) and substitution definitions
+/// (`.. |name| ...`) are not comments
+fn comment_start(line: &str) -> Option<&str> {
+    let rest = line.strip_prefix("..")?;
+    if !rest.is_empty() && !rest.starts_with([' ', '\t']) {
+        return None;
+    }
+    let rest = rest.trim();
+    (!rest.starts_with('_') && !rest.starts_with('|')).then_some(rest)
+}
+
+/// The name of an internal hyperlink target (`.. _name:`); targets with a
+/// URL after the colon are external and not labels
+fn internal_target(line: &str) -> Option<&str> {
+    let name = line
+        .trim_end()
+        .strip_prefix(".. _")?
+        .strip_suffix(':')?
+        .trim();
+    let name = name
+        .strip_prefix('`')
+        .and_then(|name| name.strip_suffix('`'))
+        .unwrap_or(name);
+    (!name.is_empty() && !name.contains(": ")).then_some(name)
+}
+
+/// The name and URI of an external hyperlink target (`.. _name: uri`)
+fn external_target(line: &str) -> Option<(&str, &str)> {
+    let rest = line.trim_end().strip_prefix(".. _")?;
+    let (name, uri) = match rest.strip_prefix('`') {
+        Some(quoted) => quoted.split_once("`:")?,
+        None => rest.split_once(": ")?,
+    };
+    let (name, uri) = (name.trim(), uri.trim());
+    (!name.is_empty() && !uri.is_empty()).then_some((name, uri))
+}
+
+/// Whether a line is non-blank and indented
+fn is_indented(line: &str) -> bool {
+    line.starts_with([' ', '\t']) && !line.trim().is_empty()
+}
+
+fn indentation(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+/// Where a list item's marker sits and its text starts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ListMarker {
+    indent: usize,
+    text_offset: usize,
+    /// The bullet character; `None` for an enumerated item
+    bullet: Option<char>,
+}
+
+/// The marker of a bullet (`- `, `* `, `+ `) or enumerated (`1. `, `#. `,
+/// `1) `) list item starting the line, if any
+fn list_marker(line: &str) -> Option<ListMarker> {
+    let indent = indentation(line);
+    let rest = &line[indent..];
+    let (marker_len, bullet) = match rest.chars().next()? {
+        c @ ('-' | '*' | '+') => (1, Some(c)),
+        _ => {
+            let enumerator = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .filter(|&end| end > 0)
+                .or_else(|| rest.starts_with('#').then_some(1))?;
+            if !rest[enumerator..].starts_with(['.', ')']) {
+                return None;
+            }
+            (enumerator + 1, None)
+        }
+    };
+    let after = &rest[marker_len..];
+    if after.is_empty() || after.starts_with([' ', '\t']) {
+        Some(ListMarker {
+            indent,
+            text_offset: indent + marker_len,
+            bullet,
+        })
+    } else {
+        None
+    }
+}
+
+/// Whether a line is 4+ repetitions of a single punctuation character
+fn is_transition_line(line: &str) -> bool {
+    let line = line.trim_end();
+    let mut chars = line.chars();
+    chars.next().is_some_and(|first| {
+        first.is_ascii_punctuation() && line.len() >= 4 && chars.all(|c| c == first)
+    })
+}
+
+/// Whether a line belongs to a line block (`|` followed by a space or nothing)
+fn is_line_block_line(line: &str) -> bool {
+    line.strip_prefix('|')
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with(' '))
+}
+
+/// Whether a bracketed label names a footnote (`1`, `#`, `#name`, `*`) rather than a citation
+pub fn is_footnote_label(label: &str) -> bool {
+    label == "*" || label.starts_with('#') || label.chars().all(|c| c.is_ascii_digit())
+}
+
+/// Populate document metadata from RST docinfo fields
+///
+/// Every field is kept in `custom` under its lowercased name; well-known fields
+/// (author(s), date, tags, category) also fill the structured metadata.
+fn apply_docinfo(metadata: &mut DocumentMetadata, docinfo: Vec<(String, String)>) {
+    for (name, value) in docinfo {
+        let key = name.to_lowercase();
+        match key.as_str() {
+            "author" => metadata.authors.push(value.clone()),
+            "authors" => metadata.authors.extend(
+                value
+                    .split([';', ','])
+                    .map(str::trim)
+                    .filter(|author| !author.is_empty())
+                    .map(str::to_string),
+            ),
+            "date" => metadata.modified = parse_docinfo_date(&value),
+            "tags" => metadata.tags.extend(
+                value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|tag| !tag.is_empty())
+                    .map(str::to_string),
+            ),
+            "category" => metadata.category = Some(value.clone()),
+            _ => {}
+        }
+        metadata
+            .custom
+            .insert(key, serde_json::Value::String(value));
+    }
+}
+
+/// Split a leading `---`-delimited YAML block off Markdown source, returning
+/// the YAML, if any, and the Markdown after it
+fn split_front_matter(content: &str) -> (Option<&str>, &str) {
+    let Some(rest) = content.strip_prefix("---\n") else {
+        return (None, content);
+    };
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if matches!(line.trim_end(), "---" | "...") {
+            return (Some(&rest[..offset]), &rest[offset + line.len()..]);
+        }
+        offset += line.len();
+    }
+    (None, content)
+}
+
+/// A YAML scalar as text; `None` for mappings, sequences and null
+fn yaml_scalar(value: &serde_yaml::Value) -> Option<String> {
+    match value {
+        serde_yaml::Value::String(text) => Some(text.clone()),
+        serde_yaml::Value::Number(number) => Some(number.to_string()),
+        serde_yaml::Value::Bool(flag) => Some(flag.to_string()),
+        _ => None,
+    }
+}
+
+/// A YAML list of scalars, or a single comma-separated string, as a list
+fn yaml_list(value: &serde_yaml::Value, separators: &[char]) -> Vec<String> {
+    match value {
+        serde_yaml::Value::Sequence(items) => items.iter().filter_map(yaml_scalar).collect(),
+        _ => yaml_scalar(value)
+            .map(|text| {
+                text.split(separators)
+                    .map(str::trim)
+                    .filter(|item| !item.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default(),
+    }
+}
+
+/// Populate document metadata from Markdown front matter, as docinfo fields
+/// do for reStructuredText; every key is kept in the custom metadata
+///
+/// `tags` is descriptive metadata only; `only` holds the tag expression that
+/// decides whether the page is built.
+fn apply_front_matter(metadata: &mut DocumentMetadata, front_matter: &serde_yaml::Value) {
+    let Some(mapping) = front_matter.as_mapping() else {
+        return;
+    };
+    for (key, value) in mapping {
+        let Some(key) = key.as_str().map(str::to_lowercase) else {
+            continue;
+        };
+        match key.as_str() {
+            "author" | "authors" => metadata.authors.extend(yaml_list(value, &[';', ','])),
+            "tags" => metadata.tags.extend(yaml_list(value, &[','])),
+            "category" => metadata.category = yaml_scalar(value),
+            _ => {}
+        }
+        if let Ok(value) = serde_json::to_value(value) {
+            metadata.custom.insert(key, value);
+        }
+    }
+}
+
+/// Add the authors named by `sectionauthor`, `moduleauthor` and `codeauthor`
+/// directives to the metadata, once each
+fn collect_authors(document: &mut Document) {
+    let DocumentContent::RestructuredText(rst) = &document.content else {
+        return;
+    };
+    for node in &rst.ast {
+        if let RstNode::Directive { name, args, .. } = node {
+            if !is_author_directive(name) {
+                continue;
+            }
+            let author = args.join(" ").trim().to_string();
+            if !author.is_empty() && !document.metadata.authors.contains(&author) {
+                document.metadata.authors.push(author);
+            }
+        }
+    }
+}
+
+/// Directives recording who wrote a section, module or code
+pub fn is_author_directive(name: &str) -> bool {
+    matches!(name, "sectionauthor" | "moduleauthor" | "codeauthor")
+}
+
+fn parse_docinfo_date(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|date| date.with_timezone(&Utc))
+        .ok()
+        .or_else(|| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+                .map(|date| date.and_utc())
+        })
+}
+
+/// Strip a leading byte-order mark and normalize CRLF/CR line endings to LF.
+fn normalize_source(content: &str) -> Cow<'_, str> {
+    let content = content.strip_prefix('\u{feff}').unwrap_or(content);
+    if content.contains('\r') {
+        Cow::Owned(content.replace("\r\n", "\n").replace('\r', "\n"))
+    } else {
+        Cow::Borrowed(content)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_source_with_latin1_encoding() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("legacy.rst");
+        // "Café" / "####" encoded as latin-1
+        std::fs::write(&path, b"Caf\xe9\n####\n\nR\xe9sum\xe9 text.\n").unwrap();
+
+        let config = BuildConfig {
+            source_encoding: Some("latin-1".to_string()),
+            ..BuildConfig::default()
+        };
+        let parser = Parser::new(&config).unwrap();
+        let content = parser.read_source(&path).unwrap();
+        let document = parser.parse(&path, &content).unwrap();
+
+        assert_eq!(document.title, "Café");
+        assert!(content.contains("Résumé text."));
+    }
+
+    #[test]
+    fn test_parse_strips_bom_and_normalizes_line_endings() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("windows.rst");
+        let content = "\u{feff}Windows Doc\r\n###########\r\n\r\n.. note::\r\n   :class: tip\r\n\r\n   Body text.\r\n\rTrailing paragraph.\r";
+        std::fs::write(&path, content).unwrap();
+
+        let parser = Parser::new(&BuildConfig::default()).unwrap();
+        let document = parser.parse(&path, content).unwrap();
+
+        assert_eq!(document.title, "Windows Doc");
+        let DocumentContent::RestructuredText(rst) = &document.content else {
+            panic!("expected reStructuredText content");
+        };
+        assert!(!rst.raw.contains('\r'));
+        assert_eq!(rst.directives.len(), 1);
+        assert_eq!(rst.directives[0].name, "note");
+        assert_eq!(rst.directives[0].options["class"], "tip");
+        assert_eq!(rst.directives[0].content, "Body text.");
+    }
+
+    #[test]
+    fn test_docinfo_populates_metadata() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("report.rst");
+        let content = "Report\n######\n\n:Author: Jane\n:Date: 2024-03-01\n:Version: 1.2\n:Abstract: A long\n   abstract.\n\nBody text.\n";
+        std::fs::write(&path, content).unwrap();
+
+        let parser = Parser::new(&BuildConfig::default()).unwrap();
+        let document = parser.parse(&path, content).unwrap();
+
+        assert_eq!(document.metadata.authors, vec!["Jane".to_string()]);
+        assert_eq!(
+            document.metadata.modified.unwrap().date_naive(),
+            NaiveDate::from_ymd_opt(2024, 3, 1).unwrap()
+        );
+        assert_eq!(document.metadata.custom["version"], "1.2");
+        assert_eq!(document.metadata.custom["abstract"], "A long abstract.");
+
+        // The docinfo block is metadata, not body content
+        let DocumentContent::RestructuredText(rst) = &document.content else {
+            panic!("expected reStructuredText content");
+        };
+        assert_eq!(rst.ast.len(), 2);
+    }
+
+    #[test]
+    fn test_read_source_reports_invalid_utf8() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("legacy.rst");
+        std::fs::write(&path, b"Title\n=====\n\nCaf\xe9\n").unwrap();
+
+        let parser = Parser::new(&BuildConfig::default()).unwrap();
+        let message = parser.read_source(&path).unwrap_err().to_string();
+
+        assert!(message.contains("legacy.rst"));
+        assert!(message.contains("invalid utf-8 at line 4"));
+    }
+
+    #[test]
+    fn test_unknown_source_encoding_is_rejected() {
+        let config = BuildConfig {
+            source_encoding: Some("klingon".to_string()),
+            ..BuildConfig::default()
+        };
+        assert!(Parser::new(&config).is_err());
+    }
+
+    #[test]
+    fn test_title_falls_back_to_first_heading_of_any_level() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let parser = Parser::new(&BuildConfig::default()).unwrap();
+
+        let path = temp_dir.path().join("section.rst");
+        let content = "Configuration Options\n---------------------\n\nText.\n\nDetails\n^^^^^^^\n";
+        std::fs::write(&path, content).unwrap();
+        let document = parser.parse(&path, content).unwrap();
+        assert_eq!(document.title, "Configuration Options");
+        assert_eq!(document.title_level, Some(1));
+
+        let path = temp_dir.path().join("notes.rst");
+        let content = "Just text.\n";
+        std::fs::write(&path, content).unwrap();
+        let document = parser.parse(&path, content).unwrap();
+        assert_eq!(document.title, "Untitled");
+        assert_eq!(document.title_level, None);
+    }
+
+    #[test]
+    fn test_markdown_front_matter_sets_title_and_metadata() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let parser = Parser::new(&BuildConfig::default()).unwrap();
+
+        let path = temp_dir.path().join("guide.md");
+        let content = "---\ntitle: The Guide\nauthor: Jane, John\ntags: [intro, setup]\n\
+                       draft: true\n---\n# Heading\n\nBody.\n";
+        std::fs::write(&path, content).unwrap();
+        let (document, warnings, _) = parser.parse_with_diagnostics(&path, content).unwrap();
+
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        assert_eq!(document.title, "The Guide");
+        assert_eq!(document.metadata.authors, vec!["Jane", "John"]);
+        assert_eq!(document.metadata.tags, vec!["intro", "setup"]);
+        assert_eq!(
+            document.metadata.custom.get("draft"),
+            Some(&serde_json::Value::Bool(true))
+        );
+
+        let path = temp_dir.path().join("broken.md");
+        let content = "---\ntitle: Broken\ntags: [intro\n---\n# Heading\n";
+        std::fs::write(&path, content).unwrap();
+        let (document, warnings, _) = parser.parse_with_diagnostics(&path, content).unwrap();
+
+        let DocumentContent::Markdown(md) = &document.content else {
+            panic!("expected Markdown content");
+        };
+        assert!(md.front_matter.is_none());
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].line, Some(4));
+        assert!(warnings[0].message.starts_with("invalid YAML front matter"));
+    }
+
+    #[test]
+    fn test_markdown_headings_code_blocks_and_lists() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let parser = Parser::new(&BuildConfig::default()).unwrap();
+
+        let path = temp_dir.path().join("guide.md");
+        let content = "---\nauthor: Jane\n---\n# Title\n\nSome **bold** and `code`.\n\n\
+                       ## Usage\n\n```python\nprint(1)\n```\n\n- one\n- *two*\n";
+        std::fs::write(&path, content).unwrap();
+        let document = parser.parse(&path, content).unwrap();
+
+        let DocumentContent::Markdown(md) = &document.content else {
+            panic!("expected Markdown content");
+        };
+        assert_eq!(
+            md.ast,
+            vec![
+                MarkdownNode::Heading {
+                    text: "Title".to_string(),
+                    level: 1,
+                    line: 4,
+                },
+                MarkdownNode::Paragraph {
+                    content: "Some **bold** and `code`.".to_string(),
+                    line: 6,
+                },
+                MarkdownNode::Heading {
+                    text: "Usage".to_string(),
+                    level: 2,
+                    line: 8,
+                },
+                MarkdownNode::CodeBlock {
+                    language: Some("python".to_string()),
+                    content: "print(1)".to_string(),
+                    line: 10,
+                },
+                MarkdownNode::List {
+                    items: vec!["one".to_string(), "*two*".to_string()],
+                    ordered: false,
+                    line: 14,
+                },
+            ]
+        );
+        assert_eq!(document.title, "Title");
+        let toc: Vec<(&str, usize)> = document
+            .toc
+            .iter()
+            .map(|entry| (entry.title.as_str(), entry.level))
+            .collect();
+        assert_eq!(toc, [("Title", 1), ("Usage", 2)]);
+    }
+
+    #[test]
+    fn test_include_splices_parsed_file_into_document() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        std::fs::create_dir_all(root.join("guide")).unwrap();
+        std::fs::write(
+            root.join("shared.rst"),
+            "Skipped line\nShared Section\n==============\n\nSee :ref:`intro`.\n\n.. include:: nested.rst\n\nTrailing line\n",
+        )
+        .unwrap();
+        std::fs::write(root.join("nested.rst"), ".. note::\n\n   Nested note.\n").unwrap();
+        let path = root.join("guide/page.rst");
+        let content = "Page\n####\n\n.. include:: /shared.rst\n   :start-line: 1\n   :end-line: -2\n\nAfter.\n";
+        std::fs::write(&path, content).unwrap();
+
+        let mut parser = Parser::new(&BuildConfig::default()).unwrap();
+        parser.set_source_root(root.to_path_buf());
+        let (document, warnings, errors) = parser.parse_with_diagnostics(&path, content).unwrap();
+
+        assert!(warnings.is_empty());
+        assert!(errors.is_empty());
+        let DocumentContent::RestructuredText(rst) = &document.content else {
+            panic!("expected reStructuredText content");
+        };
+        let kinds: Vec<String> = rst
+            .ast
+            .iter()
+            .map(|node| match node {
+                RstNode::Title { text, .. } => format!("title {}", text),
+                RstNode::Paragraph { content, .. } => format!("paragraph {}", content),
+                RstNode::Directive { name, .. } => format!("directive {}", name),
+                other => format!("{:?}", other),
+            })
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                "title Page",
+                "title Shared Section",
+                "paragraph See :ref:`intro`.",
+                "directive note",
+                "paragraph After.",
+            ]
+        );
+        assert_eq!(rst.directives.len(), 1);
+        assert_eq!(document.cross_refs.len(), 1);
+        assert!(document
+            .toc
+            .iter()
+            .any(|entry| entry.title == "Shared Section"));
+    }
+
+    #[test]
+    fn test_circular_include_is_an_error() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        std::fs::write(root.join("a.inc"), "From a.\n\n.. include:: b.inc\n").unwrap();
+        std::fs::write(root.join("b.inc"), "From b.\n\n.. include:: a.inc\n").unwrap();
+        std::fs::write(root.join("c.inc"), "From c.\n").unwrap();
+        let path = root.join("index.rst");
+        let content = "Index\n#####\n\n.. include:: a.inc\n\n.. include:: c.inc\n\n.. include:: missing.inc\n";
+        std::fs::write(&path, content).unwrap();
+
+        let parser = Parser::new(&BuildConfig::default()).unwrap();
+        let (document, warnings, errors) = parser.parse_with_diagnostics(&path, content).unwrap();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].file, root.join("b.inc"));
+        assert_eq!(errors[0].line, Some(3));
+        assert!(errors[0].message.starts_with("circular inclusion"));
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].message.contains("missing.inc"));
+
+        let DocumentContent::RestructuredText(rst) = &document.content else {
+            panic!("expected reStructuredText content");
+        };
+        let paragraphs: Vec<&str> = rst
+            .ast
+            .iter()
+            .filter_map(|node| match node {
+                RstNode::Paragraph { content, .. } => Some(content.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(paragraphs, ["From a.", "From b.", "From c."]);
+    }
+
+    #[test]
+    fn test_paragraph_ending_in_double_colon_introduces_literal_block() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("guide.rst");
+        let content = "Guide\n#####\n\nA paragraph that runs\nover two lines::\n\n   def f():\n       return 1\n\n\
+                       Expanded form ::\n\n   text\n\n::\n\n   bare\n\nAfter.\n";
+        std::fs::write(&path, content).unwrap();
+
+        let parser = Parser::new(&BuildConfig::default()).unwrap();
+        let document = parser.parse(&path, content).unwrap();
+
+        let DocumentContent::RestructuredText(rst) = &document.content else {
+            panic!("expected reStructuredText content");
+        };
+        let nodes: Vec<(&str, &str)> = rst
+            .ast
+            .iter()
+            .filter_map(|node| match node {
+                RstNode::Paragraph { content, .. } => Some(("p", content.as_str())),
+                RstNode::CodeBlock { content, .. } => Some(("code", content.as_str())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            nodes,
+            vec![
+                ("p", "A paragraph that runs over two lines:"),
+                ("code", "def f():\n    return 1"),
+                ("p", "Expanded form"),
+                ("code", "text"),
+                ("code", "bare"),
+                ("p", "After."),
+            ]
+        );
+    }
+}
@@ -1,13 +1,19 @@
 use anyhow::{anyhow, Result};
+use log::{debug, warn};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyModule};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 
-use crate::config::BuildConfig;
+use crate::config::{BuildConfig, DomainIndices, IntersphinxTarget, TlsCacerts};
 
 /// Python configuration parser that can execute conf.py files
 pub struct PythonConfigParser {
     conf_namespace: HashMap<String, serde_json::Value>,
+    /// Run conf.py in the embedded interpreter rather than only pattern-matching
+    /// its assignments
+    execute: bool,
 }
 
 /// Represents a parsed conf.py configuration
@@ -25,6 +31,7 @@ pub struct ConfPyConfig {
     pub templates_path: Vec<String>,
     pub exclude_patterns: Vec<String>,
     pub source_suffix: HashMap<String, String>,
+    pub source_encoding: Option<String>,
     pub root_doc: Option<String>,
     pub language: Option<String>,
     pub locale_dirs: Vec<String>,
@@ -43,6 +50,7 @@ pub struct ConfPyConfig {
     pub html_extra_path: Vec<String>,
     pub html_use_index: Option<bool>,
     pub html_split_index: Option<bool>,
+    pub html_domain_indices: Option<DomainIndices>,
     pub html_copy_source: Option<bool>,
     pub html_show_sourcelink: Option<bool>,
     pub html_sourcelink_suffix: Option<String>,
@@ -112,6 +120,7 @@ pub struct ConfPyConfig {
     pub needs_extensions: HashMap<String, String>,
     pub manpages_url: Option<String>,
     pub nitpicky: Option<bool>,
+    pub suppress_warnings: Vec<String>,
     pub nitpick_ignore: Vec<(String, String)>,
     pub nitpick_ignore_regex: Vec<(String, String)>,
     pub numfig: Option<bool>,
@@ -121,7 +130,7 @@ pub struct ConfPyConfig {
     pub math_eqref_format: Option<String>,
     pub math_numfig: Option<bool>,
     pub tls_verify: Option<bool>,
-    pub tls_cacerts: Option<String>,
+    pub tls_cacerts: Option<TlsCacerts>,
     pub user_agent: Option<String>,
 
     // Internationalization
@@ -139,21 +148,44 @@ impl PythonConfigParser {
     pub fn new() -> Result<Self> {
         let conf_namespace = HashMap::new();
 
-        Ok(Self { conf_namespace })
+        Ok(Self {
+            conf_namespace,
+            execute: true,
+        })
+    }
+
+    /// Choose between executing conf.py with Python (the default) and reading
+    /// its simple assignments by pattern matching
+    pub fn set_execute(&mut self, execute: bool) {
+        self.execute = execute;
     }
 
     /// Parse a conf.py file and extract configuration
     pub fn parse_conf_py<P: AsRef<Path>>(&mut self, conf_py_path: P) -> Result<ConfPyConfig> {
         let conf_py_path = conf_py_path.as_ref();
-        let _conf_dir = conf_py_path
+        let conf_dir = conf_py_path
             .parent()
             .ok_or_else(|| anyhow!("Invalid conf.py path"))?;
 
         // Read the conf.py file
         let conf_py_content = std::fs::read_to_string(conf_py_path)?;
 
-        // For now, implement a simple parser that extracts basic configuration
-        // In a full implementation, this would execute the Python code
+        // Pattern matching is the fallback for a conf.py Python can't run, e.g.
+        // one importing a package that isn't installed
+        if self.execute {
+            match execute_conf_py(conf_py_path, conf_dir, &conf_py_content) {
+                Ok(namespace) => {
+                    debug!("Executed {}", conf_py_path.display());
+                    self.conf_namespace = namespace;
+                    return self.extract_configuration();
+                }
+                Err(e) => warn!(
+                    "Could not execute {}, reading its plain assignments instead: {}",
+                    conf_py_path.display(),
+                    e
+                ),
+            }
+        }
         self.simple_parse_conf_py(&conf_py_content)?;
 
         // Extract configuration values
@@ -180,42 +212,41 @@ impl PythonConfigParser {
 
     /// Parse simple Python assignments
     fn parse_simple_assignment(&self, line: &str) -> Option<(String, serde_json::Value)> {
-        if let Some(eq_pos) = line.find('=') {
-            let key = line[..eq_pos].trim().to_string();
-            let value_str = line[eq_pos + 1..].trim();
+        if let Some((key, value_str)) = line.split_once('=') {
+            let key = key.trim().to_string();
+            let value_str = value_str.trim();
 
             // Parse common value types
-            if value_str.starts_with('"') && value_str.ends_with('"') {
+            if let Some(value) = unquote(value_str) {
                 // String value
-                let value = value_str[1..value_str.len() - 1].to_string();
-                return Some((key, serde_json::Value::String(value)));
-            } else if value_str.starts_with('\'') && value_str.ends_with('\'') {
-                // String value with single quotes
-                let value = value_str[1..value_str.len() - 1].to_string();
-                return Some((key, serde_json::Value::String(value)));
+                return Some((key, serde_json::Value::String(value.to_string())));
             } else if value_str == "True" {
                 return Some((key, serde_json::Value::Bool(true)));
             } else if value_str == "False" {
                 return Some((key, serde_json::Value::Bool(false)));
             } else if let Ok(num) = value_str.parse::<i64>() {
                 return Some((key, serde_json::Value::Number(num.into())));
-            } else if value_str.starts_with('[') && value_str.ends_with(']') {
+            } else if let Some(list_content) = value_str
+                .strip_prefix('[')
+                .and_then(|rest| rest.strip_suffix(']'))
+            {
+                // Lists of dicts, lists or tuples need the full literal parser
+                if list_content.contains(['{', '[', '(']) {
+                    return python_literal_to_json(value_str).map(|value| (key, value));
+                }
+
                 // Simple list parsing
-                let list_content = &value_str[1..value_str.len() - 1];
                 let items: Vec<serde_json::Value> = list_content
                     .split(',')
                     .map(|item| {
                         let item = item.trim();
-                        if (item.starts_with('"') && item.ends_with('"'))
-                            || (item.starts_with('\'') && item.ends_with('\''))
-                        {
-                            serde_json::Value::String(item[1..item.len() - 1].to_string())
-                        } else {
-                            serde_json::Value::String(item.to_string())
-                        }
+                        serde_json::Value::String(unquote(item).unwrap_or(item).to_string())
                     })
                     .collect();
                 return Some((key, serde_json::Value::Array(items)));
+            } else if value_str.starts_with('{') && value_str.ends_with('}') {
+                // Single-line dict literal
+                return python_literal_to_json(value_str).map(|value| (key, value));
             }
         }
         None
@@ -277,7 +308,13 @@ impl PythonConfigParser {
         config.extensions = extract_string_list("extensions");
         config.templates_path = extract_string_list("templates_path");
         config.exclude_patterns = extract_string_list("exclude_patterns");
+        config.source_suffix = self
+            .conf_namespace
+            .get("source_suffix")
+            .map(normalize_source_suffix)
+            .unwrap_or_default();
         config.root_doc = extract_string("root_doc").or_else(|| extract_string("master_doc"));
+        config.source_encoding = extract_string("source_encoding");
         config.language = extract_string("language");
         config.locale_dirs = extract_string_list("locale_dirs");
         config.gettext_compact = extract_bool("gettext_compact");
@@ -295,6 +332,13 @@ impl PythonConfigParser {
         config.html_extra_path = extract_string_list("html_extra_path");
         config.html_use_index = extract_bool("html_use_index");
         config.html_split_index = extract_bool("html_split_index");
+        config.html_domain_indices = match self.conf_namespace.get("html_domain_indices") {
+            Some(serde_json::Value::Bool(all)) => Some(DomainIndices::All(*all)),
+            Some(serde_json::Value::Array(_)) => Some(DomainIndices::Only(extract_string_list(
+                "html_domain_indices",
+            ))),
+            _ => None,
+        };
         config.html_copy_source = extract_bool("html_copy_source");
         config.html_show_sourcelink = extract_bool("html_show_sourcelink");
         config.html_sourcelink_suffix = extract_string("html_sourcelink_suffix");
@@ -319,13 +363,17 @@ impl PythonConfigParser {
         // Extract build options
         config.needs_sphinx = extract_string("needs_sphinx");
         config.nitpicky = extract_bool("nitpicky");
+        config.suppress_warnings = extract_string_list("suppress_warnings");
         config.numfig = extract_bool("numfig");
         config.numfig_secnum_depth = extract_int("numfig_secnum_depth");
         config.math_number_all = extract_bool("math_number_all");
         config.math_eqref_format = extract_string("math_eqref_format");
         config.math_numfig = extract_bool("math_numfig");
         config.tls_verify = extract_bool("tls_verify");
-        config.tls_cacerts = extract_string("tls_cacerts");
+        config.tls_cacerts = self
+            .conf_namespace
+            .get("tls_cacerts")
+            .and_then(|value| serde_json::from_value(value.clone()).ok());
         config.user_agent = extract_string("user_agent");
 
         // Extract internationalization
@@ -357,6 +405,7 @@ impl PythonConfigParser {
                 | "templates_path"
                 | "exclude_patterns"
                 | "source_suffix"
+                | "source_encoding"
                 | "root_doc"
                 | "master_doc"
                 | "language"
@@ -374,6 +423,7 @@ impl PythonConfigParser {
                 | "html_extra_path"
                 | "html_use_index"
                 | "html_split_index"
+                | "html_domain_indices"
                 | "html_copy_source"
                 | "html_show_sourcelink"
                 | "html_sourcelink_suffix"
@@ -396,6 +446,7 @@ impl PythonConfigParser {
                 | "html_math_renderer_options"
                 | "needs_sphinx"
                 | "nitpicky"
+                | "suppress_warnings"
                 | "numfig"
                 | "numfig_secnum_depth"
                 | "math_number_all"
@@ -412,6 +463,52 @@ impl PythonConfigParser {
     }
 }
 
+/// Read `intersphinx_mapping`, whose entries are `(uri, inventory)` pairs;
+/// `inventory` may be `None` or a tuple of locations, of which the first
+/// string is used
+fn normalize_intersphinx_mapping(value: &serde_json::Value) -> HashMap<String, IntersphinxTarget> {
+    let Some(map) = value.as_object() else {
+        return HashMap::new();
+    };
+    map.iter()
+        .filter_map(|(name, entry)| {
+            let entry = entry.as_array()?;
+            let uri = entry.first()?.as_str()?.to_string();
+            let inventory = match entry.get(1) {
+                Some(serde_json::Value::String(location)) => Some(location.clone()),
+                Some(serde_json::Value::Array(locations)) => locations
+                    .iter()
+                    .find_map(|location| location.as_str().map(str::to_string)),
+                _ => None,
+            };
+            Some((name.clone(), IntersphinxTarget { uri, inventory }))
+        })
+        .collect()
+}
+
+/// Normalize `source_suffix` to a suffix -> file type map; Sphinx accepts a
+/// string or a list of suffixes, which are read as reStructuredText
+fn normalize_source_suffix(value: &serde_json::Value) -> HashMap<String, String> {
+    let rst = |suffix: &str| (suffix.to_string(), "restructuredtext".to_string());
+    match value {
+        serde_json::Value::String(suffix) => HashMap::from([rst(suffix)]),
+        serde_json::Value::Array(suffixes) => suffixes
+            .iter()
+            .filter_map(|suffix| suffix.as_str().map(rst))
+            .collect(),
+        serde_json::Value::Object(map) => map
+            .iter()
+            .map(|(suffix, file_type)| {
+                (
+                    suffix.clone(),
+                    file_type.as_str().unwrap_or("restructuredtext").to_string(),
+                )
+            })
+            .collect(),
+        _ => HashMap::new(),
+    }
+}
+
 impl Default for ConfPyConfig {
     fn default() -> Self {
         Self {
@@ -424,6 +521,7 @@ impl Default for ConfPyConfig {
             templates_path: vec!["_templates".to_string()],
             exclude_patterns: Vec::new(),
             source_suffix: HashMap::new(),
+            source_encoding: None,
             root_doc: Some("index".to_string()),
             language: None,
             locale_dirs: vec!["locales".to_string()],
@@ -440,6 +538,7 @@ impl Default for ConfPyConfig {
             html_extra_path: Vec::new(),
             html_use_index: Some(true),
             html_split_index: Some(false),
+            html_domain_indices: None,
             html_copy_source: Some(true),
             html_show_sourcelink: Some(true),
             html_sourcelink_suffix: Some(".txt".to_string()),
@@ -501,6 +600,7 @@ impl Default for ConfPyConfig {
             needs_extensions: HashMap::new(),
             manpages_url: None,
             nitpicky: Some(false),
+            suppress_warnings: Vec::new(),
             nitpick_ignore: Vec::new(),
             nitpick_ignore_regex: Vec::new(),
             numfig: Some(false),
@@ -545,6 +645,20 @@ impl ConfPyConfig {
         if let Some(root_doc) = &self.root_doc {
             config.root_doc = Some(root_doc.clone());
         }
+        if let Some(source_encoding) = &self.source_encoding {
+            config.source_encoding = Some(source_encoding.clone());
+        }
+
+        if !self.source_suffix.is_empty() {
+            config.source_suffix = self.source_suffix.clone();
+        }
+
+        config.exclude_patterns = self.exclude_patterns.clone();
+        config.math_number_all = self.math_number_all.unwrap_or(false);
+        config.html_codeblock_linenos_style = self.html_codeblock_linenos_style.clone();
+        config.tls_verify = self.tls_verify.unwrap_or(true);
+        config.tls_cacerts = self.tls_cacerts.clone();
+        config.user_agent = self.user_agent.clone();
 
         // Map extensions
         config.extensions = self.extensions.clone();
@@ -555,6 +669,7 @@ impl ConfPyConfig {
         // Map static paths
         config.static_dirs = self.html_static_path.iter().map(PathBuf::from).collect();
         config.html_static_path = self.html_static_path.iter().map(PathBuf::from).collect();
+        config.html_extra_path = self.html_extra_path.iter().map(PathBuf::from).collect();
 
         // Map HTML configuration
         if let Some(html_theme) = &self.html_theme {
@@ -590,12 +705,155 @@ impl ConfPyConfig {
         if let Some(html_sourcelink_suffix) = &self.html_sourcelink_suffix {
             config.html_sourcelink_suffix = Some(html_sourcelink_suffix.clone());
         }
+        if let Some(html_link_suffix) = &self.html_link_suffix {
+            config.html_link_suffix = Some(html_link_suffix.clone());
+        }
         if let Some(html_use_index) = self.html_use_index {
             config.html_use_index = Some(html_use_index);
         }
+        if let Some(html_domain_indices) = &self.html_domain_indices {
+            config.html_domain_indices = Some(html_domain_indices.clone());
+        }
         if let Some(html_use_opensearch) = &self.html_use_opensearch {
             config.html_use_opensearch = Some(!html_use_opensearch.is_empty());
         }
+        if let Some(html_baseurl) = &self.html_baseurl {
+            config.html_baseurl = Some(html_baseurl.clone());
+        }
+        if let Some(html_search_shards) = self
+            .custom_configs
+            .get("html_search_shards")
+            .and_then(|v| v.as_u64())
+        {
+            config.html_search_shards = Some(html_search_shards as usize);
+        }
+        if let Some(mapping) = self.custom_configs.get("intersphinx_mapping") {
+            config.intersphinx_mapping = normalize_intersphinx_mapping(mapping);
+        }
+        if let Some(cache_limit) = self
+            .custom_configs
+            .get("intersphinx_cache_limit")
+            .and_then(|v| v.as_i64())
+        {
+            config.intersphinx_cache_limit = cache_limit;
+        }
+        config.intersphinx_timeout = self
+            .custom_configs
+            .get("intersphinx_timeout")
+            .and_then(|v| v.as_f64());
+        if let Some(html_version_path) = self
+            .custom_configs
+            .get("html_version_path")
+            .and_then(|v| v.as_str())
+        {
+            config.html_version_path = Some(html_version_path.to_string());
+        }
+        if let Some(html_opengraph) = self
+            .custom_configs
+            .get("html_opengraph")
+            .and_then(|v| v.as_bool())
+        {
+            config.html_opengraph = Some(html_opengraph);
+        }
+        if let Some(custom_roles) = self
+            .custom_configs
+            .get("custom_roles")
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+        {
+            config.custom_roles = custom_roles;
+        }
+        if let Some(redirects) = self
+            .custom_configs
+            .get("redirects")
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+        {
+            config.redirects = redirects;
+        }
+        if let Some(output_replacements) = self
+            .custom_configs
+            .get("output_replacements")
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+        {
+            config.output_replacements = output_replacements;
+        }
+        if let Some(page_size_budget_kb) = self
+            .custom_configs
+            .get("page_size_budget_kb")
+            .and_then(|v| v.as_u64())
+        {
+            config.page_size_budget_kb = Some(page_size_budget_kb);
+        }
+        if let Some(output_size_budget_mb) = self
+            .custom_configs
+            .get("output_size_budget_mb")
+            .and_then(|v| v.as_f64())
+        {
+            config.output_size_budget_mb = Some(output_size_budget_mb);
+        }
+        if let Some(html_head_extra) = self
+            .custom_configs
+            .get("html_head_extra")
+            .and_then(|v| v.as_str())
+        {
+            config.html_head_extra = Some(html_head_extra.to_string());
+        }
+        if let Some(html_body_end_extra) = self
+            .custom_configs
+            .get("html_body_end_extra")
+            .and_then(|v| v.as_str())
+        {
+            config.html_body_end_extra = Some(html_body_end_extra.to_string());
+        }
+        if let Some(html_copy_code_button) = self
+            .custom_configs
+            .get("html_copy_code_button")
+            .and_then(|v| v.as_bool())
+        {
+            config.html_copy_code_button = html_copy_code_button;
+        }
+        if let Some(show_authors) = self
+            .custom_configs
+            .get("show_authors")
+            .and_then(|v| v.as_bool())
+        {
+            config.show_authors = show_authors;
+        }
+        if let Some(strict_templates) = self
+            .custom_configs
+            .get("strict_templates")
+            .and_then(|v| v.as_bool())
+        {
+            config.strict_templates = strict_templates;
+        }
+        if let Some(localtoc_depth) = self
+            .custom_configs
+            .get("localtoc_depth")
+            .and_then(|v| v.as_u64())
+        {
+            config.localtoc_depth = Some(localtoc_depth as usize);
+        }
+        if let Some(toc_object_entries) = self
+            .custom_configs
+            .get("toc_object_entries")
+            .and_then(|v| v.as_bool())
+        {
+            config.toc_object_entries = Some(toc_object_entries);
+        }
+        if let Some(math_inline_delimiters) = self
+            .custom_configs
+            .get("math_inline_delimiters")
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+        {
+            config.math_inline_delimiters = math_inline_delimiters;
+        } else if self
+            .custom_configs
+            .get("myst_enable_extensions")
+            .and_then(|v| v.as_array())
+            .is_some_and(|extensions| extensions.iter().any(|e| e == "dollarmath"))
+        {
+            // MyST's dollarmath extension: `$...$` is inline math
+            config.math_inline_delimiters = vec![("$".to_string(), "$".to_string())];
+        }
         if let Some(html_last_updated_fmt) = &self.html_context.get("last_updated") {
             if let Some(fmt_str) = html_last_updated_fmt.as_str() {
                 config.html_last_updated_fmt = Some(fmt_str.to_string());
@@ -605,6 +863,354 @@ impl ConfPyConfig {
         // Map templates path
         config.templates_path = self.templates_path.iter().map(PathBuf::from).collect();
 
+        if !self.suppress_warnings.is_empty() {
+            config.suppress_warnings = Some(self.suppress_warnings.clone());
+        }
+
         config
     }
 }
+
+/// Python side of `execute_conf_py`: runs conf.py from its directory and then
+/// puts the working directory, `sys.path` and `sys.modules` back
+const CONF_PY_RUNNER: &std::ffi::CStr = c"
+import os, sys
+
+def run_conf_py(code, namespace, conf_dir):
+    previous_dir = os.getcwd()
+    previous_path = list(sys.path)
+    known_modules = set(sys.modules)
+    if conf_dir not in sys.path:
+        sys.path.insert(0, conf_dir)
+    os.chdir(conf_dir)
+    try:
+        exec(compile(code, namespace['__file__'], 'exec'), namespace)
+    finally:
+        os.chdir(previous_dir)
+        added_paths = [os.path.abspath(entry) for entry in sys.path if entry not in previous_path]
+        sys.path[:] = previous_path
+        # Modules conf.py imported from its own paths would shadow another
+        # project's modules of the same name
+        for name in set(sys.modules) - known_modules:
+            origin = os.path.abspath(getattr(sys.modules[name], '__file__', None) or os.sep)
+            if any(origin.startswith(path + os.sep) for path in added_paths):
+                del sys.modules[name]
+";
+
+/// Serializes conf.py runs; see `execute_conf_py`
+static CONF_PY_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+/// Run conf.py in a fresh namespace of the embedded interpreter and collect its
+/// public globals; modules, functions and classes, and values with no JSON
+/// equivalent, are left out
+///
+/// As in Sphinx, conf.py runs from its own directory, which also leads
+/// `sys.path`: relative paths (`os.path.abspath('..')`) and imports of modules
+/// next to it resolve as they do under `sphinx-build`. The working directory
+/// belongs to the whole process, so while conf.py runs other threads see it
+/// too; runs are serialized behind a global lock, and afterwards the working
+/// directory, `sys.path` and the modules conf.py imported from its own paths
+/// are restored.
+fn execute_conf_py(
+    path: &Path,
+    conf_dir: &Path,
+    content: &str,
+) -> Result<HashMap<String, serde_json::Value>> {
+    let conf_dir = std::path::absolute(conf_dir)?;
+    let _guard = CONF_PY_LOCK
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    Python::with_gil(|py| {
+        let runner = PyDict::new(py);
+        py.run(CONF_PY_RUNNER, Some(&runner), None)?;
+
+        let globals = PyDict::new(py);
+        globals.set_item("__file__", path.to_string_lossy())?;
+        globals.set_item("__name__", "conf")?;
+        runner
+            .get_item("run_conf_py")?
+            .ok_or_else(|| anyhow!("conf.py runner is missing"))?
+            .call1((content, &globals, conf_dir.to_string_lossy()))
+            .map_err(|e| anyhow!("{}", e))?;
+
+        let mut namespace = HashMap::new();
+        for (name, value) in globals.iter() {
+            let name: String = name.extract()?;
+            if name.starts_with('_') || value.is_callable() || value.is_instance_of::<PyModule>() {
+                continue;
+            }
+            if let Ok(value) = pythonize::depythonize::<serde_json::Value>(&value) {
+                namespace.insert(name, value);
+            }
+        }
+        Ok(namespace)
+    })
+}
+
+/// The contents of a single- or double-quoted string literal
+fn unquote(value: &str) -> Option<&str> {
+    ['"', '\''].into_iter().find_map(|quote| {
+        value
+            .strip_prefix(quote)
+            .and_then(|rest| rest.strip_suffix(quote))
+    })
+}
+
+/// Convert a Python dict/list literal of strings, numbers, booleans and `None`
+/// to JSON by rewriting quotes, constants and trailing commas
+fn python_literal_to_json(literal: &str) -> Option<serde_json::Value> {
+    let mut json = String::with_capacity(literal.len());
+    let mut chars = literal.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' | '\'' => {
+                // Re-quote every string with double quotes
+                let quote = c;
+                json.push('"');
+                while let Some(c) = chars.next() {
+                    match c {
+                        '\\' => match chars.next() {
+                            Some('\'') => json.push('\''),
+                            Some(escaped) => {
+                                json.push('\\');
+                                json.push(escaped);
+                            }
+                            None => return None,
+                        },
+                        '"' if quote == '\'' => json.push_str("\\\""),
+                        _ if c == quote => break,
+                        _ => json.push(c),
+                    }
+                }
+                json.push('"');
+            }
+            // Tuples become arrays
+            '(' => json.push('['),
+            ')' => json.push(']'),
+            ',' => {
+                // JSON has no trailing commas
+                let next = chars.clone().find(|c| !c.is_whitespace());
+                if !matches!(next, Some('}' | ']' | ')')) {
+                    json.push(',');
+                }
+            }
+            _ if c.is_alphabetic() => {
+                let mut word = c.to_string();
+                while let Some(&c) = chars.peek().filter(|c| c.is_alphanumeric() || **c == '_') {
+                    word.push(c);
+                    chars.next();
+                }
+                json.push_str(match word.as_str() {
+                    "True" => "true",
+                    "False" => "false",
+                    "None" => "null",
+                    other => other,
+                });
+            }
+            _ => json.push(c),
+        }
+    }
+
+    serde_json::from_str(&json).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unicode_strings_in_conf_py() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let conf_py = temp_dir.path().join("conf.py");
+        std::fs::write(
+            &conf_py,
+            "project = 'Café – Dokumentation'\ncopyright = \"2024, Jürgen Müller\"\n\
+             broken = \"\nhtml_static_path = ['_stätic', \"ünï\"]\n",
+        )
+        .unwrap();
+
+        let config = PythonConfigParser::new()
+            .unwrap()
+            .parse_conf_py(&conf_py)
+            .unwrap();
+        assert_eq!(config.project.as_deref(), Some("Café – Dokumentation"));
+        assert_eq!(config.copyright.as_deref(), Some("2024, Jürgen Müller"));
+        assert_eq!(config.html_static_path, ["_stätic", "ünï"]);
+    }
+
+    #[test]
+    fn test_math_inline_delimiters_from_conf_py() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let conf_py = temp_dir.path().join("conf.py");
+        std::fs::write(
+            &conf_py,
+            "myst_enable_extensions = ['colon_fence', 'dollarmath']\n",
+        )
+        .unwrap();
+        let config = PythonConfigParser::new()
+            .unwrap()
+            .parse_conf_py(&conf_py)
+            .unwrap()
+            .to_build_config();
+        assert_eq!(
+            config.math_inline_delimiters,
+            [("$".to_string(), "$".to_string())]
+        );
+
+        std::fs::write(
+            &conf_py,
+            "math_inline_delimiters = [('\\\\(', '\\\\)'), (\"$$\", \"$$\")]\n",
+        )
+        .unwrap();
+        let config = PythonConfigParser::new()
+            .unwrap()
+            .parse_conf_py(&conf_py)
+            .unwrap()
+            .to_build_config();
+        assert_eq!(
+            config.math_inline_delimiters,
+            [
+                ("\\(".to_string(), "\\)".to_string()),
+                ("$$".to_string(), "$$".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_conf_py_is_executed_with_python() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let conf_py = temp_dir.path().join("conf.py");
+        std::fs::write(
+            &conf_py,
+            "import os\n\
+             name = 'Demo'\n\
+             project = f'{name} Docs'\n\
+             version = '.'.join(str(n) for n in (1, 4))\n\
+             extensions = [f'sphinx.ext.{ext}' for ext in ('autodoc', 'todo')]\n\
+             html_static_path = [os.path.basename(os.path.dirname(__file__))]\n\
+             if version.startswith('1.'):\n    html_title = 'Legacy'\n\
+             def setup(app):\n    pass\n",
+        )
+        .unwrap();
+
+        let config = PythonConfigParser::new()
+            .unwrap()
+            .parse_conf_py(&conf_py)
+            .unwrap();
+        assert_eq!(config.project.as_deref(), Some("Demo Docs"));
+        assert_eq!(config.version.as_deref(), Some("1.4"));
+        assert_eq!(config.extensions, ["sphinx.ext.autodoc", "sphinx.ext.todo"]);
+        let dir_name = temp_dir.path().file_name().unwrap().to_str().unwrap();
+        assert_eq!(config.html_static_path, [dir_name]);
+        assert_eq!(config.html_title.as_deref(), Some("Legacy"));
+        assert_eq!(
+            config.custom_configs.get("name"),
+            Some(&serde_json::json!("Demo"))
+        );
+        assert!(!config.custom_configs.contains_key("os"));
+        assert!(!config.custom_configs.contains_key("setup"));
+
+        // Statically, only the plain assignments are seen
+        let mut parser = PythonConfigParser::new().unwrap();
+        parser.set_execute(false);
+        let config = parser.parse_conf_py(&conf_py).unwrap();
+        assert_eq!(config.project, None);
+        assert!(config.extensions.is_empty());
+
+        // A conf.py that fails to run falls back to its plain assignments
+        std::fs::write(
+            &conf_py,
+            "import not_an_installed_theme\nproject = 'Fallback'\n",
+        )
+        .unwrap();
+        let config = PythonConfigParser::new()
+            .unwrap()
+            .parse_conf_py(&conf_py)
+            .unwrap();
+        assert_eq!(config.project.as_deref(), Some("Fallback"));
+    }
+
+    #[test]
+    fn test_conf_py_runs_from_its_directory() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let docs = temp_dir.path().join("docs");
+        std::fs::create_dir_all(&docs).unwrap();
+        std::fs::write(temp_dir.path().join("demo_pkg_meta.py"), "NAME = 'Demo'\n").unwrap();
+        std::fs::write(docs.join("demo_conf_helper.py"), "VERSION = '2.0'\n").unwrap();
+        std::fs::write(docs.join("release.txt"), "2.0.1\n").unwrap();
+        std::fs::write(
+            docs.join("conf.py"),
+            "import os, sys\n\
+             sys.path.insert(0, os.path.abspath('..'))\n\
+             import demo_pkg_meta, demo_conf_helper\n\
+             project = demo_pkg_meta.NAME\n\
+             version = demo_conf_helper.VERSION\n\
+             with open('release.txt') as f:\n    release = f.read().strip()\n",
+        )
+        .unwrap();
+        let cwd = std::env::current_dir().unwrap();
+
+        let config = PythonConfigParser::new()
+            .unwrap()
+            .parse_conf_py(docs.join("conf.py"))
+            .unwrap();
+        assert_eq!(config.project.as_deref(), Some("Demo"));
+        assert_eq!(config.version.as_deref(), Some("2.0"));
+        assert_eq!(config.release.as_deref(), Some("2.0.1"));
+        assert_eq!(std::env::current_dir().unwrap(), cwd);
+
+        // Nothing of this project is left behind for the next conf.py
+        Python::with_gil(|py| {
+            let sys = py.import("sys").unwrap();
+            let path: Vec<String> = sys.getattr("path").unwrap().extract().unwrap();
+            let root = temp_dir.path().to_string_lossy();
+            assert!(!path.iter().any(|entry| entry.starts_with(root.as_ref())));
+            let modules = sys.getattr("modules").unwrap();
+            assert!(!modules.contains("demo_conf_helper").unwrap());
+            assert!(!modules.contains("demo_pkg_meta").unwrap());
+        });
+    }
+
+    #[test]
+    fn test_source_suffix_forms_are_normalized() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let conf_py = temp_dir.path().join("conf.py");
+        let parse = |source: &str| {
+            std::fs::write(&conf_py, source).unwrap();
+            PythonConfigParser::new()
+                .unwrap()
+                .parse_conf_py(&conf_py)
+                .unwrap()
+                .source_suffix
+        };
+        let map = |entries: &[(&str, &str)]| -> HashMap<String, String> {
+            entries
+                .iter()
+                .map(|(suffix, file_type)| (suffix.to_string(), file_type.to_string()))
+                .collect()
+        };
+
+        assert_eq!(
+            parse("source_suffix = '.txt'\n"),
+            map(&[(".txt", "restructuredtext")])
+        );
+        assert_eq!(
+            parse("source_suffix = ['.rst', '.rest']\n"),
+            map(&[(".rst", "restructuredtext"), (".rest", "restructuredtext")])
+        );
+        assert_eq!(
+            parse("source_suffix = {'.rst': 'restructuredtext', '.md': 'markdown'}\n"),
+            map(&[(".rst", "restructuredtext"), (".md", "markdown")])
+        );
+
+        // Unset keeps the builder's defaults
+        std::fs::write(&conf_py, "project = 'Demo'\n").unwrap();
+        let config = PythonConfigParser::new()
+            .unwrap()
+            .parse_conf_py(&conf_py)
+            .unwrap()
+            .to_build_config();
+        assert_eq!(config.source_suffix, BuildConfig::default().source_suffix);
+    }
+}
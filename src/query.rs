@@ -0,0 +1,81 @@
+//! Read-only queries over the parsed documents of a build
+//!
+//! Lets extensions and tooling inspect the corpus (e.g. collect every `todo`
+//! directive) without parsing the sources again.
+
+use std::collections::BTreeMap;
+
+use crate::document::{CrossReference, Document, DocumentContent, RstDirective};
+
+/// The documents of a build, keyed and ordered by docname
+#[derive(Debug, Clone, Default)]
+pub struct DocumentQuery {
+    documents: BTreeMap<String, Document>,
+}
+
+impl DocumentQuery {
+    pub fn new<I>(documents: I) -> Self
+    where
+        I: IntoIterator<Item = (String, Document)>,
+    {
+        Self {
+            documents: documents.into_iter().collect(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.documents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.documents.is_empty()
+    }
+
+    /// The document with the given docname
+    pub fn get(&self, docname: &str) -> Option<&Document> {
+        self.documents.get(docname)
+    }
+
+    /// All docnames, sorted
+    pub fn docnames(&self) -> impl Iterator<Item = &str> {
+        self.documents.keys().map(String::as_str)
+    }
+
+    /// `(docname, title)` of every document
+    pub fn titles(&self) -> Vec<(&str, &str)> {
+        self.documents
+            .iter()
+            .map(|(docname, doc)| (docname.as_str(), doc.title.as_str()))
+            .collect()
+    }
+
+    /// Every directive called `name`, with the docname it appears in,
+    /// in docname then source order
+    pub fn directives(&self, name: &str) -> Vec<(&str, &RstDirective)> {
+        self.documents
+            .iter()
+            .flat_map(|(docname, doc)| {
+                let directives = match &doc.content {
+                    DocumentContent::RestructuredText(rst) => rst.directives.as_slice(),
+                    _ => &[],
+                };
+                directives
+                    .iter()
+                    .filter(move |directive| directive.name == name)
+                    .map(move |directive| (docname.as_str(), directive))
+            })
+            .collect()
+    }
+
+    /// Every cross-reference, with the docname it appears in
+    pub fn cross_references(&self) -> Vec<(&str, &CrossReference)> {
+        self.documents
+            .iter()
+            .flat_map(|(docname, doc)| {
+                doc.cross_refs
+                    .iter()
+                    .map(move |cross_ref| (docname.as_str(), cross_ref))
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,2817 @@
+use regex::Regex;
+use std::any::Any;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::config::BuildConfig;
+use crate::directives::{
+    admonition_content, admonition_html, equation_id, figure_html, highlight_block, index_anchor,
+    is_admonition, math_block, name_to_id, number_lines, parsed_literal, py_object_html,
+    read_include_file, read_table, select_include_lines, table_html, version_change_html,
+    Directive, DirectiveRegistry, LinenosStyle, ToctreeEntry, PY_OBJECT_DIRECTIVES,
+    VERSION_CHANGE_DIRECTIVES,
+};
+use crate::document::{Document, DocumentContent, MarkdownNode, RstNode};
+use crate::environment::{normalize_label, BuildEnvironment};
+use crate::error::{BuildErrorReport, BuildWarning};
+use crate::highlight::highlight_code;
+use crate::parser::{is_author_directive, Parser};
+use crate::roles::{math_span, Role, RoleRegistry};
+use crate::tags::Tags;
+
+/// HTML body produced for a document, with the warnings and errors raised while
+/// rendering it
+#[derive(Debug, Clone)]
+pub struct RenderedDocument {
+    pub html: String,
+    pub warnings: Vec<BuildWarning>,
+    pub errors: Vec<BuildErrorReport>,
+    /// Directives and roles processed, in order; recorded when tracing is on
+    pub trace: Vec<TraceEvent>,
+}
+
+/// One directive or role processed while rendering a document
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceEvent {
+    /// "directive" or "role"
+    pub kind: &'static str,
+    pub name: String,
+    pub line: usize,
+    /// Bytes of HTML it produced
+    pub output_bytes: usize,
+}
+
+/// Renders parsed documents to HTML, dispatching directives and roles through
+/// their registries
+pub struct HtmlRenderer {
+    directives: DirectiveRegistry,
+    roles: RoleRegistry,
+    inline_regex: Regex,
+    /// Inline math between the configured delimiters, when there are any
+    inline_math_regex: Option<Regex>,
+    warn_unknown_directives: bool,
+    warn_unknown_roles: bool,
+    show_authors: bool,
+    /// Number unlabeled displayed equations too
+    math_number_all: bool,
+    /// Layout of numbered code lines
+    linenos_style: LinenosStyle,
+    /// Add a copy button and language label to code blocks
+    copy_code_button: bool,
+    /// Suffix of links to pages, as `:ref:` resolves them
+    link_suffix: String,
+    /// Tags deciding `only` directives
+    tags: Tags,
+    /// Record a render trace even when trace logging is off
+    trace: bool,
+    /// Documentation source root, for directives naming `/`-prefixed files
+    source_root: Option<PathBuf>,
+    /// Stub directives already reported during this build
+    reported_stubs: Mutex<HashSet<String>>,
+    /// Parses the reStructuredText content of admonitions
+    parser: Parser,
+}
+
+/// Per-document rendering state
+struct RenderContext<'a> {
+    file: &'a Path,
+    warnings: Vec<BuildWarning>,
+    errors: Vec<BuildErrorReport>,
+    /// Processed directives and roles, when tracing
+    trace: Option<Vec<TraceEvent>>,
+    notes: NoteTable,
+    equations: EquationNumbers,
+    highlight: HighlightSettings,
+    /// Hyperlink targets of the document (`name_` references), by
+    /// normalized name
+    targets: HashMap<String, String>,
+    /// Module of the last `py:module` or `py:currentmodule`, qualifying the
+    /// Python objects described after it
+    py_module: Option<String>,
+    /// Ids the environment gave the document's Python objects, in document
+    /// order, by object type and full name
+    py_anchors: HashMap<(String, String), VecDeque<String>>,
+    /// The document's docname and the environment its toctrees resolve
+    /// against, when rendering within a build
+    toctree: Option<(&'a str, &'a BuildEnvironment)>,
+}
+
+/// Settings of the last `highlight` directive, applied to the code blocks after it
+#[derive(Debug, Default)]
+struct HighlightSettings {
+    language: Option<String>,
+    /// Number the lines of blocks longer than this
+    linenothreshold: Option<usize>,
+}
+
+impl HighlightSettings {
+    fn from_directive(directive: &Directive) -> Self {
+        Self {
+            language: directive.arguments.first().cloned(),
+            linenothreshold: directive
+                .options
+                .get("linenothreshold")
+                .and_then(|threshold| threshold.trim().parse().ok()),
+        }
+    }
+
+    fn exceeds_threshold(&self, content: &str) -> bool {
+        self.linenothreshold
+            .is_some_and(|threshold| content.lines().count() > threshold)
+    }
+}
+
+/// Symbols used for auto-symbol footnotes (`[*]`), as in docutils
+const FOOTNOTE_SYMBOLS: [char; 10] = ['*', '†', '‡', '§', '¶', '#', '♠', '♥', '♦', '♣'];
+
+/// A footnote or citation definition
+struct Note {
+    id: String,
+    display: String,
+    citation: bool,
+    content: String,
+    line: usize,
+    backrefs: Vec<String>,
+}
+
+/// Footnote and citation definitions of a document, resolved for referencing
+#[derive(Default)]
+struct NoteTable {
+    notes: Vec<Note>,
+    /// Anonymous auto-numbered (`[#]`) footnotes, in definition order
+    auto_numbered: VecDeque<usize>,
+    /// Auto-symbol (`[*]`) footnotes, in definition order
+    auto_symbol: VecDeque<usize>,
+    /// Numbered and named footnotes by label, citations by lowercased label
+    by_label: HashMap<String, usize>,
+    references: usize,
+}
+
+impl NoteTable {
+    fn from_nodes(nodes: &[RstNode]) -> Self {
+        let mut table = Self::default();
+        let manual: HashSet<&str> = nodes
+            .iter()
+            .filter_map(|node| match node {
+                RstNode::Footnote { label, .. } => Some(label.as_str()),
+                _ => None,
+            })
+            .collect();
+        let mut next_number = 1;
+        let mut footnotes = 0;
+
+        for node in nodes {
+            match node {
+                RstNode::Footnote {
+                    label,
+                    content,
+                    line,
+                } => {
+                    footnotes += 1;
+                    let index = table.notes.len();
+                    let display = if label == "*" {
+                        table.auto_symbol.push_back(index);
+                        let n = table.auto_symbol.len() - 1;
+                        let symbol = FOOTNOTE_SYMBOLS[n % FOOTNOTE_SYMBOLS.len()];
+                        symbol.to_string().repeat(n / FOOTNOTE_SYMBOLS.len() + 1)
+                    } else if label.starts_with('#') {
+                        while manual.contains(next_number.to_string().as_str()) {
+                            next_number += 1;
+                        }
+                        if label == "#" {
+                            table.auto_numbered.push_back(index);
+                        } else {
+                            table.by_label.insert(label.clone(), index);
+                        }
+                        next_number += 1;
+                        (next_number - 1).to_string()
+                    } else {
+                        table.by_label.insert(label.clone(), index);
+                        label.clone()
+                    };
+
+                    table.notes.push(Note {
+                        id: format!("footnote-{}", footnotes),
+                        display,
+                        citation: false,
+                        content: content.clone(),
+                        line: *line,
+                        backrefs: Vec::new(),
+                    });
+                }
+                RstNode::Citation {
+                    label,
+                    content,
+                    line,
+                } => {
+                    table
+                        .by_label
+                        .insert(label.to_lowercase(), table.notes.len());
+                    table.notes.push(Note {
+                        id: label.to_lowercase(),
+                        display: label.clone(),
+                        citation: true,
+                        content: content.clone(),
+                        line: *line,
+                        backrefs: Vec::new(),
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        table
+    }
+
+    /// Resolve a `[label]_` reference to its link markup, recording the backlink
+    fn reference(&mut self, label: &str) -> Option<String> {
+        let index = match label {
+            "#" => self.auto_numbered.pop_front()?,
+            "*" => self.auto_symbol.pop_front()?,
+            _ => *self
+                .by_label
+                .get(label)
+                .or_else(|| self.by_label.get(&label.to_lowercase()))?,
+        };
+
+        self.references += 1;
+        let note = &mut self.notes[index];
+        if note.citation {
+            let ref_id = format!("citation-reference-{}", self.references);
+            note.backrefs.push(ref_id.clone());
+            Some(format!(
+                "<a class=\"citation-reference\" href=\"#{}\" id=\"{}\" role=\"doc-biblioref\">[{}]</a>",
+                note.id,
+                ref_id,
+                html_escape::encode_text(&note.display)
+            ))
+        } else {
+            let ref_id = format!("footnote-reference-{}", self.references);
+            note.backrefs.push(ref_id.clone());
+            Some(format!(
+                "<a class=\"footnote-reference brackets\" href=\"#{}\" id=\"{}\" role=\"doc-noteref\"><span class=\"fn-bracket\">[</span>{}<span class=\"fn-bracket\">]</span></a>",
+                note.id,
+                ref_id,
+                html_escape::encode_text(&note.display)
+            ))
+        }
+    }
+}
+
+impl RenderContext<'_> {
+    fn warn(&mut self, warning: BuildWarning) {
+        self.warnings.push(warning);
+    }
+
+    fn error(&mut self, error: BuildErrorReport) {
+        self.errors.push(error);
+    }
+
+    fn trace(&mut self, kind: &'static str, name: &str, line: usize, output_bytes: usize) {
+        if let Some(trace) = &mut self.trace {
+            trace.push(TraceEvent {
+                kind,
+                name: name.to_string(),
+                line,
+                output_bytes,
+            });
+        }
+    }
+}
+
+/// Numbers of a document's displayed equations
+#[derive(Default)]
+struct EquationNumbers {
+    /// By `:label:`, for `:eq:` references
+    labels: HashMap<String, usize>,
+    /// By the line of their `math` directive
+    lines: HashMap<usize, usize>,
+}
+
+/// Number the labeled `math` directives of a document in order, as Sphinx
+/// does; with `number_all`, the unlabeled ones as well
+fn number_equations(nodes: &[RstNode], number_all: bool) -> EquationNumbers {
+    let mut equations = EquationNumbers::default();
+    for node in nodes {
+        let RstNode::Directive {
+            name,
+            options,
+            line,
+            ..
+        } = node
+        else {
+            continue;
+        };
+        if name != "math" {
+            continue;
+        }
+        let label = options
+            .get("label")
+            .map(|label| label.trim())
+            .filter(|label| !label.is_empty());
+        if label.is_none() && !number_all {
+            continue;
+        }
+        let number = equations.lines.len() + 1;
+        equations.lines.insert(*line, number);
+        if let Some(label) = label {
+            equations.labels.entry(label.to_string()).or_insert(number);
+        }
+    }
+    equations
+}
+
+/// The `<ul>` of toctree entries at nesting `level`, linked from `docname`'s
+/// page; each listed document's own toctrees nest below it while `level` is
+/// under `maxdepth`. Documents the environment doesn't know are left out, and
+/// `ancestors` stops a document from nesting inside itself.
+fn toctree_list(
+    env: &BuildEnvironment,
+    docname: &str,
+    entries: &[ToctreeEntry],
+    level: usize,
+    maxdepth: usize,
+    ancestors: &mut HashSet<String>,
+) -> String {
+    let mut html = String::from("<ul>\n");
+    for entry in entries {
+        let target = match entry.target.as_str() {
+            _ if entry.external => None,
+            "self" => Some(docname),
+            target if env.titles.contains_key(target) => Some(target),
+            _ => continue,
+        };
+        let (class, href, title) = match target {
+            None => (
+                "reference external",
+                entry.target.clone(),
+                entry.title.as_deref().unwrap_or(&entry.target),
+            ),
+            Some(target) => (
+                "reference internal",
+                crate::utils::relative_uri(
+                    &format!("{}.html", docname),
+                    &format!("{}.html", target),
+                    "",
+                ),
+                entry
+                    .title
+                    .as_deref()
+                    .or_else(|| env.titles.get(target).map(String::as_str))
+                    .unwrap_or(target),
+            ),
+        };
+        html.push_str(&format!(
+            "<li class=\"toctree-l{}\"><a class=\"{}\" href=\"{}\">{}</a>",
+            level,
+            class,
+            html_escape::encode_double_quoted_attribute(&href),
+            html_escape::encode_text(title)
+        ));
+
+        let children: Vec<ToctreeEntry> = target
+            .filter(|_| level < maxdepth && entry.target != "self")
+            .and_then(|target| env.toctree_includes.get(target))
+            .into_iter()
+            .flatten()
+            .map(|child| ToctreeEntry {
+                title: None,
+                target: child.clone(),
+                external: false,
+            })
+            .collect();
+        if let Some(target) = target.filter(|_| !children.is_empty()) {
+            if ancestors.insert(target.to_string()) {
+                html.push('\n');
+                html.push_str(&toctree_list(
+                    env,
+                    docname,
+                    &children,
+                    level + 1,
+                    maxdepth,
+                    ancestors,
+                ));
+                ancestors.remove(target);
+            }
+        }
+        html.push_str("</li>\n");
+    }
+    html.push_str("</ul>\n");
+    html
+}
+
+/// Run a directive or role processor, catching a panic so one faulty processor
+/// doesn't take down the whole build; a panic comes back as its message
+fn run_processor(
+    process: impl FnOnce() -> anyhow::Result<String>,
+) -> Result<anyhow::Result<String>, String> {
+    panic::catch_unwind(AssertUnwindSafe(process)).map_err(|payload| panic_message(&*payload))
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+impl HtmlRenderer {
+    pub fn new(config: &BuildConfig) -> Self {
+        let mut roles = RoleRegistry::new();
+        if let Some(link_suffix) = &config.html_link_suffix {
+            roles.set_link_suffix(link_suffix);
+        }
+        roles.register_custom_roles(&config.custom_roles);
+
+        // Math must start and end next to a non-space, so `$5 and $6` stays text
+        let inline_math: Vec<String> = config
+            .math_inline_delimiters
+            .iter()
+            .filter(|(open, close)| !open.is_empty() && !close.is_empty())
+            .map(|(open, close)| {
+                format!(
+                    r"{}(\S(?:.*?\S)?){}",
+                    regex::escape(open),
+                    regex::escape(close)
+                )
+            })
+            .collect();
+        let inline_math_regex =
+            (!inline_math.is_empty()).then(|| Regex::new(&inline_math.join("|")).unwrap());
+
+        let linenos_style =
+            LinenosStyle::from_config(config.html_codeblock_linenos_style.as_deref());
+        let mut directives = DirectiveRegistry::new();
+        directives.set_linenos_style(linenos_style);
+
+        Self {
+            directives,
+            linenos_style,
+            roles,
+            inline_regex: Regex::new(
+                r"(?x)
+                \\(?P<escaped>(?s:.))
+                | :(?P<role>[a-zA-Z][a-zA-Z0-9_:+.-]*):`(?P<role_body>[^`]+)`
+                | ``(?P<literal>.+?)``
+                | \[(?P<note>\#[\w-]*|\*|\d+|[A-Za-z][\w.-]*)\]_
+                | `(?P<link_text>[^`<]*?)\s*<(?P<link_uri>[^`>]+)>`__?
+                | `(?P<reference>[^`]+)`(?P<anonymous>_?)_
+                | \b(?P<simple_reference>[A-Za-z0-9](?:[\w.+-]*[A-Za-z0-9])?)_\b
+                | \*\*(?P<strong>(?:\\.|[^*\s\\])(?:\\.|[^*\\]|\*[^*])*?)\*\*
+                | \*(?P<emphasis>(?:\\.|[^*\s\\])(?:\s+\*?(?:\\.|[^*\s\\])|\\.|[^*\s\\])*)\*",
+            )
+            .unwrap(),
+            inline_math_regex,
+            warn_unknown_directives: !config.is_warning_suppressed("directive.unknown"),
+            warn_unknown_roles: !config.is_warning_suppressed("role.unknown"),
+            show_authors: config.show_authors,
+            math_number_all: config.math_number_all,
+            copy_code_button: config.html_copy_code_button,
+            link_suffix: config
+                .html_link_suffix
+                .clone()
+                .unwrap_or_else(|| ".html".to_string()),
+            tags: Tags::for_builder(&config.tags, "html", "html"),
+            trace: false,
+            source_root: None,
+            reported_stubs: Mutex::new(HashSet::new()),
+            parser: Parser::new(&BuildConfig::default()).unwrap(),
+        }
+    }
+
+    /// Record the directives and roles each render processes in
+    /// [`RenderedDocument::trace`], whatever the log level
+    pub fn set_trace(&mut self, trace: bool) {
+        self.trace = trace;
+    }
+
+    /// Link pages from `:doc:` and `:ref:` with `link_suffix`, overriding
+    /// `html_link_suffix`
+    pub fn set_link_suffix(&mut self, link_suffix: &str) {
+        self.roles.set_link_suffix(link_suffix);
+        self.link_suffix = link_suffix.to_string();
+    }
+
+    /// Resolve `/`-prefixed file arguments of directives against `root`
+    pub fn set_source_root(&mut self, root: PathBuf) {
+        self.parser.set_source_root(root.clone());
+        self.source_root = Some(root);
+    }
+
+    /// Tags active while rendering: the configured ones plus the `html` builder's
+    pub fn tags(&self) -> &Tags {
+        &self.tags
+    }
+
+    /// Replace the active tags, for a builder of another format reusing this renderer
+    pub fn set_tags(&mut self, tags: Tags) {
+        self.tags = tags;
+    }
+
+    /// Render a document's body to HTML
+    pub fn render(&self, document: &Document) -> RenderedDocument {
+        self.render_with(document, None)
+    }
+
+    /// Render a document's body to HTML, resolving its toctrees against `env`:
+    /// entries link with their documents' titles, expand `:glob:` patterns
+    /// and nest the listed documents' own toctrees down to `:maxdepth:`
+    pub fn render_in_env(
+        &self,
+        document: &Document,
+        docname: &str,
+        env: &BuildEnvironment,
+    ) -> RenderedDocument {
+        self.render_with(document, Some((docname, env)))
+    }
+
+    fn render_with(
+        &self,
+        document: &Document,
+        toctree: Option<(&str, &BuildEnvironment)>,
+    ) -> RenderedDocument {
+        let mut ctx = RenderContext {
+            file: &document.source_path,
+            warnings: Vec::new(),
+            errors: Vec::new(),
+            trace: (self.trace || log::log_enabled!(log::Level::Trace)).then(Vec::new),
+            notes: match &document.content {
+                DocumentContent::RestructuredText(rst) => NoteTable::from_nodes(&rst.ast),
+                _ => NoteTable::default(),
+            },
+            equations: match &document.content {
+                DocumentContent::RestructuredText(rst) => {
+                    number_equations(&rst.ast, self.math_number_all)
+                }
+                _ => EquationNumbers::default(),
+            },
+            highlight: HighlightSettings::default(),
+            targets: hyperlink_targets(document),
+            py_module: None,
+            py_anchors: match toctree {
+                Some((docname, env)) => py_object_anchors(env, docname),
+                None => {
+                    let mut env = BuildEnvironment::new(BuildConfig::default());
+                    env.note_python_objects("", document);
+                    py_object_anchors(&env, "")
+                }
+            },
+            toctree,
+        };
+
+        let mut blocks = Vec::new();
+        let mut anchors = document.toc.iter().map(|entry| entry.anchor.as_str());
+
+        match &document.content {
+            DocumentContent::RestructuredText(rst) => {
+                blocks.extend(self.render_rst_nodes(&rst.ast, &mut anchors, &mut ctx));
+            }
+            DocumentContent::Markdown(md) => {
+                for node in &md.ast {
+                    blocks.push(self.render_markdown_node(node, &mut anchors));
+                }
+            }
+            DocumentContent::PlainText(text) => {
+                blocks.push(format!(
+                    "<pre class=\"literal-block\">{}</pre>",
+                    html_escape::encode_text(text)
+                ));
+            }
+        }
+
+        blocks.extend(self.render_notes(&mut ctx));
+
+        let trace = ctx.trace.unwrap_or_default();
+        if !trace.is_empty() && log::log_enabled!(log::Level::Trace) {
+            log::trace!("Render trace for {}:", document.source_path.display());
+            for event in &trace {
+                log::trace!(
+                    "  line {}: {} '{}' -> {} bytes",
+                    event.line,
+                    event.kind,
+                    event.name,
+                    event.output_bytes
+                );
+            }
+        }
+
+        RenderedDocument {
+            html: blocks.join("\n"),
+            warnings: ctx.warnings,
+            errors: ctx.errors,
+            trace,
+        }
+    }
+
+    /// Render a sequence of nodes, attaching the classes of `rst-class`
+    /// directives to the element after them
+    fn render_rst_nodes<'a>(
+        &self,
+        nodes: &[RstNode],
+        anchors: &mut impl Iterator<Item = &'a str>,
+        ctx: &mut RenderContext,
+    ) -> Vec<String> {
+        let mut blocks = Vec::new();
+        // Classes from `rst-class` waiting for the next element
+        let mut pending_classes: Vec<String> = Vec::new();
+
+        for node in nodes {
+            if let RstNode::Directive { name, args, .. } = node {
+                if name == "rst-class" || name == "class" {
+                    pending_classes.extend(
+                        args.iter()
+                            .flat_map(|arg| arg.split_whitespace())
+                            .map(str::to_string),
+                    );
+                    continue;
+                }
+            }
+
+            let html = self.render_rst_node(node, anchors, ctx);
+            match add_classes_to_first_element(&html, &pending_classes) {
+                Some(with_classes) => {
+                    pending_classes.clear();
+                    blocks.push(with_classes);
+                }
+                None => blocks.push(html),
+            }
+        }
+        blocks
+    }
+
+    /// Render an admonition, its content parsed as reStructuredText and its
+    /// title, when it takes one, as inline markup
+    fn render_admonition(&self, directive: &Directive, ctx: &mut RenderContext) -> String {
+        // Content usually starts after the options and a blank line, unless
+        // text on the directive line leads it
+        let content = admonition_content(directive);
+        let first_line = if content.len() == directive.content.len() {
+            directive.line_number + directive.options.len() + 2
+        } else {
+            directive.line_number
+        };
+        let (nodes, warnings, errors) =
+            self.parser
+                .parse_rst_fragment(ctx.file, &content.join("\n"), first_line);
+        warnings.into_iter().for_each(|warning| ctx.warn(warning));
+        errors.into_iter().for_each(|error| ctx.error(error));
+
+        let title = match directive.name.as_str() {
+            "admonition" => self.render_inline(
+                directive.arguments.join(" ").trim(),
+                directive.line_number,
+                ctx,
+            ),
+            _ => String::new(),
+        };
+        // Sections can't nest in an admonition, so no heading takes an anchor
+        let body = self.render_rst_nodes(&nodes, &mut std::iter::empty(), ctx);
+        admonition_html(directive, &title, &body.join("\n"))
+    }
+
+    fn render_rst_node<'a>(
+        &self,
+        node: &RstNode,
+        anchors: &mut impl Iterator<Item = &'a str>,
+        ctx: &mut RenderContext,
+    ) -> String {
+        match node {
+            RstNode::Title { text, level, line } => {
+                let level = (*level).clamp(1, 6);
+                let anchor = anchors.next().unwrap_or_default();
+                format!(
+                    "<h{} id=\"{}\">{}</h{}>",
+                    level,
+                    html_escape::encode_double_quoted_attribute(anchor),
+                    self.render_inline(text, *line, ctx),
+                    level
+                )
+            }
+            RstNode::Paragraph { content, line } => {
+                format!("<p>{}</p>", self.render_inline(content, *line, ctx))
+            }
+            RstNode::CodeBlock {
+                language, content, ..
+            } => {
+                let language = language.as_ref().or(ctx.highlight.language.as_ref());
+                self.with_copy_button(
+                    render_code_block(
+                        language,
+                        content,
+                        ctx.highlight.exceeds_threshold(content),
+                        self.linenos_style,
+                    ),
+                    language.map(String::as_str),
+                )
+            }
+            RstNode::List {
+                items,
+                ordered,
+                line,
+            } => {
+                let tag = if *ordered { "ol" } else { "ul" };
+                let items: String = items
+                    .iter()
+                    .map(|item| {
+                        let children: String = item
+                            .children
+                            .iter()
+                            .map(|child| self.render_rst_node(child, anchors, ctx))
+                            .collect();
+                        format!(
+                            "<li>{}{}</li>",
+                            self.render_inline(&item.text, *line, ctx),
+                            children
+                        )
+                    })
+                    .collect();
+                format!("<{} class=\"simple\">{}</{}>", tag, items, tag)
+            }
+            RstNode::Table {
+                headers,
+                rows,
+                line,
+            } => {
+                let header: String = headers
+                    .iter()
+                    .map(|cell| format!("<th>{}</th>", self.render_inline(cell, *line, ctx)))
+                    .collect();
+                let body: String = rows
+                    .iter()
+                    .map(|row| {
+                        let cells: String = row
+                            .iter()
+                            .map(|cell| {
+                                format!("<td>{}</td>", self.render_inline(cell, *line, ctx))
+                            })
+                            .collect();
+                        format!("<tr>{}</tr>", cells)
+                    })
+                    .collect();
+                format!(
+                    "<table class=\"docutils\"><thead><tr>{}</tr></thead><tbody>{}</tbody></table>",
+                    header, body
+                )
+            }
+            RstNode::DefinitionList { items, line } => {
+                let simple = items.iter().all(|item| item.definition.len() <= 1);
+                let items: String = items
+                    .iter()
+                    .map(|item| {
+                        let classifiers: String = item
+                            .classifiers
+                            .iter()
+                            .map(|classifier| {
+                                format!(
+                                    "<span class=\"classifier-delimiter\">:</span> <span class=\"classifier\">{}</span>",
+                                    self.render_inline(classifier, *line, ctx)
+                                )
+                            })
+                            .collect();
+                        let definition: String = item
+                            .definition
+                            .iter()
+                            .map(|paragraph| {
+                                format!("<p>{}</p>", self.render_inline(paragraph, *line, ctx))
+                            })
+                            .collect();
+                        format!(
+                            "<dt>{}{}</dt>\n<dd>{}</dd>\n",
+                            self.render_inline(&item.term, *line, ctx),
+                            classifiers,
+                            definition
+                        )
+                    })
+                    .collect();
+                if simple {
+                    format!("<dl class=\"simple\">\n{}</dl>", items)
+                } else {
+                    format!("<dl>\n{}</dl>", items)
+                }
+            }
+            RstNode::BlockQuote {
+                paragraphs,
+                attribution,
+                line,
+            } => {
+                let mut html = String::from("<blockquote>\n");
+                for paragraph in paragraphs {
+                    html.push_str(&format!(
+                        "<p>{}</p>\n",
+                        self.render_inline(paragraph, *line, ctx)
+                    ));
+                }
+                if let Some(attribution) = attribution {
+                    html.push_str(&format!(
+                        "<p class=\"attribution\">\u{2014}{}</p>\n",
+                        self.render_inline(attribution, *line, ctx)
+                    ));
+                }
+                html.push_str("</blockquote>");
+                html
+            }
+            RstNode::Transition { .. } => "<hr class=\"docutils\" />".to_string(),
+            RstNode::LineBlock { lines, line } => self.render_line_block(lines, *line, ctx),
+            RstNode::FieldList { fields, line } => {
+                let fields: String = fields
+                    .iter()
+                    .enumerate()
+                    .map(|(i, (name, value))| {
+                        let parity = if i % 2 == 0 { "field-odd" } else { "field-even" };
+                        format!(
+                            "<dt class=\"{}\">{}<span class=\"colon\">:</span></dt>\n<dd class=\"{}\"><p>{}</p></dd>\n",
+                            parity,
+                            self.render_inline(name, *line, ctx),
+                            parity,
+                            self.render_inline(value, *line, ctx)
+                        )
+                    })
+                    .collect();
+                format!("<dl class=\"field-list simple\">\n{}</dl>", fields)
+            }
+            RstNode::Target {
+                name, refuri: None, ..
+            } => {
+                format!("<span id=\"{}\"></span>", name_to_id(name))
+            }
+            // External targets are where references to them link
+            RstNode::Target { .. } => String::new(),
+            // Definitions are collected into the notes sections at the end
+            RstNode::Footnote { .. } | RstNode::Citation { .. } | RstNode::Comment { .. } => {
+                String::new()
+            }
+            RstNode::Directive {
+                name,
+                args,
+                options,
+                content,
+                line,
+            } => {
+                let directive = Directive {
+                    name: name.clone(),
+                    arguments: args.clone(),
+                    options: options.clone(),
+                    content: content.lines().map(str::to_string).collect(),
+                    line_number: *line,
+                    source_file: ctx.file.to_string_lossy().to_string(),
+                    source_root: self.source_root.clone(),
+                };
+                self.render_directive(&directive, ctx)
+            }
+        }
+    }
+
+    fn render_toctree(
+        &self,
+        directive: &Directive,
+        docname: &str,
+        env: &BuildEnvironment,
+    ) -> String {
+        if directive.options.contains_key("hidden") {
+            return String::new();
+        }
+        // Sphinx's default of -1, like 0, means no limit
+        let maxdepth = directive
+            .options
+            .get("maxdepth")
+            .and_then(|depth| depth.trim().parse::<usize>().ok())
+            .filter(|&depth| depth > 0)
+            .unwrap_or(usize::MAX);
+        let entries = env.toctree_entries(
+            docname,
+            directive.content.iter().map(String::as_str),
+            directive.options.contains_key("glob"),
+        );
+
+        let mut html = String::from("<div class=\"toctree-wrapper compound\">\n");
+        if let Some(caption) = directive.options.get("caption") {
+            html.push_str(&format!(
+                "<p class=\"caption\"><span class=\"caption-text\">{}</span></p>\n",
+                html_escape::encode_text(caption)
+            ));
+        }
+        let mut ancestors = HashSet::from([docname.to_string()]);
+        html.push_str(&toctree_list(
+            env,
+            docname,
+            &entries,
+            1,
+            maxdepth,
+            &mut ancestors,
+        ));
+        html.push_str("</div>");
+        html
+    }
+
+    fn render_directive(&self, directive: &Directive, ctx: &mut RenderContext) -> String {
+        let html = self.dispatch_directive(directive, ctx);
+        ctx.trace(
+            "directive",
+            &directive.name,
+            directive.line_number,
+            html.len(),
+        );
+        html
+    }
+
+    fn dispatch_directive(&self, directive: &Directive, ctx: &mut RenderContext) -> String {
+        for problem in self.directives.validate_options(directive) {
+            ctx.warn(BuildWarning::new(
+                ctx.file.to_path_buf(),
+                Some(directive.line_number),
+                format!("error in \"{}\" directive: {}", directive.name, problem),
+                crate::error::WarningType::Other,
+            ));
+        }
+        if directive.name == "only" {
+            return self.render_only(directive, ctx);
+        }
+        if is_admonition(&directive.name) {
+            return self.render_admonition(directive, ctx);
+        }
+        if is_author_directive(&directive.name) && !self.show_authors {
+            return String::new();
+        }
+        if let ("toctree", Some((docname, env))) = (directive.name.as_str(), ctx.toctree) {
+            return self.render_toctree(directive, docname, env);
+        }
+        if directive.name == "math" {
+            let number = match directive.options.get("label") {
+                Some(label) => ctx.equations.labels.get(label.trim()),
+                None => ctx.equations.lines.get(&directive.line_number),
+            }
+            .copied();
+            return math_block(directive, number);
+        }
+        if directive.name == "parsed-literal" {
+            // Inline markup is interpreted, whitespace kept as written
+            let body =
+                self.render_inline(&directive.content.join("\n"), directive.line_number, ctx);
+            return parsed_literal(directive, &body);
+        }
+        if matches!(directive.name.as_str(), "csv-table" | "list-table") {
+            // Cells hold inline markup
+            let html = read_table(directive).and_then(|table| {
+                table_html(directive, &table, |text| {
+                    self.render_inline(text, directive.line_number, ctx)
+                })
+            });
+            return html.unwrap_or_else(|e| {
+                ctx.warn(BuildWarning::new(
+                    ctx.file.to_path_buf(),
+                    Some(directive.line_number),
+                    format!("error in \"{}\" directive: {}", directive.name, e),
+                    crate::error::WarningType::Other,
+                ));
+                String::new()
+            });
+        }
+        if directive.name == "include"
+            && (directive.options.contains_key("code") || directive.options.contains_key("literal"))
+        {
+            return self.render_verbatim_include(directive, ctx);
+        }
+        if VERSION_CHANGE_DIRECTIVES.contains(&directive.name.as_str()) {
+            // The explanation holds inline markup
+            return version_change_html(directive, |text| {
+                self.render_inline(text, directive.line_number, ctx)
+            });
+        }
+        if PY_OBJECT_DIRECTIVES.contains(&directive.name.as_str()) {
+            // Descriptions hold inline markup
+            let module = ctx.py_module.clone();
+            let mut anchors = std::mem::take(&mut ctx.py_anchors);
+            let html = py_object_html(
+                directive,
+                module.as_deref(),
+                None,
+                &mut |object| {
+                    anchors
+                        .get_mut(&(object.objtype.clone(), object.fullname()))
+                        .and_then(VecDeque::pop_front)
+                        .unwrap_or_else(|| object.fullname())
+                },
+                &mut |text| self.render_inline(text, directive.line_number, ctx),
+            );
+            ctx.py_anchors = anchors;
+            return html;
+        }
+
+        let mut directive = Cow::Borrowed(directive);
+        match directive.name.as_str() {
+            "highlight" => ctx.highlight = HighlightSettings::from_directive(&directive),
+            "py:module" | "module" | "py:currentmodule" | "currentmodule" => {
+                let module = directive.arguments.join(" ");
+                let module = module.trim();
+                ctx.py_module =
+                    (!module.is_empty() && module != "None").then(|| module.to_string());
+            }
+            "code-block" | "code" => {
+                if directive.arguments.is_empty() {
+                    if let Some(language) = &ctx.highlight.language {
+                        directive.to_mut().arguments.push(language.clone());
+                    }
+                }
+                if ctx
+                    .highlight
+                    .exceeds_threshold(&directive.content.join("\n"))
+                {
+                    directive
+                        .to_mut()
+                        .options
+                        .entry("linenos".to_string())
+                        .or_default();
+                }
+            }
+            "image" | "figure" => {
+                if let (Some(uri), Some((docname, env))) =
+                    (directive.arguments.first(), ctx.toctree)
+                {
+                    let uri = env.image_uri(docname, uri);
+                    directive.to_mut().arguments = vec![uri];
+                }
+            }
+            _ => {}
+        }
+        let directive = directive.as_ref();
+        if directive.name == "figure" && !directive.arguments.is_empty() {
+            // The caption holds inline markup
+            return figure_html(directive, |text| {
+                self.render_inline(text, directive.line_number, ctx)
+            });
+        }
+
+        match self.directives.get(&directive.name) {
+            Some(processor) => {
+                if processor.is_stub() && self.first_use_of_stub(&directive.name) {
+                    ctx.warn(BuildWarning::unsupported_directive(
+                        ctx.file.to_path_buf(),
+                        Some(directive.line_number),
+                        &directive.name,
+                    ));
+                }
+            }
+            None if self.warn_unknown_directives => {
+                ctx.warn(BuildWarning::unknown_directive(
+                    ctx.file.to_path_buf(),
+                    Some(directive.line_number),
+                    &directive.name,
+                ));
+            }
+            None => {}
+        }
+
+        match run_processor(|| self.directives.process_directive(directive)) {
+            Ok(Ok(html)) => match directive.name.as_str() {
+                "code-block" | "code" => {
+                    self.with_copy_button(html, directive.arguments.first().map(String::as_str))
+                }
+                "literalinclude" => self
+                    .with_copy_button(html, directive.options.get("language").map(String::as_str)),
+                _ => html,
+            },
+            Ok(Err(e)) => {
+                ctx.warn(BuildWarning::new(
+                    ctx.file.to_path_buf(),
+                    Some(directive.line_number),
+                    format!("error in \"{}\" directive: {}", directive.name, e),
+                    crate::error::WarningType::Other,
+                ));
+                String::new()
+            }
+            Err(message) => {
+                ctx.error(BuildErrorReport::processor_panic(
+                    ctx.file.to_path_buf(),
+                    Some(directive.line_number),
+                    "directive",
+                    &directive.name,
+                    &message,
+                ));
+                format!(
+                    "<div class=\"system-message\">\n<p class=\"system-message-title\">Error in \"{}\" directive</p>\n</div>",
+                    html_escape::encode_text(&directive.name)
+                )
+            }
+        }
+    }
+
+    /// Wrap a rendered code block with a copy button and a language label, if
+    /// configured; both sit outside the `<pre>`, so they are never copied
+    fn with_copy_button(&self, html: String, language: Option<&str>) -> String {
+        if !self.copy_code_button {
+            return html;
+        }
+
+        let label = language
+            .filter(|language| !language.is_empty())
+            .map(|language| {
+                format!(
+                    "<span class=\"code-language\">{}</span>",
+                    html_escape::encode_text(language)
+                )
+            })
+            .unwrap_or_default();
+        format!(
+            "<div class=\"copyable\">{}<button class=\"copybtn\" type=\"button\" \
+             title=\"Copy\" aria-label=\"Copy code to clipboard\">Copy</button>\n{}\n</div>",
+            label, html
+        )
+    }
+
+    /// Render the paragraphs of an `only` directive if its tag expression holds
+    fn render_only(&self, directive: &Directive, ctx: &mut RenderContext) -> String {
+        let expr = directive.arguments.join(" ");
+        match self.tags.eval(&expr) {
+            Ok(true) => {}
+            Ok(false) => return String::new(),
+            Err(e) => {
+                ctx.warn(BuildWarning::new(
+                    ctx.file.to_path_buf(),
+                    Some(directive.line_number),
+                    format!("error in \"only\" directive: {}", e),
+                    crate::error::WarningType::Other,
+                ));
+                return String::new();
+            }
+        }
+
+        // The body is ordinary reStructuredText, after the options and a blank line
+        let first_line = directive.line_number + directive.options.len() + 2;
+        let (nodes, warnings, errors) =
+            self.parser
+                .parse_rst_fragment(ctx.file, &directive.content.join("\n"), first_line);
+        warnings.into_iter().for_each(|warning| ctx.warn(warning));
+        errors.into_iter().for_each(|error| ctx.error(error));
+
+        self.render_rst_nodes(&nodes, &mut std::iter::empty(), ctx)
+            .join("\n")
+    }
+
+    /// Render an `include` with `:code:` or `:literal:`: the file's text goes into a
+    /// code or literal block instead of being parsed as reStructuredText
+    fn render_verbatim_include(&self, directive: &Directive, ctx: &mut RenderContext) -> String {
+        let text = directive
+            .arguments
+            .first()
+            .ok_or_else(|| anyhow::anyhow!("missing file name"))
+            .and_then(|name| read_include_file(directive, name));
+        let text = match text {
+            Ok(text) => select_include_lines(&text, &directive.options),
+            Err(e) => {
+                ctx.warn(BuildWarning::new(
+                    ctx.file.to_path_buf(),
+                    Some(directive.line_number),
+                    format!("error in \"include\" directive: {}", e),
+                    crate::error::WarningType::Other,
+                ));
+                return String::new();
+            }
+        };
+
+        if let Some(language) = directive.options.get("code") {
+            let mut options = HashMap::new();
+            for option in ["number-lines", "class", "name"] {
+                if let Some(value) = directive.options.get(option) {
+                    options.insert(option.to_string(), value.clone());
+                }
+            }
+            let code_block = Directive {
+                name: "code".to_string(),
+                arguments: language.split_whitespace().map(str::to_string).collect(),
+                options,
+                content: text.lines().map(str::to_string).collect(),
+                line_number: directive.line_number,
+                source_file: directive.source_file.clone(),
+                source_root: directive.source_root.clone(),
+            };
+            return self.render_directive(&code_block, ctx);
+        }
+
+        let code = html_escape::encode_text(text.trim_end_matches('\n'));
+        let body = match directive.options.get("number-lines") {
+            Some(start) => number_lines(&code, start.trim().parse().unwrap_or(1)),
+            None => code.into_owned(),
+        };
+        parsed_literal(directive, &body)
+    }
+
+    /// Resolve a `:ref:` against the labels of every document: a link to the
+    /// label's page and anchor, titled by its section or caption unless the
+    /// role gives a title
+    ///
+    /// `None` leaves the role to [`RoleRegistry`], as without an environment
+    /// or for `page#label` targets. Unresolved labels render as plain text;
+    /// the builder reports them.
+    fn render_ref(&self, role: &Role, ctx: &RenderContext) -> Option<String> {
+        let (docname, env) = ctx.toctree.filter(|_| role.name == "ref")?;
+        let label = role.target.trim();
+        if label.contains('#') {
+            return None;
+        }
+        let text = |default: &str| {
+            html_escape::encode_text(role.text.as_deref().unwrap_or(default)).into_owned()
+        };
+
+        Some(match env.resolve_label(label) {
+            Some(object) => {
+                let anchor = object.anchor.as_deref().unwrap_or_default();
+                let href = if object.docname == docname {
+                    format!("#{}", anchor)
+                } else if anchor.is_empty() {
+                    // A page of its own, such as `py-modindex`
+                    self.page_uri(docname, &object.docname)
+                } else {
+                    self.page_uri(docname, &format!("{}#{}", object.docname, anchor))
+                };
+                format!(
+                    "<a class=\"reference internal\" href=\"{}\">{}</a>",
+                    href,
+                    text(object.display_name.as_deref().unwrap_or(label))
+                )
+            }
+            None => format!("<span class=\"xref std std-ref\">{}</span>", text(label)),
+        })
+    }
+
+    /// Resolve a `:doc:` against the documents of the build: a link to the
+    /// page, relative to `docname` (or to the source root when the target
+    /// starts with `/`), titled by the page's title unless the role gives one
+    ///
+    /// `None` without an environment; missing documents render as plain text
+    /// and are reported by the builder.
+    fn render_doc(&self, role: &Role, ctx: &RenderContext) -> Option<String> {
+        let (docname, env) = ctx.toctree.filter(|_| role.name == "doc")?;
+        let target = crate::utils::docname_join(docname, role.target.trim());
+        let text = |default: &str| {
+            html_escape::encode_text(role.text.as_deref().unwrap_or(default)).into_owned()
+        };
+
+        Some(match env.titles.get(&target) {
+            Some(title) => format!(
+                "<a class=\"reference internal\" href=\"{}\">{}</a>",
+                self.page_uri(docname, &target),
+                text(if title.is_empty() { &target } else { title })
+            ),
+            None => format!(
+                "<span class=\"xref std std-doc\">{}</span>",
+                text(role.target.trim())
+            ),
+        })
+    }
+
+    /// URI of a `docname` or `docname#anchor` target from `docname`'s page
+    fn page_uri(&self, docname: &str, target: &str) -> String {
+        crate::utils::relative_uri(
+            &format!("{}.html", docname),
+            &crate::utils::target_uri(target, &self.link_suffix),
+            "",
+        )
+    }
+
+    /// Render an `:eq:` reference as the linked number of the labeled equation
+    fn render_eq(&self, role: &Role, ctx: &mut RenderContext) -> String {
+        let label = role.target.trim();
+        match ctx.equations.labels.get(label) {
+            Some(number) => format!(
+                "<a class=\"reference internal\" href=\"#{}\">({})</a>",
+                equation_id(label),
+                number
+            ),
+            None => {
+                ctx.warn(BuildWarning::new(
+                    ctx.file.to_path_buf(),
+                    Some(role.line_number),
+                    format!("equation not found: {}", label),
+                    crate::error::WarningType::BrokenCrossReference,
+                ));
+                format!(
+                    "<span class=\"problematic\">:eq:`{}`</span>",
+                    html_escape::encode_text(label)
+                )
+            }
+        }
+    }
+
+    /// Render a line block, nesting further-indented lines in their own block
+    fn render_line_block(&self, lines: &[String], line: usize, ctx: &mut RenderContext) -> String {
+        let mut html = String::from("<div class=\"line-block\">\n");
+        let mut indents = vec![0];
+
+        for text in lines {
+            if text.trim().is_empty() {
+                html.push_str("<div class=\"line\"><br /></div>\n");
+                continue;
+            }
+
+            let indent = text.len() - text.trim_start().len();
+            while indent < *indents.last().unwrap() {
+                indents.pop();
+                html.push_str("</div>\n");
+            }
+            if indent > *indents.last().unwrap() {
+                indents.push(indent);
+                html.push_str("<div class=\"line-block\">\n");
+            }
+            html.push_str(&format!(
+                "<div class=\"line\">{}</div>\n",
+                self.render_inline(text.trim(), line, ctx)
+            ));
+        }
+
+        for _ in &indents {
+            html.push_str("</div>\n");
+        }
+        html.pop();
+        html
+    }
+
+    /// Render the footnote and citation sections appended to the document
+    fn render_notes(&self, ctx: &mut RenderContext) -> Vec<String> {
+        let mut footnotes = String::new();
+        let mut citations = String::new();
+
+        for index in 0..ctx.notes.notes.len() {
+            let (content, line) = {
+                let note = &ctx.notes.notes[index];
+                (note.content.clone(), note.line)
+            };
+            let body = self.render_inline(&content, line, ctx);
+
+            let note = &ctx.notes.notes[index];
+            let display = html_escape::encode_text(&note.display);
+            let label = match note.backrefs.as_slice() {
+                [] => display.to_string(),
+                [backref] => format!(
+                    "<a role=\"doc-backlink\" href=\"#{}\">{}</a>",
+                    backref, display
+                ),
+                backrefs => {
+                    let links: Vec<String> = backrefs
+                        .iter()
+                        .enumerate()
+                        .map(|(i, backref)| {
+                            format!(
+                                "<a role=\"doc-backlink\" href=\"#{}\">{}</a>",
+                                backref,
+                                i + 1
+                            )
+                        })
+                        .collect();
+                    format!(
+                        "{}<span class=\"backrefs\">({})</span>",
+                        display,
+                        links.join(",")
+                    )
+                }
+            };
+            let label = format!(
+                "<span class=\"label\"><span class=\"fn-bracket\">[</span>{}<span class=\"fn-bracket\">]</span></span>",
+                label
+            );
+
+            if note.citation {
+                citations.push_str(&format!(
+                    "<div class=\"citation\" id=\"{}\" role=\"doc-biblioentry\">\n{}\n<p>{}</p>\n</div>\n",
+                    html_escape::encode_double_quoted_attribute(&note.id),
+                    label,
+                    body
+                ));
+            } else {
+                footnotes.push_str(&format!(
+                    "<aside class=\"footnote brackets\" id=\"{}\" role=\"doc-footnote\">\n{}\n<p>{}</p>\n</aside>\n",
+                    note.id, label, body
+                ));
+            }
+        }
+
+        let mut sections = Vec::new();
+        if !footnotes.is_empty() {
+            sections.push(format!(
+                "<aside class=\"footnote-list brackets\">\n{}</aside>",
+                footnotes
+            ));
+        }
+        if !citations.is_empty() {
+            sections.push(format!(
+                "<div role=\"list\" class=\"citation-list\">\n{}</div>",
+                citations
+            ));
+        }
+        sections
+    }
+
+    fn first_use_of_stub(&self, name: &str) -> bool {
+        self.reported_stubs.lock().unwrap().insert(name.to_string())
+    }
+
+    /// Render inline markup (roles, literals, strong and emphasis) in a text run
+    fn render_inline(&self, text: &str, line: usize, ctx: &mut RenderContext) -> String {
+        self.with_inline_math(text, |text| self.render_markup(text, line, ctx))
+    }
+
+    /// Render inline math between the configured delimiters, passing the text
+    /// around it to `render`
+    fn with_inline_math(&self, text: &str, mut render: impl FnMut(&str) -> String) -> String {
+        let Some(regex) = &self.inline_math_regex else {
+            return render(text);
+        };
+
+        let mut html = String::new();
+        let mut last = 0;
+        for captures in regex.captures_iter(text) {
+            let whole = captures.get(0).unwrap();
+            let tex = captures.iter().skip(1).flatten().next().unwrap();
+            html.push_str(&render(&text[last..whole.start()]));
+            html.push_str(&math_span(tex.as_str()));
+            last = whole.end();
+        }
+        html.push_str(&render(&text[last..]));
+        html
+    }
+
+    fn render_markup(&self, text: &str, line: usize, ctx: &mut RenderContext) -> String {
+        let mut html = String::new();
+        let mut last = 0;
+
+        for captures in self.inline_regex.captures_iter(text) {
+            let whole = captures.get(0).unwrap();
+            html.push_str(&html_escape::encode_text(&text[last..whole.start()]));
+            last = whole.end();
+
+            // Markup is only recognized between whitespace or punctuation,
+            // so `2*3*4` stays text
+            let delimited = captures.name("escaped").is_some()
+                || captures.name("role").is_some()
+                || captures.name("note").is_some()
+                || is_markup_delimited(text, whole.start(), whole.end());
+            if !delimited {
+                html.push_str(&html_escape::encode_text(whole.as_str()));
+                continue;
+            }
+            if let Some(escaped) = captures.name("escaped") {
+                // An escaped whitespace character is removed altogether
+                if !escaped.as_str().trim().is_empty() {
+                    html.push_str(&html_escape::encode_text(escaped.as_str()));
+                }
+                continue;
+            }
+            if let Some(uri) = captures.name("link_uri") {
+                let uri = uri.as_str().split_whitespace().collect::<String>();
+                let text = captures
+                    .name("link_text")
+                    .map(|text| text.as_str().trim())
+                    .filter(|text| !text.is_empty())
+                    .unwrap_or(&uri);
+                let href = match uri.strip_suffix('_') {
+                    // `text <name_>`_ links to the target `name`
+                    Some(name) => ctx.targets.get(&normalize_label(name)).cloned(),
+                    None => Some(uri.clone()),
+                };
+                match href {
+                    Some(href) => html.push_str(&reference_link(&href, text)),
+                    None => html.push_str(&self.unknown_target(whole.as_str(), &uri, line, ctx)),
+                }
+                continue;
+            }
+            let reference = captures
+                .name("reference")
+                .filter(|_| captures["anonymous"].is_empty())
+                .or(captures.name("simple_reference"));
+            if let Some(reference) = reference {
+                let name = reference.as_str().split_whitespace().collect::<Vec<_>>();
+                let name = name.join(" ");
+                match ctx.targets.get(&normalize_label(&name)) {
+                    Some(href) => html.push_str(&reference_link(href, &name)),
+                    None => html.push_str(&self.unknown_target(whole.as_str(), &name, line, ctx)),
+                }
+                continue;
+            }
+            if captures.name("reference").is_some() {
+                // Anonymous references need anonymous targets, which aren't
+                // supported
+                html.push_str(&html_escape::encode_text(whole.as_str()));
+                continue;
+            }
+
+            if let Some(name) = captures.name("role") {
+                let role = parse_role_body(
+                    name.as_str(),
+                    &captures["role_body"],
+                    line,
+                    &ctx.file.to_string_lossy(),
+                );
+                let start = html.len();
+                if role.name == "eq" {
+                    html.push_str(&self.render_eq(&role, ctx));
+                    ctx.trace("role", &role.name, line, html.len() - start);
+                    continue;
+                }
+                if role.name == "index" {
+                    html.push_str(&render_index_role(&role));
+                    ctx.trace("role", &role.name, line, html.len() - start);
+                    continue;
+                }
+                let xref = self
+                    .render_ref(&role, ctx)
+                    .or_else(|| self.render_doc(&role, ctx));
+                if let Some(rendered) = xref {
+                    html.push_str(&rendered);
+                    ctx.trace("role", &role.name, line, html.len() - start);
+                    continue;
+                }
+                if self.warn_unknown_roles && self.roles.get(&role.name).is_none() {
+                    ctx.warn(BuildWarning::unknown_role(
+                        ctx.file.to_path_buf(),
+                        Some(line),
+                        &role.name,
+                    ));
+                }
+                match run_processor(|| self.roles.process_role(&role)) {
+                    Ok(Ok(rendered)) => html.push_str(&rendered),
+                    Ok(Err(e)) => ctx.warn(BuildWarning::new(
+                        ctx.file.to_path_buf(),
+                        Some(line),
+                        format!("error in \"{}\" role: {}", role.name, e),
+                        crate::error::WarningType::Other,
+                    )),
+                    Err(message) => {
+                        ctx.error(BuildErrorReport::processor_panic(
+                            ctx.file.to_path_buf(),
+                            Some(line),
+                            "role",
+                            &role.name,
+                            &message,
+                        ));
+                        html.push_str(&format!(
+                            "<span class=\"problematic\">{}</span>",
+                            html_escape::encode_text(whole.as_str())
+                        ));
+                    }
+                }
+                ctx.trace("role", &role.name, line, html.len() - start);
+            } else if let Some(label) = captures.name("note") {
+                match ctx.notes.reference(label.as_str()) {
+                    Some(link) => html.push_str(&link),
+                    None => {
+                        ctx.warn(BuildWarning::new(
+                            ctx.file.to_path_buf(),
+                            Some(line),
+                            format!(
+                                "footnote or citation reference [{}]_ has no matching definition",
+                                label.as_str()
+                            ),
+                            crate::error::WarningType::BrokenCrossReference,
+                        ));
+                        html.push_str(&html_escape::encode_text(whole.as_str()));
+                    }
+                }
+            } else if let Some(literal) = captures.name("literal") {
+                html.push_str(&format!(
+                    "<code class=\"docutils literal notranslate\"><span class=\"pre\">{}</span></code>",
+                    html_escape::encode_text(literal.as_str())
+                ));
+            } else if let Some(strong) = captures.name("strong") {
+                html.push_str(&format!(
+                    "<strong>{}</strong>",
+                    html_escape::encode_text(&unescape(strong.as_str()))
+                ));
+            } else if let Some(emphasis) = captures.name("emphasis") {
+                html.push_str(&format!(
+                    "<em>{}</em>",
+                    html_escape::encode_text(&unescape(emphasis.as_str()))
+                ));
+            }
+        }
+
+        html.push_str(&html_escape::encode_text(&text[last..]));
+        html
+    }
+
+    /// A reference to a target the document doesn't define: reported, and
+    /// left as text
+    fn unknown_target(
+        &self,
+        source: &str,
+        name: &str,
+        line: usize,
+        ctx: &mut RenderContext,
+    ) -> String {
+        ctx.warn(BuildWarning::new(
+            ctx.file.to_path_buf(),
+            Some(line),
+            format!("Unknown target name: \"{}\"", name),
+            crate::error::WarningType::BrokenCrossReference,
+        ));
+        html_escape::encode_text(source).into_owned()
+    }
+
+    fn render_markdown_node<'a>(
+        &self,
+        node: &MarkdownNode,
+        anchors: &mut impl Iterator<Item = &'a str>,
+    ) -> String {
+        match node {
+            MarkdownNode::Heading { text, level, .. } => {
+                let level = (*level).clamp(1, 6);
+                let anchor = anchors.next().unwrap_or_default();
+                format!(
+                    "<h{} id=\"{}\">{}</h{}>",
+                    level,
+                    html_escape::encode_double_quoted_attribute(anchor),
+                    html_escape::encode_text(text),
+                    level
+                )
+            }
+            MarkdownNode::Paragraph { content, .. } => {
+                format!("<p>{}</p>", self.with_inline_math(content, markdown_inline))
+            }
+            MarkdownNode::CodeBlock {
+                language, content, ..
+            } => self.with_copy_button(
+                render_code_block(language.as_ref(), content, false, self.linenos_style),
+                language.as_deref(),
+            ),
+            MarkdownNode::List { items, ordered, .. } => {
+                let tag = if *ordered { "ol" } else { "ul" };
+                let items: String = items
+                    .iter()
+                    .map(|item| {
+                        format!("<li>{}</li>", self.with_inline_math(item, markdown_inline))
+                    })
+                    .collect();
+                format!("<{}>{}</{}>", tag, items, tag)
+            }
+            MarkdownNode::Table { headers, rows, .. } => {
+                let header: String = headers
+                    .iter()
+                    .map(|cell| format!("<th>{}</th>", markdown_inline(cell)))
+                    .collect();
+                let body: String = rows
+                    .iter()
+                    .map(|row| {
+                        let cells: String = row
+                            .iter()
+                            .map(|cell| format!("<td>{}</td>", markdown_inline(cell)))
+                            .collect();
+                        format!("<tr>{}</tr>", cells)
+                    })
+                    .collect();
+                format!(
+                    "<table><thead><tr>{}</tr></thead><tbody>{}</tbody></table>",
+                    header, body
+                )
+            }
+        }
+    }
+}
+
+/// The de-duplicated anchors `env` registered for the Python objects of
+/// `docname`, in the order they were registered
+fn py_object_anchors(
+    env: &BuildEnvironment,
+    docname: &str,
+) -> HashMap<(String, String), VecDeque<String>> {
+    let mut anchors: HashMap<_, VecDeque<_>> = HashMap::new();
+    let Some(domain) = env.domains.get("py") else {
+        return anchors;
+    };
+    for (objtype, objects) in &domain.objects {
+        for object in objects.iter().filter(|object| object.docname == docname) {
+            if let Some(anchor) = &object.anchor {
+                anchors
+                    .entry((objtype.clone(), object.name.clone()))
+                    .or_default()
+                    .push_back(anchor.clone());
+            }
+        }
+    }
+    anchors
+}
+
+/// The hyperlink targets a document's `name_` references resolve to: its
+/// external targets' URIs, and the ids of its internal targets and sections
+fn hyperlink_targets(document: &Document) -> HashMap<String, String> {
+    let mut targets = HashMap::new();
+    for entry in &document.toc {
+        targets
+            .entry(normalize_label(&entry.title))
+            .or_insert_with(|| format!("#{}", entry.anchor));
+    }
+    if let DocumentContent::RestructuredText(rst) = &document.content {
+        for node in &rst.ast {
+            if let RstNode::Target { name, refuri, .. } = node {
+                let href = match refuri {
+                    Some(refuri) => refuri.clone(),
+                    None => format!("#{}", name_to_id(name)),
+                };
+                targets.insert(normalize_label(name), href);
+            }
+        }
+    }
+    targets
+}
+
+/// A link to a hyperlink target: external for URIs, internal for `#` ids
+fn reference_link(href: &str, text: &str) -> String {
+    let class = match href.starts_with('#') {
+        true => "reference internal",
+        false => "reference external",
+    };
+    format!(
+        "<a class=\"{}\" href=\"{}\">{}</a>",
+        class,
+        html_escape::encode_double_quoted_attribute(href),
+        html_escape::encode_text(text)
+    )
+}
+
+/// Whether inline markup spanning `start..end` of `text` follows
+/// reStructuredText's recognition rules: preceded by whitespace or opening
+/// punctuation, followed by whitespace or closing punctuation
+fn is_markup_delimited(text: &str, start: usize, end: usize) -> bool {
+    let before = text[..start].chars().next_back();
+    let after = text[end..].chars().next();
+    let opens = before.is_none_or(|c| c.is_whitespace() || "-:/'\"<([{".contains(c));
+    let closes = after.is_none_or(|c| c.is_whitespace() || "-.,:;!?\\/'\")]}>".contains(c));
+    opens && closes
+}
+
+/// Remove backslash escapes; an escaped whitespace character is removed too
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some(escaped) if escaped.is_whitespace() => {}
+            Some(escaped) => unescaped.push(escaped),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// Render Markdown inline markup (emphasis, code, links, ...) as HTML,
+/// keeping the whitespace around it
+fn markdown_inline(text: &str) -> String {
+    let core = text.trim();
+    if core.is_empty() {
+        return text.to_string();
+    }
+    let options = pulldown_cmark::Options::ENABLE_STRIKETHROUGH;
+    let mut html = String::new();
+    pulldown_cmark::html::push_html(&mut html, pulldown_cmark::Parser::new_ext(core, options));
+    let html = html.trim_end();
+    let html = html
+        .strip_prefix("<p>")
+        .and_then(|html| html.strip_suffix("</p>"))
+        .unwrap_or(html);
+    let start = text.len() - text.trim_start().len();
+    format!("{}{}{}", &text[..start], html, &text[start + core.len()..])
+}
+
+/// Add classes to the first element of an HTML fragment, skipping leading comments
+///
+/// Returns `None` when there are no classes or the fragment has no element.
+fn add_classes_to_first_element(html: &str, classes: &[String]) -> Option<String> {
+    if classes.is_empty() {
+        return None;
+    }
+
+    let mut offset = 0;
+    let start = loop {
+        let candidate = offset + html[offset..].find('<')?;
+        if html[candidate + 1..].starts_with(|c: char| c.is_ascii_alphabetic()) {
+            break candidate;
+        }
+        offset = candidate + 1;
+    };
+    let end = start + html[start..].find('>')?;
+    let tag = &html[start..end];
+    let classes = classes.join(" ");
+
+    let tag = match tag.find("class=\"") {
+        Some(position) => {
+            let value_start = position + "class=\"".len();
+            format!("{}{} {}", &tag[..value_start], classes, &tag[value_start..])
+        }
+        None => {
+            let tag_end = tag
+                .strip_suffix('/')
+                .map_or(tag.len(), |t| t.trim_end().len());
+            format!(
+                "{} class=\"{}\"{}",
+                &tag[..tag_end],
+                classes,
+                &tag[tag_end..]
+            )
+        }
+    };
+
+    Some(format!("{}{}{}", &html[..start], tag, &html[end..]))
+}
+
+fn render_code_block(
+    language: Option<&String>,
+    content: &str,
+    linenos: bool,
+    style: LinenosStyle,
+) -> String {
+    match language {
+        Some(language) => highlight_block(
+            language,
+            &highlight_code(language, content),
+            linenos.then_some(1),
+            style,
+        ),
+        None => {
+            let code = html_escape::encode_text(content);
+            if linenos {
+                format!(
+                    "<pre class=\"literal-block\">{}</pre>",
+                    number_lines(&code, 1)
+                )
+            } else {
+                format!("<pre class=\"literal-block\">{}</pre>", code)
+            }
+        }
+    }
+}
+
+/// An `:index:` role: the target its index entries link to, then its text
+fn render_index_role(role: &Role) -> String {
+    let text = match &role.text {
+        Some(text) => text.as_str(),
+        None => role.target.trim().trim_start_matches('!'),
+    };
+    format!(
+        "<span class=\"target\" id=\"{}\"></span>{}",
+        index_anchor(role.line_number, Some(&role.target)),
+        html_escape::encode_text(text)
+    )
+}
+
+/// Split a role body into explicit title and target (`text <target>`)
+pub(crate) fn parse_role_body(name: &str, body: &str, line: usize, source_file: &str) -> Role {
+    let (text, target) = match body
+        .trim_end()
+        .strip_suffix('>')
+        .and_then(|b| b.rsplit_once('<'))
+    {
+        Some((text, target)) if !text.trim().is_empty() => {
+            (Some(text.trim().to_string()), target.trim().to_string())
+        }
+        _ => (None, body.to_string()),
+    };
+
+    Role {
+        name: name.to_string(),
+        target,
+        text,
+        line_number: line,
+        source_file: source_file.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::BuildConfig;
+    use crate::parser::Parser;
+
+    fn render_source(name: &str, content: &str) -> (Document, RenderedDocument) {
+        render_with_config(&BuildConfig::default(), name, content)
+    }
+
+    fn render_with_config(
+        config: &BuildConfig,
+        name: &str,
+        content: &str,
+    ) -> (Document, RenderedDocument) {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join(name);
+        std::fs::write(&path, content).unwrap();
+
+        let parser = Parser::new(config).unwrap();
+        let document = parser.parse(&path, content).unwrap();
+        let rendered = HtmlRenderer::new(config).render(&document);
+        (document, rendered)
+    }
+
+    #[test]
+    fn test_renders_titles_paragraphs_and_roles() {
+        let (_, rendered) = render_source(
+            "index.rst",
+            "Guide\n#####\n\nUse ``make html`` and :doc:`the intro <intro>` *now*.\n",
+        );
+
+        assert!(rendered.html.contains("<h1 id=\"guide\">Guide</h1>"));
+        assert!(rendered
+            .html
+            .contains("<span class=\"pre\">make html</span>"));
+        assert!(rendered
+            .html
+            .contains("<a class=\"reference internal\" href=\"intro.html\">the intro</a>"));
+        assert!(rendered.html.contains("<em>now</em>"));
+        assert!(rendered.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_footnotes_link_to_definitions_and_back() {
+        let (_, rendered) = render_source(
+            "index.rst",
+            "Text [#]_ and [#note]_ and [*]_ and [3]_ per [CIT2002]_.\n\n.. [#] Auto.\n.. [#note] Named\n   footnote.\n.. [*] Symbol.\n.. [3] Manual.\n.. [CIT2002] A citation.\n",
+        );
+        let html = &rendered.html;
+
+        assert!(html.contains(
+            "<a class=\"footnote-reference brackets\" href=\"#footnote-1\" id=\"footnote-reference-1\" role=\"doc-noteref\"><span class=\"fn-bracket\">[</span>1<span class=\"fn-bracket\">]</span></a>"
+        ));
+        assert!(html.contains("href=\"#footnote-2\" id=\"footnote-reference-2\" role=\"doc-noteref\"><span class=\"fn-bracket\">[</span>2<"));
+        assert!(html.contains("href=\"#footnote-3\" id=\"footnote-reference-3\" role=\"doc-noteref\"><span class=\"fn-bracket\">[</span>*<"));
+        assert!(html.contains("href=\"#footnote-4\" id=\"footnote-reference-4\" role=\"doc-noteref\"><span class=\"fn-bracket\">[</span>3<"));
+        assert!(html.contains(
+            "<a class=\"citation-reference\" href=\"#cit2002\" id=\"citation-reference-5\" role=\"doc-biblioref\">[CIT2002]</a>"
+        ));
+
+        // Definitions are appended with backlinks to their references
+        assert!(html.contains("<aside class=\"footnote brackets\" id=\"footnote-1\" role=\"doc-footnote\">\n<span class=\"label\"><span class=\"fn-bracket\">[</span><a role=\"doc-backlink\" href=\"#footnote-reference-1\">1</a>"));
+        assert!(html.contains("<p>Named footnote.</p>"));
+        assert!(html.contains("<div class=\"citation\" id=\"cit2002\" role=\"doc-biblioentry\">"));
+        assert!(html.contains("href=\"#citation-reference-5\">CIT2002</a>"));
+        assert!(rendered.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_definition_and_field_lists_render_as_description_lists() {
+        let (_, rendered) = render_source(
+            "index.rst",
+            "Intro.\n\n:Parameters: the **inputs**\n:Returns: a value\n   spanning lines\n\nterm\n   Definition of\n   the term.\n\nother : type\n   First paragraph.\n\n   Second paragraph.\n\nAfter.\n",
+        );
+        let html = &rendered.html;
+
+        assert!(html.contains(
+            "<dl class=\"field-list simple\">\n<dt class=\"field-odd\">Parameters<span class=\"colon\">:</span></dt>\n<dd class=\"field-odd\"><p>the <strong>inputs</strong></p></dd>\n<dt class=\"field-even\">Returns<span class=\"colon\">:</span></dt>\n<dd class=\"field-even\"><p>a value spanning lines</p></dd>\n</dl>"
+        ));
+        assert!(html.contains(
+            "<dl>\n<dt>term</dt>\n<dd><p>Definition of the term.</p></dd>\n<dt>other<span class=\"classifier-delimiter\">:</span> <span class=\"classifier\">type</span></dt>\n<dd><p>First paragraph.</p><p>Second paragraph.</p></dd>\n</dl>"
+        ));
+        assert!(html.contains("<p>Intro.</p>"));
+        assert!(html.ends_with("<p>After.</p>"));
+    }
+
+    #[test]
+    fn test_line_blocks_and_block_quotes() {
+        let (_, rendered) = render_source(
+            "index.rst",
+            "| Roses are red,\n|    Violets are blue.\n|\n| Sugar is\n  sweet.\n\nSaid:\n\n   To be, or not\n   to be.\n\n   -- Hamlet\n",
+        );
+        let html = &rendered.html;
+
+        assert!(html.contains(
+            "<div class=\"line-block\">\n<div class=\"line\">Roses are red,</div>\n<div class=\"line-block\">\n<div class=\"line\">Violets are blue.</div>\n<div class=\"line\"><br /></div>\n</div>\n<div class=\"line\">Sugar is sweet.</div>\n</div>"
+        ));
+        assert!(html.contains(
+            "<blockquote>\n<p>To be, or not to be.</p>\n<p class=\"attribution\">\u{2014}Hamlet</p>\n</blockquote>"
+        ));
+    }
+
+    #[test]
+    fn test_transition_is_not_a_title_underline() {
+        let (document, rendered) = render_source(
+            "index.rst",
+            "Title\n#####\n\nBefore.\n\n----------\n\nAfter.\n",
+        );
+
+        assert_eq!(document.toc.len(), 1);
+        assert_eq!(
+            rendered.html,
+            "<h1 id=\"title\">Title</h1>\n<p>Before.</p>\n<hr class=\"docutils\" />\n<p>After.</p>"
+        );
+    }
+
+    #[test]
+    fn test_rst_class_applies_to_next_element() {
+        let (_, rendered) = render_source(
+            "index.rst",
+            ".. rst-class:: lead\n\nIntro paragraph.\n\n.. rst-class:: wide striped\n\n.. note::\n\n   Styled note.\n\nPlain paragraph.\n",
+        );
+
+        assert!(rendered
+            .html
+            .contains("<p class=\"lead\">Intro paragraph.</p>"));
+        assert!(rendered
+            .html
+            .contains("<div class=\"wide striped admonition note\">"));
+        assert!(rendered.html.contains("<p>Plain paragraph.</p>"));
+        assert!(rendered.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_unknown_directive_and_role_warn_with_location() {
+        let content = "Guide\n#####\n\nSee :fnuc:`open`.\n\n.. notee::\n\n   Typo.\n";
+        let (document, rendered) = render_source("index.rst", content);
+
+        let warnings: Vec<(Option<usize>, &str)> = rendered
+            .warnings
+            .iter()
+            .map(|warning| (warning.line, warning.message.as_str()))
+            .collect();
+        assert_eq!(
+            warnings,
+            vec![
+                (Some(4), "Unknown interpreted text role \"fnuc\"."),
+                (Some(6), "Unknown directive type \"notee\"."),
+            ]
+        );
+        assert!(rendered
+            .warnings
+            .iter()
+            .all(|warning| warning.file == document.source_path));
+        assert!(rendered.html.contains("<!-- Unknown directive: notee -->"));
+
+        let config = BuildConfig {
+            suppress_warnings: Some(vec!["directive".to_string(), "role.unknown".to_string()]),
+            ..BuildConfig::default()
+        };
+        let (_, rendered) = render_with_config(&config, "index.rst", content);
+        assert!(rendered.warnings.is_empty());
+        assert!(rendered.html.contains("<!-- Unknown directive: notee -->"));
+    }
+
+    #[test]
+    fn test_stub_directive_warns_once_with_location() {
+        let (document, rendered) = render_source(
+            "index.rst",
+            "Guide\n#####\n\n.. sidebar:: Extra\n\n   Side text.\n\n.. sidebar:: More\n\n   Again.\n",
+        );
+
+        assert_eq!(rendered.warnings.len(), 1);
+        let warning = &rendered.warnings[0];
+        assert_eq!(warning.file, document.source_path);
+        assert_eq!(warning.line, Some(4));
+        assert_eq!(
+            warning.message,
+            "directive 'sidebar' is not yet fully supported"
+        );
+        assert!(rendered.html.contains("<!-- sidebar directive: Extra -->"));
+    }
+
+    #[test]
+    fn test_highlight_linenothreshold_numbers_long_blocks() {
+        let (_, rendered) = render_source(
+            "index.rst",
+            "Guide\n#####\n\n.. highlight:: python\n   :linenothreshold: 2\n\n\
+             .. code-block::\n\n   a = 1\n   b = 2\n   c = 3\n\n\
+             .. code-block:: rust\n\n   let x = 1;\n",
+        );
+
+        assert!(rendered.html.contains(
+            "<pre><span class=\"normal\">1</span>\n<span class=\"normal\">2</span>\n\
+             <span class=\"normal\">3</span></pre></div></td><td class=\"code\">\
+             <pre><code class=\"language-python\">a <span class=\"o\">=</span> <span class=\"mi\">1</span>\n"
+        ));
+        // Short blocks stay unnumbered
+        assert!(rendered
+            .html
+            .contains("<code class=\"language-rust\"><span class=\"kd\">let</span> x"));
+    }
+
+    #[test]
+    fn test_custom_roles_from_conf_py() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let conf_py = temp_dir.path().join("conf.py");
+        std::fs::write(
+            &conf_py,
+            "custom_roles = {'issue': {'template': 'https://example.org/issues/{target}', \
+             'class': 'issue'}, 'kbd': {'template': '[{text}]',},}\n",
+        )
+        .unwrap();
+        let config = crate::python_config::PythonConfigParser::new()
+            .unwrap()
+            .parse_conf_py(&conf_py)
+            .unwrap()
+            .to_build_config();
+
+        let (_, rendered) = render_with_config(
+            &config,
+            "index.rst",
+            "Guide\n#####\n\nFixed in :issue:`Bug 42 <42>`, press :kbd:`Ctrl`.\n",
+        );
+
+        assert!(rendered.html.contains(
+            "<a class=\"reference external issue\" href=\"https://example.org/issues/42\">Bug 42</a>"
+        ));
+        assert!(rendered.html.contains("<span class=\"kbd\">[Ctrl]</span>"));
+        assert!(rendered.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_ref_anchor_survives_directory_style_links() {
+        let config = BuildConfig {
+            html_link_suffix: Some(String::new()),
+            ..BuildConfig::default()
+        };
+        let (_, rendered) = render_with_config(
+            &config,
+            "index.rst",
+            "Guide\n#####\n\nSee :ref:`guide#intro`, :ref:`setup <install/index#Getting Started>` \
+             and :doc:`guide`.\n",
+        );
+
+        assert!(rendered
+            .html
+            .contains("<a class=\"reference internal\" href=\"guide/#intro\">guide#intro</a>"));
+        assert!(rendered
+            .html
+            .contains("href=\"install/#getting-started\">setup</a>"));
+        assert!(rendered.html.contains("href=\"guide/\">guide</a>"));
+    }
+
+    #[test]
+    fn test_only_directive_follows_builder_tags() {
+        let content = "Guide\n#####\n\n.. only:: html\n\n   Web *only*.\n\n   Second paragraph.\n\n.. only:: latex\n\n   Print only.\n";
+        let (document, rendered) = render_source("index.rst", content);
+        assert!(rendered
+            .html
+            .contains("<p>Web <em>only</em>.</p>\n<p>Second paragraph.</p>"));
+        assert!(!rendered.html.contains("Print only."));
+        assert!(rendered.warnings.is_empty());
+
+        let content = "Guide\n#####\n\n.. only:: html\n\n   .. note::\n\n      Mind the *gap*.\n\n   - first\n   - second\n\n   .. only:: latex\n\n      Nested print.\n";
+        let (_, rendered) = render_source("index.rst", content);
+        assert!(rendered.html.contains("<div class=\"admonition note\">"));
+        assert!(rendered.html.contains("Mind the <em>gap</em>."));
+        assert!(rendered
+            .html
+            .contains("<ul class=\"simple\"><li>first</li><li>second</li></ul>"));
+        assert!(!rendered.html.contains(".. note::"));
+        assert!(!rendered.html.contains("Nested print."));
+
+        let mut renderer = HtmlRenderer::new(&BuildConfig::default());
+        renderer.set_tags(Tags::for_builder(&[], "latex", "latex"));
+        let html = renderer.render(&document).html;
+        assert!(!html.contains("Web"));
+        assert!(html.contains("<p>Print only.</p>"));
+    }
+
+    #[test]
+    fn test_named_admonition_is_ref_target() {
+        let (_, rendered) = render_source(
+            "index.rst",
+            "Guide\n#####\n\n.. warning::\n   :name: Upgrade_Warning\n\n   Back up first.\n\n\
+             See :ref:`the warning <upgrade_warning>`.\n",
+        );
+
+        assert!(rendered
+            .html
+            .contains("<div class=\"admonition warning\" id=\"upgrade-warning\">"));
+        assert!(rendered
+            .html
+            .contains("<a class=\"reference internal\" href=\"#upgrade-warning\">the warning</a>"));
+    }
+
+    struct PanickingDirective;
+
+    impl crate::directives::DirectiveProcessor for PanickingDirective {
+        fn process(&self, directive: &Directive) -> anyhow::Result<String> {
+            Ok(directive.arguments[3].clone())
+        }
+
+        fn get_name(&self) -> &str {
+            "explode"
+        }
+
+        fn get_option_spec(&self) -> HashMap<String, crate::directives::DirectiveOptionType> {
+            HashMap::new()
+        }
+    }
+
+    struct PanickingRole;
+
+    impl crate::roles::RoleProcessor for PanickingRole {
+        fn process(&self, _role: &Role) -> anyhow::Result<String> {
+            panic!("role exploded");
+        }
+
+        fn get_name(&self) -> &str {
+            "boom"
+        }
+    }
+
+    #[test]
+    fn test_panicking_processors_become_located_errors() {
+        let content =
+            "Guide\n#####\n\n.. explode::\n\nSay :boom:`now` please.\n\nStill rendered.\n";
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("index.rst");
+        std::fs::write(&path, content).unwrap();
+
+        let config = BuildConfig::default();
+        let document = Parser::new(&config).unwrap().parse(&path, content).unwrap();
+        let mut renderer = HtmlRenderer::new(&config);
+        renderer.directives.register(Box::new(PanickingDirective));
+        renderer.roles.register(Box::new(PanickingRole));
+        let rendered = renderer.render(&document);
+
+        let errors: Vec<(Option<usize>, &str)> = rendered
+            .errors
+            .iter()
+            .map(|error| (error.line, error.message.as_str()))
+            .collect();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].0, Some(4));
+        assert!(errors[0]
+            .1
+            .starts_with("\"explode\" directive panicked: index out of bounds"));
+        assert_eq!(
+            errors[1],
+            (Some(6), "\"boom\" role panicked: role exploded")
+        );
+        assert!(rendered.errors.iter().all(|error| error.file == path));
+
+        assert!(rendered.html.contains("<div class=\"system-message\">"));
+        assert!(rendered
+            .html
+            .contains("Say <span class=\"problematic\">:boom:`now`</span> please."));
+        assert!(rendered.html.contains("Still rendered."));
+    }
+
+    #[test]
+    fn test_docutils_code_directive_matches_code_block() {
+        let (_, code) = render_source(
+            "index.rst",
+            "Guide\n#####\n\n.. code:: python\n   :number-lines: 5\n\n   a = 1\n   b = 2\n",
+        );
+        let (_, code_block) = render_source(
+            "index.rst",
+            "Guide\n#####\n\n.. code-block:: python\n   :lineno-start: 5\n\n   a = 1\n   b = 2\n",
+        );
+
+        assert!(code.warnings.is_empty());
+        assert_eq!(code.html, code_block.html);
+        assert!(code.html.contains(
+            "<div class=\"highlight-python\"><table class=\"highlighttable\"><tr>\
+             <td class=\"linenos\"><div class=\"linenodiv\"><pre><span class=\"normal\">5</span>\n\
+             <span class=\"normal\">6</span></pre></div></td><td class=\"code\">\
+             <pre><code class=\"language-python\">a <span class=\"o\">=</span> <span class=\"mi\">1</span>\n\
+             b <span class=\"o\">=</span> <span class=\"mi\">2</span></code>"
+        ));
+    }
+
+    #[test]
+    fn test_parsed_literal_keeps_whitespace_and_links_refs() {
+        let (_, rendered) = render_source(
+            "index.rst",
+            "Guide\n#####\n\n.. parsed-literal::\n\n   $ sphinx-ultra build  *SOURCE*\n       see :ref:`setup <install#setup>`\n",
+        );
+
+        assert!(rendered.html.contains(
+            "<pre class=\"literal-block\">$ sphinx-ultra build  <em>SOURCE</em>\n    \
+             see <a class=\"reference internal\" href=\"install.html#setup\">setup</a></pre>"
+        ));
+    }
+
+    #[test]
+    fn test_include_code_renders_file_as_highlighted_source() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            temp_dir.path().join("sample.py"),
+            "def greet():\n    print(\"*hi* <there>\")\n",
+        )
+        .unwrap();
+        std::fs::write(temp_dir.path().join("notes.txt"), b"caf\xe9\n").unwrap();
+
+        let path = temp_dir.path().join("index.rst");
+        let content =
+            "Guide\n#####\n\n.. include:: sample.py\n   :code: python\n   :number-lines:\n\n\
+                       .. include:: notes.txt\n   :literal:\n   :encoding: latin-1\n";
+        std::fs::write(&path, content).unwrap();
+        let config = BuildConfig::default();
+        let document = Parser::new(&config).unwrap().parse(&path, content).unwrap();
+        let rendered = HtmlRenderer::new(&config).render(&document);
+
+        assert!(rendered.warnings.is_empty(), "{:?}", rendered.warnings);
+        assert!(rendered.html.contains(
+            "<pre><span class=\"normal\">1</span>\n<span class=\"normal\">2</span></pre></div></td>\
+             <td class=\"code\"><pre><code class=\"language-python\">\
+             <span class=\"kd\">def</span> <span class=\"nf\">greet</span>():\n\
+             \x20   <span class=\"nb\">print</span>(<span class=\"s2\">\"*hi* &lt;there&gt;\"</span>)\
+             </code></pre></td></tr></table></div>"
+        ));
+        assert!(rendered
+            .html
+            .contains("<pre class=\"literal-block\">caf\u{e9}</pre>"));
+    }
+
+    #[test]
+    fn test_inline_math_delimiters_when_configured() {
+        let content = "Guide\n#####\n\nArea is $x^2$, or :math:`x^2`; costs $5 and $6.\n";
+        let (_, plain) = render_source("index.rst", content);
+        assert!(plain.html.contains("Area is $x^2$"));
+
+        let config = BuildConfig {
+            math_inline_delimiters: vec![
+                ("$".to_string(), "$".to_string()),
+                ("\\(".to_string(), "\\)".to_string()),
+            ],
+            ..BuildConfig::default()
+        };
+        let (_, rendered) = render_with_config(&config, "index.rst", content);
+        let span = "<span class=\"math notranslate nohighlight\">\\(x^2\\)</span>";
+        assert!(rendered
+            .html
+            .contains(&format!("Area is {}, or {}; costs $5 and $6.", span, span)));
+
+        let (_, markdown) =
+            render_with_config(&config, "guide.md", "# Guide\n\nWith $a + b$ here.\n");
+        assert!(markdown.html.contains(
+            "<p>With <span class=\"math notranslate nohighlight\">\\(a + b\\)</span> here.</p>"
+        ));
+    }
+
+    #[test]
+    fn test_rst_inline_markup_respects_delimiters_and_escapes() {
+        let (_, rendered) = render_source(
+            "index.rst",
+            "Guide\n#####\n\n**bold** *italic* ``a*b*c`` (*x*) 2*3*4 \\*not\\* *a *b* **c *d***.\n",
+        );
+
+        assert!(
+            rendered.html.contains(
+                "<p><strong>bold</strong> <em>italic</em> <code class=\"docutils literal notranslate\">\
+                 <span class=\"pre\">a*b*c</span></code> (<em>x</em>) 2*3*4 *not* <em>a *b</em> \
+                 **c *d***.</p>"
+            ),
+            "{}",
+            rendered.html
+        );
+    }
+
+    #[test]
+    fn test_rst_hyperlink_references_resolve_to_targets() {
+        let (_, rendered) = render_source(
+            "index.rst",
+            "Guide\n#####\n\n.. _python: https://www.python.org/\n\n.. _`Rust docs`:\n   https://doc.rust-lang.org/\n\n\
+             Install python_ and read `Rust docs`_, `the book <https://doc.rust-lang.org/book/>`_, \
+             `Guide`_ or `home <python_>`_; not missing_ or __init__.\n",
+        );
+
+        let html = &rendered.html;
+        assert!(html.contains(
+            "Install <a class=\"reference external\" href=\"https://www.python.org/\">python</a> \
+             and read <a class=\"reference external\" href=\"https://doc.rust-lang.org/\">Rust docs</a>, \
+             <a class=\"reference external\" href=\"https://doc.rust-lang.org/book/\">the book</a>, \
+             <a class=\"reference internal\" href=\"#guide\">Guide</a> or \
+             <a class=\"reference external\" href=\"https://www.python.org/\">home</a>; \
+             not missing_ or __init__.</p>"
+        ), "{}", html);
+        assert!(!html.contains("https://doc.rust-lang.org/</p>"), "{}", html);
+        assert_eq!(rendered.warnings.len(), 1, "{:?}", rendered.warnings);
+        assert_eq!(
+            rendered.warnings[0].message,
+            "Unknown target name: \"missing\""
+        );
+    }
+
+    #[test]
+    fn test_headings_targets_and_objects_share_one_anchor_namespace() {
+        let (document, rendered) = render_source(
+            "index.rst",
+            "Guide\n#####\n\n.. _sec:\n\nSec\n===\n\nSec\n===\n\n.. py:function:: sec()\n\nSee sec_.\n",
+        );
+
+        let html = &rendered.html;
+        assert!(html.contains("<span id=\"sec\"></span>"), "{}", html);
+        assert!(html.contains(" id=\"sec-1\">Sec</h"), "{}", html);
+        assert!(html.contains(" id=\"sec-2\">Sec</h"), "{}", html);
+        assert!(
+            html.contains("<dt class=\"sig sig-object py\" id=\"sec-3\">"),
+            "{}",
+            html
+        );
+        assert_eq!(html.matches("id=\"sec\"").count(), 1, "{}", html);
+        assert!(html.contains("<a class=\"reference internal\" href=\"#sec\">sec</a>"));
+
+        // The object's anchor steers clear of both
+        let mut env = BuildEnvironment::new(BuildConfig::default());
+        env.note_python_objects("index", &document);
+        let anchors: Vec<_> = env
+            .get_all_objects()
+            .iter()
+            .filter_map(|object| object.anchor.clone())
+            .collect();
+        assert_eq!(anchors, vec!["sec-3"]);
+    }
+
+    #[test]
+    fn test_admonition_content_is_rendered_as_nested_rst() {
+        let (_, rendered) = render_source(
+            "index.rst",
+            "Guide\n#####\n\n.. seealso:: Module *zipfile*\n\n   Its ``ZipFile`` class.\n\n\
+             .. admonition:: Use **care**\n\n   Run::\n\n      rm -rf <dir>\n\n\
+             .. acks::\n\n   * Alice & Bob\n\n.. tip::\n\n   Try :bogus:`it`.\n",
+        );
+
+        let html = &rendered.html;
+        assert!(
+            html.contains(
+                "<div class=\"admonition seealso\">\n<p class=\"admonition-title\">See also</p>\n\
+             <p>Module <em>zipfile</em></p>\n<p>Its <code class=\"docutils literal notranslate\">"
+            ),
+            "{}",
+            html
+        );
+        assert!(
+            html.contains(
+                "<p class=\"admonition-title\">Use <strong>care</strong></p>\n<p>Run:</p>"
+            ),
+            "{}",
+            html
+        );
+        assert!(html.contains("rm -rf &lt;dir&gt;"), "{}", html);
+        assert!(
+            html.contains(
+                "<div class=\"acks\">\n<ul class=\"simple\"><li>Alice &amp; Bob</li></ul>\n</div>"
+            ),
+            "{}",
+            html
+        );
+        // Problems in the content are reported at their own line
+        assert_eq!(rendered.warnings.len(), 1, "{:?}", rendered.warnings);
+        assert_eq!(rendered.warnings[0].line, Some(20));
+    }
+
+    #[test]
+    fn test_heading_levels_follow_order_of_first_appearance() {
+        let (_, rendered) = render_source(
+            "index.rst",
+            "Guide\n=====\n\nPart\n----\n\nDetail\n######\n\nNext part\n---------\n",
+        );
+
+        assert_eq!(
+            rendered.html,
+            "<h1 id=\"guide\">Guide</h1>\n<h2 id=\"part\">Part</h2>\n\
+             <h3 id=\"detail\">Detail</h3>\n<h2 id=\"next-part\">Next part</h2>"
+        );
+    }
+
+    #[test]
+    fn test_overloaded_python_objects_render_distinct_ids() {
+        let (document, rendered) = render_source(
+            "api.rst",
+            "API\n###\n\n.. py:function:: foo(a)\n\n.. py:function:: foo(a, b)\n",
+        );
+
+        let html = &rendered.html;
+        assert!(
+            html.contains("<dt class=\"sig sig-object py\" id=\"foo\">"),
+            "{}",
+            html
+        );
+        assert!(
+            html.contains("<dt class=\"sig sig-object py\" id=\"foo-1\">"),
+            "{}",
+            html
+        );
+        assert!(html.contains("href=\"#foo-1\" title=\"Link to this definition\""));
+
+        // The ids match the anchors the environment records for the inventory
+        let mut env = BuildEnvironment::new(BuildConfig::default());
+        env.note_python_objects("api", &document);
+        let html = HtmlRenderer::new(&BuildConfig::default())
+            .render_in_env(&document, "api", &env)
+            .html;
+        assert_eq!(html, rendered.html);
+    }
+
+    #[test]
+    fn test_markdown_inline_markup_is_rendered() {
+        let (_, rendered) = render_source(
+            "guide.md",
+            "# Guide\n\nSome **bold**, `a < b` and [a link](other.html).\n\n\
+             1. *first*\n2. second\n\n| Name | Kind |\n| --- | --- |\n| `x` | int |\n",
+        );
+
+        assert!(rendered.html.contains(
+            "<p>Some <strong>bold</strong>, <code>a &lt; b</code> and \
+             <a href=\"other.html\">a link</a>.</p>"
+        ));
+        assert!(rendered
+            .html
+            .contains("<ol><li><em>first</em></li><li>second</li></ol>"));
+        assert!(rendered
+            .html
+            .contains("<tbody><tr><td><code>x</code></td><td>int</td></tr></tbody>"));
+    }
+
+    #[test]
+    fn test_eq_role_links_to_numbered_equation() {
+        let (_, rendered) = render_source(
+            "index.rst",
+            "Guide\n#####\n\n.. math:: a + b\n\n.. math::\n   :label: euler\n\n   e^{i\\pi} + 1 = 0\n\n\
+             .. math::\n   :label: pythagoras\n\n   a^2 + b^2 = c^2\n\n\
+             By :eq:`pythagoras` and :eq:`euler`, not :eq:`missing`.\n",
+        );
+
+        assert!(rendered
+            .html
+            .contains("<div class=\"math notranslate nohighlight\">\\[a + b\\]</div>"));
+        assert!(rendered.html.contains(
+            "<div class=\"math notranslate nohighlight\" id=\"equation-pythagoras\">\
+             <span class=\"eqno\">(2)<a class=\"headerlink\" href=\"#equation-pythagoras\" \
+             title=\"Link to this equation\">\u{b6}</a></span>\\[a^2 + b^2 = c^2\\]</div>"
+        ));
+        assert!(rendered.html.contains(
+            "By <a class=\"reference internal\" href=\"#equation-pythagoras\">(2)</a> and \
+             <a class=\"reference internal\" href=\"#equation-euler\">(1)</a>"
+        ));
+        assert_eq!(rendered.warnings.len(), 1);
+        assert_eq!(rendered.warnings[0].message, "equation not found: missing");
+    }
+
+    #[test]
+    fn test_math_uses_mathjax_delimiters_and_number_all() {
+        let config = BuildConfig {
+            math_number_all: true,
+            ..BuildConfig::default()
+        };
+        let (_, rendered) = render_with_config(
+            &config,
+            "index.rst",
+            "Guide\n#####\n\nInline :math:`x < y` math.\n\n\
+             .. math::\n\n   a = b\n\n   c = d \\\\\n     = e\n\n\
+             .. math::\n   :label: last\n\n   f = g\n\n\
+             .. math::\n   :nowrap:\n\n   \\begin{eqnarray}h\\end{eqnarray}\n",
+        );
+
+        assert!(rendered.html.contains(
+            "Inline <span class=\"math notranslate nohighlight\">\\(x &lt; y\\)</span> math."
+        ));
+        assert!(rendered.html.contains(
+            "<div class=\"math notranslate nohighlight\"><span class=\"eqno\">(1)</span>\
+             \\[\\begin{align}\\begin{aligned}a = b\\\\\\begin{split}c = d \\\\\n  = e\\end{split}\
+             \\end{aligned}\\end{align}\\]</div>"
+        ));
+        assert!(rendered
+            .html
+            .contains("id=\"equation-last\"><span class=\"eqno\">(2)<a class=\"headerlink\""));
+        assert!(rendered
+            .html
+            .contains("<span class=\"eqno\">(3)</span>\\begin{eqnarray}h\\end{eqnarray}</div>"));
+    }
+
+    #[test]
+    fn test_render_trace_lists_directives_and_roles() {
+        let content = "Guide\n#####\n\n.. note::\n\n   Hi.\n\nSee :doc:`intro`.\n\n\
+                       .. code-block:: python\n\n   x = 1\n";
+        let (document, _) = render_source("index.rst", content);
+
+        let mut renderer = HtmlRenderer::new(&BuildConfig::default());
+        renderer.set_trace(true);
+        let rendered = renderer.render(&document);
+
+        let events: Vec<(&str, &str, usize)> = rendered
+            .trace
+            .iter()
+            .map(|event| (event.kind, event.name.as_str(), event.line))
+            .collect();
+        assert_eq!(
+            events,
+            [
+                ("directive", "note", 4),
+                ("role", "doc", 8),
+                ("directive", "code-block", 10)
+            ]
+        );
+        assert!(rendered.trace.iter().all(|event| event.output_bytes > 0));
+    }
+
+    #[test]
+    fn test_author_bylines_follow_show_authors() {
+        let content = ":Author: Ada\n\nGuide\n#####\n\n.. sectionauthor:: Grace Hopper <grace@example.com>\n\n\
+                       Text.\n\n.. codeauthor:: Ada\n";
+        let (document, hidden) = render_source("index.rst", content);
+        assert_eq!(
+            document.metadata.authors,
+            ["Ada", "Grace Hopper <grace@example.com>"]
+        );
+        assert!(hidden.warnings.is_empty());
+        assert!(!hidden.html.contains("author:"));
+
+        let config = BuildConfig {
+            show_authors: true,
+            ..BuildConfig::default()
+        };
+        let (_, shown) = render_with_config(&config, "index.rst", content);
+        assert!(shown
+            .html
+            .contains("<p><em>Section author: Grace Hopper &lt;grace@example.com&gt;</em></p>"));
+        assert!(shown.html.contains("<p><em>Code author: Ada</em></p>"));
+    }
+
+    #[test]
+    fn test_comments_render_nothing() {
+        let (document, rendered) = render_source(
+            "index.rst",
+            "Title\n#####\n\n.. This is a comment\n   spanning lines.\n\n..\n   Another\n\n   comment.\n\n\
+             ..\n\nStill shown.\n\n.. note:: Kept.\n",
+        );
+
+        let DocumentContent::RestructuredText(rst) = &document.content else {
+            panic!("expected reStructuredText");
+        };
+        let comments: Vec<&str> = rst
+            .ast
+            .iter()
+            .filter_map(|node| match node {
+                RstNode::Comment { content, .. } => Some(content.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            comments,
+            ["This is a comment spanning lines.", "Another comment.", ""]
+        );
+
+        assert!(!rendered.html.contains("comment"));
+        assert!(!rendered.html.contains(".."));
+        assert!(rendered.html.contains("<p>Still shown.</p>"));
+        assert!(rendered.html.contains("Kept."));
+    }
+
+    #[test]
+    fn test_directive_option_problems_warn_with_location() {
+        let content = "Code\n####\n\n.. code-block:: python\n   :linenos: yes\n    :lineno-start: ten\n   :emphasise-lines: 2\n   :caption\n\n   print(1)\n\n\
+                       .. note::\n\n   :field: kept as content\n";
+        let (document, rendered) = render_source("index.rst", content);
+
+        let warnings: Vec<(Option<usize>, &str)> = rendered
+            .warnings
+            .iter()
+            .map(|warning| (warning.line, warning.message.as_str()))
+            .collect();
+        assert_eq!(
+            warnings,
+            vec![
+                (Some(4), "error in \"code-block\" directive: unknown option: \":caption\""),
+                (Some(4), "error in \"code-block\" directive: unknown option: \"emphasise-lines\""),
+                (
+                    Some(4),
+                    "error in \"code-block\" directive: invalid option value: \
+                     (option: \"lineno-start\"; value: \"ten\") \"ten\" is not an integer"
+                ),
+                (
+                    Some(4),
+                    "error in \"code-block\" directive: invalid option value: \
+                     (option: \"linenos\"; value: \"yes\") no argument is permitted; \"yes\" supplied"
+                ),
+            ]
+        );
+
+        // Options end at the first blank line; the misindented one still counts
+        let DocumentContent::RestructuredText(rst) = &document.content else {
+            panic!("expected reStructuredText");
+        };
+        let RstNode::Directive { content, .. } = &rst.ast[1] else {
+            panic!("expected a directive");
+        };
+        assert_eq!(content, "print(1)");
+        assert!(
+            rendered.html.contains("<p>kept as content</p></dd>"),
+            "{}",
+            rendered.html
+        );
+    }
+
+    #[test]
+    fn test_copy_code_button_wraps_code_blocks_when_enabled() {
+        let content = "Code\n####\n\n.. code-block:: python\n   :linenos:\n\n   print(1)\n\nPlain::\n\n   text\n";
+        let (_, rendered) = render_source("index.rst", content);
+        assert!(!rendered.html.contains("copybtn"));
+
+        let config = BuildConfig {
+            html_copy_code_button: true,
+            ..BuildConfig::default()
+        };
+        let (_, rendered) = render_with_config(&config, "index.rst", content);
+        assert!(rendered.html.starts_with(
+            "<h1 id=\"code\">Code</h1>\n<div class=\"copyable\"><span class=\"code-language\">python</span>\
+             <button class=\"copybtn\" type=\"button\" title=\"Copy\" \
+             aria-label=\"Copy code to clipboard\">Copy</button>\n<div class=\"highlight-python\">\
+             <table class=\"highlighttable\">"
+        ));
+        // The literal block gets a button but no language label
+        assert_eq!(rendered.html.matches("class=\"copybtn\"").count(), 2);
+        assert_eq!(rendered.html.matches("code-language").count(), 1);
+        // The button stays out of the copied <pre> text
+        for pre in rendered.html.split("<pre").skip(1) {
+            let code = &pre[..pre.find("</pre>").unwrap()];
+            assert!(!code.contains("Copy"));
+        }
+    }
+
+    #[test]
+    fn test_literalinclude_reads_and_slices_the_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(temp_dir.path().join("guide")).unwrap();
+        std::fs::write(
+            temp_dir.path().join("example.py"),
+            "import os\n\n\nclass Greeter:\n    @staticmethod\n    def greet(name):\n\
+             \x20   \treturn f\"<{name}>\"\n\n    def leave(self):\n        pass\n\n\n\
+             # start\nRUN = True\n# end\n",
+        )
+        .unwrap();
+
+        let path = temp_dir.path().join("guide").join("index.rst");
+        let content = "Guide\n#####\n\n\
+            .. literalinclude:: /example.py\n   :pyobject: Greeter.greet\n   :dedent:\n\
+            \x20  :tab-width: 4\n   :lineno-match:\n   :emphasize-lines: 3\n\n\
+            .. literalinclude:: ../example.py\n   :start-after: # start\n   :end-before: # end\n\n\
+            .. literalinclude:: ../example.py\n   :lines: 1,4-\n   :end-line: 4\n\n\
+            .. literalinclude:: missing.py\n";
+        std::fs::write(&path, content).unwrap();
+        let config = BuildConfig::default();
+        let document = Parser::new(&config).unwrap().parse(&path, content).unwrap();
+        let mut renderer = HtmlRenderer::new(&config);
+        renderer.set_source_root(temp_dir.path().to_path_buf());
+        let rendered = renderer.render(&document);
+
+        assert!(rendered.html.contains(
+            "<span class=\"normal\">5</span>\n<span class=\"normal\">6</span>\n\
+             <span class=\"normal\">7</span></pre></div></td><td class=\"code\">\
+             <pre><code class=\"language-python\">\
+             <span class=\"nd\">@</span><span class=\"nb\">staticmethod</span>\n\
+             <span class=\"kd\">def</span> <span class=\"nf\">greet</span>(name):\n\
+             <span class=\"hll\">    <span class=\"k\">return</span> <span class=\"sa\">f</span>\
+             <span class=\"s2\">\"&lt;</span>{name}<span class=\"s2\">&gt;\"</span></span></code>"
+        ));
+        assert!(rendered.html.contains(
+            "<code class=\"language-python\">RUN <span class=\"o\">=</span> \
+                       <span class=\"kc\">True</span></code>"
+        ));
+        assert!(rendered.html.contains(
+            "<code class=\"language-python\"><span class=\"kn\">import</span> os\n\
+                       <span class=\"kd\">class</span> <span class=\"nc\">Greeter</span>:</code>"
+        ));
+        assert_eq!(rendered.warnings.len(), 1, "{:?}", rendered.warnings);
+        assert_eq!(rendered.warnings[0].line, Some(19));
+        assert!(rendered.warnings[0]
+            .message
+            .starts_with("error in \"literalinclude\" directive: cannot read"));
+    }
+
+    #[test]
+    fn test_bullet_and_enumerated_lists_nest_by_indentation() {
+        let content = "Guide\n#####\n\n\
+            - First item,\n  continued\n\n  1. Step one\n  #. Step two\n\n     * Deep\n\n\
+            - Second item\n\n\
+            .. note::\n\n   Noted.\n\n\
+            * After the note\n* Another\n\n\
+            1) Numbered\n2) Again\n\n\
+            2024 was a year.\n";
+        let (_, rendered) = render_source("index.rst", content);
+
+        assert!(rendered.html.contains(
+            "<ul class=\"simple\"><li>First item, continued\
+             <ol class=\"simple\"><li>Step one</li><li>Step two\
+             <ul class=\"simple\"><li>Deep</li></ul></li></ol></li>\
+             <li>Second item</li></ul>"
+        ));
+        assert!(rendered
+            .html
+            .contains("<ul class=\"simple\"><li>After the note</li><li>Another</li></ul>"));
+        assert!(rendered
+            .html
+            .contains("<ol class=\"simple\"><li>Numbered</li><li>Again</li></ol>"));
+        assert!(rendered.html.contains("<p>2024 was a year.</p>"));
+    }
+
+    #[test]
+    fn test_table_cells_render_inline_markup() {
+        let (_, rendered) = render_source(
+            "index.rst",
+            "Guide\n#####\n\n.. list-table:: *Options*\n   :header-rows: 1\n   :name: options\n\n   * - Option\n     - Effect\n   * - ``-j``\n     - Runs **parallel** jobs\n\n.. csv-table::\n\n   \"see :ref:`setup <install#setup>`\", plain\n",
+        );
+
+        assert!(rendered.warnings.is_empty());
+        assert!(rendered.html.contains(
+            "<table class=\"docutils align-default\" id=\"options\">\n<caption><span class=\"caption-text\"><em>Options</em></span></caption>"
+        ));
+        assert!(rendered
+            .html
+            .contains("<td><p>Runs <strong>parallel</strong> jobs</p></td>"));
+        assert!(rendered.html.contains(
+            "<td><p>see <a class=\"reference internal\" href=\"install.html#setup\">setup</a></p></td><td><p>plain</p></td>"
+        ));
+    }
+}
@@ -3,6 +3,10 @@ use regex::Regex;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+use crate::config::CustomRoleConfig;
+use crate::directives::name_to_id;
+use crate::utils;
+
 /// Represents a parsed Sphinx role
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Role {
@@ -50,6 +54,13 @@ impl RoleRegistry {
         self.processors.get(name).map(|boxed| boxed.as_ref())
     }
 
+    /// Names of all registered roles, sorted
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.processors.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
     pub fn process_role(&self, role: &Role) -> Result<String> {
         if let Some(processor) = self.get(&role.name) {
             processor.process(role)
@@ -59,10 +70,29 @@ impl RoleRegistry {
         }
     }
 
+    /// Re-register the cross-reference roles to link pages with `link_suffix`
+    pub fn set_link_suffix(&mut self, link_suffix: &str) {
+        self.register(Box::new(RefRole {
+            link_suffix: link_suffix.to_string(),
+        }));
+        self.register(Box::new(DocRole {
+            link_suffix: link_suffix.to_string(),
+        }));
+    }
+
+    /// Register the roles defined in configuration, replacing built-ins of the same name
+    pub fn register_custom_roles(&mut self, roles: &HashMap<String, CustomRoleConfig>) {
+        for (name, config) in roles {
+            self.register(Box::new(TemplateRole {
+                name: name.clone(),
+                config: config.clone(),
+            }));
+        }
+    }
+
     fn register_builtin_roles(&mut self) {
         // Cross-reference roles
-        self.register(Box::new(RefRole));
-        self.register(Box::new(DocRole));
+        self.set_link_suffix(".html");
         self.register(Box::new(DownloadRole));
         self.register(Box::new(NumRefRole));
 
@@ -73,6 +103,7 @@ impl RoleRegistry {
 
         // Math roles
         self.register(Box::new(MathRole));
+        self.register(Box::new(EqRole));
 
         // Generic emphasis roles
         self.register(Box::new(EmphasisRole::new("emphasis")));
@@ -116,15 +147,69 @@ pub fn parse_role(text: &str, line_number: usize, source_file: &str) -> Result<O
     }
 }
 
+/// `{target}` and `{text}` in a config-defined role's template
+static ROLE_TEMPLATE_PLACEHOLDER_REGEX: std::sync::LazyLock<Regex> =
+    std::sync::LazyLock::new(|| Regex::new(r"\{(target|text)\}").unwrap());
+
+// Config-defined roles
+struct TemplateRole {
+    name: String,
+    config: CustomRoleConfig,
+}
+
+impl RoleProcessor for TemplateRole {
+    fn process(&self, role: &Role) -> Result<String> {
+        let display_text = role.text.as_ref().unwrap_or(&role.target);
+        // One pass, so placeholders inside the substituted values stay as written
+        let formatted = ROLE_TEMPLATE_PLACEHOLDER_REGEX.replace_all(
+            &self.config.template,
+            |captures: &regex::Captures| match &captures[1] {
+                "target" => role.target.clone(),
+                _ => display_text.clone(),
+            },
+        );
+        let class = self.config.class.as_deref().unwrap_or(&self.name);
+
+        if self.config.template.contains("://") {
+            Ok(format!(
+                "<a class=\"reference external {}\" href=\"{}\">{}</a>",
+                html_escape::encode_double_quoted_attribute(class),
+                html_escape::encode_double_quoted_attribute(&formatted),
+                html_escape::encode_text(display_text)
+            ))
+        } else {
+            Ok(format!(
+                "<span class=\"{}\">{}</span>",
+                html_escape::encode_double_quoted_attribute(class),
+                html_escape::encode_text(&formatted)
+            ))
+        }
+    }
+
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+}
+
 // Cross-reference roles
-struct RefRole;
+struct RefRole {
+    link_suffix: String,
+}
 
 impl RoleProcessor for RefRole {
     fn process(&self, role: &Role) -> Result<String> {
         let display_text = role.text.as_ref().unwrap_or(&role.target);
+        // `page#label` points into another page
+        let href = match role.target.split_once('#') {
+            Some((docname, label)) => utils::target_uri(
+                &format!("{}#{}", docname, name_to_id(label)),
+                &self.link_suffix,
+            ),
+            None => format!("#{}", name_to_id(&role.target)),
+        };
         Ok(format!(
-            "<a class=\"reference internal\" href=\"#{}\">{}</a>",
-            role.target, display_text
+            "<a class=\"reference internal\" href=\"{}\">{}</a>",
+            href, display_text
         ))
     }
 
@@ -133,14 +218,17 @@ impl RoleProcessor for RefRole {
     }
 }
 
-struct DocRole;
+struct DocRole {
+    link_suffix: String,
+}
 
 impl RoleProcessor for DocRole {
     fn process(&self, role: &Role) -> Result<String> {
         let display_text = role.text.as_ref().unwrap_or(&role.target);
         Ok(format!(
-            "<a class=\"reference internal\" href=\"{}.html\">{}</a>",
-            role.target, display_text
+            "<a class=\"reference internal\" href=\"{}\">{}</a>",
+            utils::target_uri(&role.target, &self.link_suffix),
+            display_text
         ))
     }
 
@@ -233,15 +321,39 @@ impl RoleProcessor for ProgramRole {
 // Math roles
 struct MathRole;
 
-impl RoleProcessor for MathRole {
+/// `:eq:` without its document's equation numbers; the renderer shows the number
+struct EqRole;
+
+impl RoleProcessor for EqRole {
     fn process(&self, role: &Role) -> Result<String> {
         let display_text = role.text.as_ref().unwrap_or(&role.target);
         Ok(format!(
-            "<span class=\"math notranslate nohighlight\">\\({}\\)</span>",
+            "<a class=\"reference internal\" href=\"#{}\">{}</a>",
+            crate::directives::equation_id(&role.target),
             html_escape::encode_text(display_text)
         ))
     }
 
+    fn get_name(&self) -> &str {
+        "eq"
+    }
+}
+
+/// Inline math as MathJax/KaTeX pick it up, for the `math` role and inline math
+/// delimiters alike
+pub fn math_span(tex: &str) -> String {
+    format!(
+        "<span class=\"math notranslate nohighlight\">\\({}\\)</span>",
+        html_escape::encode_text(tex)
+    )
+}
+
+impl RoleProcessor for MathRole {
+    fn process(&self, role: &Role) -> Result<String> {
+        let display_text = role.text.as_ref().unwrap_or(&role.target);
+        Ok(math_span(display_text))
+    }
+
     fn get_name(&self) -> &str {
         "math"
     }
@@ -289,3 +401,39 @@ impl RoleProcessor for EmphasisRole {
         &self.name
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_enumerates_roles() {
+        let names = RoleRegistry::new().names();
+
+        for name in ["ref", "doc", "code", "math", "strong"] {
+            assert!(names.contains(&name.to_string()), "missing {}", name);
+        }
+        assert!(names.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+
+    #[test]
+    fn test_template_role_substitutes_placeholders_once() {
+        let role = TemplateRole {
+            name: "issue".to_string(),
+            config: CustomRoleConfig {
+                template: "#{target}: {text}".to_string(),
+                class: None,
+            },
+        };
+        let html = role
+            .process(&Role {
+                name: "issue".to_string(),
+                target: "{text}".to_string(),
+                text: Some("title".to_string()),
+                line_number: 1,
+                source_file: "index.rst".to_string(),
+            })
+            .unwrap();
+        assert_eq!(html, "<span class=\"issue\">#{text}: title</span>");
+    }
+}
@@ -1,13 +1,20 @@
 use anyhow::Result;
+use rust_stemmers::{Algorithm, Stemmer};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// Approximate length, in characters, of a search result's excerpt
+const EXCERPT_LENGTH: usize = 200;
 
 /// Search index that mirrors Sphinx's search functionality
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct SearchIndex {
     pub docnames: Vec<String>,
     pub filenames: Vec<String>,
     pub titles: Vec<String>,
+    /// Indexed text of each document, for result excerpts
+    #[serde(default)]
+    pub contents: Vec<String>,
     pub terms: HashMap<String, Vec<DocumentMatch>>,
     pub objects: HashMap<String, ObjectReference>,
     pub objnames: HashMap<String, String>,
@@ -29,6 +36,35 @@ pub struct ObjectReference {
     pub anchor: Option<String>,
     pub name: String,
     pub description: Option<String>,
+    /// Object type, as `domain:type` or a bare type
+    #[serde(default)]
+    pub obj_type: String,
+}
+
+/// An object entry of Sphinx's search index: `[docindex, typeindex, priority,
+/// anchor, name]`; an empty anchor stands for the full name
+pub type SphinxObjectEntry = (usize, usize, i32, String, String);
+
+/// A search index in the shape Sphinx's `searchtools.js` loads through
+/// `Search.setIndex(...)`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SphinxSearchData {
+    pub docnames: Vec<String>,
+    pub filenames: Vec<String>,
+    pub titles: Vec<String>,
+    /// Each term with the indices of the documents containing it
+    pub terms: BTreeMap<String, Vec<usize>>,
+    /// Each term with the indices of the documents whose title contains it
+    pub titleterms: BTreeMap<String, Vec<usize>>,
+    /// Objects by the prefix of their dotted name
+    pub objects: BTreeMap<String, Vec<SphinxObjectEntry>>,
+    /// Object types by index, as `[domain, type, label]`
+    pub objnames: BTreeMap<usize, (String, String, String)>,
+    /// Object types by index, as `domain:type`
+    pub objtypes: BTreeMap<usize, String>,
+    /// Titles with the documents, and anchors within them, they head
+    pub alltitles: BTreeMap<String, Vec<(usize, Option<String>)>>,
+    pub indexentries: BTreeMap<String, Vec<(usize, Option<String>)>>,
 }
 
 impl SearchIndex {
@@ -50,10 +86,12 @@ impl SearchIndex {
         let docname_idx = self.docnames.len();
         self.docnames.push(docname);
         self.filenames.push(filename);
-        self.titles.push(title);
 
         // Extract and index terms from content
         self.index_content(docname_idx, content)?;
+        self.index_title(docname_idx, &title);
+        self.titles.push(title);
+        self.contents.push(content.to_string());
 
         Ok(())
     }
@@ -81,6 +119,7 @@ impl SearchIndex {
             anchor,
             name: name.clone(),
             description,
+            obj_type: obj_type.to_string(),
         };
 
         self.objects.insert(name, object_ref);
@@ -114,6 +153,27 @@ impl SearchIndex {
         Ok(())
     }
 
+    /// Give the document's matches for the words of its title a title score,
+    /// which outweighs any body score
+    fn index_title(&mut self, docname_idx: usize, title: &str) {
+        for word in self.extract_words(title).into_keys() {
+            let normalized_word = self.normalize_word(&word);
+            if normalized_word.len() < 2 {
+                continue;
+            }
+            let matches = self.terms.entry(normalized_word).or_default();
+            match matches.iter_mut().find(|m| m.docname_idx == docname_idx) {
+                Some(doc_match) => doc_match.title_score = 1.0,
+                None => matches.push(DocumentMatch {
+                    docname_idx,
+                    title_score: 1.0,
+                    content_score: 0.0,
+                    positions: Vec::new(),
+                }),
+            }
+        }
+    }
+
     /// Extract words and their positions from content
     fn extract_words(&self, content: &str) -> HashMap<String, Vec<usize>> {
         let mut words = HashMap::new();
@@ -139,28 +199,13 @@ impl SearchIndex {
             .to_lowercase()
     }
 
-    /// Normalize a word for indexing
+    /// Normalize a word for indexing: lowercased and, for languages with a
+    /// Snowball stemmer, stemmed
     fn normalize_word(&self, word: &str) -> String {
-        // Apply language-specific normalization
-        match self.language.as_str() {
-            "en" => self.normalize_english(word),
-            _ => word.to_lowercase(),
-        }
-    }
-
-    /// English-specific word normalization (basic stemming)
-    fn normalize_english(&self, word: &str) -> String {
         let word = word.to_lowercase();
-
-        // Very basic stemming - remove common suffixes
-        if word.ends_with("ing") && word.len() > 4 {
-            word[..word.len() - 3].to_string()
-        } else if word.ends_with("ed") && word.len() > 3 {
-            word[..word.len() - 2].to_string()
-        } else if word.ends_with("s") && word.len() > 2 {
-            word[..word.len() - 1].to_string()
-        } else {
-            word
+        match stemmer_algorithm(&self.language) {
+            Some(algorithm) => Stemmer::create(algorithm).stem(&word).into_owned(),
+            None => word,
         }
     }
 
@@ -200,10 +245,12 @@ impl SearchIndex {
             })
             .collect();
 
+        // Ties are broken by docname so the ranking doesn't depend on hash order
         results.sort_by(|a, b| {
             b.score
                 .partial_cmp(&a.score)
                 .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| a.docname.cmp(&b.docname))
         });
         results.truncate(50); // Limit results
 
@@ -211,9 +258,64 @@ impl SearchIndex {
     }
 
     /// Generate an excerpt for search results
-    fn generate_excerpt(&self, _docname_idx: usize, _query_terms: &[String]) -> String {
-        // TODO: Implement excerpt generation
-        String::new()
+    /// About [`EXCERPT_LENGTH`] characters of a document's text around the first
+    /// query term it contains, with the terms highlighted; the leading sentence
+    /// when none of them occurs
+    fn generate_excerpt(&self, docname_idx: usize, query_terms: &[String]) -> String {
+        let text = self.contents.get(docname_idx).map_or("", String::as_str);
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let matches =
+            |word: &str| query_terms.contains(&self.normalize_word(&self.clean_word(word)));
+
+        let (start, end) = match words.iter().position(|word| matches(word)) {
+            Some(first) => {
+                // Center the window on the match, then fill it up to the length
+                let mut start = first;
+                let mut length = words[first].chars().count();
+                while start > 0 && length < EXCERPT_LENGTH / 2 {
+                    start -= 1;
+                    length += words[start].chars().count() + 1;
+                }
+                let mut end = first + 1;
+                while end < words.len() && length < EXCERPT_LENGTH {
+                    length += words[end].chars().count() + 1;
+                    end += 1;
+                }
+                (start, end)
+            }
+            None => {
+                let mut end = 0;
+                let mut length = 0;
+                while end < words.len() && length < EXCERPT_LENGTH {
+                    length += words[end].chars().count() + 1;
+                    end += 1;
+                    if words[end - 1].ends_with(['.', '!', '?']) {
+                        break;
+                    }
+                }
+                (0, end)
+            }
+        };
+
+        let mut excerpt = words[start..end]
+            .iter()
+            .map(|word| {
+                let escaped = html_escape::encode_text(word);
+                if matches(word) {
+                    format!("<span class=\"highlighted\">{}</span>", escaped)
+                } else {
+                    escaped.into_owned()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        if start > 0 {
+            excerpt.insert_str(0, "...");
+        }
+        if end < words.len() {
+            excerpt.push_str("...");
+        }
+        excerpt
     }
 
     /// Prune the search index by removing documents not in the given set
@@ -221,6 +323,7 @@ impl SearchIndex {
         let mut new_docnames = Vec::new();
         let mut new_filenames = Vec::new();
         let mut new_titles = Vec::new();
+        let mut new_contents = Vec::new();
         let mut doc_mapping = HashMap::new();
 
         // Build new document lists and mapping
@@ -231,6 +334,7 @@ impl SearchIndex {
                 new_docnames.push(docname.clone());
                 new_filenames.push(self.filenames.get(old_idx).cloned().unwrap_or_default());
                 new_titles.push(self.titles.get(old_idx).cloned().unwrap_or_default());
+                new_contents.push(self.contents.get(old_idx).cloned().unwrap_or_default());
             }
         }
 
@@ -238,6 +342,7 @@ impl SearchIndex {
         self.docnames = new_docnames;
         self.filenames = new_filenames;
         self.titles = new_titles;
+        self.contents = new_contents;
 
         // Update terms with new document indices
         for matches in self.terms.values_mut() {
@@ -265,30 +370,187 @@ impl SearchIndex {
         });
     }
 
+    /// Convert to the layout of Sphinx's search index
+    pub fn to_sphinx_data(&self) -> SphinxSearchData {
+        let mut data = SphinxSearchData {
+            docnames: self.docnames.clone(),
+            filenames: self.filenames.clone(),
+            titles: self.titles.clone(),
+            ..SphinxSearchData::default()
+        };
+
+        for (term, matches) in &self.terms {
+            let documents: BTreeSet<usize> = matches.iter().map(|m| m.docname_idx).collect();
+            data.terms
+                .insert(term.clone(), documents.into_iter().collect());
+        }
+        for (docname_idx, title) in self.titles.iter().enumerate() {
+            for word in self.extract_words(title).into_keys() {
+                let term = self.normalize_word(&word);
+                if term.len() >= 2 {
+                    let documents = data.titleterms.entry(term).or_default();
+                    if !documents.contains(&docname_idx) {
+                        documents.push(docname_idx);
+                    }
+                }
+            }
+            data.alltitles
+                .entry(title.clone())
+                .or_default()
+                .push((docname_idx, None));
+        }
+
+        let types: BTreeSet<&str> = self.objects.values().map(|o| o.obj_type.as_str()).collect();
+        let type_indices: HashMap<&str, usize> = types
+            .iter()
+            .enumerate()
+            .map(|(i, &obj_type)| (obj_type, i))
+            .collect();
+        for (i, obj_type) in types.iter().enumerate() {
+            let (domain, name) = obj_type.split_once(':').unwrap_or(("std", obj_type));
+            let label = self
+                .objnames
+                .get(*obj_type)
+                .cloned()
+                .unwrap_or_else(|| name.to_string());
+            data.objnames
+                .insert(i, (domain.to_string(), name.to_string(), label));
+            data.objtypes.insert(i, format!("{}:{}", domain, name));
+        }
+
+        let mut objects: Vec<&ObjectReference> = self.objects.values().collect();
+        objects.sort_by(|a, b| a.name.cmp(&b.name));
+        for object in objects {
+            let (prefix, name) = object.name.rsplit_once('.').unwrap_or(("", &object.name));
+            let anchor = match &object.anchor {
+                Some(anchor) if *anchor != object.name => anchor.clone(),
+                _ => String::new(),
+            };
+            data.objects.entry(prefix.to_string()).or_default().push((
+                object.docname_idx,
+                type_indices[object.obj_type.as_str()],
+                1,
+                anchor,
+                name.to_string(),
+            ));
+        }
+
+        data
+    }
+
     /// Export search index to JSON format compatible with Sphinx
     pub fn to_json(&self) -> Result<String> {
-        #[derive(Serialize)]
-        struct JsonSearchIndex<'a> {
-            docnames: &'a Vec<String>,
-            filenames: &'a Vec<String>,
-            titles: &'a Vec<String>,
-            terms: &'a HashMap<String, Vec<DocumentMatch>>,
-            objects: &'a HashMap<String, ObjectReference>,
-            objnames: &'a HashMap<String, String>,
-            objtypes: &'a HashMap<String, String>,
+        Ok(serde_json::to_string(&self.to_sphinx_data())?)
+    }
+
+    /// The contents of `searchindex.js`: the JSON index handed to `Search.setIndex`
+    pub fn to_js(&self) -> Result<String> {
+        Ok(format!("Search.setIndex({})", self.to_json()?))
+    }
+}
+
+/// The Snowball stemmer for a language code such as `en` or `pt_BR`
+fn stemmer_algorithm(language: &str) -> Option<Algorithm> {
+    let code = language.split(['_', '-']).next().unwrap_or_default();
+    Some(match code.to_lowercase().as_str() {
+        "ar" => Algorithm::Arabic,
+        "da" => Algorithm::Danish,
+        "de" => Algorithm::German,
+        "el" => Algorithm::Greek,
+        "en" => Algorithm::English,
+        "es" => Algorithm::Spanish,
+        "fi" => Algorithm::Finnish,
+        "fr" => Algorithm::French,
+        "hu" => Algorithm::Hungarian,
+        "it" => Algorithm::Italian,
+        "nl" => Algorithm::Dutch,
+        "no" | "nb" | "nn" => Algorithm::Norwegian,
+        "pt" => Algorithm::Portuguese,
+        "ro" => Algorithm::Romanian,
+        "ru" => Algorithm::Russian,
+        "sv" => Algorithm::Swedish,
+        "ta" => Algorithm::Tamil,
+        "tr" => Algorithm::Turkish,
+        _ => return None,
+    })
+}
+
+/// Document and object tables of a sharded search index
+///
+/// See [`SearchIndex::to_shards`] for the sharding scheme.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchIndexManifest {
+    pub shard_count: usize,
+    pub docnames: Vec<String>,
+    pub filenames: Vec<String>,
+    pub titles: Vec<String>,
+    pub objects: HashMap<String, ObjectReference>,
+    pub objnames: HashMap<String, String>,
+    pub objtypes: HashMap<String, String>,
+}
+
+/// The terms of a sharded search index that hash to one shard
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SearchIndexShard {
+    pub terms: HashMap<String, Vec<DocumentMatch>>,
+}
+
+/// Shard holding `term`: the 32-bit FNV-1a hash of its UTF-8 bytes modulo `shard_count`
+pub fn shard_for_term(term: &str, shard_count: usize) -> usize {
+    let hash = term.bytes().fold(0x811c_9dc5_u32, |hash, byte| {
+        (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+    });
+    hash as usize % shard_count.max(1)
+}
+
+impl SearchIndex {
+    /// Split the index into a manifest and `shard_count` term shards
+    ///
+    /// The manifest carries everything except the term table, so results can be
+    /// displayed once matches are known. Each term lives in exactly one shard,
+    /// chosen by [`shard_for_term`] on the normalized (stemmed) term. A client
+    /// loads the manifest up front, normalizes each query word the same way,
+    /// fetches only the shards those words hash to and looks the words up in
+    /// their `terms`; `docname_idx` values index the manifest's tables.
+    pub fn to_shards(&self, shard_count: usize) -> (SearchIndexManifest, Vec<SearchIndexShard>) {
+        let shard_count = shard_count.max(1);
+        let mut shards = vec![SearchIndexShard::default(); shard_count];
+        for (term, matches) in &self.terms {
+            shards[shard_for_term(term, shard_count)]
+                .terms
+                .insert(term.clone(), matches.clone());
         }
 
-        let json_index = JsonSearchIndex {
-            docnames: &self.docnames,
-            filenames: &self.filenames,
-            titles: &self.titles,
-            terms: &self.terms,
-            objects: &self.objects,
-            objnames: &self.objnames,
-            objtypes: &self.objtypes,
+        let manifest = SearchIndexManifest {
+            shard_count,
+            docnames: self.docnames.clone(),
+            filenames: self.filenames.clone(),
+            titles: self.titles.clone(),
+            objects: self.objects.clone(),
+            objnames: self.objnames.clone(),
+            objtypes: self.objtypes.clone(),
         };
 
-        Ok(serde_json::to_string(&json_index)?)
+        (manifest, shards)
+    }
+
+    /// Reassemble an index from its manifest and shards
+    pub fn from_shards(
+        language: String,
+        manifest: SearchIndexManifest,
+        shards: Vec<SearchIndexShard>,
+    ) -> Self {
+        Self {
+            docnames: manifest.docnames,
+            filenames: manifest.filenames,
+            titles: manifest.titles,
+            contents: Vec::new(),
+            terms: shards.into_iter().flat_map(|shard| shard.terms).collect(),
+            objects: manifest.objects,
+            objnames: manifest.objnames,
+            objtypes: manifest.objtypes,
+            language,
+        }
     }
 }
 
@@ -303,9 +565,16 @@ pub struct SearchResult {
 }
 
 /// Search index builder for incremental updates
+///
+/// Serializable so the index can be persisted between builds and only changed
+/// documents re-indexed.
+#[derive(Debug, Serialize, Deserialize)]
 pub struct SearchIndexBuilder {
     index: SearchIndex,
     processed_docs: std::collections::HashSet<String>,
+    /// Hash of the content each document was last indexed from
+    #[serde(default)]
+    fingerprints: HashMap<String, String>,
 }
 
 impl SearchIndexBuilder {
@@ -313,9 +582,51 @@ impl SearchIndexBuilder {
         Self {
             index: SearchIndex::new(language),
             processed_docs: std::collections::HashSet::new(),
+            fingerprints: HashMap::new(),
         }
     }
 
+    /// Re-index a document only if its content changed since it was last indexed;
+    /// returns whether it was (re-)indexed
+    pub fn update_document_if_changed(
+        &mut self,
+        docname: String,
+        filename: String,
+        title: String,
+        content: &str,
+    ) -> Result<bool> {
+        let fingerprint = blake3::hash(format!("{}\0{}\0{}", filename, title, content).as_bytes())
+            .to_hex()
+            .to_string();
+        if self.processed_docs.contains(&docname)
+            && self.fingerprints.get(&docname) == Some(&fingerprint)
+        {
+            return Ok(false);
+        }
+
+        self.add_or_update_document(docname.clone(), filename, title, content)?;
+        self.fingerprints.insert(docname, fingerprint);
+        Ok(true)
+    }
+
+    /// Remove every document not in `docnames`
+    pub fn retain_documents(&mut self, docnames: &std::collections::HashSet<String>) {
+        let stale: Vec<String> = self
+            .processed_docs
+            .iter()
+            .filter(|docname| !docnames.contains(*docname))
+            .cloned()
+            .collect();
+        for docname in stale {
+            self.remove_document(&docname);
+        }
+    }
+
+    /// The index built so far
+    pub fn index(&self) -> &SearchIndex {
+        &self.index
+    }
+
     /// Add or update a document in the search index
     pub fn add_or_update_document(
         &mut self,
@@ -337,6 +648,33 @@ impl SearchIndexBuilder {
         Ok(())
     }
 
+    /// Add the objects of `env`'s domains described in `docname`, which must
+    /// be indexed already; objects with a negative priority (like labels)
+    /// aren't searchable, as in Sphinx
+    pub fn add_document_objects(
+        &mut self,
+        docname: &str,
+        env: &crate::environment::BuildEnvironment,
+    ) -> Result<()> {
+        for domain in env.domains.values() {
+            for (obj_type, objects) in &domain.objects {
+                for object in objects {
+                    if object.docname != docname || object.priority < 0 {
+                        continue;
+                    }
+                    self.index.add_object(
+                        object.name.clone(),
+                        docname,
+                        object.anchor.clone(),
+                        &format!("{}:{}", domain.name, obj_type),
+                        object.description.clone(),
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+
     /// Remove a document from the search index
     pub fn remove_document(&mut self, docname: &str) {
         if let Some(docname_idx) = self.index.docnames.iter().position(|d| d == docname) {
@@ -348,6 +686,9 @@ impl SearchIndexBuilder {
             if docname_idx < self.index.titles.len() {
                 self.index.titles.remove(docname_idx);
             }
+            if docname_idx < self.index.contents.len() {
+                self.index.contents.remove(docname_idx);
+            }
 
             // Update indices in terms
             for matches in self.index.terms.values_mut() {
@@ -380,6 +721,7 @@ impl SearchIndexBuilder {
         }
 
         self.processed_docs.remove(docname);
+        self.fingerprints.remove(docname);
     }
 
     /// Get the built search index
@@ -421,10 +763,25 @@ mod tests {
     fn test_word_normalization() {
         let index = SearchIndex::new("en".to_string());
 
-        assert_eq!(index.normalize_english("running"), "runn");
-        assert_eq!(index.normalize_english("walked"), "walk");
-        assert_eq!(index.normalize_english("tests"), "test");
-        assert_eq!(index.normalize_english("test"), "test");
+        assert_eq!(index.normalize_word("Running"), "run");
+        assert_eq!(index.normalize_word("walked"), "walk");
+        assert_eq!(index.normalize_word("studies"), "studi");
+        assert_eq!(index.normalize_word("business"), "busi");
+        assert_eq!(index.normalize_word("test"), "test");
+
+        let french = SearchIndex::new("fr".to_string());
+        assert_eq!(french.normalize_word("continuellement"), "continuel");
+        assert_eq!(french.normalize_word("Maisons"), "maison");
+
+        let german = SearchIndex::new("de_DE".to_string());
+        assert_eq!(german.normalize_word("Häuser"), "haus");
+        assert_eq!(
+            german.normalize_word("aufeinanderfolgenden"),
+            "aufeinanderfolg"
+        );
+
+        let unsupported = SearchIndex::new("ja".to_string());
+        assert_eq!(unsupported.normalize_word("Running"), "running");
     }
 
     #[test]
@@ -454,6 +811,61 @@ mod tests {
             .any(|r| r.docname == "test1" || r.docname == "test2"));
     }
 
+    #[test]
+    fn test_sharded_index_gives_same_results() {
+        let mut index = SearchIndex::new("en".to_string());
+        for (i, content) in [
+            "Installing the builder from source",
+            "Configuring themes and templates for the builder",
+            "Writing directives and roles in extensions",
+            "Search index sharding for huge sites",
+        ]
+        .iter()
+        .enumerate()
+        {
+            index
+                .add_document(
+                    format!("doc{}", i),
+                    format!("doc{}.html", i),
+                    format!("Document {}", i),
+                    content,
+                )
+                .unwrap();
+        }
+
+        let (manifest, shards) = index.to_shards(3);
+        assert_eq!(shards.len(), 3);
+        assert!(
+            shards
+                .iter()
+                .filter(|shard| !shard.terms.is_empty())
+                .count()
+                > 1
+        );
+
+        // Round-trip through the on-disk JSON form
+        let manifest = serde_json::from_str(&serde_json::to_string(&manifest).unwrap()).unwrap();
+        let shards = shards
+            .iter()
+            .map(|shard| serde_json::from_str(&serde_json::to_string(shard).unwrap()).unwrap())
+            .collect();
+        let rebuilt = SearchIndex::from_shards("en".to_string(), manifest, shards);
+
+        for query in ["builder", "themes templates", "sharding sites", "missing"] {
+            let expected: Vec<_> = index
+                .search(query)
+                .into_iter()
+                .map(|r| (r.docname, r.score))
+                .collect();
+            let actual: Vec<_> = rebuilt
+                .search(query)
+                .into_iter()
+                .map(|r| (r.docname, r.score))
+                .collect();
+            assert_eq!(expected, actual, "query {:?}", query);
+        }
+    }
+
     #[test]
     fn test_search_index_builder() {
         let mut builder = SearchIndexBuilder::new("en".to_string());
@@ -470,4 +882,149 @@ mod tests {
         let index = builder.build();
         assert_eq!(index.docnames.len(), 1);
     }
+
+    #[test]
+    fn test_incremental_update_only_reindexes_changed_documents() {
+        let mut builder = SearchIndexBuilder::new("en".to_string());
+        for (docname, content) in [("a", "alpha apples"), ("b", "beta bananas")] {
+            let indexed = builder
+                .update_document_if_changed(
+                    docname.to_string(),
+                    format!("{}.html", docname),
+                    docname.to_uppercase(),
+                    content,
+                )
+                .unwrap();
+            assert!(indexed);
+        }
+
+        // Round-trip through the persisted form
+        let json = serde_json::to_string(&builder).unwrap();
+        let mut builder: SearchIndexBuilder = serde_json::from_str(&json).unwrap();
+
+        let unchanged = builder
+            .update_document_if_changed(
+                "a".to_string(),
+                "a.html".to_string(),
+                "A".to_string(),
+                "alpha apples",
+            )
+            .unwrap();
+        let changed = builder
+            .update_document_if_changed(
+                "b".to_string(),
+                "b.html".to_string(),
+                "B".to_string(),
+                "beta cherries",
+            )
+            .unwrap();
+        assert!(!unchanged);
+        assert!(changed);
+
+        let index = builder.build();
+        let docs_for = |term: &str| -> Vec<String> {
+            index
+                .search(term)
+                .into_iter()
+                .map(|result| result.docname)
+                .collect()
+        };
+        assert_eq!(docs_for("apples"), vec!["a"]);
+        assert_eq!(docs_for("cherries"), vec!["b"]);
+        assert!(docs_for("bananas").is_empty());
+    }
+
+    #[test]
+    fn test_searchindex_js_round_trips_in_sphinx_layout() {
+        let mut index = SearchIndex::new("en".to_string());
+        index
+            .add_document(
+                "intro".to_string(),
+                "intro.rst".to_string(),
+                "Getting Started".to_string(),
+                "Install the package, then install plugins.",
+            )
+            .unwrap();
+        index
+            .add_document(
+                "api".to_string(),
+                "api.rst".to_string(),
+                "API".to_string(),
+                "The package exposes greet.",
+            )
+            .unwrap();
+        index
+            .add_object(
+                "demo.greet".to_string(),
+                "api",
+                Some("demo.greet".to_string()),
+                "py:function",
+                None,
+            )
+            .unwrap();
+
+        let js = index.to_js().unwrap();
+        let json = js
+            .strip_prefix("Search.setIndex(")
+            .and_then(|rest| rest.strip_suffix(')'))
+            .unwrap();
+        let data: SphinxSearchData = serde_json::from_str(json).unwrap();
+
+        assert_eq!(data, index.to_sphinx_data());
+        assert_eq!(data.docnames, vec!["intro", "api"]);
+        assert_eq!(data.terms["packag"], vec![0, 1]);
+        assert_eq!(data.terms["instal"], vec![0]);
+        assert_eq!(data.titleterms["start"], vec![0]);
+        assert_eq!(data.alltitles["API"], vec![(1, None)]);
+        assert_eq!(
+            data.objects["demo"],
+            vec![(1, 0, 1, String::new(), "greet".to_string())]
+        );
+        assert_eq!(data.objtypes[&0], "py:function");
+        assert_eq!(
+            data.objnames[&0],
+            (
+                "py".to_string(),
+                "function".to_string(),
+                "function".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_excerpt_highlights_query_terms_in_context() {
+        let mut index = SearchIndex::new("en".to_string());
+        let filler = "Lorem ipsum dolor sit amet. ".repeat(20);
+        index
+            .add_document(
+                "guide".to_string(),
+                "guide.html".to_string(),
+                "Guide".to_string(),
+                &format!(
+                    "{}Install the builder, then run <cargo>. {}",
+                    filler, filler
+                ),
+            )
+            .unwrap();
+        index
+            .add_document(
+                "about".to_string(),
+                "about.html".to_string(),
+                "Builder".to_string(),
+                "An overview. Nothing else here.",
+            )
+            .unwrap();
+
+        let results = index.search("builder");
+        let guide = results.iter().find(|r| r.docname == "guide").unwrap();
+        assert!(guide.excerpt.contains(
+            "Install the <span class=\"highlighted\">builder,</span> then run &lt;cargo&gt;."
+        ));
+        assert!(guide.excerpt.starts_with("...") && guide.excerpt.ends_with("..."));
+        assert!(guide.excerpt.chars().count() < 300);
+
+        // Only the title matches: the excerpt is the leading sentence
+        let about = results.iter().find(|r| r.docname == "about").unwrap();
+        assert_eq!(about.excerpt, "An overview....");
+    }
 }
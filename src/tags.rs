@@ -0,0 +1,205 @@
+//! Build tags and tag expressions, as Sphinx's `-t` option and `only` conditions
+
+use anyhow::{anyhow, Result};
+use std::collections::HashSet;
+
+use crate::document::DocumentMetadata;
+
+/// The set of tags active for a build
+#[derive(Debug, Clone, Default)]
+pub struct Tags {
+    tags: HashSet<String>,
+}
+
+impl Tags {
+    pub fn new<I, S>(tags: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            tags: tags.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    /// Tags from the configuration plus those of the active builder: like Sphinx,
+    /// its name and format, and `builder_<name>` / `format_<format>`
+    pub fn for_builder(tags: &[String], name: &str, format: &str) -> Self {
+        let mut tags = Self::new(tags.iter().cloned());
+        tags.add(name);
+        tags.add(format);
+        tags.add(&format!("builder_{}", name));
+        tags.add(&format!("format_{}", format));
+        tags
+    }
+
+    pub fn add(&mut self, tag: &str) {
+        self.tags.insert(tag.to_string());
+    }
+
+    pub fn has(&self, tag: &str) -> bool {
+        self.tags.contains(tag)
+    }
+
+    /// Evaluate a tag expression such as `html and not (internal or draft)`
+    pub fn eval(&self, expr: &str) -> Result<bool> {
+        let tokens = tokenize(expr)?;
+        let mut parser = ExprParser {
+            tokens: &tokens,
+            pos: 0,
+            tags: self,
+        };
+        let value = parser.parse_or()?;
+        match parser.tokens.get(parser.pos) {
+            None => Ok(value),
+            Some(token) => Err(anyhow!(
+                "unexpected '{}' in tag expression '{}'",
+                token,
+                expr
+            )),
+        }
+    }
+
+    /// Whether a document belongs in this build
+    ///
+    /// A document's `:only:` docinfo field is a tag expression that must hold.
+    /// Its `:tags:` field is ordinary metadata and plays no part.
+    pub fn includes(&self, metadata: &DocumentMetadata) -> Result<bool> {
+        match metadata.custom.get("only").and_then(|value| value.as_str()) {
+            Some(expr) => self.eval(expr),
+            None => Ok(true),
+        }
+    }
+}
+
+fn tokenize(expr: &str) -> Result<Vec<String>> {
+    let mut tokens = Vec::new();
+    let mut chars = expr.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '(' || c == ')' {
+            tokens.push(c.to_string());
+            chars.next();
+        } else if c.is_alphanumeric() || c == '_' || c == '-' || c == '.' {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_alphanumeric() || c == '_' || c == '-' || c == '.' {
+                    word.push(c);
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            tokens.push(word);
+        } else {
+            return Err(anyhow!(
+                "invalid character '{}' in tag expression '{}'",
+                c,
+                expr
+            ));
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Recursive descent over `or` < `and` < `not` < atoms
+struct ExprParser<'a> {
+    tokens: &'a [String],
+    pos: usize,
+    tags: &'a Tags,
+}
+
+impl ExprParser<'_> {
+    fn next_is(&self, keyword: &str) -> bool {
+        self.tokens
+            .get(self.pos)
+            .is_some_and(|token| token == keyword)
+    }
+
+    fn parse_or(&mut self) -> Result<bool> {
+        let mut value = self.parse_and()?;
+        while self.next_is("or") {
+            self.pos += 1;
+            // Evaluate both sides so syntax errors are never skipped
+            let rhs = self.parse_and()?;
+            value = value || rhs;
+        }
+        Ok(value)
+    }
+
+    fn parse_and(&mut self) -> Result<bool> {
+        let mut value = self.parse_not()?;
+        while self.next_is("and") {
+            self.pos += 1;
+            let rhs = self.parse_not()?;
+            value = value && rhs;
+        }
+        Ok(value)
+    }
+
+    fn parse_not(&mut self) -> Result<bool> {
+        if self.next_is("not") {
+            self.pos += 1;
+            return Ok(!self.parse_not()?);
+        }
+        self.parse_atom()
+    }
+
+    fn parse_atom(&mut self) -> Result<bool> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .ok_or_else(|| anyhow!("unexpected end of tag expression"))?;
+        self.pos += 1;
+
+        match token.as_str() {
+            "(" => {
+                let value = self.parse_or()?;
+                if !self.next_is(")") {
+                    return Err(anyhow!("unclosed '(' in tag expression"));
+                }
+                self.pos += 1;
+                Ok(value)
+            }
+            ")" | "and" | "or" => Err(anyhow!("unexpected '{}' in tag expression", token)),
+            "true" | "True" => Ok(true),
+            "false" | "False" => Ok(false),
+            tag => Ok(self.tags.has(tag)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_eval_tag_expressions() {
+        let tags = Tags::new(["html", "internal"]);
+        assert!(tags.eval("html").unwrap());
+        assert!(!tags.eval("latex").unwrap());
+        assert!(tags.eval("html and not latex").unwrap());
+        assert!(!tags.eval("not (internal or draft)").unwrap());
+        assert!(tags.eval("latex or html and internal").unwrap());
+        assert!(tags.eval("html and (").is_err());
+        assert!(tags.eval("html internal").is_err());
+    }
+
+    #[test]
+    fn test_includes_document() {
+        // Descriptive tags don't filter the build
+        let mut metadata = DocumentMetadata::default();
+        metadata.tags.push("python".to_string());
+        assert!(Tags::default().includes(&metadata).unwrap());
+
+        let mut metadata = DocumentMetadata::default();
+        metadata
+            .custom
+            .insert("only".to_string(), serde_json::json!("not public"));
+        assert!(Tags::default().includes(&metadata).unwrap());
+        assert!(!Tags::new(["public"]).includes(&metadata).unwrap());
+    }
+}
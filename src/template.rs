@@ -1,8 +1,10 @@
 use anyhow::Result;
-use log::info;
-use minijinja::{Environment, Error as MinijinjaError, ErrorKind, Value};
+use log::{debug, info};
+use minijinja::{
+    AutoEscape, Environment, Error as MinijinjaError, ErrorKind, UndefinedBehavior, Value,
+};
 use serde::Serialize;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::path::{Path, PathBuf};
 
 /// Template engine for rendering HTML pages (similar to Jinja2 in Sphinx)
@@ -11,11 +13,22 @@ pub struct TemplateEngine {
     env: Environment<'static>,
     template_dirs: Vec<PathBuf>,
     global_context: HashMap<String, Value>,
+    strict: bool,
 }
 
 impl TemplateEngine {
     pub fn new(config: &crate::config::BuildConfig) -> Result<Self> {
         let mut env = Environment::new();
+        // Like Sphinx's Jinja environment: body, metatags etc. are pre-rendered
+        // HTML, and templates escape plain values explicitly with `|e`
+        env.set_auto_escape_callback(|_| AutoEscape::None);
+        // Themes ported from Sphinx may use context we don't provide yet: render
+        // it empty (even `missing.attr`) unless strict templates are asked for
+        env.set_undefined_behavior(if config.strict_templates {
+            UndefinedBehavior::Strict
+        } else {
+            UndefinedBehavior::Chainable
+        });
 
         // Set up template directories
         let mut template_dirs = Vec::new();
@@ -49,6 +62,7 @@ impl TemplateEngine {
             env,
             template_dirs,
             global_context,
+            strict: config.strict_templates,
         })
     }
 
@@ -200,6 +214,11 @@ impl TemplateEngine {
             },
         );
 
+        // Add _ function (gettext); messages are not translated yet
+        env.add_function("_", |message: String| -> Result<Value, MinijinjaError> {
+            Ok(Value::from(message))
+        });
+
         // Add |e filter (HTML escape)
         env.add_filter("e", |value: Value| -> Result<Value, MinijinjaError> {
             if let Some(s) = value.as_str() {
@@ -241,6 +260,18 @@ impl TemplateEngine {
             full_context.insert(key.clone(), Self::json_to_value(value));
         }
 
+        // Only worth walking the template when the result is logged
+        if !self.strict && log::log_enabled!(log::Level::Debug) {
+            let undefined = self.undefined_variables(template_name, &full_context)?;
+            if !undefined.is_empty() {
+                debug!(
+                    "Template '{}' uses undefined variables, rendered empty: {}",
+                    template_name,
+                    undefined.into_iter().collect::<Vec<_>>().join(", ")
+                );
+            }
+        }
+
         let rendered = template
             .render(&full_context)
             .map_err(|e| anyhow::anyhow!("Failed to render template '{}': {}", template_name, e))?;
@@ -248,6 +279,22 @@ impl TemplateEngine {
         Ok(rendered)
     }
 
+    /// Variables a template reads that neither the context nor the environment's
+    /// globals define
+    fn undefined_variables(
+        &self,
+        template_name: &str,
+        context: &HashMap<String, Value>,
+    ) -> Result<BTreeSet<String>> {
+        let template = self.env.get_template(template_name)?;
+        let globals: Vec<&str> = self.env.globals().map(|(name, _)| name).collect();
+        Ok(template
+            .undeclared_variables(false)
+            .into_iter()
+            .filter(|name| !context.contains_key(name) && !globals.contains(&name.as_str()))
+            .collect())
+    }
+
     /// Convert serde_json::Value to minijinja::Value
     fn json_to_value(json_value: &serde_json::Value) -> Value {
         match json_value {
@@ -290,23 +337,18 @@ impl TemplateEngine {
 
     /// Get newest template modification time
     pub fn newest_template_mtime(&self) -> std::time::SystemTime {
-        let mut newest = std::time::UNIX_EPOCH;
-
-        for template_dir in &self.template_dirs {
-            if let Ok(entries) = std::fs::read_dir(template_dir) {
-                for entry in entries.flatten() {
-                    if let Ok(metadata) = entry.metadata() {
-                        if let Ok(mtime) = metadata.modified() {
-                            if mtime > newest {
-                                newest = mtime;
-                            }
-                        }
-                    }
-                }
-            }
-        }
+        Self::newest_mtime(&self.template_dirs)
+    }
 
-        newest
+    /// Newest modification time of any file under the given template or theme
+    /// directories, including subdirectories; the epoch when there are none
+    pub fn newest_mtime(dirs: &[PathBuf]) -> std::time::SystemTime {
+        dirs.iter()
+            .flat_map(|dir| walkdir::WalkDir::new(dir).into_iter().flatten())
+            .filter(|entry| entry.file_type().is_file())
+            .filter_map(|entry| entry.metadata().ok()?.modified().ok())
+            .max()
+            .unwrap_or(std::time::UNIX_EPOCH)
     }
 
     /// Get newest template name (for logging)
@@ -384,4 +426,47 @@ mod tests {
         );
         assert_eq!(context.get("count").and_then(|v| v.as_i64()), Some(42));
     }
+
+    #[test]
+    fn test_undefined_variables_render_empty_unless_strict() {
+        let mut engine = TemplateEngine::new(&BuildConfig::default()).unwrap();
+        engine
+            .env
+            .add_template(
+                "theme.html",
+                "<h1>{{ title }}</h1>{{ theme_sidebar.width }}{% if show_banner %}!{% endif %}",
+            )
+            .unwrap();
+        let mut ctx = TemplateContext::new();
+        ctx.insert("title", "Guide").unwrap();
+        let context = ctx.build();
+
+        assert_eq!(
+            engine.render("theme.html", &context).unwrap(),
+            "<h1>Guide</h1>"
+        );
+        let full_context: HashMap<String, Value> = context
+            .iter()
+            .map(|(key, value)| (key.clone(), TemplateEngine::json_to_value(value)))
+            .collect();
+        assert_eq!(
+            engine
+                .undefined_variables("theme.html", &full_context)
+                .unwrap()
+                .into_iter()
+                .collect::<Vec<_>>(),
+            ["show_banner", "theme_sidebar"]
+        );
+
+        let config = BuildConfig {
+            strict_templates: true,
+            ..BuildConfig::default()
+        };
+        let mut engine = TemplateEngine::new(&config).unwrap();
+        engine
+            .env
+            .add_template("theme.html", "<h1>{{ title }}</h1>{{ theme_sidebar }}")
+            .unwrap();
+        assert!(engine.render("theme.html", &context).is_err());
+    }
 }
@@ -1,6 +1,19 @@
 use anyhow::Result;
 use chrono::{DateTime, Utc};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+/// `href` and `src` attributes of HTML tags
+static URL_ATTRIBUTE_REGEX: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r#"\b(href|src)="([^"]*)""#).unwrap());
+
+/// The scheme of an absolute URL (`https:`, `mailto:`, ...)
+static URL_SCHEME_REGEX: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"^[A-Za-z][A-Za-z0-9+.-]*:").unwrap());
+
+/// An HTML tag
+static HTML_TAG_REGEX: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"<[^>]*>").unwrap());
 
 #[derive(Debug)]
 pub struct ProjectStats {
@@ -174,6 +187,146 @@ fn copy_dir_recursive_sync(src: &Path, dst: &Path) -> Result<()> {
     Ok(())
 }
 
+/// Name of the file in which `sync_dir` records what it wrote to a mirror
+pub const SYNC_MANIFEST_NAME: &str = ".sphinx-ultra-sync.json";
+
+/// Mirror `src` into `dst`, copying only files whose contents changed and
+/// removing files an earlier sync wrote that `src` no longer has
+///
+/// Files are only ever deleted when the previous sync's manifest lists them,
+/// so entries of `dst` that did not come from a sync are left alone.
+/// Top-level entries of `src` named in `exclude` are skipped. Returns the
+/// number of files copied.
+pub async fn sync_dir(src: &Path, dst: &Path, exclude: &[&str]) -> Result<usize> {
+    // Use synchronous approach
+    sync_dir_sync(src, dst, exclude)
+}
+
+fn sync_dir_sync(src: &Path, dst: &Path, exclude: &[&str]) -> Result<usize> {
+    std::fs::create_dir_all(dst)?;
+    let manifest_path = dst.join(SYNC_MANIFEST_NAME);
+
+    let mut files = Vec::new();
+    list_relative_files(src, Path::new(""), exclude, &mut files)?;
+    let current: std::collections::HashSet<&PathBuf> = files.iter().collect();
+
+    let previous: Vec<PathBuf> = std::fs::read_to_string(&manifest_path)
+        .ok()
+        .and_then(|manifest| serde_json::from_str(&manifest).ok())
+        .unwrap_or_default();
+    for stale in previous.iter().filter(|path| !current.contains(path)) {
+        // The manifest is only trusted for plain relative paths inside `dst`
+        if !stale
+            .components()
+            .all(|component| matches!(component, std::path::Component::Normal(_)))
+        {
+            continue;
+        }
+        let stale_path = dst.join(stale);
+        if std::fs::symlink_metadata(&stale_path).is_ok_and(|meta| !meta.is_dir()) {
+            std::fs::remove_file(&stale_path)?;
+        }
+        // Prune directories the removal left empty
+        let mut parent = stale_path.parent();
+        while let Some(dir) = parent.filter(|dir| *dir != dst) {
+            if std::fs::remove_dir(dir).is_err() {
+                break;
+            }
+            parent = dir.parent();
+        }
+    }
+
+    let mut copied = 0;
+    for file in &files {
+        let src_path = src.join(file);
+        let dst_path = dst.join(file);
+        if dst_path.is_dir() {
+            return Err(anyhow::anyhow!(
+                "Cannot sync {}: a directory not written by a previous sync is in the way",
+                dst_path.display()
+            ));
+        }
+        if let Some(parent) = dst_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        if !same_contents(&src_path, &dst_path) {
+            std::fs::copy(&src_path, &dst_path)?;
+            copied += 1;
+        }
+    }
+
+    std::fs::write(&manifest_path, serde_json::to_string_pretty(&files)?)?;
+    Ok(copied)
+}
+
+/// Collect the files below `dir`, as paths relative to the sync root
+fn list_relative_files(
+    dir: &Path,
+    prefix: &Path,
+    exclude: &[&str],
+    files: &mut Vec<PathBuf>,
+) -> Result<()> {
+    let mut entries = std::fs::read_dir(dir)?.collect::<std::io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let name = entry.file_name();
+        if name == SYNC_MANIFEST_NAME || exclude.iter().any(|excluded| name == *excluded) {
+            continue;
+        }
+        let relative = prefix.join(&name);
+        if entry.path().is_dir() {
+            list_relative_files(&entry.path(), &relative, &[], files)?;
+        } else {
+            files.push(relative);
+        }
+    }
+    Ok(())
+}
+
+/// Make `path` absolute and resolve symlinks and `..`, even when its last
+/// components don't exist yet
+pub fn resolve_path(path: &Path) -> Result<PathBuf> {
+    let absolute = match path.is_absolute() {
+        true => path.to_path_buf(),
+        false => std::env::current_dir()?.join(path),
+    };
+
+    let mut existing = absolute.as_path();
+    let mut missing = Vec::new();
+    while !existing.exists() {
+        match (existing.parent(), existing.components().next_back()) {
+            (Some(parent), Some(last)) => {
+                missing.push(last.as_os_str().to_os_string());
+                existing = parent;
+            }
+            _ => break,
+        }
+    }
+
+    let mut resolved = existing
+        .canonicalize()
+        .unwrap_or_else(|_| existing.to_path_buf());
+    for name in missing.into_iter().rev() {
+        match name.to_str() {
+            Some("..") => {
+                resolved.pop();
+            }
+            Some(".") => {}
+            _ => resolved.push(name),
+        }
+    }
+    Ok(resolved)
+}
+
+fn same_contents(a: &Path, b: &Path) -> bool {
+    match (std::fs::metadata(a), std::fs::metadata(b)) {
+        (Ok(a_meta), Ok(b_meta)) if a_meta.len() == b_meta.len() => {
+            matches!((std::fs::read(a), std::fs::read(b)), (Ok(a), Ok(b)) if a == b)
+        }
+        _ => false,
+    }
+}
+
 #[allow(dead_code)]
 pub fn format_duration(duration: std::time::Duration) -> String {
     let secs = duration.as_secs();
@@ -236,30 +389,236 @@ pub async fn ensure_dir(path: &Path) -> Result<()> {
     Ok(())
 }
 
-/// Calculate relative URI from one path to another
-#[allow(dead_code)]
+/// Return `anchor`, or `anchor-N` with the first free counter, and mark it as used
+pub fn unique_anchor(used: &mut std::collections::HashSet<String>, anchor: &str) -> String {
+    if used.insert(anchor.to_string()) {
+        return anchor.to_string();
+    }
+
+    let mut counter = 1;
+    loop {
+        let candidate = format!("{}-{}", anchor, counter);
+        if used.insert(candidate.clone()) {
+            return candidate;
+        }
+        counter += 1;
+    }
+}
+
+/// Calculate relative URI from one document to another (mirrors Sphinx's relative_uri)
 pub fn relative_uri(from: &str, to: &str, suffix: &str) -> String {
-    use std::path::Path;
-
-    let from_path = Path::new(from);
-    let to_path = Path::new(to);
-
-    // Get the relative path
-    if let Some(rel_path) =
-        pathdiff::diff_paths(to_path, from_path.parent().unwrap_or(Path::new("")))
-    {
-        let mut result = rel_path.to_string_lossy().to_string();
-        if !suffix.is_empty() && !result.ends_with(suffix) {
-            result.push_str(suffix);
+    if to.starts_with('/') {
+        return to.to_string();
+    }
+
+    let (to, fragment) = match to.split_once('#') {
+        Some((path, fragment)) => (path, Some(fragment)),
+        None => (to, None),
+    };
+    let mut target_path = to.replace('\\', "/");
+    if !suffix.is_empty() && !target_path.is_empty() && !target_path.ends_with(suffix) {
+        target_path.push_str(suffix);
+    }
+    let mut from = from.split('#').next().unwrap_or("").replace('\\', "/");
+    if !suffix.is_empty() && !from.is_empty() && !from.ends_with(suffix) {
+        from.push_str(suffix);
+    }
+
+    let mut base_parts: Vec<&str> = from.split('/').collect();
+    let mut target_parts: Vec<&str> = target_path.split('/').collect();
+
+    // Drop the common leading directories, but never the final segment
+    while base_parts.len() > 1 && target_parts.len() > 1 && base_parts[0] == target_parts[0] {
+        base_parts.remove(0);
+        target_parts.remove(0);
+    }
+
+    let mut result = if base_parts == target_parts {
+        String::new()
+    } else if base_parts.len() == 1 && target_parts == [""] {
+        "./".to_string()
+    } else {
+        "../".repeat(base_parts.len() - 1) + &target_parts.join("/")
+    };
+
+    if let Some(fragment) = fragment {
+        result.push('#');
+        result.push_str(fragment);
+    }
+    result
+}
+
+/// URI of a `docname` or `docname#anchor` target under `link_suffix`
+///
+/// An empty or `/` suffix gives directory-style URIs (`guide/`, with `index`
+/// pages mapping to their directory). The anchor always stays after the path.
+pub fn target_uri(target: &str, link_suffix: &str) -> String {
+    let (docname, anchor) = match target.split_once('#') {
+        Some((docname, anchor)) => (docname, Some(anchor)),
+        None => (target, None),
+    };
+
+    let mut uri = if docname.is_empty() {
+        String::new()
+    } else if link_suffix.is_empty() || link_suffix == "/" {
+        match docname.strip_suffix("index") {
+            Some(dir) if dir.is_empty() || dir.ends_with('/') => dir.to_string(),
+            _ => format!("{}/", docname),
         }
-        result.replace('\\', "/") // Ensure forward slashes
     } else {
-        format!("{}{}", to, suffix)
+        format!("{}{}", docname, link_suffix)
+    };
+
+    if let Some(anchor) = anchor {
+        uri.push('#');
+        uri.push_str(anchor);
+    }
+    uri
+}
+
+/// `file://` URL of an absolute local path
+pub fn file_url(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    let mut url = String::from("file://");
+    if !path.starts_with('/') {
+        // Windows drive paths (`C:/...`)
+        url.push('/');
+    }
+    for byte in path.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~/:".contains(&byte) {
+            url.push(byte as char);
+        } else {
+            url.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    url
+}
+
+/// Whether `--open` should launch a browser: not on CI, and on Linux and the
+/// BSDs only with a graphical display; `var` looks up environment variables
+pub fn should_open_browser(open: bool, var: impl Fn(&str) -> Option<String>) -> bool {
+    let set = |name: &str| var(name).is_some_and(|value| !value.is_empty());
+    if !open || set("CI") {
+        return false;
+    }
+    if cfg!(any(target_os = "macos", windows)) {
+        return true;
+    }
+    set("DISPLAY") || set("WAYLAND_DISPLAY")
+}
+
+/// Program and arguments opening `target` in the default browser on this platform
+pub fn browser_command(target: &str) -> (&'static str, Vec<String>) {
+    if cfg!(target_os = "macos") {
+        ("open", vec![target.to_string()])
+    } else if cfg!(windows) {
+        // The empty argument is `start`'s window title
+        (
+            "cmd",
+            vec![
+                "/C".to_string(),
+                "start".to_string(),
+                String::new(),
+                target.to_string(),
+            ],
+        )
+    } else {
+        ("xdg-open", vec![target.to_string()])
+    }
+}
+
+/// Open `target` in the default browser without waiting for it
+pub fn open_in_browser(target: &str) -> Result<()> {
+    let (program, args) = browser_command(target);
+    std::process::Command::new(program)
+        .args(&args)
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .map_err(|e| anyhow::anyhow!("Failed to run {} to open {}: {}", program, target, e))?;
+    Ok(())
+}
+
+/// Rewrite the relative `href` and `src` URLs of an HTML fragment through
+/// `rewrite`; fragment-only, root-relative and scheme URLs (`https:`,
+/// `mailto:`, `data:`, ...) are left alone
+pub fn rewrite_relative_urls(html: &str, rewrite: impl Fn(&str) -> String) -> String {
+    URL_ATTRIBUTE_REGEX
+        .replace_all(html, |captures: &regex::Captures| {
+            let url = &captures[2];
+            if url.is_empty() || url.starts_with(['#', '/']) || URL_SCHEME_REGEX.is_match(url) {
+                return captures[0].to_string();
+            }
+            format!("{}=\"{}\"", &captures[1], rewrite(url))
+        })
+        .into_owned()
+}
+
+/// Text of an HTML fragment: tags dropped, entities decoded and whitespace
+/// collapsed
+pub fn html_to_text(html: &str) -> String {
+    let text = HTML_TAG_REGEX.replace_all(html, " ");
+    html_escape::decode_html_entities(&text)
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Resolve a docname referenced from `base` (mirrors Sphinx's docname_join)
+///
+/// Names are relative to the directory of `base` unless they start with `/`.
+pub fn docname_join(base: &str, docname: &str) -> String {
+    let mut parts: Vec<&str> = match docname.strip_prefix('/') {
+        Some(_) => Vec::new(),
+        None => {
+            let mut parts: Vec<&str> = base.split('/').collect();
+            parts.pop();
+            parts
+        }
+    };
+
+    for part in docname.trim_start_matches('/').split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            _ => parts.push(part),
+        }
+    }
+
+    parts.join("/")
+}
+
+/// Strong ETag for a page: a quoted content hash, identical for identical bytes
+pub fn content_etag(content: &[u8]) -> String {
+    format!("\"{}\"", &blake3::hash(content).to_hex()[..32])
+}
+
+/// Whether an `If-None-Match` header matches `etag`, i.e. the client copy is
+/// current and a server can answer `304 Not Modified`
+pub fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    if_none_match.split(',').map(str::trim).any(|candidate| {
+        // Weak comparison, as RFC 9110 requires for If-None-Match
+        candidate == "*" || candidate.trim_start_matches("W/") == etag.trim_start_matches("W/")
+    })
+}
+
+/// Copy an `html_extra_path` entry into the output root: the contents of a
+/// directory, or a single file
+pub async fn copy_extra_path(src: &Path, outdir: &Path) -> Result<()> {
+    if src.is_dir() {
+        copy_dir_all(src, outdir).await
+    } else if let Some(file_name) = src.file_name() {
+        ensure_dir(outdir).await?;
+        tokio::fs::copy(src, outdir.join(file_name)).await?;
+        Ok(())
+    } else {
+        Ok(())
     }
 }
 
 /// Copy all files and directories from source to destination
-#[allow(dead_code)]
 pub async fn copy_dir_all(src: &Path, dst: &Path) -> Result<()> {
     use tokio::fs;
 
@@ -284,3 +643,118 @@ pub async fn copy_dir_all(src: &Path, dst: &Path) -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relative_uri_from_root_doc() {
+        assert_eq!(
+            relative_uri("index", "guide/intro", ".html"),
+            "guide/intro.html"
+        );
+        assert_eq!(relative_uri("index", "about", ".html"), "about.html");
+    }
+
+    #[test]
+    fn test_relative_uri_between_siblings() {
+        assert_eq!(relative_uri("guide/a", "guide/b", ".html"), "b.html");
+        assert_eq!(relative_uri("guide/a", "guide/a", ".html"), "");
+    }
+
+    #[test]
+    fn test_relative_uri_deep_nesting() {
+        assert_eq!(relative_uri("a/b/c", "a/x", ".html"), "../x.html");
+        assert_eq!(relative_uri("a/b/c", "index", ".html"), "../../index.html");
+        assert_eq!(
+            relative_uri("guide/intro", "api/ref", ".html"),
+            "../api/ref.html"
+        );
+    }
+
+    #[test]
+    fn test_docname_join() {
+        assert_eq!(docname_join("index", "guide/intro"), "guide/intro");
+        assert_eq!(docname_join("guide/index", "intro"), "guide/intro");
+        assert_eq!(docname_join("guide/index", "../api"), "api");
+        assert_eq!(docname_join("guide/index", "/api/ref"), "api/ref");
+    }
+
+    #[test]
+    fn test_relative_uri_directories_and_fragments() {
+        assert_eq!(
+            relative_uri("guide/intro.html", "_images", ""),
+            "../_images"
+        );
+        assert_eq!(relative_uri("index.html", "_images", ""), "_images");
+        assert_eq!(relative_uri("guide/intro", "", ""), "../");
+        assert_eq!(relative_uri("index", "", ""), "./");
+        assert_eq!(
+            relative_uri("guide/intro", "api#module-foo", ".html"),
+            "../api.html#module-foo"
+        );
+    }
+
+    #[test]
+    fn test_target_uri_keeps_anchor_after_suffix() {
+        assert_eq!(target_uri("guide#intro", ".html"), "guide.html#intro");
+        assert_eq!(target_uri("guide#intro", ""), "guide/#intro");
+        assert_eq!(target_uri("guide/index#intro", "/"), "guide/#intro");
+        assert_eq!(target_uri("index", ""), "");
+        assert_eq!(target_uri("reindex", ""), "reindex/");
+        assert_eq!(target_uri("#intro", ".html"), "#intro");
+        assert_eq!(
+            relative_uri(
+                &target_uri("api/ref", ""),
+                &target_uri("guide#intro", ""),
+                ""
+            ),
+            "../../guide/#intro"
+        );
+    }
+
+    #[test]
+    fn test_open_built_index_in_browser() {
+        let url = file_url(Path::new("/home/me/my docs/_build/index.html"));
+        assert_eq!(url, "file:///home/me/my%20docs/_build/index.html");
+        assert_eq!(
+            file_url(Path::new("C:\\docs\\index.html")),
+            "file:///C:/docs/index.html"
+        );
+
+        let (program, args) = browser_command(&url);
+        assert!(["open", "cmd", "xdg-open"].contains(&program));
+        assert_eq!(args.last(), Some(&url));
+
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(var, _)| *var == name)
+                    .map(|(_, value)| value.to_string())
+            }
+        };
+        assert!(should_open_browser(true, env(&[("DISPLAY", ":0")])));
+        assert!(!should_open_browser(false, env(&[("DISPLAY", ":0")])));
+        assert!(!should_open_browser(
+            true,
+            env(&[("DISPLAY", ":0"), ("CI", "true")])
+        ));
+        if cfg!(not(any(target_os = "macos", windows))) {
+            assert!(!should_open_browser(true, env(&[])));
+        }
+    }
+
+    #[test]
+    fn test_content_etag() {
+        let etag = content_etag(b"<p>Hello</p>");
+        assert_eq!(etag, content_etag(b"<p>Hello</p>"));
+        assert_ne!(etag, content_etag(b"<p>Hello!</p>"));
+        assert!(etag.starts_with('"') && etag.ends_with('"'));
+
+        assert!(etag_matches(&etag, &etag));
+        assert!(etag_matches(&format!("\"other\", W/{}", etag), &etag));
+        assert!(etag_matches("*", &etag));
+        assert!(!etag_matches("\"other\"", &etag));
+    }
+}
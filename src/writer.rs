@@ -0,0 +1,40 @@
+//! Pluggable output formats
+//!
+//! A [`DocumentWriter`] receives every document of a build after it has been
+//! parsed, so a library user can produce formats other than the built-in HTML
+//! (JSON dumps, man pages, ...). Register one with
+//! [`SphinxBuilder::add_writer`](crate::SphinxBuilder::add_writer); the
+//! builder then drives it, mirroring Sphinx's builder lifecycle:
+//!
+//! 1. [`init`](DocumentWriter::init) once, before any document is written
+//! 2. [`write_doc`](DocumentWriter::write_doc) once per document of the build
+//! 3. [`finish`](DocumentWriter::finish) once, after the last document
+//!
+//! An error from any step fails the build. [`HTMLBuilder`](crate::HTMLBuilder)
+//! is the reference implementation.
+
+use anyhow::Result;
+use async_trait::async_trait;
+
+use crate::document::Document;
+
+/// An output format driven by [`SphinxBuilder`](crate::SphinxBuilder)
+#[async_trait]
+pub trait DocumentWriter: Send {
+    /// Short name of the format, used in log messages (e.g. `"html"`)
+    fn name(&self) -> &str;
+
+    /// Prepare the output (create directories, load templates, ...)
+    async fn init(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Write one document; `docname` is its source path relative to the
+    /// source directory, without extension and with `/` separators
+    async fn write_doc(&mut self, docname: &str, doctree: &Document) -> Result<()>;
+
+    /// Write whatever depends on all documents (indices, search data, ...)
+    async fn finish(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,21 @@
+/* Copy the code of a block to the clipboard; line numbers are left out */
+document.addEventListener('DOMContentLoaded', function() {
+    document.querySelectorAll('.copyable .copybtn').forEach(function(button) {
+        button.addEventListener('click', function() {
+            var pre = button.closest('.copyable').querySelector('pre');
+            if (!pre) return;
+
+            var code = pre.cloneNode(true);
+            code.querySelectorAll('.linenos').forEach(function(lineno) {
+                lineno.remove();
+            });
+
+            navigator.clipboard.writeText(code.textContent).then(function() {
+                button.textContent = 'Copied!';
+                setTimeout(function() {
+                    button.textContent = 'Copy';
+                }, 2000);
+            });
+        });
+    });
+});
@@ -122,3 +122,55 @@ async fn test_template_rendering() -> Result<()> {
     Ok(())
 }
 */
+
+#[tokio::test]
+async fn test_documents_render_directives_and_roles() {
+    use sphinx_ultra::{BuildConfig, DocumentWriter, HTMLBuilder, Parser, SphinxBuilder};
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let source_dir = temp_dir.path().join("source");
+    std::fs::create_dir_all(&source_dir).unwrap();
+    let content = "Guide\n#####\n\n.. _setup:\n\nSetup\n=====\n\n\
+                   .. note::\n\n   Read this <b>first</b>.\n\n   See :ref:`setup`.\n";
+    std::fs::write(source_dir.join("index.rst"), content).unwrap();
+
+    // Through a full build
+    let output_dir = temp_dir.path().join("build");
+    SphinxBuilder::new(
+        BuildConfig::default(),
+        source_dir.clone(),
+        output_dir.clone(),
+    )
+    .unwrap()
+    .build()
+    .await
+    .unwrap();
+
+    // Through a writer handed the parsed, unrendered document
+    let writer_dir = temp_dir.path().join("writer");
+    let config = BuildConfig::default();
+    let path = source_dir.join("index.rst");
+    let document = Parser::new(&config).unwrap().parse(&path, content).unwrap();
+    let mut writer = HTMLBuilder::new(config, source_dir, writer_dir.clone()).unwrap();
+    DocumentWriter::init(&mut writer).await.unwrap();
+    DocumentWriter::write_doc(&mut writer, "index", &document)
+        .await
+        .unwrap();
+
+    for dir in [output_dir, writer_dir] {
+        let html = std::fs::read_to_string(dir.join("index.html")).unwrap();
+        assert!(html.contains("<div class=\"admonition note\">"), "{}", html);
+        // The note's content is escaped, with its paragraphs and roles rendered
+        assert!(
+            html.contains(
+                "<p>Read this &lt;b&gt;first&lt;/b&gt;.</p>\n\
+                 <p>See <a class=\"reference internal\" href=\"#setup\">Setup</a>.</p>\n</div>"
+            ),
+            "{}",
+            html
+        );
+        assert!(!html.contains("<b>first</b>"), "{}", html);
+        assert!(!html.contains(".. note::"), "{}", html);
+        assert!(!html.contains(":ref:`setup`"), "{}", html);
+    }
+}